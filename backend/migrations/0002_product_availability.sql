@@ -0,0 +1,8 @@
+-- ============================================================================
+-- Product availability
+-- ============================================================================
+-- Checkout reprices every cart from the products table and rejects items
+-- that are switched off here.
+-- ============================================================================
+
+ALTER TABLE products ADD COLUMN is_available INTEGER NOT NULL DEFAULT 1;  -- 1 = orderable, 0 = unavailable
@@ -0,0 +1,11 @@
+-- ============================================================================
+-- Payment providers
+-- ============================================================================
+-- Records which PaymentProvider (paypal, mock, pickup) handled an order or a
+-- gift purchase, and the provider capture id needed for refunds.
+-- ============================================================================
+
+ALTER TABLE pending_orders ADD COLUMN payment_provider TEXT;
+ALTER TABLE pending_gifts ADD COLUMN payment_provider TEXT;
+ALTER TABLE orders ADD COLUMN payment_provider TEXT;
+ALTER TABLE orders ADD COLUMN payment_capture_id TEXT;
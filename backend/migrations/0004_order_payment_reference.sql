@@ -0,0 +1,10 @@
+-- ============================================================================
+-- Order payment reference
+-- ============================================================================
+-- Links every finalized order to the provider payment it came from. The
+-- unique index makes finalization idempotent: the PayPal return redirect and
+-- the webhook can both fire for the same payment, only one order is created.
+-- ============================================================================
+
+ALTER TABLE orders ADD COLUMN payment_order_id TEXT;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_orders_payment_order_id ON orders(payment_order_id);
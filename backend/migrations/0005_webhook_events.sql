@@ -0,0 +1,23 @@
+-- ============================================================================
+-- Webhook events
+-- ============================================================================
+-- Every verified provider webhook delivery, keyed by the provider event id.
+-- PayPal retries deliveries, so the id doubles as the dedupe key; the stored
+-- payload lets admins replay an event after fixing whatever made it fail.
+-- status: received, processed, ignored, failed
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS webhook_events (
+  id TEXT PRIMARY KEY,
+  provider TEXT NOT NULL,
+  event_type TEXT NOT NULL,
+  resource_id TEXT,
+  payload TEXT NOT NULL,
+  status TEXT NOT NULL DEFAULT 'received',
+  error TEXT,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  received_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  processed_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_events_status ON webhook_events(status);
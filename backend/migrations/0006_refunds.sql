@@ -0,0 +1,36 @@
+-- ============================================================================
+-- Refunds
+-- ============================================================================
+-- One row per refund sent to the payment provider. A partial refund leaves
+-- orders.status alone; what was refunded is the sum of the order's booked
+-- refunds, and the order moves to refunded once that reaches total_cents.
+-- order_items.refunded_quantity tracks which lines have been given back.
+--
+-- pending: 1 while the refund is being sent to the provider. The row is
+--   written first, with a guarded insert that checks what is left of the
+--   order and that no other refund of it is pending, so two refunds of one
+--   order are never in flight at once. provider_refund_id is filled in as soon
+--   as the provider confirms; the row is booked (pending = 0) after that, or
+--   deleted if the provider refused.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS refunds (
+  id TEXT PRIMARY KEY,
+  order_id TEXT NOT NULL,
+  amount_cents INTEGER NOT NULL,
+  currency TEXT NOT NULL DEFAULT 'EUR',
+  reason TEXT,
+  provider TEXT,
+  provider_refund_id TEXT,
+  provider_status TEXT,             -- Refund status reported by the provider (completed, pending, ...)
+  items_json TEXT,                  -- JSON: [{order_item_id, quantity}], empty for whole-order refunds of itemless orders
+  created_by TEXT,                  -- Admin email, or 'webhook' for refunds issued in the PayPal dashboard
+  pending INTEGER NOT NULL DEFAULT 0,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  FOREIGN KEY(order_id) REFERENCES orders(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_refunds_order_id ON refunds(order_id);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_refunds_provider_refund_id ON refunds(provider_refund_id);
+
+ALTER TABLE order_items ADD COLUMN refunded_quantity INTEGER NOT NULL DEFAULT 0;
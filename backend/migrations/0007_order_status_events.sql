@@ -0,0 +1,32 @@
+-- ============================================================================
+-- Order status history
+-- ============================================================================
+-- orders.status follows orders::status::OrderStatus:
+--   awaiting_payment, paid, accepted, preparing, ready, picked_up,
+--   cancelled, refunded
+-- Every change is recorded here with the user (or 'system') that made it.
+-- Users with role 'staff' may advance orders through the kitchen states.
+-- orders.paid_at is set when the money came in: at capture for online
+-- payments, at hand-over for pay-at-pickup orders.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS order_status_events (
+  id TEXT PRIMARY KEY,
+  order_id TEXT NOT NULL,
+  from_status TEXT,                 -- NULL for the status an order was created with
+  to_status TEXT NOT NULL,
+  actor TEXT NOT NULL,              -- Staff/admin email, 'system' or 'webhook'
+  note TEXT,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  FOREIGN KEY(order_id) REFERENCES orders(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_order_status_events_order_id ON order_status_events(order_id);
+CREATE INDEX IF NOT EXISTS idx_orders_status ON orders(status);
+
+-- Pre-state-machine spellings
+UPDATE orders SET status = 'paid' WHERE status = 'completed';
+UPDATE orders SET status = 'awaiting_payment' WHERE status = 'pending';
+
+ALTER TABLE orders ADD COLUMN paid_at TEXT;
+UPDATE orders SET paid_at = created_at WHERE payment_capture_id IS NOT NULL OR status = 'paid';
@@ -0,0 +1,21 @@
+-- ============================================================================
+-- Customer order tracking
+-- ============================================================================
+-- tracking_token: random secret that must accompany GET /api/orders/:id, so
+--   an order cannot be looked up by its UUID alone. Links to the tracking
+--   page (/thank-you/:id?t=<token>) carry it.
+-- estimated_ready_at: set when the order is created, refined by the kitchen.
+-- notify_ready: customer opted in to an email once the order is ready;
+--   ready_notified_at makes sure that email goes out only once.
+-- ============================================================================
+
+ALTER TABLE orders ADD COLUMN tracking_token TEXT;
+ALTER TABLE orders ADD COLUMN estimated_ready_at TEXT;
+ALTER TABLE orders ADD COLUMN notify_ready INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE orders ADD COLUMN ready_notified_at TEXT;
+
+ALTER TABLE pending_orders ADD COLUMN notify_ready INTEGER NOT NULL DEFAULT 0;
+
+UPDATE orders SET tracking_token = lower(hex(randomblob(16))) WHERE tracking_token IS NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_orders_tracking_token ON orders(tracking_token);
@@ -0,0 +1,36 @@
+-- ============================================================================
+-- Opening hours and scheduled pickup
+-- ============================================================================
+-- opening_hours: weekly service windows in restaurant local time
+--   (RESTAURANT_TIMEZONE). weekday 0 = Monday ... 6 = Sunday; a day may
+--   have several windows (lunch and dinner).
+-- Pickup slots are cut from these windows (PICKUP_SLOT_MINUTES) and limited
+--   by PICKUP_SLOT_MAX_ORDERS / PICKUP_SLOT_MAX_ITEMS.
+-- orders.pickup_at / pending_orders.pickup_at: UTC start of the chosen slot,
+--   NULL for "as soon as possible".
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS opening_hours (
+  id TEXT PRIMARY KEY,
+  weekday INTEGER NOT NULL CHECK (weekday BETWEEN 0 AND 6),
+  opens_at TEXT NOT NULL,           -- 'HH:MM'
+  closes_at TEXT NOT NULL,          -- 'HH:MM', after opens_at
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_opening_hours_weekday ON opening_hours(weekday);
+
+-- Mo–Fr & So 12:00–15:00 and 17:30–22:30, Sa 17:30–22:30
+INSERT INTO opening_hours (id, weekday, opens_at, closes_at) VALUES
+  ('oh-0-lunch', 0, '12:00', '15:00'), ('oh-0-dinner', 0, '17:30', '22:30'),
+  ('oh-1-lunch', 1, '12:00', '15:00'), ('oh-1-dinner', 1, '17:30', '22:30'),
+  ('oh-2-lunch', 2, '12:00', '15:00'), ('oh-2-dinner', 2, '17:30', '22:30'),
+  ('oh-3-lunch', 3, '12:00', '15:00'), ('oh-3-dinner', 3, '17:30', '22:30'),
+  ('oh-4-lunch', 4, '12:00', '15:00'), ('oh-4-dinner', 4, '17:30', '22:30'),
+  ('oh-5-dinner', 5, '17:30', '22:30'),
+  ('oh-6-lunch', 6, '12:00', '15:00'), ('oh-6-dinner', 6, '17:30', '22:30');
+
+ALTER TABLE orders ADD COLUMN pickup_at TEXT;
+ALTER TABLE pending_orders ADD COLUMN pickup_at TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_orders_pickup_at ON orders(pickup_at);
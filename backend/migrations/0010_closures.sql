@@ -0,0 +1,24 @@
+-- ============================================================================
+-- Closures and ordering cutoffs
+-- ============================================================================
+-- opening_hours.last_order_at: last local time orders are taken in a window
+--   ('HH:MM', NULL = until closes_at). Pickup slots end there as well.
+-- closures: whole local days without service (holidays, vacation), from
+--   starts_on to ends_on inclusive ('YYYY-MM-DD'). They override opening_hours.
+-- ============================================================================
+
+ALTER TABLE opening_hours ADD COLUMN last_order_at TEXT;
+
+-- Warme Küche: Mo–Fr bis 21:00
+UPDATE opening_hours SET last_order_at = '21:00' WHERE weekday BETWEEN 0 AND 4 AND closes_at = '22:30';
+
+CREATE TABLE IF NOT EXISTS closures (
+  id TEXT PRIMARY KEY,
+  starts_on TEXT NOT NULL,
+  ends_on TEXT NOT NULL,
+  reason TEXT,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  CHECK (starts_on <= ends_on)
+);
+
+CREATE INDEX IF NOT EXISTS idx_closures_range ON closures(starts_on, ends_on);
@@ -0,0 +1,60 @@
+-- ============================================================================
+-- Table reservations
+-- ============================================================================
+-- tables: bookable tables; a booking gets the smallest free table that seats
+--   the party (seats >= party_size >= min_party).
+-- reservations: starts_at / ends_at in UTC, length RESERVATION_MINUTES.
+--   status: booked, seated, cancelled, no_show. manage_token lets the guest
+--   view or cancel the booking from the emailed link.
+-- reservation_rules: pacing per time window in local time. Bookings starting
+--   in [starts_at, ends_at) on the weekday (NULL = every day) may not exceed
+--   max_bookings bookings or max_covers guests.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS tables (
+  id TEXT PRIMARY KEY,
+  name TEXT NOT NULL UNIQUE,
+  seats INTEGER NOT NULL CHECK (seats > 0),
+  min_party INTEGER NOT NULL DEFAULT 1,
+  active INTEGER NOT NULL DEFAULT 1,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+INSERT INTO tables (id, name, seats, min_party) VALUES
+  ('table-1', 'Tisch 1', 2, 1),
+  ('table-2', 'Tisch 2', 2, 1),
+  ('table-3', 'Tisch 3', 4, 2),
+  ('table-4', 'Tisch 4', 4, 2),
+  ('table-5', 'Tisch 5', 4, 2),
+  ('table-6', 'Tisch 6', 6, 3),
+  ('table-7', 'Tisch 7', 8, 5);
+
+CREATE TABLE IF NOT EXISTS reservations (
+  id TEXT PRIMARY KEY,
+  table_id TEXT REFERENCES tables(id),
+  name TEXT NOT NULL,
+  email TEXT NOT NULL,
+  phone TEXT,
+  party_size INTEGER NOT NULL CHECK (party_size > 0),
+  starts_at TEXT NOT NULL,
+  ends_at TEXT NOT NULL,
+  status TEXT NOT NULL DEFAULT 'booked',
+  notes TEXT,
+  manage_token TEXT NOT NULL,
+  cancel_reason TEXT,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_reservations_starts_at ON reservations(starts_at);
+CREATE INDEX IF NOT EXISTS idx_reservations_table ON reservations(table_id, starts_at);
+
+CREATE TABLE IF NOT EXISTS reservation_rules (
+  id TEXT PRIMARY KEY,
+  weekday INTEGER CHECK (weekday IS NULL OR weekday BETWEEN 0 AND 6),
+  starts_at TEXT NOT NULL,          -- 'HH:MM'
+  ends_at TEXT NOT NULL,            -- 'HH:MM'
+  max_bookings INTEGER,
+  max_covers INTEGER,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
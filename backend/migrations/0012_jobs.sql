@@ -0,0 +1,30 @@
+-- ============================================================================
+-- Background jobs
+-- ============================================================================
+-- jobs: persistent work queue polled by `jobs::spawn`. A job runs once
+--   run_at has passed; status: pending, running, done, failed. A failed run
+--   is retried with backoff until max_attempts, keeping the error in
+--   last_error. Recurring jobs (recurring = 1) schedule their next run when
+--   they finish; at most one of each kind is queued at a time.
+-- reservations.reminder_sent_at: set once the day-before reminder went out.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS jobs (
+  id TEXT PRIMARY KEY,
+  kind TEXT NOT NULL,
+  payload TEXT,
+  recurring INTEGER NOT NULL DEFAULT 0,
+  status TEXT NOT NULL DEFAULT 'pending',
+  run_at TEXT NOT NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  max_attempts INTEGER NOT NULL DEFAULT 5,
+  last_error TEXT,
+  started_at TEXT,
+  finished_at TEXT,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_jobs_due ON jobs(status, run_at);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_jobs_recurring ON jobs(kind) WHERE recurring = 1 AND status IN ('pending', 'running');
+
+ALTER TABLE reservations ADD COLUMN reminder_sent_at TEXT;
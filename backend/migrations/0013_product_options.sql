@@ -0,0 +1,62 @@
+-- ============================================================================
+-- Product options and modifiers
+-- ============================================================================
+-- option_groups: a choice on a product, e.g. spice level or extras. A cart
+--   line must select between min_select and max_select options of each group
+--   (max_select NULL = no limit); min_select >= 1 makes the group required.
+-- options: the choices of a group with their surcharge in cents.
+-- product_option_groups: which groups a product offers, so one group (spice
+--   level) can be shared by many dishes.
+-- order_items.options_json: the selected options as priced at checkout,
+--   [{"id", "group", "name", "priceDeltaCents"}]; unit_amount includes them.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS option_groups (
+  id TEXT PRIMARY KEY,
+  name TEXT NOT NULL,
+  min_select INTEGER NOT NULL DEFAULT 0 CHECK (min_select >= 0),
+  max_select INTEGER CHECK (max_select IS NULL OR max_select >= 1),
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  CHECK (max_select IS NULL OR max_select >= min_select)
+);
+
+CREATE TABLE IF NOT EXISTS options (
+  id TEXT PRIMARY KEY,
+  group_id TEXT NOT NULL REFERENCES option_groups(id) ON DELETE CASCADE,
+  name TEXT NOT NULL,
+  price_delta_cents INTEGER NOT NULL DEFAULT 0,
+  is_available INTEGER NOT NULL DEFAULT 1,
+  sort_order INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_options_group ON options(group_id, sort_order);
+
+CREATE TABLE IF NOT EXISTS product_option_groups (
+  product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  group_id TEXT NOT NULL REFERENCES option_groups(id) ON DELETE CASCADE,
+  sort_order INTEGER NOT NULL DEFAULT 0,
+  PRIMARY KEY (product_id, group_id)
+);
+
+ALTER TABLE order_items ADD COLUMN options_json TEXT;
+
+-- Seed: spice level for the dishes that list one, extras for main courses.
+-- Both are optional so carts without options keep working.
+INSERT INTO option_groups (id, name, min_select, max_select) VALUES
+  ('spice-level', 'Schärfegrad', 0, 1),
+  ('extras', 'Extras', 0, 3);
+
+INSERT INTO options (id, group_id, name, price_delta_cents, sort_order) VALUES
+  ('spice-none', 'spice-level', 'nicht scharf', 0, 0),
+  ('spice-mild', 'spice-level', 'leicht scharf', 0, 1),
+  ('spice-medium', 'spice-level', 'mittel scharf', 0, 2),
+  ('spice-hot', 'spice-level', 'scharf', 0, 3),
+  ('extra-tofu', 'extras', 'Extra Tofu', 200, 0),
+  ('extra-chicken', 'extras', 'Extra Hühnerfilet', 300, 1),
+  ('extra-rice', 'extras', 'Extra Reis', 150, 2);
+
+INSERT INTO product_option_groups (product_id, group_id, sort_order)
+  SELECT id, 'spice-level', 0 FROM products WHERE spice_level IS NOT NULL;
+
+INSERT INTO product_option_groups (product_id, group_id, sort_order)
+  SELECT id, 'extras', 1 FROM products WHERE category = 'Hauptspeisen';
@@ -0,0 +1,95 @@
+-- ============================================================================
+-- EU allergens and German additives
+-- ============================================================================
+-- allergens: the 14 allergens of Regulation (EU) No 1169/2011, keyed by the
+--   letter used on the printed menu.
+-- additives: additives that must be declared on German menus, keyed by their
+--   footnote number.
+-- product_allergens / product_additives: what a product contains. These are
+--   the source of truth; products.allergens / products.additives are kept as
+--   the normalized comma-separated codes for older clients.
+-- Backfill: existing codes are split and kept where they match a reference
+-- row. Letters outside the 14 (older menus used "s" and "t") are dropped.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS allergens (
+  code TEXT PRIMARY KEY,
+  name_de TEXT NOT NULL,
+  name_en TEXT NOT NULL,
+  sort_order INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT INTO allergens (code, name_de, name_en, sort_order) VALUES
+  ('a', 'Glutenhaltiges Getreide', 'Cereals containing gluten', 1),
+  ('b', 'Krebstiere', 'Crustaceans', 2),
+  ('c', 'Eier', 'Eggs', 3),
+  ('d', 'Fisch', 'Fish', 4),
+  ('e', 'Erdnüsse', 'Peanuts', 5),
+  ('f', 'Soja', 'Soybeans', 6),
+  ('g', 'Milch und Laktose', 'Milk and lactose', 7),
+  ('h', 'Schalenfrüchte', 'Tree nuts', 8),
+  ('l', 'Sellerie', 'Celery', 9),
+  ('m', 'Senf', 'Mustard', 10),
+  ('n', 'Sesamsamen', 'Sesame seeds', 11),
+  ('o', 'Schwefeldioxid und Sulfite', 'Sulphur dioxide and sulphites', 12),
+  ('p', 'Lupinen', 'Lupin', 13),
+  ('r', 'Weichtiere', 'Molluscs', 14);
+
+CREATE TABLE IF NOT EXISTS additives (
+  code TEXT PRIMARY KEY,
+  name_de TEXT NOT NULL,
+  name_en TEXT NOT NULL,
+  sort_order INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT INTO additives (code, name_de, name_en, sort_order) VALUES
+  ('1', 'mit Farbstoff', 'with colouring', 1),
+  ('2', 'mit Konservierungsstoff', 'with preservative', 2),
+  ('3', 'mit Antioxidationsmittel', 'with antioxidant', 3),
+  ('4', 'mit Geschmacksverstärker', 'with flavour enhancer', 4),
+  ('5', 'geschwefelt', 'sulphurised', 5),
+  ('6', 'geschwärzt', 'blackened', 6),
+  ('7', 'gewachst', 'waxed', 7),
+  ('8', 'mit Phosphat', 'with phosphate', 8),
+  ('9', 'mit Süßungsmittel', 'with sweetener', 9),
+  ('10', 'enthält eine Phenylalaninquelle', 'contains a source of phenylalanine', 10),
+  ('11', 'koffeinhaltig', 'contains caffeine', 11),
+  ('12', 'chininhaltig', 'contains quinine', 12);
+
+CREATE TABLE IF NOT EXISTS product_allergens (
+  product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  allergen_code TEXT NOT NULL REFERENCES allergens(code),
+  PRIMARY KEY (product_id, allergen_code)
+);
+
+CREATE TABLE IF NOT EXISTS product_additives (
+  product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  additive_code TEXT NOT NULL REFERENCES additives(code),
+  PRIMARY KEY (product_id, additive_code)
+);
+
+CREATE INDEX IF NOT EXISTS idx_product_allergens_code ON product_allergens(allergen_code);
+
+INSERT OR IGNORE INTO product_allergens (product_id, allergen_code)
+WITH RECURSIVE split(product_id, code, rest) AS (
+  SELECT id, '', allergens || ',' FROM products WHERE allergens IS NOT NULL AND trim(allergens) != ''
+  UNION ALL
+  SELECT product_id, lower(trim(substr(rest, 1, instr(rest, ',') - 1))), substr(rest, instr(rest, ',') + 1) FROM split WHERE rest != ''
+)
+SELECT product_id, code FROM split WHERE code IN (SELECT code FROM allergens);
+
+INSERT OR IGNORE INTO product_additives (product_id, additive_code)
+WITH RECURSIVE split(product_id, code, rest) AS (
+  SELECT id, '', additives || ',' FROM products WHERE additives IS NOT NULL AND trim(additives) != ''
+  UNION ALL
+  SELECT product_id, trim(substr(rest, 1, instr(rest, ',') - 1)), substr(rest, instr(rest, ',') + 1) FROM split WHERE rest != ''
+)
+SELECT product_id, code FROM split WHERE code IN (SELECT code FROM additives);
+
+-- Normalize the legacy columns to what was kept
+UPDATE products SET allergens = (
+  SELECT group_concat(allergen_code, ',') FROM (SELECT pa.allergen_code FROM product_allergens pa JOIN allergens a ON a.code = pa.allergen_code WHERE pa.product_id = products.id ORDER BY a.sort_order)
+);
+UPDATE products SET additives = (
+  SELECT group_concat(additive_code, ',') FROM (SELECT pa.additive_code FROM product_additives pa JOIN additives a ON a.code = pa.additive_code WHERE pa.product_id = products.id ORDER BY a.sort_order)
+);
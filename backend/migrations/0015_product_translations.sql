@@ -0,0 +1,41 @@
+-- ============================================================================
+-- Menu translations
+-- ============================================================================
+-- products keeps the German text, which is the default locale.
+-- product_translations: English and Vietnamese text per product. Every column
+--   is optional; a missing row or NULL column falls back to the German text.
+-- pending_orders.locale / orders.locale: the language the customer ordered
+--   in, used for the confirmation email.
+-- Seed: category names for the existing menu sections.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS product_translations (
+  product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  locale TEXT NOT NULL CHECK (locale IN ('en', 'vi')),
+  name TEXT,
+  description TEXT,
+  category TEXT,
+  updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  PRIMARY KEY (product_id, locale)
+);
+
+ALTER TABLE pending_orders ADD COLUMN locale TEXT NOT NULL DEFAULT 'de';
+ALTER TABLE orders ADD COLUMN locale TEXT NOT NULL DEFAULT 'de';
+
+INSERT OR IGNORE INTO product_translations (product_id, locale, category)
+SELECT p.id, t.locale, t.category
+FROM products p
+JOIN (
+  SELECT 'Aperitif' AS de, 'en' AS locale, 'Aperitifs' AS category
+  UNION ALL SELECT 'Aperitif', 'vi', 'Khai vị đồ uống'
+  UNION ALL SELECT 'Vorspeisen', 'en', 'Starters'
+  UNION ALL SELECT 'Vorspeisen', 'vi', 'Món khai vị'
+  UNION ALL SELECT 'Suppen', 'en', 'Soups'
+  UNION ALL SELECT 'Suppen', 'vi', 'Món canh'
+  UNION ALL SELECT 'Hauptspeisen', 'en', 'Main courses'
+  UNION ALL SELECT 'Hauptspeisen', 'vi', 'Món chính'
+  UNION ALL SELECT 'Dessert', 'en', 'Desserts'
+  UNION ALL SELECT 'Dessert', 'vi', 'Tráng miệng'
+  UNION ALL SELECT 'Extra', 'en', 'Sides'
+  UNION ALL SELECT 'Extra', 'vi', 'Món thêm'
+) t ON t.de = p.category;
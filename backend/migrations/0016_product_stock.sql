@@ -0,0 +1,39 @@
+-- ============================================================================
+-- Sold-out flags and daily portion limits
+-- ============================================================================
+-- products.is_available stays the admin switch that takes a dish off the menu.
+-- products.sold_out_on: the stock day (YYYY-MM-DD) the kitchen marked the dish
+--   sold out; it is orderable again on the next stock day.
+-- products.daily_limit: portions per stock day, NULL = unlimited.
+-- product_stock: portions sold per product and stock day, counted when an
+--   order is finalized. A new day starts with no row, which is the morning
+--   reset. Stock days start at STOCK_RESET_HOUR (default 04:00) restaurant time.
+-- stock_holds: portions of limited dishes a checkout is about to buy, held
+--   against its pending order until the payment is finalized, like
+--   discount_holds. Only holds whose expires_at lies in the future count.
+-- orders.stock_day: the stock day an order's portions were counted on;
+--   cleared once a cancellation or refund has given them back.
+-- ============================================================================
+
+ALTER TABLE products ADD COLUMN sold_out_on TEXT;
+ALTER TABLE products ADD COLUMN daily_limit INTEGER CHECK (daily_limit IS NULL OR daily_limit >= 0);
+
+CREATE TABLE IF NOT EXISTS product_stock (
+  product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  stock_day TEXT NOT NULL,
+  sold INTEGER NOT NULL DEFAULT 0,
+  PRIMARY KEY (product_id, stock_day)
+);
+
+CREATE TABLE IF NOT EXISTS stock_holds (
+  pending_order_id TEXT NOT NULL REFERENCES pending_orders(order_id) ON DELETE CASCADE,
+  product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  stock_day TEXT NOT NULL,
+  quantity INTEGER NOT NULL,
+  expires_at TEXT NOT NULL,
+  PRIMARY KEY (pending_order_id, product_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_stock_holds_day ON stock_holds(product_id, stock_day, expires_at);
+
+ALTER TABLE orders ADD COLUMN stock_day TEXT;
@@ -0,0 +1,47 @@
+-- ============================================================================
+-- Time-based menus
+-- ============================================================================
+-- menus: a named set of dishes that is only orderable at certain times, e.g.
+--   the weekday lunch menu. Inactive menus are never open.
+-- menu_windows: when a menu is open, in local time. weekdays is a
+--   comma-separated list (0 = Monday ... 6 = Sunday, NULL = every day);
+--   starts_at/ends_at bound the time of day ([starts_at, ends_at), NULL = all
+--   day); starts_on/ends_on bound the dates (inclusive, NULL = open-ended).
+--   A menu without windows is open whenever it is active.
+-- menu_products / menu_categories: what a menu contains, by dish or by whole
+--   category. A dish on no menu is orderable at any time; a dish on one or
+--   more menus only while at least one of them is open.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS menus (
+  id TEXT PRIMARY KEY,
+  name TEXT NOT NULL UNIQUE,
+  description TEXT,
+  is_active INTEGER NOT NULL DEFAULT 1,
+  sort_order INTEGER NOT NULL DEFAULT 0,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE TABLE IF NOT EXISTS menu_windows (
+  id TEXT PRIMARY KEY,
+  menu_id TEXT NOT NULL REFERENCES menus(id) ON DELETE CASCADE,
+  weekdays TEXT,
+  starts_at TEXT,
+  ends_at TEXT,
+  starts_on TEXT,
+  ends_on TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_menu_windows_menu ON menu_windows(menu_id);
+
+CREATE TABLE IF NOT EXISTS menu_products (
+  menu_id TEXT NOT NULL REFERENCES menus(id) ON DELETE CASCADE,
+  product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  PRIMARY KEY (menu_id, product_id)
+);
+
+CREATE TABLE IF NOT EXISTS menu_categories (
+  menu_id TEXT NOT NULL REFERENCES menus(id) ON DELETE CASCADE,
+  category TEXT NOT NULL,
+  PRIMARY KEY (menu_id, category)
+);
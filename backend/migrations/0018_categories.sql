@@ -0,0 +1,63 @@
+-- ============================================================================
+-- Menu categories
+-- ============================================================================
+-- categories: the menu sections with their display order, German name,
+--   description, image and visibility. A hidden category hides its dishes.
+-- category_translations: English and Vietnamese name/description; missing
+--   rows or NULL columns fall back to the German text.
+-- products.category_id: the section a dish is listed under. products.category
+--   keeps the German name in sync for older readers.
+-- product_translations.category is dropped; the seeded section names move to
+--   category_translations.
+-- Backfill: one category per distinct products.category, in menu order.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS categories (
+  id TEXT PRIMARY KEY,
+  name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+  description TEXT,
+  image_url TEXT,
+  sort_order INTEGER NOT NULL DEFAULT 0,
+  is_visible INTEGER NOT NULL DEFAULT 1,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE TABLE IF NOT EXISTS category_translations (
+  category_id TEXT NOT NULL REFERENCES categories(id) ON DELETE CASCADE,
+  locale TEXT NOT NULL CHECK (locale IN ('en', 'vi')),
+  name TEXT,
+  description TEXT,
+  PRIMARY KEY (category_id, locale)
+);
+
+ALTER TABLE products ADD COLUMN category_id TEXT REFERENCES categories(id);
+CREATE INDEX IF NOT EXISTS idx_products_category_id ON products(category_id);
+
+INSERT OR IGNORE INTO categories (id, name, sort_order)
+SELECT lower(replace(trim(category), ' ', '-')), trim(category),
+  CASE trim(category)
+    WHEN 'Aperitif' THEN 10
+    WHEN 'Vorspeisen' THEN 20
+    WHEN 'Suppen' THEN 30
+    WHEN 'Hauptspeisen' THEN 40
+    WHEN 'Extra' THEN 50
+    WHEN 'Dessert' THEN 60
+    ELSE 100
+  END
+FROM products
+WHERE category IS NOT NULL AND trim(category) <> ''
+GROUP BY trim(category);
+
+UPDATE products
+SET category_id = (SELECT c.id FROM categories c WHERE c.name = trim(products.category))
+WHERE category IS NOT NULL AND trim(category) <> '';
+
+INSERT OR IGNORE INTO category_translations (category_id, locale, name)
+SELECT p.category_id, t.locale, MIN(t.category)
+FROM product_translations t
+JOIN products p ON p.id = t.product_id
+WHERE t.category IS NOT NULL AND p.category_id IS NOT NULL
+GROUP BY p.category_id, t.locale;
+
+DELETE FROM product_translations WHERE name IS NULL AND description IS NULL;
+ALTER TABLE product_translations DROP COLUMN category;
@@ -0,0 +1,28 @@
+-- ============================================================================
+-- Uploaded product images
+-- ============================================================================
+-- product_images: the uploaded photo of a product (one per product), with the
+--   size of the original after EXIF rotation.
+-- product_image_variants: the resized copies on disk, one per width and
+--   format (webp, jpeg), named <image id>-<width>.<ext> in the media directory.
+-- products.image_url points at the medium JPEG variant of an upload; links
+--   set by hand keep working as before.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS product_images (
+  id TEXT PRIMARY KEY,
+  product_id TEXT NOT NULL UNIQUE REFERENCES products(id) ON DELETE CASCADE,
+  width INTEGER NOT NULL,
+  height INTEGER NOT NULL,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE TABLE IF NOT EXISTS product_image_variants (
+  image_id TEXT NOT NULL REFERENCES product_images(id) ON DELETE CASCADE,
+  format TEXT NOT NULL CHECK (format IN ('webp', 'jpeg')),
+  width INTEGER NOT NULL,
+  height INTEGER NOT NULL,
+  file_name TEXT NOT NULL UNIQUE,
+  bytes INTEGER NOT NULL,
+  PRIMARY KEY (image_id, format, width)
+);
@@ -0,0 +1,29 @@
+-- ============================================================================
+-- Coupon rules
+-- ============================================================================
+-- coupons.starts_at / expires_at: validity window (UTC, RFC 3339); NULL means
+--   open-ended. expires_at itself is no longer valid.
+-- coupons.min_subtotal_cents: cart subtotal needed before the code applies.
+-- coupons.max_discount_cents: cap on what a single order can save.
+-- coupons.is_scoped: the code only discounts the products and categories
+--   listed in coupon_products / coupon_categories. Kept as a flag so a scoped
+--   code whose dishes were all deleted does not turn into a storewide one.
+-- ============================================================================
+
+ALTER TABLE coupons ADD COLUMN starts_at TEXT;
+ALTER TABLE coupons ADD COLUMN expires_at TEXT;
+ALTER TABLE coupons ADD COLUMN min_subtotal_cents INTEGER;
+ALTER TABLE coupons ADD COLUMN max_discount_cents INTEGER;
+ALTER TABLE coupons ADD COLUMN is_scoped INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS coupon_products (
+  code TEXT NOT NULL REFERENCES coupons(code) ON DELETE CASCADE,
+  product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+  PRIMARY KEY (code, product_id)
+);
+
+CREATE TABLE IF NOT EXISTS coupon_categories (
+  code TEXT NOT NULL REFERENCES coupons(code) ON DELETE CASCADE,
+  category_id TEXT NOT NULL REFERENCES categories(id) ON DELETE CASCADE,
+  PRIMARY KEY (code, category_id)
+);
@@ -0,0 +1,38 @@
+-- ============================================================================
+-- Coupon redemptions
+-- ============================================================================
+-- coupon_redemptions: one row per paid order that used a coupon, with who
+--   used it (user id and/or email) and how much it took off. No foreign key
+--   to coupons so the history survives deleting a code. reversed_at is set
+--   when the order is fully refunded; reversed rows do not count as uses.
+-- coupons.max_uses_per_customer: uses allowed per customer (NULL = no limit).
+-- coupons.first_order_only: only customers without an earlier order qualify.
+-- Backfill: existing coupon orders, so limits also cover past orders.
+-- ============================================================================
+
+ALTER TABLE coupons ADD COLUMN max_uses_per_customer INTEGER;
+ALTER TABLE coupons ADD COLUMN first_order_only INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS coupon_redemptions (
+  id TEXT PRIMARY KEY,
+  code TEXT NOT NULL,
+  order_id TEXT NOT NULL UNIQUE REFERENCES orders(id),
+  user_id TEXT,
+  email TEXT,
+  discount_cents INTEGER NOT NULL,
+  redeemed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  reversed_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_coupon_redemptions_code ON coupon_redemptions(code);
+CREATE INDEX IF NOT EXISTS idx_coupon_redemptions_user ON coupon_redemptions(user_id);
+CREATE INDEX IF NOT EXISTS idx_coupon_redemptions_email ON coupon_redemptions(email COLLATE NOCASE);
+
+INSERT OR IGNORE INTO coupon_redemptions (id, code, order_id, user_id, email, discount_cents, redeemed_at, reversed_at)
+SELECT lower(hex(randomblob(16))), upper(o.coupon_code), o.id, o.user_id, NULLIF(o.email, ''),
+       COALESCE(json_extract(o.items_json, '$.discount_cents'), 0), o.created_at,
+       CASE WHEN o.status = 'refunded' THEN o.created_at END
+FROM orders o
+WHERE o.coupon_code IS NOT NULL
+  AND COALESCE(json_extract(o.items_json, '$.discount_kind'), 'coupon') = 'coupon'
+  AND EXISTS (SELECT 1 FROM coupons c WHERE c.code = upper(o.coupon_code));
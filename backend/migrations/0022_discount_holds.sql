@@ -0,0 +1,23 @@
+-- ============================================================================
+-- Discount holds
+-- ============================================================================
+-- discount_holds: the coupon use or gift-code balance a checkout is about to
+--   spend, held against its pending order until the payment is finalized.
+--   Only holds whose expires_at lies in the future count against the code.
+--   Deleting the pending order (finalized, cancelled, cleaned up) releases
+--   its hold. user_id/email let per-customer coupon limits see checkouts that
+--   are still in flight.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS discount_holds (
+  pending_order_id TEXT PRIMARY KEY REFERENCES pending_orders(order_id) ON DELETE CASCADE,
+  kind TEXT NOT NULL CHECK (kind IN ('coupon', 'gift_code')),
+  code TEXT NOT NULL,
+  amount_cents INTEGER NOT NULL DEFAULT 0,
+  user_id TEXT,
+  email TEXT,
+  expires_at TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_discount_holds_code ON discount_holds(kind, code, expires_at);
@@ -0,0 +1,23 @@
+-- ============================================================================
+-- Coupon campaigns
+-- ============================================================================
+-- coupon_campaigns: a batch of generated single-use codes (flyers, partner
+--   promotions) with the template they were generated from.
+-- coupons.campaign_id: the campaign a generated code belongs to; its rules are
+--   copied onto every code. Deleting a campaign deletes its codes; the
+--   redemption history stays.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS coupon_campaigns (
+  id TEXT PRIMARY KEY,
+  name TEXT NOT NULL,
+  description TEXT,
+  prefix TEXT NOT NULL DEFAULT '',
+  code_length INTEGER NOT NULL,
+  created_by TEXT,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+ALTER TABLE coupons ADD COLUMN campaign_id TEXT REFERENCES coupon_campaigns(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS idx_coupons_campaign ON coupons(campaign_id);
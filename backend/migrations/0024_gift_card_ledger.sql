@@ -0,0 +1,74 @@
+-- ============================================================================
+-- Gift card ledger
+-- ============================================================================
+-- gift_card_transactions: append-only history of every gift card balance
+--   change (issue, bonus, redeem, refund, adjustment). amount_cents is signed:
+--   redemptions are negative. Rows can be neither updated nor deleted, and
+--   cards with history cannot be deleted either.
+-- gift_codes.remaining_cents: now only a cached sum of the card's ledger,
+--   rewritten by gift_cards::post in the same transaction as each entry.
+-- Backfill: the issue and bonus of each existing card from its purchase order,
+--   its gift-code orders (and their refunds), then one adjustment so the
+--   ledger adds up to the current balance. Cards deleted once spent are gone.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS gift_card_transactions (
+  id TEXT PRIMARY KEY,
+  code TEXT NOT NULL REFERENCES gift_codes(code),
+  kind TEXT NOT NULL CHECK (kind IN ('issue', 'bonus', 'redeem', 'refund', 'adjustment')),
+  amount_cents INTEGER NOT NULL,
+  order_id TEXT,
+  note TEXT,
+  created_by TEXT,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_gift_card_transactions_code ON gift_card_transactions(code, created_at);
+CREATE INDEX IF NOT EXISTS idx_gift_card_transactions_order ON gift_card_transactions(order_id);
+
+CREATE TRIGGER IF NOT EXISTS gift_card_transactions_no_update
+BEFORE UPDATE ON gift_card_transactions
+BEGIN
+  SELECT RAISE(ABORT, 'gift card transactions are append-only');
+END;
+
+CREATE TRIGGER IF NOT EXISTS gift_card_transactions_no_delete
+BEFORE DELETE ON gift_card_transactions
+BEGIN
+  SELECT RAISE(ABORT, 'gift card transactions are append-only');
+END;
+
+-- Issue: the paid amount of the purchase order, or the full value without one
+INSERT INTO gift_card_transactions (id, code, kind, amount_cents, order_id, created_by, created_at)
+SELECT lower(hex(randomblob(16))), g.code, 'issue',
+       COALESCE(json_extract(o.items_json, '$.base_amount_cents'), g.value_cents), o.id, 'system', g.created_at
+FROM gift_codes g
+LEFT JOIN orders o ON json_extract(o.items_json, '$.type') = 'gift_coupon' AND json_extract(o.items_json, '$.code') = g.code;
+
+INSERT INTO gift_card_transactions (id, code, kind, amount_cents, order_id, created_by, created_at)
+SELECT lower(hex(randomblob(16))), g.code, 'bonus', json_extract(o.items_json, '$.bonus_cents'), o.id, 'system', g.created_at
+FROM gift_codes g
+JOIN orders o ON json_extract(o.items_json, '$.type') = 'gift_coupon' AND json_extract(o.items_json, '$.code') = g.code
+WHERE COALESCE(json_extract(o.items_json, '$.bonus_cents'), 0) > 0;
+
+INSERT INTO gift_card_transactions (id, code, kind, amount_cents, order_id, created_by, created_at)
+SELECT lower(hex(randomblob(16))), g.code, 'redeem', -json_extract(o.items_json, '$.discount_cents'), o.id, 'system', o.created_at
+FROM gift_codes g
+JOIN orders o ON o.coupon_code = g.code COLLATE NOCASE
+WHERE json_extract(o.items_json, '$.discount_kind') = 'gift_code'
+  AND COALESCE(json_extract(o.items_json, '$.discount_cents'), 0) > 0;
+
+INSERT INTO gift_card_transactions (id, code, kind, amount_cents, order_id, created_by, created_at)
+SELECT lower(hex(randomblob(16))), g.code, 'refund', json_extract(o.items_json, '$.discount_cents'), o.id, 'system', o.created_at
+FROM gift_codes g
+JOIN orders o ON o.coupon_code = g.code COLLATE NOCASE
+WHERE o.status = 'refunded'
+  AND json_extract(o.items_json, '$.discount_kind') = 'gift_code'
+  AND COALESCE(json_extract(o.items_json, '$.discount_cents'), 0) > 0;
+
+INSERT INTO gift_card_transactions (id, code, kind, amount_cents, note, created_by)
+SELECT lower(hex(randomblob(16))), g.code, 'adjustment',
+       g.remaining_cents - (SELECT COALESCE(SUM(t.amount_cents), 0) FROM gift_card_transactions t WHERE t.code = g.code),
+       'balance carried over into the ledger', 'system'
+FROM gift_codes g
+WHERE g.remaining_cents != (SELECT COALESCE(SUM(t.amount_cents), 0) FROM gift_card_transactions t WHERE t.code = g.code);
@@ -0,0 +1,11 @@
+-- ============================================================================
+-- Order discount issues
+-- ============================================================================
+-- orders.discount_issue: set when an order was paid with a discount whose
+--   hold had lapsed and whose coupon use or gift-code balance was gone by the
+--   time the payment came in. The order keeps its price; staff see the note
+--   and settle the difference with the customer. Also set when the captured
+--   amount differs from the order total. NULL for every other order.
+-- ============================================================================
+
+ALTER TABLE orders ADD COLUMN discount_issue TEXT;
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use serde::Serialize;
+use sqlx::{Row, SqliteConnection, SqlitePool};
+
+/// One of the 14 EU allergens, keyed by its menu letter.
+#[derive(Serialize, Clone, Debug)]
+pub struct Allergen {
+    pub code: String,
+    pub name_de: String,
+    pub name_en: String,
+}
+
+/// A declarable additive, keyed by its footnote number.
+#[derive(Serialize, Clone, Debug)]
+pub struct Additive {
+    pub code: String,
+    pub name_de: String,
+    pub name_en: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AllergenError {
+    #[error("unknown allergen code(s): {}", .0.join(", "))]
+    UnknownAllergen(Vec<String>),
+    #[error("unknown additive code(s): {}", .0.join(", "))]
+    UnknownAdditive(Vec<String>),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for AllergenError {
+    fn into_response(self) -> Response {
+        let (status, code, codes) = match self {
+            AllergenError::UnknownAllergen(ref c) => (StatusCode::BAD_REQUEST, "unknown_allergen", c.clone()),
+            AllergenError::UnknownAdditive(ref c) => (StatusCode::BAD_REQUEST, "unknown_additive", c.clone()),
+            AllergenError::Database(ref e) => {
+                tracing::error!("Allergen lookup failed: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error", Vec::new())
+            }
+        };
+        (status, Json(serde_json::json!({ "error": code, "message": self.to_string(), "codes": codes }))).into_response()
+    }
+}
+
+/// Split "a, F,g" into distinct, lowercased codes.
+pub fn split_codes(raw: &str) -> Vec<String> {
+    let mut codes: Vec<String> = Vec::new();
+    for code in raw.split(',').map(|c| c.trim().to_lowercase()).filter(|c| !c.is_empty()) {
+        if !codes.contains(&code) {
+            codes.push(code);
+        }
+    }
+    codes
+}
+
+/// Known codes of `table` in menu order, and the unknown ones.
+async fn check_codes(pool: &SqlitePool, table: &str, raw: &str) -> Result<(Vec<String>, Vec<String>), sqlx::Error> {
+    let known: Vec<String> = sqlx::query_scalar(&format!("SELECT code FROM {} ORDER BY sort_order", table))
+        .fetch_all(pool)
+        .await?;
+    let codes = split_codes(raw);
+    let unknown = codes.iter().filter(|c| !known.contains(c)).cloned().collect();
+    Ok((known.into_iter().filter(|k| codes.contains(k)).collect(), unknown))
+}
+
+/// Validate comma-separated allergen letters, e.g. from the admin form or a filter.
+pub async fn parse_allergens(pool: &SqlitePool, raw: &str) -> Result<Vec<String>, AllergenError> {
+    let (codes, unknown) = check_codes(pool, "allergens", raw).await?;
+    if !unknown.is_empty() {
+        return Err(AllergenError::UnknownAllergen(unknown));
+    }
+    Ok(codes)
+}
+
+/// Validate comma-separated additive numbers.
+pub async fn parse_additives(pool: &SqlitePool, raw: &str) -> Result<Vec<String>, AllergenError> {
+    let (codes, unknown) = check_codes(pool, "additives", raw).await?;
+    if !unknown.is_empty() {
+        return Err(AllergenError::UnknownAdditive(unknown));
+    }
+    Ok(codes)
+}
+
+/// Replace a product's allergens with already validated codes and keep the
+/// legacy `products.allergens` column in step.
+pub async fn set_allergens(conn: &mut SqliteConnection, product_id: &str, codes: &[String]) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"DELETE FROM product_allergens WHERE product_id = ?"#).bind(product_id).execute(&mut *conn).await?;
+    for code in codes {
+        sqlx::query(r#"INSERT INTO product_allergens (product_id, allergen_code) VALUES (?, ?)"#)
+            .bind(product_id)
+            .bind(code)
+            .execute(&mut *conn)
+            .await?;
+    }
+    sqlx::query(r#"UPDATE products SET allergens = ? WHERE id = ?"#)
+        .bind((!codes.is_empty()).then(|| codes.join(",")))
+        .bind(product_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Replace a product's additives; see `set_allergens`.
+pub async fn set_additives(conn: &mut SqliteConnection, product_id: &str, codes: &[String]) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"DELETE FROM product_additives WHERE product_id = ?"#).bind(product_id).execute(&mut *conn).await?;
+    for code in codes {
+        sqlx::query(r#"INSERT INTO product_additives (product_id, additive_code) VALUES (?, ?)"#)
+            .bind(product_id)
+            .bind(code)
+            .execute(&mut *conn)
+            .await?;
+    }
+    sqlx::query(r#"UPDATE products SET additives = ? WHERE id = ?"#)
+        .bind((!codes.is_empty()).then(|| codes.join(",")))
+        .bind(product_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+pub async fn list_allergens(pool: &SqlitePool) -> Result<Vec<Allergen>, sqlx::Error> {
+    let rows = sqlx::query(r#"SELECT code, name_de, name_en FROM allergens ORDER BY sort_order"#).fetch_all(pool).await?;
+    Ok(rows.into_iter().map(|r| Allergen {
+        code: r.try_get("code").unwrap_or_default(),
+        name_de: r.try_get("name_de").unwrap_or_default(),
+        name_en: r.try_get("name_en").unwrap_or_default(),
+    }).collect())
+}
+
+pub async fn list_additives(pool: &SqlitePool) -> Result<Vec<Additive>, sqlx::Error> {
+    let rows = sqlx::query(r#"SELECT code, name_de, name_en FROM additives ORDER BY sort_order"#).fetch_all(pool).await?;
+    Ok(rows.into_iter().map(|r| Additive {
+        code: r.try_get("code").unwrap_or_default(),
+        name_de: r.try_get("name_de").unwrap_or_default(),
+        name_en: r.try_get("name_en").unwrap_or_default(),
+    }).collect())
+}
+
+/// Resolved allergens of every product that declares any.
+pub async fn allergens_by_product(pool: &SqlitePool) -> Result<HashMap<String, Vec<Allergen>>, sqlx::Error> {
+    let rows = sqlx::query(r#"
+        SELECT pa.product_id, a.code, a.name_de, a.name_en
+        FROM product_allergens pa
+        JOIN allergens a ON a.code = pa.allergen_code
+        ORDER BY pa.product_id, a.sort_order
+    "#)
+        .fetch_all(pool)
+        .await?;
+    let mut by_product: HashMap<String, Vec<Allergen>> = HashMap::new();
+    for r in rows {
+        by_product.entry(r.try_get("product_id")?).or_default().push(Allergen {
+            code: r.try_get("code")?,
+            name_de: r.try_get("name_de")?,
+            name_en: r.try_get("name_en")?,
+        });
+    }
+    Ok(by_product)
+}
+
+/// Resolved additives of every product that declares any.
+pub async fn additives_by_product(pool: &SqlitePool) -> Result<HashMap<String, Vec<Additive>>, sqlx::Error> {
+    let rows = sqlx::query(r#"
+        SELECT pa.product_id, a.code, a.name_de, a.name_en
+        FROM product_additives pa
+        JOIN additives a ON a.code = pa.additive_code
+        ORDER BY pa.product_id, a.sort_order
+    "#)
+        .fetch_all(pool)
+        .await?;
+    let mut by_product: HashMap<String, Vec<Additive>> = HashMap::new();
+    for r in rows {
+        by_product.entry(r.try_get("product_id")?).or_default().push(Additive {
+            code: r.try_get("code")?,
+            name_de: r.try_get("name_de")?,
+            name_en: r.try_get("name_en")?,
+        });
+    }
+    Ok(by_product)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{add_product, mock_state};
+
+    #[test]
+    fn codes_are_split_trimmed_and_deduplicated() {
+        assert_eq!(split_codes(" a, F,g,,f "), ["a", "f", "g"]);
+        assert!(split_codes(" , ").is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_codes_are_named_and_known_ones_kept_in_menu_order() {
+        let pool = mock_state().await.pool.clone();
+        assert_eq!(parse_allergens(&pool, "g, A, c").await.unwrap(), ["a", "c", "g"]);
+        assert!(matches!(parse_allergens(&pool, "a, s, t").await, Err(AllergenError::UnknownAllergen(c)) if c == ["s", "t"]));
+        assert_eq!(parse_additives(&pool, "2,1").await.unwrap(), ["1", "2"]);
+        assert!(matches!(parse_additives(&pool, "a").await, Err(AllergenError::UnknownAdditive(_))));
+        assert_eq!(list_allergens(&pool).await.unwrap().len(), 14);
+    }
+
+    #[tokio::test]
+    async fn product_links_and_legacy_columns_move_together() {
+        let pool = mock_state().await.pool.clone();
+        add_product(&pool, "pho", 1250).await;
+        let codes = parse_allergens(&pool, "g,a").await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        set_allergens(&mut conn, "pho", &codes).await.unwrap();
+        set_additives(&mut conn, "pho", &["1".to_string()]).await.unwrap();
+        drop(conn);
+
+        let legacy: (Option<String>, Option<String>) = sqlx::query_as(r#"SELECT allergens, additives FROM products WHERE id = 'pho'"#).fetch_one(&pool).await.unwrap();
+        assert_eq!(legacy, (Some("a,g".into()), Some("1".into())));
+        let allergens = allergens_by_product(&pool).await.unwrap();
+        let names: Vec<&str> = allergens["pho"].iter().map(|a| a.name_en.as_str()).collect();
+        assert_eq!(names, ["Cereals containing gluten", "Milk and lactose"]);
+        assert_eq!(additives_by_product(&pool).await.unwrap()["pho"].len(), 1);
+
+        let mut conn = pool.acquire().await.unwrap();
+        set_allergens(&mut conn, "pho", &[]).await.unwrap();
+        drop(conn);
+        let cleared: Option<String> = sqlx::query_scalar(r#"SELECT allergens FROM products WHERE id = 'pho'"#).fetch_one(&pool).await.unwrap();
+        assert_eq!(cleared, None);
+        assert!(!allergens_by_product(&pool).await.unwrap().contains_key("pho"));
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+
+use crate::catalog::translations::{translated_locale, Locale};
+
+/// Translated text of a category; `None` falls back to the German text.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CategoryText {
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+/// A menu section. `name` and `description` are German.
+#[derive(Serialize, Clone, Debug)]
+pub struct Category {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub sort_order: i64,
+    /// Hidden categories and their dishes are left out of the menu.
+    pub is_visible: bool,
+    /// Keyed by locale (`en`, `vi`).
+    pub translations: HashMap<String, CategoryText>,
+}
+
+impl Category {
+    /// The category as shown to guests in `locale`.
+    pub fn localized(&self, locale: Locale) -> LocalizedCategory {
+        let t = self.translations.get(locale.as_str()).cloned().unwrap_or_default();
+        LocalizedCategory {
+            id: self.id.clone(),
+            name: t.name.unwrap_or_else(|| self.name.clone()),
+            description: t.description.or_else(|| self.description.clone()),
+            image_url: self.image_url.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LocalizedCategory {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CategoryInput {
+    /// Only used on create; defaults to a slug of the name.
+    pub id: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    /// Defaults to the end of the menu on create.
+    pub sort_order: Option<i64>,
+    pub is_visible: Option<bool>,
+    /// Replaces the translations when given.
+    pub translations: Option<HashMap<String, CategoryText>>,
+}
+
+/// The category a product is filed under.
+pub struct CategoryRef {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CategoryError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("unknown category '{0}'")]
+    Unknown(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for CategoryError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            CategoryError::Invalid(_) => (StatusCode::BAD_REQUEST, "invalid_category"),
+            CategoryError::Unknown(_) => (StatusCode::BAD_REQUEST, "unknown_category"),
+            CategoryError::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            CategoryError::Conflict(_) => (StatusCode::CONFLICT, "category_conflict"),
+            CategoryError::Database(ref e) => {
+                tracing::error!("Category update failed: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            }
+        };
+        (status, Json(serde_json::json!({ "error": code, "message": self.to_string() }))).into_response()
+    }
+}
+
+/// "Hauptspeisen & Co." -> "hauptspeisen-co"
+fn slug(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Categories in menu order, optionally only the visible ones.
+pub async fn list_categories(pool: &SqlitePool, visible_only: bool) -> Result<Vec<Category>, sqlx::Error> {
+    let mut translations: HashMap<String, HashMap<String, CategoryText>> = HashMap::new();
+    for r in sqlx::query(r#"SELECT category_id, locale, name, description FROM category_translations"#).fetch_all(pool).await? {
+        translations.entry(r.try_get("category_id")?).or_default().insert(r.try_get("locale")?, CategoryText {
+            name: r.try_get("name")?,
+            description: r.try_get("description")?,
+        });
+    }
+    let rows = sqlx::query(r#"SELECT id, name, description, image_url, sort_order, is_visible FROM categories WHERE ?1 = 0 OR is_visible = 1 ORDER BY sort_order, name"#)
+        .bind(visible_only)
+        .fetch_all(pool)
+        .await?;
+    let mut categories = Vec::with_capacity(rows.len());
+    for r in rows {
+        let id: String = r.try_get("id")?;
+        categories.push(Category {
+            name: r.try_get("name")?,
+            description: r.try_get("description")?,
+            image_url: r.try_get("image_url")?,
+            sort_order: r.try_get("sort_order")?,
+            is_visible: r.try_get("is_visible")?,
+            translations: translations.remove(&id).unwrap_or_default(),
+            id,
+        });
+    }
+    Ok(categories)
+}
+
+/// Look up the category a product names by id or (case-insensitive) name;
+/// blank means uncategorized.
+pub async fn resolve(pool: &SqlitePool, raw: &str) -> Result<Option<CategoryRef>, CategoryError> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Ok(None);
+    }
+    let row = sqlx::query(r#"SELECT id, name FROM categories WHERE id = ?1 OR name = ?1 COLLATE NOCASE LIMIT 1"#)
+        .bind(raw)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| CategoryError::Unknown(raw.to_string()))?;
+    Ok(Some(CategoryRef { id: row.try_get("id")?, name: row.try_get("name")? }))
+}
+
+fn map_name_conflict(e: sqlx::Error, name: &str) -> CategoryError {
+    match e {
+        sqlx::Error::Database(ref db) if db.is_unique_violation() => CategoryError::Conflict(format!("a category named '{}' already exists", name)),
+        e => CategoryError::Database(e),
+    }
+}
+
+fn validate_category(input: &CategoryInput) -> Result<String, CategoryError> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(CategoryError::Invalid("name must not be empty".into()));
+    }
+    Ok(name.to_string())
+}
+
+/// Replace the translations of a category where the input gives them.
+async fn set_translations(tx: &mut SqliteConnection, category_id: &str, input: &CategoryInput) -> Result<(), CategoryError> {
+    let Some(ref translations) = input.translations else { return Ok(()) };
+    sqlx::query(r#"DELETE FROM category_translations WHERE category_id = ?"#).bind(category_id).execute(&mut *tx).await?;
+    let blank_to_none = |v: &Option<String>| v.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string);
+    for (locale, text) in translations {
+        let locale = translated_locale(locale).map_err(|e| CategoryError::Invalid(e.to_string()))?;
+        sqlx::query(r#"INSERT INTO category_translations (category_id, locale, name, description) VALUES (?, ?, ?, ?)"#)
+            .bind(category_id)
+            .bind(locale.as_str())
+            .bind(blank_to_none(&text.name))
+            .bind(blank_to_none(&text.description))
+            .execute(&mut *tx)
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn create_category(pool: &SqlitePool, input: &CategoryInput) -> Result<String, CategoryError> {
+    let name = validate_category(input)?;
+    let id = slug(input.id.as_deref().unwrap_or(&name));
+    if id.is_empty() {
+        return Err(CategoryError::Invalid("id must contain letters or digits".into()));
+    }
+    let taken: Option<i64> = sqlx::query_scalar(r#"SELECT 1 FROM categories WHERE id = ?"#).bind(&id).fetch_optional(pool).await?;
+    if taken.is_some() {
+        return Err(CategoryError::Conflict(format!("a category with id '{}' already exists", id)));
+    }
+    let mut tx = pool.begin().await?;
+    sqlx::query(r#"
+        INSERT INTO categories (id, name, description, image_url, sort_order, is_visible)
+        VALUES (?, ?, ?, ?, COALESCE(?, (SELECT COALESCE(MAX(sort_order), 0) + 10 FROM categories)), ?)
+    "#)
+        .bind(&id)
+        .bind(&name)
+        .bind(input.description.as_deref())
+        .bind(input.image_url.as_deref())
+        .bind(input.sort_order)
+        .bind(input.is_visible.unwrap_or(true))
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| map_name_conflict(e, &name))?;
+    set_translations(&mut tx, &id, input).await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Update a category; a new name is carried over to its products and to
+/// menus that list the category by name.
+pub async fn update_category(pool: &SqlitePool, id: &str, input: &CategoryInput) -> Result<(), CategoryError> {
+    let name = validate_category(input)?;
+    let mut tx = pool.begin().await?;
+    let old_name: String = sqlx::query_scalar(r#"SELECT name FROM categories WHERE id = ?"#)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| CategoryError::NotFound(format!("category {}", id)))?;
+    sqlx::query(r#"UPDATE categories SET name = ?, description = ?, image_url = ?, sort_order = COALESCE(?, sort_order), is_visible = COALESCE(?, is_visible) WHERE id = ?"#)
+        .bind(&name)
+        .bind(input.description.as_deref())
+        .bind(input.image_url.as_deref())
+        .bind(input.sort_order)
+        .bind(input.is_visible)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| map_name_conflict(e, &name))?;
+    if old_name != name {
+        sqlx::query(r#"UPDATE products SET category = ? WHERE category_id = ?"#).bind(&name).bind(id).execute(&mut *tx).await?;
+        sqlx::query(r#"UPDATE OR IGNORE menu_categories SET category = ? WHERE category = ?"#).bind(&name).bind(&old_name).execute(&mut *tx).await?;
+    }
+    set_translations(&mut tx, id, input).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Delete an empty category; dishes have to be moved elsewhere first.
+pub async fn delete_category(pool: &SqlitePool, id: &str) -> Result<(), CategoryError> {
+    let products: i64 = sqlx::query_scalar(r#"SELECT COUNT(*) FROM products WHERE category_id = ?"#).bind(id).fetch_one(pool).await?;
+    if products > 0 {
+        return Err(CategoryError::Conflict(format!("category {} still has {} product(s)", id, products)));
+    }
+    let deleted = sqlx::query(r#"DELETE FROM categories WHERE id = ?"#).bind(id).execute(pool).await?.rows_affected();
+    if deleted == 0 {
+        return Err(CategoryError::NotFound(format!("category {}", id)));
+    }
+    Ok(())
+}
+
+/// Put the given categories first, in that order; the rest keep their
+/// relative order behind them.
+pub async fn reorder(pool: &SqlitePool, ids: &[String]) -> Result<(), CategoryError> {
+    let mut tx = pool.begin().await?;
+    let current: Vec<String> = sqlx::query_scalar(r#"SELECT id FROM categories ORDER BY sort_order, name"#).fetch_all(&mut *tx).await?;
+    let mut ordered: Vec<&String> = Vec::with_capacity(current.len());
+    for id in ids {
+        if ordered.contains(&id) {
+            return Err(CategoryError::Invalid(format!("category {} is listed twice", id)));
+        }
+        if !current.contains(id) {
+            return Err(CategoryError::Unknown(id.clone()));
+        }
+        ordered.push(id);
+    }
+    ordered.extend(current.iter().filter(|id| !ids.contains(id)));
+    for (position, id) in ordered.iter().enumerate() {
+        sqlx::query(r#"UPDATE categories SET sort_order = ? WHERE id = ?"#)
+            .bind((position as i64 + 1) * 10)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{add_product, mock_state};
+
+    fn category(name: &str) -> CategoryInput {
+        CategoryInput { id: None, name: name.into(), description: None, image_url: None, sort_order: None, is_visible: None, translations: None }
+    }
+
+    #[test]
+    fn ids_are_slugs_of_the_name() {
+        assert_eq!(slug("Hauptspeisen & Co."), "hauptspeisen-co");
+        assert_eq!(slug("  Bánh mì  "), "bánh-mì");
+        assert_eq!(slug("--"), "");
+    }
+
+    #[tokio::test]
+    async fn categories_are_created_resolved_and_translated() {
+        let pool = mock_state().await.pool.clone();
+        let translations = HashMap::from([("en".to_string(), CategoryText { name: Some("Noodle soups".into()), description: Some(" ".into()) })]);
+        let id = create_category(&pool, &CategoryInput { description: Some("Mit Reisnudeln".into()), translations: Some(translations), ..category("Nudelsuppen") })
+            .await
+            .unwrap();
+        assert_eq!(id, "nudelsuppen");
+
+        assert!(matches!(create_category(&pool, &category("  ")).await, Err(CategoryError::Invalid(_))));
+        assert!(matches!(create_category(&pool, &category("NUDELSUPPEN!")).await, Err(CategoryError::Conflict(_))));
+        assert!(matches!(create_category(&pool, &CategoryInput { id: Some("soups".into()), ..category("nudelsuppen") }).await, Err(CategoryError::Conflict(_))));
+        let bad_locale = HashMap::from([("fr".to_string(), CategoryText::default())]);
+        assert!(matches!(create_category(&pool, &CategoryInput { translations: Some(bad_locale), ..category("Salate") }).await, Err(CategoryError::Invalid(_))));
+
+        let found = resolve(&pool, " nudelSUPPEN ").await.unwrap().unwrap();
+        assert_eq!((found.id.as_str(), found.name.as_str()), ("nudelsuppen", "Nudelsuppen"));
+        assert!(resolve(&pool, "").await.unwrap().is_none());
+        assert!(matches!(resolve(&pool, "Salate").await, Err(CategoryError::Unknown(_))));
+
+        let listed = list_categories(&pool, true).await.unwrap().into_iter().find(|c| c.id == id).unwrap();
+        let english = listed.localized(Locale::En);
+        assert_eq!((english.name.as_str(), english.description.as_deref()), ("Noodle soups", Some("Mit Reisnudeln")));
+        assert_eq!(listed.localized(Locale::Vi).name, "Nudelsuppen");
+
+        update_category(&pool, &id, &CategoryInput { is_visible: Some(false), ..category("Nudelsuppen") }).await.unwrap();
+        assert!(list_categories(&pool, true).await.unwrap().iter().all(|c| c.id != id));
+        assert!(matches!(update_category(&pool, "salate", &category("Salate")).await, Err(CategoryError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn renames_follow_products_and_menus_and_full_categories_stay() {
+        let pool = mock_state().await.pool.clone();
+        add_product(&pool, "pho", 1250).await;
+        let id = create_category(&pool, &category("Suppen & Brühen")).await.unwrap();
+        sqlx::query(r#"UPDATE products SET category = 'Suppen & Brühen', category_id = ? WHERE id = 'pho'"#).bind(&id).execute(&pool).await.unwrap();
+        sqlx::query(r#"INSERT INTO menus (id, name) VALUES ('lunch', 'Lunch')"#).execute(&pool).await.unwrap();
+        sqlx::query(r#"INSERT INTO menu_categories (menu_id, category) VALUES ('lunch', 'Suppen & Brühen')"#).execute(&pool).await.unwrap();
+
+        update_category(&pool, &id, &category("Nudelsuppen")).await.unwrap();
+        let product: String = sqlx::query_scalar(r#"SELECT category FROM products WHERE id = 'pho'"#).fetch_one(&pool).await.unwrap();
+        let on_menu: String = sqlx::query_scalar(r#"SELECT category FROM menu_categories WHERE menu_id = 'lunch'"#).fetch_one(&pool).await.unwrap();
+        assert_eq!((product.as_str(), on_menu.as_str()), ("Nudelsuppen", "Nudelsuppen"));
+
+        assert!(matches!(delete_category(&pool, &id).await, Err(CategoryError::Conflict(_))));
+        sqlx::query(r#"UPDATE products SET category = NULL, category_id = NULL WHERE id = 'pho'"#).execute(&pool).await.unwrap();
+        delete_category(&pool, &id).await.unwrap();
+        assert!(matches!(delete_category(&pool, &id).await, Err(CategoryError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn reordering_puts_the_given_categories_first() {
+        let pool = mock_state().await.pool.clone();
+        for name in ["Vorspeisen", "Suppen", "Desserts"] {
+            create_category(&pool, &CategoryInput { id: Some(format!("test-{}", name)), ..category(&format!("Test {}", name)) }).await.unwrap();
+        }
+        let ids = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        reorder(&pool, &ids(&["test-desserts", "test-vorspeisen"])).await.unwrap();
+        let order: Vec<String> = list_categories(&pool, false).await.unwrap().into_iter().map(|c| c.id).filter(|id| id.starts_with("test-")).collect();
+        assert_eq!(order, ids(&["test-desserts", "test-vorspeisen", "test-suppen"]));
+        let first = list_categories(&pool, false).await.unwrap();
+        assert_eq!((first[0].id.as_str(), first[0].sort_order), ("test-desserts", 10));
+
+        assert!(matches!(reorder(&pool, &ids(&["test-suppen", "test-suppen"])).await, Err(CategoryError::Invalid(_))));
+        assert!(matches!(reorder(&pool, &ids(&["salate"])).await, Err(CategoryError::Unknown(_))));
+    }
+}
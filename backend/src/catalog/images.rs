@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use uuid::Uuid;
+
+/// Largest upload accepted.
+pub const MAX_UPLOAD_BYTES: usize = 10 * 1024 * 1024;
+/// Uploads must be at least this wide and high.
+const MIN_EDGE: u32 = 200;
+/// Larger images are rejected before decoding.
+const MAX_EDGE: u32 = 10_000;
+const JPEG_QUALITY: u8 = 82;
+const WEBP_QUALITY: f32 = 80.0;
+/// `products.image_url` gets the JPEG of this width (or the widest below it).
+const CARD_WIDTH: u32 = 640;
+
+/// Where uploaded images are kept and how they are linked.
+#[derive(Clone, Debug)]
+pub struct ImageConfig {
+    /// Variants are written to `<dir>/products`.
+    pub dir: PathBuf,
+    /// Public URL of `dir`, without a trailing slash.
+    pub base_url: String,
+    /// Widths of the resized copies; never wider than the upload.
+    pub widths: Vec<u32>,
+}
+
+impl ImageConfig {
+    pub fn from_env() -> Self {
+        let mut widths: Vec<u32> = std::env::var("MEDIA_IMAGE_WIDTHS")
+            .ok()
+            .map(|v| v.split(',').filter_map(|w| w.trim().parse().ok()).filter(|w| *w > 0).collect())
+            .filter(|w: &Vec<u32>| !w.is_empty())
+            .unwrap_or_else(|| vec![320, 640, 1280]);
+        widths.sort_unstable();
+        widths.dedup();
+        Self {
+            dir: PathBuf::from(std::env::var("MEDIA_DIR").unwrap_or_else(|_| "media".into())),
+            base_url: std::env::var("MEDIA_BASE_URL").unwrap_or_else(|_| "/api/media".into()).trim_end_matches('/').to_string(),
+            widths,
+        }
+    }
+
+    pub fn products_dir(&self) -> PathBuf {
+        self.dir.join("products")
+    }
+
+    pub fn url(&self, file_name: &str) -> String {
+        format!("{}/products/{}", self.base_url, file_name)
+    }
+}
+
+/// One resized copy of a product image.
+#[derive(Serialize, Clone, Debug)]
+pub struct ImageVariant {
+    /// `webp` or `jpeg`
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub url: String,
+}
+
+/// The uploaded photo of a product, e.g. for a `srcset`.
+#[derive(Serialize, Clone, Debug)]
+pub struct ProductImage {
+    pub id: String,
+    pub width: u32,
+    pub height: u32,
+    pub variants: Vec<ImageVariant>,
+}
+
+impl ProductImage {
+    /// The JPEG used as `products.image_url`.
+    pub fn card_url(&self) -> Option<String> {
+        self.variants
+            .iter()
+            .filter(|v| v.format == "jpeg")
+            .min_by_key(|v| (v.width > CARD_WIDTH, v.width.abs_diff(CARD_WIDTH)))
+            .map(|v| v.url.clone())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImageError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("image is larger than {} MB", MAX_UPLOAD_BYTES / 1024 / 1024)]
+    TooLarge,
+    #[error("unsupported image format, expected JPEG, PNG or WebP")]
+    Unsupported,
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("storage error: {0}")]
+    Storage(#[from] std::io::Error),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for ImageError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            ImageError::Invalid(_) => (StatusCode::BAD_REQUEST, "invalid_image"),
+            ImageError::TooLarge => (StatusCode::PAYLOAD_TOO_LARGE, "image_too_large"),
+            ImageError::Unsupported => (StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_image"),
+            ImageError::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            ImageError::Storage(ref e) => {
+                tracing::error!("Image storage failed: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            }
+            ImageError::Database(ref e) => {
+                tracing::error!("Image update failed: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            }
+        };
+        (status, Json(serde_json::json!({ "error": code, "message": self.to_string() }))).into_response()
+    }
+}
+
+/// A variant written to disk.
+struct StoredVariant {
+    format: &'static str,
+    width: u32,
+    height: u32,
+    file_name: String,
+    bytes: usize,
+}
+
+/// Decode an upload, upright according to its EXIF orientation.
+fn decode(bytes: &[u8]) -> Result<DynamicImage, ImageError> {
+    if bytes.len() > MAX_UPLOAD_BYTES {
+        return Err(ImageError::TooLarge);
+    }
+    let mut reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    if !matches!(reader.format(), Some(ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP)) {
+        return Err(ImageError::Unsupported);
+    }
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_EDGE);
+    limits.max_image_height = Some(MAX_EDGE);
+    reader.limits(limits);
+    let unreadable = |e: image::ImageError| ImageError::Invalid(format!("image could not be read: {}", e.to_string().trim()));
+    let mut decoder = reader.into_decoder().map_err(unreadable)?;
+    let orientation = decoder.orientation().map_err(unreadable)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(unreadable)?;
+    image.apply_orientation(orientation);
+    if image.width() < MIN_EDGE || image.height() < MIN_EDGE {
+        return Err(ImageError::Invalid(format!("image must be at least {0}x{0} pixels", MIN_EDGE)));
+    }
+    Ok(image)
+}
+
+/// Write a WebP and a JPEG copy of `image` per configured width into `dir`.
+/// Images are never upscaled: one narrower than the widest width gets a copy
+/// at its own size instead.
+fn write_variants(image: &DynamicImage, image_id: &str, widths: &[u32], dir: &Path) -> Result<Vec<StoredVariant>, ImageError> {
+    std::fs::create_dir_all(dir)?;
+    let mut targets: Vec<u32> = widths.iter().copied().filter(|w| *w < image.width()).collect();
+    targets.push(widths.iter().copied().max().unwrap_or(image.width()).min(image.width()));
+    targets.dedup();
+    let mut stored = Vec::new();
+    for width in targets {
+        let resized = if width == image.width() { image.clone() } else { image.resize(width, u32::MAX, FilterType::Lanczos3) };
+        let (w, h) = (resized.width(), resized.height());
+
+        let rgba = resized.to_rgba8();
+        let webp = webp::Encoder::from_rgba(&rgba, w, h).encode(WEBP_QUALITY);
+        let webp_name = format!("{}-{}.webp", image_id, w);
+        std::fs::write(dir.join(&webp_name), &*webp)?;
+        stored.push(StoredVariant { format: "webp", width: w, height: h, file_name: webp_name, bytes: webp.len() });
+
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY)
+            .encode_image(&DynamicImage::ImageRgb8(resized.to_rgb8()))
+            .map_err(|e| ImageError::Invalid(format!("image could not be converted: {}", e)))?;
+        let jpeg_name = format!("{}-{}.jpg", image_id, w);
+        std::fs::write(dir.join(&jpeg_name), &jpeg)?;
+        stored.push(StoredVariant { format: "jpeg", width: w, height: h, file_name: jpeg_name, bytes: jpeg.len() });
+    }
+    Ok(stored)
+}
+
+pub fn remove_files(dir: &Path, file_names: &[String]) {
+    for name in file_names {
+        if let Err(e) = std::fs::remove_file(dir.join(name)) {
+            tracing::warn!("Could not remove image file {}: {:?}", name, e);
+        }
+    }
+}
+
+/// Files of the uploaded image of a product, e.g. to remove them with the product.
+pub async fn variant_files(pool: &SqlitePool, product_id: &str) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar(r#"
+        SELECT v.file_name FROM product_image_variants v JOIN product_images i ON i.id = v.image_id WHERE i.product_id = ?
+    "#)
+        .bind(product_id)
+        .fetch_all(pool)
+        .await
+}
+
+/// Store an upload as the product's image and point `products.image_url` at
+/// its medium JPEG. Replaces (and deletes) a previous upload.
+pub async fn upload(pool: &SqlitePool, config: &ImageConfig, product_id: &str, bytes: Vec<u8>) -> Result<ProductImage, ImageError> {
+    let exists: Option<i64> = sqlx::query_scalar(r#"SELECT 1 FROM products WHERE id = ?"#).bind(product_id).fetch_optional(pool).await?;
+    if exists.is_none() {
+        return Err(ImageError::NotFound(format!("product {}", product_id)));
+    }
+
+    // Decoding and resizing are CPU bound
+    let image_id = Uuid::new_v4().simple().to_string();
+    let (dir, widths, id) = (config.products_dir(), config.widths.clone(), image_id.clone());
+    let (width, height, variants) = tokio::task::spawn_blocking(move || {
+        let image = decode(&bytes)?;
+        let variants = write_variants(&image, &id, &widths, &dir)?;
+        Ok::<_, ImageError>((image.width(), image.height(), variants))
+    })
+        .await
+        .map_err(|e| ImageError::Storage(std::io::Error::other(e)))??;
+    let new_files: Vec<String> = variants.iter().map(|v| v.file_name.clone()).collect();
+
+    let image = ProductImage {
+        id: image_id.clone(),
+        width,
+        height,
+        variants: variants.iter().map(|v| ImageVariant { format: v.format.to_string(), width: v.width, height: v.height, url: config.url(&v.file_name) }).collect(),
+    };
+    let card = image.card_url();
+    let old_files = variant_files(pool, product_id).await?;
+    let saved: Result<(), sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        sqlx::query(r#"DELETE FROM product_images WHERE product_id = ?"#).bind(product_id).execute(&mut *tx).await?;
+        sqlx::query(r#"INSERT INTO product_images (id, product_id, width, height) VALUES (?, ?, ?, ?)"#)
+            .bind(&image_id)
+            .bind(product_id)
+            .bind(width)
+            .bind(height)
+            .execute(&mut *tx)
+            .await?;
+        for v in &variants {
+            sqlx::query(r#"INSERT INTO product_image_variants (image_id, format, width, height, file_name, bytes) VALUES (?, ?, ?, ?, ?, ?)"#)
+                .bind(&image_id)
+                .bind(v.format)
+                .bind(v.width)
+                .bind(v.height)
+                .bind(&v.file_name)
+                .bind(v.bytes as i64)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query(r#"UPDATE products SET image_url = ? WHERE id = ?"#).bind(card).bind(product_id).execute(&mut *tx).await?;
+        tx.commit().await
+    }.await;
+    if let Err(e) = saved {
+        remove_files(&config.products_dir(), &new_files);
+        return Err(e.into());
+    }
+    remove_files(&config.products_dir(), &old_files);
+
+    Ok(image)
+}
+
+/// Remove the uploaded image of a product and clear `products.image_url`.
+pub async fn delete(pool: &SqlitePool, config: &ImageConfig, product_id: &str) -> Result<(), ImageError> {
+    let files = variant_files(pool, product_id).await?;
+    let mut tx = pool.begin().await?;
+    let deleted = sqlx::query(r#"DELETE FROM product_images WHERE product_id = ?"#).bind(product_id).execute(&mut *tx).await?.rows_affected();
+    if deleted == 0 {
+        return Err(ImageError::NotFound(format!("image of product {}", product_id)));
+    }
+    sqlx::query(r#"UPDATE products SET image_url = NULL WHERE id = ?"#).bind(product_id).execute(&mut *tx).await?;
+    tx.commit().await?;
+    remove_files(&config.products_dir(), &files);
+    Ok(())
+}
+
+/// Uploaded images of every product, keyed by product id; variants go from
+/// narrow to wide, WebP before JPEG.
+pub async fn by_product(pool: &SqlitePool, config: &ImageConfig) -> Result<HashMap<String, ProductImage>, sqlx::Error> {
+    let mut variants: HashMap<String, Vec<ImageVariant>> = HashMap::new();
+    for r in sqlx::query(r#"SELECT image_id, format, width, height, file_name FROM product_image_variants ORDER BY width, format DESC"#).fetch_all(pool).await? {
+        variants.entry(r.try_get("image_id")?).or_default().push(ImageVariant {
+            format: r.try_get("format")?,
+            width: r.try_get("width")?,
+            height: r.try_get("height")?,
+            url: config.url(&r.try_get::<String, _>("file_name")?),
+        });
+    }
+    let mut by_product = HashMap::new();
+    for r in sqlx::query(r#"SELECT id, product_id, width, height FROM product_images"#).fetch_all(pool).await? {
+        let id: String = r.try_get("id")?;
+        by_product.insert(r.try_get("product_id")?, ProductImage {
+            width: r.try_get("width")?,
+            height: r.try_get("height")?,
+            variants: variants.remove(&id).unwrap_or_default(),
+            id,
+        });
+    }
+    Ok(by_product)
+}
+
+/// Content type of a stored variant, or `None` for names that are not ours
+/// (including anything with a path in it).
+pub fn variant_content_type(file_name: &str) -> Option<&'static str> {
+    let (stem, ext) = file_name.rsplit_once('.')?;
+    if stem.is_empty() || !stem.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return None;
+    }
+    match ext {
+        "webp" => Some("image/webp"),
+        "jpg" => Some("image/jpeg"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{add_product, mock_state};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        DynamicImage::new_rgb8(width, height).write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png).unwrap();
+        bytes
+    }
+
+    fn config() -> ImageConfig {
+        ImageConfig { dir: std::env::temp_dir().join(format!("images-{}", Uuid::new_v4())), base_url: "/api/media".into(), widths: vec![320, 1280] }
+    }
+
+    #[test]
+    fn only_our_variant_names_are_served() {
+        assert_eq!(variant_content_type("3f2a-640.webp"), Some("image/webp"));
+        assert_eq!(variant_content_type("3f2a-640.jpg"), Some("image/jpeg"));
+        assert_eq!(variant_content_type("../secret.jpg"), None);
+        assert_eq!(variant_content_type("3f2a-640.png"), None);
+        assert_eq!(variant_content_type(".jpg"), None);
+    }
+
+    #[tokio::test]
+    async fn uploads_are_resized_linked_and_replaced() {
+        let pool = mock_state().await.pool.clone();
+        let config = config();
+        add_product(&pool, "pho", 1250).await;
+
+        let first = upload(&pool, &config, "pho", png(800, 600)).await.unwrap();
+        let widths: Vec<(&str, u32, u32)> = first.variants.iter().map(|v| (v.format.as_str(), v.width, v.height)).collect();
+        assert_eq!(widths, vec![("webp", 320, 240), ("jpeg", 320, 240), ("webp", 800, 600), ("jpeg", 800, 600)]);
+        let card = format!("/api/media/products/{}-320.jpg", first.id);
+        assert_eq!(first.card_url(), Some(card.clone()));
+        let image_url: Option<String> = sqlx::query_scalar(r#"SELECT image_url FROM products WHERE id = 'pho'"#).fetch_one(&pool).await.unwrap();
+        assert_eq!(image_url, Some(card));
+        let first_files = variant_files(&pool, "pho").await.unwrap();
+        assert!(first_files.iter().all(|f| config.products_dir().join(f).exists()));
+
+        let second = upload(&pool, &config, "pho", png(400, 300)).await.unwrap();
+        assert!(first_files.iter().all(|f| !config.products_dir().join(f).exists()));
+        let listed = by_product(&pool, &config).await.unwrap();
+        assert_eq!((listed["pho"].id.as_str(), listed["pho"].variants.len()), (second.id.as_str(), 4));
+
+        delete(&pool, &config, "pho").await.unwrap();
+        let image_url: Option<String> = sqlx::query_scalar(r#"SELECT image_url FROM products WHERE id = 'pho'"#).fetch_one(&pool).await.unwrap();
+        assert_eq!(image_url, None);
+        assert!(matches!(delete(&pool, &config, "pho").await, Err(ImageError::NotFound(_))));
+        assert_eq!(std::fs::read_dir(config.products_dir()).unwrap().count(), 0);
+        std::fs::remove_dir_all(&config.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn bad_uploads_are_rejected() {
+        let pool = mock_state().await.pool.clone();
+        let config = config();
+        add_product(&pool, "pho", 1250).await;
+
+        assert!(matches!(upload(&pool, &config, "bun", png(800, 600)).await, Err(ImageError::NotFound(_))));
+        assert!(matches!(upload(&pool, &config, "pho", png(800, 100)).await, Err(ImageError::Invalid(_))));
+        assert!(matches!(upload(&pool, &config, "pho", b"GIF89a not really".to_vec()).await, Err(ImageError::Unsupported)));
+        assert!(matches!(upload(&pool, &config, "pho", vec![0; MAX_UPLOAD_BYTES + 1]).await, Err(ImageError::TooLarge)));
+        assert!(by_product(&pool, &config).await.unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&config.dir);
+    }
+}
@@ -0,0 +1,474 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use uuid::Uuid;
+
+use crate::pricing::{PricedLine, PricingError};
+use crate::schedule::{parse_time, ScheduleConfig};
+
+/// When a menu is open, in local time. `None` fields do not restrict.
+#[derive(Serialize, Clone, Debug)]
+pub struct MenuWindow {
+    pub id: String,
+    /// 0 = Monday ... 6 = Sunday
+    pub weekdays: Option<Vec<u32>>,
+    pub starts_at: Option<String>,
+    pub ends_at: Option<String>,
+    pub starts_on: Option<String>,
+    pub ends_on: Option<String>,
+}
+
+impl MenuWindow {
+    fn matches(&self, date: NaiveDate, time: NaiveTime) -> bool {
+        let day = date.to_string();
+        self.weekdays.as_ref().is_none_or(|w| w.contains(&date.weekday().num_days_from_monday()))
+            && self.starts_on.as_deref().is_none_or(|s| s <= day.as_str())
+            && self.ends_on.as_deref().is_none_or(|e| e >= day.as_str())
+            && self.starts_at.as_deref().and_then(parse_time).is_none_or(|s| s <= time)
+            && self.ends_at.as_deref().and_then(parse_time).is_none_or(|e| time < e)
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Menu {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub is_active: bool,
+    pub sort_order: i64,
+    pub windows: Vec<MenuWindow>,
+    pub product_ids: Vec<String>,
+    pub categories: Vec<String>,
+    /// Open at the time the list was asked for.
+    pub open: bool,
+}
+
+#[derive(Deserialize)]
+pub struct MenuInput {
+    pub name: String,
+    pub description: Option<String>,
+    pub is_active: Option<bool>,
+    pub sort_order: Option<i64>,
+    /// Replaces the menu's dishes when given.
+    pub product_ids: Option<Vec<String>>,
+    /// Replaces the menu's categories (by id or name) when given.
+    pub categories: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct MenuWindowInput {
+    pub weekdays: Option<Vec<u32>>,
+    pub starts_at: Option<String>,
+    pub ends_at: Option<String>,
+    pub starts_on: Option<String>,
+    pub ends_on: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MenuError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for MenuError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            MenuError::Invalid(_) => (StatusCode::BAD_REQUEST, "invalid_menu"),
+            MenuError::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            MenuError::Conflict(_) => (StatusCode::CONFLICT, "menu_conflict"),
+            MenuError::Database(ref e) => {
+                tracing::error!("Menu update failed: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            }
+        };
+        (status, Json(serde_json::json!({ "error": code, "message": self.to_string() }))).into_response()
+    }
+}
+
+async fn windows_by_menu(pool: &SqlitePool) -> Result<HashMap<String, Vec<MenuWindow>>, sqlx::Error> {
+    let rows = sqlx::query(r#"SELECT id, menu_id, weekdays, starts_at, ends_at, starts_on, ends_on FROM menu_windows ORDER BY starts_on, starts_at"#)
+        .fetch_all(pool)
+        .await?;
+    let mut by_menu: HashMap<String, Vec<MenuWindow>> = HashMap::new();
+    for r in rows {
+        let weekdays: Option<String> = r.try_get("weekdays")?;
+        by_menu.entry(r.try_get("menu_id")?).or_default().push(MenuWindow {
+            id: r.try_get("id")?,
+            weekdays: weekdays.map(|w| w.split(',').filter_map(|d| d.trim().parse().ok()).collect()),
+            starts_at: r.try_get("starts_at")?,
+            ends_at: r.try_get("ends_at")?,
+            starts_on: r.try_get("starts_on")?,
+            ends_on: r.try_get("ends_on")?,
+        });
+    }
+    Ok(by_menu)
+}
+
+/// Ids of the active menus open at `at`.
+async fn open_menus(pool: &SqlitePool, schedule: &ScheduleConfig, at: DateTime<Utc>) -> Result<HashSet<String>, sqlx::Error> {
+    let (date, time) = (schedule.local_date(at), schedule.local_time(at));
+    let windows = windows_by_menu(pool).await?;
+    let active: Vec<String> = sqlx::query_scalar(r#"SELECT id FROM menus WHERE is_active = 1"#).fetch_all(pool).await?;
+    Ok(active
+        .into_iter()
+        .filter(|id| windows.get(id).is_none_or(|w| w.iter().any(|w| w.matches(date, time))))
+        .collect())
+}
+
+/// Products that are on at least one menu but on no menu open at `at`.
+pub async fn off_menu_products(pool: &SqlitePool, schedule: &ScheduleConfig, at: DateTime<Utc>) -> Result<HashSet<String>, sqlx::Error> {
+    let open = open_menus(pool, schedule, at).await?;
+    let rows = sqlx::query(r#"
+        SELECT menu_id, product_id FROM menu_products
+        UNION
+        SELECT mc.menu_id, p.id AS product_id FROM menu_categories mc JOIN products p ON p.category = mc.category
+    "#)
+        .fetch_all(pool)
+        .await?;
+    let mut on_open_menu: HashMap<String, bool> = HashMap::new();
+    for r in rows {
+        let menu_id: String = r.try_get("menu_id")?;
+        *on_open_menu.entry(r.try_get("product_id")?).or_default() |= open.contains(&menu_id);
+    }
+    Ok(on_open_menu.into_iter().filter(|(_, open)| !open).map(|(id, _)| id).collect())
+}
+
+/// Reject a cart with dishes whose menus are all closed at `at`.
+pub async fn check_cart(pool: &SqlitePool, schedule: &ScheduleConfig, at: DateTime<Utc>, lines: &[PricedLine]) -> Result<(), PricingError> {
+    let off_menu = off_menu_products(pool, schedule, at).await?;
+    match lines.iter().find(|l| off_menu.contains(&l.product_id)) {
+        Some(line) => Err(PricingError::OffMenu(line.product_id.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Menus with their windows and contents; `open` is evaluated at `at`.
+pub async fn list_menus(pool: &SqlitePool, schedule: &ScheduleConfig, at: DateTime<Utc>, active_only: bool) -> Result<Vec<Menu>, sqlx::Error> {
+    let mut windows = windows_by_menu(pool).await?;
+    let open = open_menus(pool, schedule, at).await?;
+    let mut products: HashMap<String, Vec<String>> = HashMap::new();
+    for r in sqlx::query(r#"SELECT menu_id, product_id FROM menu_products ORDER BY product_id"#).fetch_all(pool).await? {
+        products.entry(r.try_get("menu_id")?).or_default().push(r.try_get("product_id")?);
+    }
+    let mut categories: HashMap<String, Vec<String>> = HashMap::new();
+    for r in sqlx::query(r#"SELECT menu_id, category FROM menu_categories ORDER BY category"#).fetch_all(pool).await? {
+        categories.entry(r.try_get("menu_id")?).or_default().push(r.try_get("category")?);
+    }
+    let rows = sqlx::query(r#"SELECT id, name, description, is_active, sort_order FROM menus WHERE ?1 = 0 OR is_active = 1 ORDER BY sort_order, name"#)
+        .bind(active_only)
+        .fetch_all(pool)
+        .await?;
+    let mut menus = Vec::with_capacity(rows.len());
+    for r in rows {
+        let id: String = r.try_get("id")?;
+        menus.push(Menu {
+            name: r.try_get("name")?,
+            description: r.try_get("description")?,
+            is_active: r.try_get("is_active")?,
+            sort_order: r.try_get("sort_order")?,
+            windows: windows.remove(&id).unwrap_or_default(),
+            product_ids: products.remove(&id).unwrap_or_default(),
+            categories: categories.remove(&id).unwrap_or_default(),
+            open: open.contains(&id),
+            id,
+        });
+    }
+    Ok(menus)
+}
+
+fn map_name_conflict(e: sqlx::Error, name: &str) -> MenuError {
+    match e {
+        sqlx::Error::Database(ref db) if db.is_unique_violation() => MenuError::Conflict(format!("a menu named '{}' already exists", name)),
+        e => MenuError::Database(e),
+    }
+}
+
+fn validate_menu(input: &MenuInput) -> Result<String, MenuError> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(MenuError::Invalid("name must not be empty".into()));
+    }
+    Ok(name.to_string())
+}
+
+/// Replace the dishes and categories of a menu where the input gives them.
+async fn set_contents(tx: &mut SqliteConnection, menu_id: &str, input: &MenuInput) -> Result<(), MenuError> {
+    if let Some(ref product_ids) = input.product_ids {
+        sqlx::query(r#"DELETE FROM menu_products WHERE menu_id = ?"#).bind(menu_id).execute(&mut *tx).await?;
+        for product_id in product_ids {
+            let exists: Option<i64> = sqlx::query_scalar(r#"SELECT 1 FROM products WHERE id = ?"#).bind(product_id).fetch_optional(&mut *tx).await?;
+            if exists.is_none() {
+                return Err(MenuError::Invalid(format!("unknown product {}", product_id)));
+            }
+            sqlx::query(r#"INSERT OR IGNORE INTO menu_products (menu_id, product_id) VALUES (?, ?)"#)
+                .bind(menu_id)
+                .bind(product_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+    if let Some(ref categories) = input.categories {
+        sqlx::query(r#"DELETE FROM menu_categories WHERE menu_id = ?"#).bind(menu_id).execute(&mut *tx).await?;
+        for category in categories.iter().map(|c| c.trim()).filter(|c| !c.is_empty()) {
+            let name: Option<String> = sqlx::query_scalar(r#"SELECT name FROM categories WHERE id = ?1 OR name = ?1 LIMIT 1"#)
+                .bind(category)
+                .fetch_optional(&mut *tx)
+                .await?;
+            let Some(name) = name else {
+                return Err(MenuError::Invalid(format!("unknown category '{}'", category)));
+            };
+            sqlx::query(r#"INSERT OR IGNORE INTO menu_categories (menu_id, category) VALUES (?, ?)"#)
+                .bind(menu_id)
+                .bind(&name)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn create_menu(pool: &SqlitePool, input: &MenuInput) -> Result<String, MenuError> {
+    let name = validate_menu(input)?;
+    let id = Uuid::new_v4().to_string();
+    let mut tx = pool.begin().await?;
+    sqlx::query(r#"INSERT INTO menus (id, name, description, is_active, sort_order) VALUES (?, ?, ?, ?, ?)"#)
+        .bind(&id)
+        .bind(&name)
+        .bind(input.description.as_deref())
+        .bind(input.is_active.unwrap_or(true))
+        .bind(input.sort_order.unwrap_or(0))
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| map_name_conflict(e, &name))?;
+    set_contents(&mut tx, &id, input).await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+pub async fn update_menu(pool: &SqlitePool, id: &str, input: &MenuInput) -> Result<(), MenuError> {
+    let name = validate_menu(input)?;
+    let mut tx = pool.begin().await?;
+    let updated = sqlx::query(r#"UPDATE menus SET name = ?, description = ?, is_active = COALESCE(?, is_active), sort_order = COALESCE(?, sort_order) WHERE id = ?"#)
+        .bind(&name)
+        .bind(input.description.as_deref())
+        .bind(input.is_active)
+        .bind(input.sort_order)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| map_name_conflict(e, &name))?
+        .rows_affected();
+    if updated == 0 {
+        return Err(MenuError::NotFound(format!("menu {}", id)));
+    }
+    set_contents(&mut tx, id, input).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn delete_menu(pool: &SqlitePool, id: &str) -> Result<(), MenuError> {
+    let deleted = sqlx::query(r#"DELETE FROM menus WHERE id = ?"#).bind(id).execute(pool).await?.rows_affected();
+    if deleted == 0 {
+        return Err(MenuError::NotFound(format!("menu {}", id)));
+    }
+    Ok(())
+}
+
+/// A window as stored: weekdays as "0,1,2", `HH:MM` times, `YYYY-MM-DD` dates.
+struct WindowRow {
+    weekdays: Option<String>,
+    starts_at: Option<String>,
+    ends_at: Option<String>,
+    starts_on: Option<String>,
+    ends_on: Option<String>,
+}
+
+fn validate_window(input: &MenuWindowInput) -> Result<WindowRow, MenuError> {
+    let weekdays = match input.weekdays.as_ref() {
+        Some(days) if days.is_empty() => return Err(MenuError::Invalid("weekdays must not be empty; omit it for every day".into())),
+        Some(days) => {
+            if let Some(bad) = days.iter().find(|d| **d > 6) {
+                return Err(MenuError::Invalid(format!("weekday {} is not 0 (Monday) to 6 (Sunday)", bad)));
+            }
+            let mut days = days.clone();
+            days.sort_unstable();
+            days.dedup();
+            Some(days.iter().map(u32::to_string).collect::<Vec<_>>().join(","))
+        }
+        None => None,
+    };
+
+    let time = |s: &Option<String>| match s.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        Some(s) => parse_time(s).map(Some).ok_or_else(|| MenuError::Invalid(format!("'{}' is not a HH:MM time", s))),
+        None => Ok(None),
+    };
+    let (starts_at, ends_at) = match (time(&input.starts_at)?, time(&input.ends_at)?) {
+        (Some(s), Some(e)) if s < e => (Some(s.format("%H:%M").to_string()), Some(e.format("%H:%M").to_string())),
+        (Some(_), Some(_)) => return Err(MenuError::Invalid("starts_at must be before ends_at".into())),
+        (None, None) => (None, None),
+        _ => return Err(MenuError::Invalid("give both starts_at and ends_at, or neither for all day".into())),
+    };
+
+    let date = |s: &Option<String>| match s.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        Some(s) => NaiveDate::parse_from_str(s, "%Y-%m-%d").map(Some).map_err(|_| MenuError::Invalid(format!("'{}' is not a YYYY-MM-DD date", s))),
+        None => Ok(None),
+    };
+    let (starts_on, ends_on) = (date(&input.starts_on)?, date(&input.ends_on)?);
+    if let (Some(s), Some(e)) = (starts_on, ends_on) {
+        if e < s {
+            return Err(MenuError::Invalid("ends_on must not be before starts_on".into()));
+        }
+    }
+    Ok(WindowRow { weekdays, starts_at, ends_at, starts_on: starts_on.map(|d| d.to_string()), ends_on: ends_on.map(|d| d.to_string()) })
+}
+
+pub async fn add_window(pool: &SqlitePool, menu_id: &str, input: &MenuWindowInput) -> Result<String, MenuError> {
+    let w = validate_window(input)?;
+    let exists: Option<i64> = sqlx::query_scalar(r#"SELECT 1 FROM menus WHERE id = ?"#).bind(menu_id).fetch_optional(pool).await?;
+    if exists.is_none() {
+        return Err(MenuError::NotFound(format!("menu {}", menu_id)));
+    }
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(r#"INSERT INTO menu_windows (id, menu_id, weekdays, starts_at, ends_at, starts_on, ends_on) VALUES (?, ?, ?, ?, ?, ?, ?)"#)
+        .bind(&id)
+        .bind(menu_id)
+        .bind(w.weekdays)
+        .bind(w.starts_at)
+        .bind(w.ends_at)
+        .bind(w.starts_on)
+        .bind(w.ends_on)
+        .execute(pool)
+        .await?;
+    Ok(id)
+}
+
+pub async fn delete_window(pool: &SqlitePool, id: &str) -> Result<(), MenuError> {
+    let deleted = sqlx::query(r#"DELETE FROM menu_windows WHERE id = ?"#).bind(id).execute(pool).await?.rows_affected();
+    if deleted == 0 {
+        return Err(MenuError::NotFound(format!("menu window {}", id)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{add_product, mock_state};
+
+    fn menu(name: &str, product_ids: &[&str], categories: &[&str]) -> MenuInput {
+        MenuInput {
+            name: name.into(),
+            description: None,
+            is_active: None,
+            sort_order: None,
+            product_ids: Some(product_ids.iter().map(|p| p.to_string()).collect()),
+            categories: Some(categories.iter().map(|c| c.to_string()).collect()),
+        }
+    }
+
+    fn window(weekdays: Option<Vec<u32>>, hours: Option<(&str, &str)>, dates: (Option<&str>, Option<&str>)) -> MenuWindowInput {
+        MenuWindowInput {
+            weekdays,
+            starts_at: hours.map(|h| h.0.into()),
+            ends_at: hours.map(|h| h.1.into()),
+            starts_on: dates.0.map(Into::into),
+            ends_on: dates.1.map(Into::into),
+        }
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn dishes_are_off_menu_outside_their_menus_windows() {
+        let state = mock_state().await;
+        let pool = &state.pool;
+        for id in ["pho", "bun", "curry", "tea"] {
+            add_product(pool, id, 1000).await;
+        }
+        sqlx::query(r#"INSERT INTO categories (id, name) VALUES ('cat-soups', 'Noodle soups')"#).execute(pool).await.unwrap();
+        sqlx::query(r#"UPDATE products SET category = 'Noodle soups' WHERE id = 'bun'"#).execute(pool).await.unwrap();
+
+        let lunch = create_menu(pool, &menu("Lunch", &["pho"], &["cat-soups"])).await.unwrap();
+        add_window(pool, &lunch, &window(Some(vec![4, 0, 1, 2, 3, 0]), Some(("11:30", "14:30")), (None, None))).await.unwrap();
+        let dinner = create_menu(pool, &MenuInput { is_active: Some(false), ..menu("Dinner", &["curry"], &[]) }).await.unwrap();
+
+        // 2026-10-19 is a Monday; Berlin is two hours ahead of UTC.
+        let monday_noon = at("2026-10-19T10:00:00Z");
+        let off = off_menu_products(pool, &state.schedule, monday_noon).await.unwrap();
+        assert_eq!(off, HashSet::from(["curry".to_string()]));
+        let off = off_menu_products(pool, &state.schedule, at("2026-10-19T12:30:00Z")).await.unwrap();
+        assert_eq!(off, HashSet::from(["pho".to_string(), "bun".to_string(), "curry".to_string()]));
+        let off = off_menu_products(pool, &state.schedule, at("2026-10-24T10:00:00Z")).await.unwrap();
+        assert!(off.contains("pho") && !off.contains("tea"));
+
+        let line = |id: &str| PricedLine {
+            product_id: id.into(),
+            name: id.into(),
+            unit_amount: 1000,
+            options: vec![],
+            quantity: 1,
+            line_total_cents: 1000,
+            currency: "eur".into(),
+        };
+        assert!(check_cart(pool, &state.schedule, monday_noon, &[line("pho"), line("tea")]).await.is_ok());
+        assert!(matches!(
+            check_cart(pool, &state.schedule, monday_noon, &[line("tea"), line("curry")]).await,
+            Err(PricingError::OffMenu(id)) if id == "curry"
+        ));
+
+        let menus = list_menus(pool, &state.schedule, monday_noon, false).await.unwrap();
+        let listed = menus.iter().find(|m| m.id == lunch).unwrap();
+        assert!(listed.open);
+        assert_eq!(listed.windows[0].weekdays, Some(vec![0, 1, 2, 3, 4]));
+        assert_eq!(listed.categories, vec!["Noodle soups".to_string()]);
+        assert!(list_menus(pool, &state.schedule, monday_noon, true).await.unwrap().iter().all(|m| m.id != dinner));
+
+        // An active menu without windows is always open.
+        update_menu(pool, &dinner, &MenuInput { is_active: Some(true), ..menu("Dinner", &["curry"], &[]) }).await.unwrap();
+        assert!(!off_menu_products(pool, &state.schedule, monday_noon).await.unwrap().contains("curry"));
+    }
+
+    #[tokio::test]
+    async fn menus_and_windows_are_validated() {
+        let state = mock_state().await;
+        let pool = &state.pool;
+        add_product(pool, "pho", 1250).await;
+
+        let lunch = create_menu(pool, &menu("Lunch", &["pho"], &[])).await.unwrap();
+        assert!(matches!(create_menu(pool, &menu(" ", &[], &[])).await, Err(MenuError::Invalid(_))));
+        assert!(matches!(create_menu(pool, &menu("Lunch", &[], &[])).await, Err(MenuError::Conflict(_))));
+        assert!(matches!(create_menu(pool, &menu("Brunch", &["soup"], &[])).await, Err(MenuError::Invalid(_))));
+        assert!(matches!(create_menu(pool, &menu("Brunch", &[], &["Desserts"])).await, Err(MenuError::Invalid(_))));
+        assert!(matches!(update_menu(pool, "nope", &menu("Brunch", &[], &[])).await, Err(MenuError::NotFound(_))));
+
+        for bad in [
+            window(Some(vec![]), None, (None, None)),
+            window(Some(vec![7]), None, (None, None)),
+            window(None, Some(("14:30", "11:30")), (None, None)),
+            window(None, Some(("11:30", "")), (None, None)),
+            window(None, None, (Some("2026-12-24"), Some("2026-12-01"))),
+            window(None, None, (Some("24.12.2026"), None)),
+        ] {
+            assert!(matches!(add_window(pool, &lunch, &bad).await, Err(MenuError::Invalid(_))));
+        }
+        let all_day = window(None, None, (Some("2026-12-01"), Some("2026-12-24")));
+        assert!(matches!(add_window(pool, "nope", &all_day).await, Err(MenuError::NotFound(_))));
+        let id = add_window(pool, &lunch, &all_day).await.unwrap();
+        delete_window(pool, &id).await.unwrap();
+        assert!(matches!(delete_window(pool, &id).await, Err(MenuError::NotFound(_))));
+
+        delete_menu(pool, &lunch).await.unwrap();
+        assert!(matches!(delete_menu(pool, &lunch).await, Err(MenuError::NotFound(_))));
+    }
+}
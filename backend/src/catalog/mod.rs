@@ -0,0 +1,10 @@
+//! Menu data beyond the `products` row itself.
+
+pub mod allergens;
+pub mod categories;
+pub mod menus;
+pub mod options;
+pub mod images;
+pub mod stock;
+pub mod transfer;
+pub mod translations;
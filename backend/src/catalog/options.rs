@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use uuid::Uuid;
+
+use crate::pricing::{PricingError, SelectedOption};
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ProductOption {
+    pub id: String,
+    pub name: String,
+    pub price_delta_cents: i64,
+    pub is_available: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct OptionGroup {
+    pub id: String,
+    pub name: String,
+    pub min_select: i64,
+    /// `None` means any number of options may be chosen.
+    pub max_select: Option<i64>,
+    pub required: bool,
+    pub options: Vec<ProductOption>,
+}
+
+#[derive(Deserialize)]
+pub struct OptionGroupInput {
+    pub name: String,
+    #[serde(default)]
+    pub min_select: i64,
+    pub max_select: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct OptionInput {
+    pub name: String,
+    #[serde(default)]
+    pub price_delta_cents: i64,
+    pub is_available: Option<bool>,
+    pub sort_order: Option<i64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OptionsError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for OptionsError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            OptionsError::Invalid(_) => (StatusCode::BAD_REQUEST, "invalid_option"),
+            OptionsError::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            OptionsError::Database(ref e) => {
+                tracing::error!("Product option update failed: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            }
+        };
+        (status, Json(serde_json::json!({ "error": code, "message": self.to_string() }))).into_response()
+    }
+}
+
+/// Check the options chosen on a cart line of `product_id` and price them.
+///
+/// Every id must belong to a group the product offers, be available and be
+/// chosen once; each group must end up between its `min_select` and
+/// `max_select`. The result is ordered like the menu shows the groups.
+pub async fn price_options(pool: &SqlitePool, product_id: &str, option_ids: &[String]) -> Result<Vec<SelectedOption>, PricingError> {
+    let groups = sqlx::query(r#"
+        SELECT g.id, g.name, g.min_select, g.max_select
+        FROM product_option_groups pg
+        JOIN option_groups g ON g.id = pg.group_id
+        WHERE pg.product_id = ?
+        ORDER BY pg.sort_order, g.name
+    "#)
+        .bind(product_id)
+        .fetch_all(pool)
+        .await?;
+    if groups.is_empty() && option_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let group_rank: HashMap<String, usize> = groups
+        .iter()
+        .enumerate()
+        .map(|(i, g)| (g.try_get::<String, _>("id").unwrap_or_default(), i))
+        .collect();
+
+    // (group rank, option sort order, group id, option)
+    let mut chosen: Vec<(usize, i64, String, SelectedOption)> = Vec::with_capacity(option_ids.len());
+    for option_id in option_ids {
+        let row = sqlx::query(r#"
+            SELECT o.id, o.group_id, g.name AS group_name, o.name, o.price_delta_cents, o.is_available, o.sort_order
+            FROM options o
+            JOIN option_groups g ON g.id = o.group_id
+            WHERE o.id = ?
+        "#)
+            .bind(option_id)
+            .fetch_optional(pool)
+            .await?;
+        let unknown = || PricingError::UnknownOption { product_id: product_id.to_string(), option_id: option_id.clone() };
+        let row = row.ok_or_else(unknown)?;
+        let group_id: String = row.try_get("group_id")?;
+        let rank = *group_rank.get(&group_id).ok_or_else(unknown)?;
+        let group: String = row.try_get("group_name")?;
+        if chosen.iter().any(|(_, _, _, o)| &o.id == option_id) {
+            return Err(PricingError::InvalidSelection {
+                product_id: product_id.to_string(),
+                group,
+                message: format!("option {} is selected more than once", option_id),
+            });
+        }
+        if !row.try_get::<bool, _>("is_available").unwrap_or(true) {
+            return Err(PricingError::UnavailableOption { product_id: product_id.to_string(), option_id: option_id.clone() });
+        }
+        chosen.push((rank, row.try_get("sort_order").unwrap_or(0), group_id, SelectedOption {
+            id: option_id.clone(),
+            group,
+            name: row.try_get("name")?,
+            price_delta_cents: row.try_get("price_delta_cents")?,
+        }));
+    }
+
+    for g in &groups {
+        let id: String = g.try_get("id")?;
+        let name: String = g.try_get("name")?;
+        let min: i64 = g.try_get("min_select").unwrap_or(0);
+        let max: Option<i64> = g.try_get("max_select").ok().flatten();
+        let count = chosen.iter().filter(|(_, _, group_id, _)| *group_id == id).count() as i64;
+        let message = match max {
+            _ if count < min && min == 1 => Some("please choose an option".to_string()),
+            _ if count < min => Some(format!("please choose at least {} options", min)),
+            Some(max) if count > max => Some(format!("choose at most {}", max)),
+            _ => None,
+        };
+        if let Some(message) = message {
+            return Err(PricingError::InvalidSelection { product_id: product_id.to_string(), group: name, message });
+        }
+    }
+
+    chosen.sort_by_key(|(rank, order, _, _)| (*rank, *order));
+    Ok(chosen.into_iter().map(|(_, _, _, o)| o).collect())
+}
+
+async fn options_by_group(pool: &SqlitePool, available_only: bool) -> Result<HashMap<String, Vec<ProductOption>>, sqlx::Error> {
+    let rows = sqlx::query(r#"SELECT id, group_id, name, price_delta_cents, is_available FROM options WHERE ? = 0 OR is_available = 1 ORDER BY group_id, sort_order, name"#)
+        .bind(available_only)
+        .fetch_all(pool)
+        .await?;
+    let mut by_group: HashMap<String, Vec<ProductOption>> = HashMap::new();
+    for r in rows {
+        by_group.entry(r.try_get("group_id")?).or_default().push(ProductOption {
+            id: r.try_get("id")?,
+            name: r.try_get("name")?,
+            price_delta_cents: r.try_get("price_delta_cents").unwrap_or(0),
+            is_available: r.try_get("is_available").unwrap_or(true),
+        });
+    }
+    Ok(by_group)
+}
+
+fn group_from_row(r: &sqlx::sqlite::SqliteRow, options: Vec<ProductOption>) -> OptionGroup {
+    let min_select: i64 = r.try_get("min_select").unwrap_or(0);
+    OptionGroup {
+        id: r.try_get("id").unwrap_or_default(),
+        name: r.try_get("name").unwrap_or_default(),
+        min_select,
+        max_select: r.try_get("max_select").ok().flatten(),
+        required: min_select > 0,
+        options,
+    }
+}
+
+/// Option groups of every product that has any, with their available options,
+/// for the public menu.
+pub async fn groups_by_product(pool: &SqlitePool) -> Result<HashMap<String, Vec<OptionGroup>>, sqlx::Error> {
+    let options = options_by_group(pool, true).await?;
+    let rows = sqlx::query(r#"
+        SELECT pg.product_id, g.id, g.name, g.min_select, g.max_select
+        FROM product_option_groups pg
+        JOIN option_groups g ON g.id = pg.group_id
+        ORDER BY pg.product_id, pg.sort_order, g.name
+    "#)
+        .fetch_all(pool)
+        .await?;
+    let mut by_product: HashMap<String, Vec<OptionGroup>> = HashMap::new();
+    for r in rows {
+        let group_id: String = r.try_get("id")?;
+        let group_options = options.get(&group_id).cloned().unwrap_or_default();
+        by_product.entry(r.try_get("product_id")?).or_default().push(group_from_row(&r, group_options));
+    }
+    Ok(by_product)
+}
+
+/// Every option group with all its options and the products offering it.
+pub async fn list_groups(pool: &SqlitePool) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let mut options = options_by_group(pool, false).await?;
+    let links = sqlx::query(r#"SELECT group_id, product_id FROM product_option_groups ORDER BY product_id"#)
+        .fetch_all(pool)
+        .await?;
+    let mut products: HashMap<String, Vec<String>> = HashMap::new();
+    for r in links {
+        products.entry(r.try_get("group_id")?).or_default().push(r.try_get("product_id")?);
+    }
+    let rows = sqlx::query(r#"SELECT id, name, min_select, max_select FROM option_groups ORDER BY name"#)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(|r| {
+        let id: String = r.try_get("id").unwrap_or_default();
+        let group = group_from_row(r, options.remove(&id).unwrap_or_default());
+        serde_json::json!({
+            "group": group,
+            "product_ids": products.remove(&id).unwrap_or_default(),
+        })
+    }).collect())
+}
+
+fn validate_group(input: &OptionGroupInput) -> Result<(), OptionsError> {
+    if input.name.trim().is_empty() {
+        return Err(OptionsError::Invalid("name is required".into()));
+    }
+    if input.min_select < 0 {
+        return Err(OptionsError::Invalid("min_select must not be negative".into()));
+    }
+    if input.max_select.is_some_and(|max| max < 1 || max < input.min_select) {
+        return Err(OptionsError::Invalid("max_select must be at least 1 and not below min_select".into()));
+    }
+    Ok(())
+}
+
+pub async fn create_group(pool: &SqlitePool, input: &OptionGroupInput) -> Result<String, OptionsError> {
+    validate_group(input)?;
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(r#"INSERT INTO option_groups (id, name, min_select, max_select) VALUES (?, ?, ?, ?)"#)
+        .bind(&id)
+        .bind(input.name.trim())
+        .bind(input.min_select)
+        .bind(input.max_select)
+        .execute(pool)
+        .await?;
+    Ok(id)
+}
+
+pub async fn update_group(pool: &SqlitePool, id: &str, input: &OptionGroupInput) -> Result<(), OptionsError> {
+    validate_group(input)?;
+    let updated = sqlx::query(r#"UPDATE option_groups SET name = ?, min_select = ?, max_select = ? WHERE id = ?"#)
+        .bind(input.name.trim())
+        .bind(input.min_select)
+        .bind(input.max_select)
+        .bind(id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if updated == 0 {
+        return Err(OptionsError::NotFound(format!("option group {}", id)));
+    }
+    Ok(())
+}
+
+/// Remove a group with its options and product links. Orders keep the
+/// options they were placed with.
+pub async fn delete_group(pool: &SqlitePool, id: &str) -> Result<(), OptionsError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(r#"DELETE FROM product_option_groups WHERE group_id = ?"#).bind(id).execute(&mut *tx).await?;
+    sqlx::query(r#"DELETE FROM options WHERE group_id = ?"#).bind(id).execute(&mut *tx).await?;
+    let deleted = sqlx::query(r#"DELETE FROM option_groups WHERE id = ?"#).bind(id).execute(&mut *tx).await?.rows_affected();
+    if deleted == 0 {
+        return Err(OptionsError::NotFound(format!("option group {}", id)));
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+fn validate_option(input: &OptionInput) -> Result<(), OptionsError> {
+    if input.name.trim().is_empty() {
+        return Err(OptionsError::Invalid("name is required".into()));
+    }
+    Ok(())
+}
+
+pub async fn create_option(pool: &SqlitePool, group_id: &str, input: &OptionInput) -> Result<String, OptionsError> {
+    validate_option(input)?;
+    let exists: Option<String> = sqlx::query_scalar(r#"SELECT id FROM option_groups WHERE id = ?"#).bind(group_id).fetch_optional(pool).await?;
+    if exists.is_none() {
+        return Err(OptionsError::NotFound(format!("option group {}", group_id)));
+    }
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(r#"INSERT INTO options (id, group_id, name, price_delta_cents, is_available, sort_order) VALUES (?, ?, ?, ?, ?, ?)"#)
+        .bind(&id)
+        .bind(group_id)
+        .bind(input.name.trim())
+        .bind(input.price_delta_cents)
+        .bind(input.is_available.unwrap_or(true))
+        .bind(input.sort_order.unwrap_or(0))
+        .execute(pool)
+        .await?;
+    Ok(id)
+}
+
+pub async fn update_option(pool: &SqlitePool, id: &str, input: &OptionInput) -> Result<(), OptionsError> {
+    validate_option(input)?;
+    let updated = sqlx::query(r#"UPDATE options SET name = ?, price_delta_cents = ?, is_available = ?, sort_order = COALESCE(?, sort_order) WHERE id = ?"#)
+        .bind(input.name.trim())
+        .bind(input.price_delta_cents)
+        .bind(input.is_available.unwrap_or(true))
+        .bind(input.sort_order)
+        .bind(id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if updated == 0 {
+        return Err(OptionsError::NotFound(format!("option {}", id)));
+    }
+    Ok(())
+}
+
+pub async fn delete_option(pool: &SqlitePool, id: &str) -> Result<(), OptionsError> {
+    let deleted = sqlx::query(r#"DELETE FROM options WHERE id = ?"#).bind(id).execute(pool).await?.rows_affected();
+    if deleted == 0 {
+        return Err(OptionsError::NotFound(format!("option {}", id)));
+    }
+    Ok(())
+}
+
+/// Replace the option groups a product offers, in menu order.
+pub async fn set_product_groups(pool: &SqlitePool, product_id: &str, group_ids: &[String]) -> Result<(), OptionsError> {
+    let mut tx = pool.begin().await?;
+    let product: Option<String> = sqlx::query_scalar(r#"SELECT id FROM products WHERE id = ?"#).bind(product_id).fetch_optional(&mut *tx).await?;
+    if product.is_none() {
+        return Err(OptionsError::NotFound(format!("product {}", product_id)));
+    }
+    sqlx::query(r#"DELETE FROM product_option_groups WHERE product_id = ?"#).bind(product_id).execute(&mut *tx).await?;
+    for (order, group_id) in group_ids.iter().enumerate() {
+        let exists: Option<String> = sqlx::query_scalar(r#"SELECT id FROM option_groups WHERE id = ?"#).bind(group_id).fetch_optional(&mut *tx).await?;
+        if exists.is_none() {
+            return Err(OptionsError::NotFound(format!("option group {}", group_id)));
+        }
+        sqlx::query(r#"INSERT OR IGNORE INTO product_option_groups (product_id, group_id, sort_order) VALUES (?, ?, ?)"#)
+            .bind(product_id)
+            .bind(group_id)
+            .bind(order as i64)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::{price_cart, CartLine};
+    use crate::test_support::{add_product, mock_state};
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|s| s.to_string()).collect()
+    }
+
+    async fn option(pool: &SqlitePool, group_id: &str, name: &str, price_delta_cents: i64, sort_order: i64) -> String {
+        create_option(pool, group_id, &OptionInput { name: name.into(), price_delta_cents, is_available: None, sort_order: Some(sort_order) }).await.unwrap()
+    }
+
+    /// Pho with a required spice level and up to two extras.
+    async fn pho(pool: &SqlitePool) -> (String, String, String, String) {
+        add_product(pool, "pho", 1250).await;
+        let spice = create_group(pool, &OptionGroupInput { name: "Spice".into(), min_select: 1, max_select: Some(1) }).await.unwrap();
+        let extras = create_group(pool, &OptionGroupInput { name: "Extras".into(), min_select: 0, max_select: Some(2) }).await.unwrap();
+        let mild = option(pool, &spice, "Mild", 0, 0).await;
+        let hot = option(pool, &spice, "Hot", 0, 1).await;
+        let tofu = option(pool, &extras, "Tofu", 150, 1).await;
+        let egg = option(pool, &extras, "Egg", 100, 0).await;
+        set_product_groups(pool, "pho", &[spice, extras]).await.unwrap();
+        (mild, hot, tofu, egg)
+    }
+
+    #[tokio::test]
+    async fn selections_are_checked_against_the_groups_and_priced() {
+        let pool = mock_state().await.pool.clone();
+        let (mild, hot, tofu, egg) = pho(&pool).await;
+
+        let chosen = price_options(&pool, "pho", &[tofu.clone(), mild.clone(), egg.clone()]).await.unwrap();
+        let names: Vec<&str> = chosen.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, ["Mild", "Egg", "Tofu"], "menu order, not the order they were picked in");
+
+        let selection = |r: Result<Vec<SelectedOption>, PricingError>| match r {
+            Err(PricingError::InvalidSelection { group, message, .. }) => format!("{}: {}", group, message),
+            other => panic!("expected an invalid selection, got {:?}", other.map(|o| o.len())),
+        };
+        assert_eq!(selection(price_options(&pool, "pho", &[]).await), "Spice: please choose an option");
+        assert_eq!(selection(price_options(&pool, "pho", &[mild.clone(), hot.clone()]).await), "Spice: choose at most 1");
+        assert!(selection(price_options(&pool, "pho", &[mild.clone(), tofu.clone(), tofu.clone()]).await).contains("more than once"));
+        assert!(matches!(price_options(&pool, "pho", &ids(&["nope"])).await, Err(PricingError::UnknownOption { .. })));
+
+        add_product(&pool, "rolls", 335).await;
+        assert!(matches!(price_options(&pool, "rolls", std::slice::from_ref(&mild)).await, Err(PricingError::UnknownOption { .. })), "not offered on rolls");
+        assert!(price_options(&pool, "rolls", &[]).await.unwrap().is_empty());
+
+        update_option(&pool, &hot, &OptionInput { name: "Hot".into(), price_delta_cents: 0, is_available: Some(false), sort_order: None }).await.unwrap();
+        assert!(matches!(price_options(&pool, "pho", &[hot]).await, Err(PricingError::UnavailableOption { .. })));
+        let menu = groups_by_product(&pool).await.unwrap();
+        assert_eq!(menu["pho"][0].options.len(), 1, "unavailable options are left off the menu");
+
+        let cart = [CartLine { product_id: "pho".into(), quantity: 2, option_ids: vec![mild, tofu] }];
+        assert_eq!(price_cart(&pool, &cart, None, None).await.unwrap().total_cents, 2 * (1250 + 150));
+    }
+
+    #[tokio::test]
+    async fn groups_are_validated_and_deleted_with_their_options() {
+        let pool = mock_state().await.pool.clone();
+        let invalid = |min_select, max_select| OptionGroupInput { name: "Size".into(), min_select, max_select };
+        assert!(matches!(create_group(&pool, &invalid(-1, None)).await, Err(OptionsError::Invalid(_))));
+        assert!(matches!(create_group(&pool, &invalid(2, Some(1))).await, Err(OptionsError::Invalid(_))));
+        assert!(matches!(create_option(&pool, "missing", &OptionInput { name: "Large".into(), price_delta_cents: 0, is_available: None, sort_order: None }).await, Err(OptionsError::NotFound(_))));
+
+        pho(&pool).await;
+        assert!(matches!(set_product_groups(&pool, "pho", &ids(&["missing"])).await, Err(OptionsError::NotFound(_))));
+        let spice: String = sqlx::query_scalar(r#"SELECT id FROM option_groups WHERE name = 'Spice'"#).fetch_one(&pool).await.unwrap();
+        delete_group(&pool, &spice).await.unwrap();
+        let left: (i64, i64) = sqlx::query_as(r#"SELECT (SELECT COUNT(*) FROM options WHERE group_id = ?1), (SELECT COUNT(*) FROM product_option_groups WHERE product_id = 'pho')"#)
+            .bind(&spice)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(left, (0, 1));
+        assert!(price_options(&pool, "pho", &[]).await.is_ok(), "the spice level is no longer required");
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::{Row, Sqlite, SqliteConnection, SqlitePool, Transaction};
+
+use crate::pricing::{PricedLine, PricingError};
+use crate::schedule::{db_timestamp, ready_at, ScheduleConfig};
+
+/// What the kitchen has left of a dish on one stock day.
+#[derive(Serialize, Clone, Debug)]
+pub struct Stock {
+    pub product_id: String,
+    pub name: String,
+    pub category: Option<String>,
+    pub is_available: bool,
+    /// Marked sold out by the kitchen or out of portions.
+    pub sold_out: bool,
+    pub daily_limit: Option<i64>,
+    pub sold: i64,
+    /// Portions held by checkouts still waiting for their payment.
+    pub held: i64,
+    /// `None` without a daily limit.
+    pub remaining: Option<i64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StockError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("product {0} not found")]
+    NotFound(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for StockError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            StockError::Invalid(_) => (StatusCode::BAD_REQUEST, "invalid_stock"),
+            StockError::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            StockError::Database(ref e) => {
+                tracing::error!("Stock update failed: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            }
+        };
+        (status, Json(serde_json::json!({ "error": code, "message": self.to_string() }))).into_response()
+    }
+}
+
+/// The stock day an order draws from: its pickup day, or today when it is
+/// picked up as soon as possible.
+pub fn order_stock_day(schedule: &ScheduleConfig, pickup_at: Option<&str>) -> NaiveDate {
+    schedule.stock_day(ready_at(pickup_at))
+}
+
+/// Stock of every product on `day`, keyed by product id.
+pub async fn for_day(pool: &SqlitePool, day: NaiveDate) -> Result<HashMap<String, Stock>, sqlx::Error> {
+    let rows = sqlx::query(r#"
+        SELECT p.id, p.name, p.category, p.is_available, p.sold_out_on, p.daily_limit, COALESCE(s.sold, 0) AS sold, COALESCE(h.held, 0) AS held
+        FROM products p
+        LEFT JOIN product_stock s ON s.product_id = p.id AND s.stock_day = ?1
+        LEFT JOIN (SELECT product_id, SUM(quantity) AS held FROM stock_holds
+                   WHERE stock_day = ?1 AND expires_at > strftime('%Y-%m-%dT%H:%M:%fZ','now')
+                   GROUP BY product_id) h ON h.product_id = p.id
+    "#)
+        .bind(day.to_string())
+        .fetch_all(pool)
+        .await?;
+    let mut by_product = HashMap::new();
+    for r in rows {
+        let id: String = r.try_get("id")?;
+        let daily_limit: Option<i64> = r.try_get("daily_limit")?;
+        let sold: i64 = r.try_get("sold")?;
+        let held: i64 = r.try_get("held")?;
+        let remaining = daily_limit.map(|limit| (limit - sold - held).max(0));
+        let marked = r.try_get::<Option<String>, _>("sold_out_on")?.as_deref() == Some(day.to_string().as_str());
+        by_product.insert(id.clone(), Stock {
+            product_id: id,
+            name: r.try_get("name")?,
+            category: r.try_get("category")?,
+            is_available: r.try_get("is_available")?,
+            sold_out: marked || remaining == Some(0),
+            daily_limit,
+            sold,
+            held,
+            remaining,
+        });
+    }
+    Ok(by_product)
+}
+
+/// Quantities per dish, added up across lines with different options.
+fn wanted(lines: &[PricedLine]) -> Vec<(&str, i64)> {
+    let mut wanted: Vec<(&str, i64)> = Vec::new();
+    for line in lines {
+        match wanted.iter_mut().find(|(id, _)| *id == line.product_id) {
+            Some((_, quantity)) => *quantity += line.quantity,
+            None => wanted.push((&line.product_id, line.quantity)),
+        }
+    }
+    wanted
+}
+
+/// Reject a priced cart when a dish is sold out on `day` or the cart wants
+/// more portions than are left after sales and live holds.
+pub async fn check_cart(pool: &SqlitePool, day: NaiveDate, lines: &[PricedLine]) -> Result<(), PricingError> {
+    let stock = for_day(pool, day).await?;
+    for (product_id, quantity) in wanted(lines) {
+        let Some(s) = stock.get(product_id) else { continue };
+        if s.sold_out {
+            return Err(PricingError::SoldOut(product_id.to_string()));
+        }
+        if let Some(remaining) = s.remaining.filter(|r| *r < quantity) {
+            return Err(PricingError::InsufficientStock { product_id: product_id.to_string(), remaining });
+        }
+    }
+    Ok(())
+}
+
+/// Hold the portions of limited dishes in `lines` on `day` for the pending
+/// order `pending_order_id` for `minutes`.
+///
+/// Like `coupons::holds::reserve`, each hold is one conditional insert that
+/// counts sales and the live holds of other checkouts, so two checkouts
+/// cannot both take the last portion. Call it inside the transaction that
+/// writes the pending order. Deleting the pending order releases the hold.
+pub async fn hold(tx: &mut Transaction<'_, Sqlite>, pending_order_id: &str, day: NaiveDate, lines: &[PricedLine], minutes: i64) -> Result<(), PricingError> {
+    let expires_at = db_timestamp(Utc::now() + Duration::minutes(minutes));
+    for (product_id, quantity) in wanted(lines) {
+        let held = sqlx::query(r#"
+            INSERT INTO stock_holds (pending_order_id, product_id, stock_day, quantity, expires_at)
+            SELECT ?1, p.id, ?3, ?4, ?5 FROM products p
+            WHERE p.id = ?2 AND p.daily_limit IS NOT NULL
+              AND p.daily_limit
+                  - COALESCE((SELECT sold FROM product_stock WHERE product_id = p.id AND stock_day = ?3), 0)
+                  - (SELECT COALESCE(SUM(quantity), 0) FROM stock_holds
+                     WHERE product_id = p.id AND stock_day = ?3 AND expires_at > strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+                  >= ?4
+        "#)
+            .bind(pending_order_id)
+            .bind(product_id)
+            .bind(day.to_string())
+            .bind(quantity)
+            .bind(&expires_at)
+            .execute(&mut **tx)
+            .await?
+            .rows_affected();
+        if held == 0 {
+            // Unlimited dishes need no hold; anything else ran out meanwhile
+            let stock = remaining_on(tx, day, product_id).await?;
+            if let Some(remaining) = stock.filter(|r| *r < quantity) {
+                return Err(PricingError::InsufficientStock { product_id: product_id.to_string(), remaining });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Portions of `product_id` left on `day`, or `None` without a daily limit.
+async fn remaining_on(conn: &mut SqliteConnection, day: NaiveDate, product_id: &str) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar(r#"
+        SELECT MAX(p.daily_limit
+                   - COALESCE((SELECT sold FROM product_stock WHERE product_id = p.id AND stock_day = ?2), 0)
+                   - (SELECT COALESCE(SUM(quantity), 0) FROM stock_holds
+                      WHERE product_id = p.id AND stock_day = ?2 AND expires_at > strftime('%Y-%m-%dT%H:%M:%fZ','now')), 0)
+        FROM products p WHERE p.id = ?1
+    "#)
+        .bind(product_id)
+        .bind(day.to_string())
+        .fetch_optional(conn)
+        .await
+        .map(Option::flatten)
+}
+
+/// Count finalized portions against `day`. Runs inside the finalize
+/// transaction; the checkout's hold kept the portions free, but a payment
+/// captured after its hold lapsed is still counted, so `sold` can exceed
+/// the limit.
+pub async fn record_sale(conn: &mut SqliteConnection, day: NaiveDate, product_id: &str, quantity: i64) -> Result<(), sqlx::Error> {
+    let row = sqlx::query(r#"
+        INSERT INTO product_stock (product_id, stock_day, sold) VALUES (?1, ?2, ?3)
+        ON CONFLICT (product_id, stock_day) DO UPDATE SET sold = sold + excluded.sold
+        RETURNING sold, (SELECT daily_limit FROM products WHERE id = ?1) AS daily_limit
+    "#)
+        .bind(product_id)
+        .bind(day.to_string())
+        .bind(quantity)
+        .fetch_one(&mut *conn)
+        .await?;
+    let sold: i64 = row.try_get("sold")?;
+    if let Some(limit) = row.try_get::<Option<i64>, _>("daily_limit")? {
+        if sold > limit {
+            tracing::warn!("Product {} oversold on {}: {} of {} portions", product_id, day, sold, limit);
+        } else if sold == limit {
+            tracing::info!("Product {} sold out on {}", product_id, day);
+        }
+    }
+    Ok(())
+}
+
+/// Give the portions of a cancelled or refunded order back to the stock day
+/// they were counted on. Clears `orders.stock_day`, so they only go back once.
+pub async fn release_order(conn: &mut SqliteConnection, order_id: &str) -> Result<(), sqlx::Error> {
+    let day: Option<String> = sqlx::query_scalar(r#"SELECT stock_day FROM orders WHERE id = ?"#)
+        .bind(order_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .flatten();
+    let Some(day) = day else { return Ok(()) };
+    sqlx::query(r#"
+        UPDATE product_stock
+        SET sold = MAX(sold - (SELECT SUM(quantity) FROM order_items WHERE order_id = ?1 AND product_id = product_stock.product_id), 0)
+        WHERE stock_day = ?2 AND product_id IN (SELECT product_id FROM order_items WHERE order_id = ?1)
+    "#)
+        .bind(order_id)
+        .bind(&day)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(r#"UPDATE orders SET stock_day = NULL WHERE id = ?"#)
+        .bind(order_id)
+        .execute(&mut *conn)
+        .await?;
+    tracing::info!("Portions of order {} given back to {}", order_id, day);
+    Ok(())
+}
+
+/// Mark a dish sold out for `day`, or orderable again.
+pub async fn set_sold_out(pool: &SqlitePool, product_id: &str, day: NaiveDate, sold_out: bool) -> Result<(), StockError> {
+    let updated = sqlx::query(r#"UPDATE products SET sold_out_on = ? WHERE id = ?"#)
+        .bind(sold_out.then(|| day.to_string()))
+        .bind(product_id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if updated == 0 {
+        return Err(StockError::NotFound(product_id.to_string()));
+    }
+    Ok(())
+}
+
+/// Set the portions per day; `None` removes the limit.
+pub async fn set_daily_limit(pool: &SqlitePool, product_id: &str, daily_limit: Option<i64>) -> Result<(), StockError> {
+    if daily_limit.is_some_and(|l| l < 0) {
+        return Err(StockError::Invalid("daily_limit must not be negative".into()));
+    }
+    let updated = sqlx::query(r#"UPDATE products SET daily_limit = ? WHERE id = ?"#)
+        .bind(daily_limit)
+        .bind(product_id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if updated == 0 {
+        return Err(StockError::NotFound(product_id.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orders::status::{transition, OrderStatus};
+    use crate::test_support::{add_product, mock_state, place_order};
+
+    fn line(product_id: &str, quantity: i64) -> PricedLine {
+        PricedLine {
+            product_id: product_id.into(),
+            name: product_id.into(),
+            unit_amount: 1250,
+            options: Vec::new(),
+            quantity,
+            line_total_cents: 1250 * quantity,
+            currency: "EUR".into(),
+        }
+    }
+
+    async fn pending(pool: &SqlitePool, id: &str) {
+        sqlx::query(r#"INSERT INTO pending_orders (order_id, email, amount_cents, items_json) VALUES (?, 'guest@example.com', 2500, '{}')"#)
+            .bind(id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn checkouts_waiting_for_payment_hold_their_portions() {
+        let pool = mock_state().await.pool.clone();
+        add_product(&pool, "pho", 1250).await;
+        set_daily_limit(&pool, "pho", Some(2)).await.unwrap();
+        let day = NaiveDate::from_ymd_opt(2026, 10, 17).unwrap();
+        pending(&pool, "PAY-1").await;
+        pending(&pool, "PAY-2").await;
+
+        let mut tx = pool.begin().await.unwrap();
+        hold(&mut tx, "PAY-1", day, &[line("pho", 2)], 30).await.unwrap();
+        tx.commit().await.unwrap();
+        let pho = for_day(&pool, day).await.unwrap().remove("pho").unwrap();
+        assert_eq!((pho.held, pho.remaining, pho.sold_out), (2, Some(0), true));
+
+        let mut tx = pool.begin().await.unwrap();
+        let err = hold(&mut tx, "PAY-2", day, &[line("pho", 1)], 30).await.unwrap_err();
+        assert!(matches!(err, PricingError::InsufficientStock { remaining: 0, .. }));
+        drop(tx);
+
+        // A lapsed hold no longer counts
+        sqlx::query(r#"UPDATE stock_holds SET expires_at = '2000-01-01T00:00:00.000Z'"#).execute(&pool).await.unwrap();
+        let mut tx = pool.begin().await.unwrap();
+        hold(&mut tx, "PAY-2", day, &[line("pho", 1)], 30).await.unwrap();
+        tx.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancelled_order_gives_its_portions_back() {
+        let state = mock_state().await;
+        add_product(&state.pool, "pho", 1250).await;
+        set_daily_limit(&state.pool, "pho", Some(5)).await.unwrap();
+        let order_id = place_order(&state, &[("pho", 2)], None).await;
+        let day: String = sqlx::query_scalar(r#"SELECT stock_day FROM orders WHERE id = ?"#).bind(&order_id).fetch_one(&state.pool).await.unwrap();
+        let sold = || sqlx::query_scalar::<_, i64>(r#"SELECT sold FROM product_stock WHERE product_id = 'pho' AND stock_day = ?"#).bind(day.clone());
+        assert_eq!(sold().fetch_one(&state.pool).await.unwrap(), 2);
+
+        let mut conn = state.pool.acquire().await.unwrap();
+        transition(&mut conn, &order_id, OrderStatus::Cancelled, "cook@example.com", None).await.unwrap();
+        assert_eq!(sold().fetch_one(&mut *conn).await.unwrap(), 0);
+        let day_left: Option<String> = sqlx::query_scalar(r#"SELECT stock_day FROM orders WHERE id = ?"#).bind(&order_id).fetch_one(&mut *conn).await.unwrap();
+        assert_eq!(day_left, None);
+    }
+}
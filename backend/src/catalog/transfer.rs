@@ -0,0 +1,595 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+
+use crate::catalog::allergens::{self, split_codes};
+
+/// One dish as exported and imported; a CSV file holds one per line.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MenuItem {
+    pub id: String,
+    pub name: String,
+    /// Price in cents.
+    pub unit_amount: i64,
+    /// Defaults to EUR.
+    pub currency: Option<String>,
+    /// Category id; the name works too on import.
+    pub category: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    /// Allergen letters, e.g. `a,g`.
+    pub allergens: Option<String>,
+    /// Additive numbers, e.g. `1,4`.
+    pub additives: Option<String>,
+    pub spice_level: Option<String>,
+    pub serving_size: Option<String>,
+    pub dietary_tags: Option<String>,
+    pub ingredients: Option<String>,
+    /// Defaults to true.
+    pub is_available: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MenuCategory {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    /// New categories go to the end without one; existing ones keep theirs.
+    pub sort_order: Option<i64>,
+    pub is_visible: Option<bool>,
+}
+
+/// The whole menu as JSON. CSV files only carry `products`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MenuFile {
+    #[serde(default)]
+    pub categories: Vec<MenuCategory>,
+    #[serde(default)]
+    pub products: Vec<MenuItem>,
+}
+
+/// A problem with one record; `row` counts from 1 (for CSV, the first line
+/// after the header).
+#[derive(Serialize, Clone, Debug)]
+pub struct RowError {
+    pub section: &'static str,
+    pub row: usize,
+    pub id: Option<String>,
+    pub message: String,
+}
+
+/// A record the import creates or changes.
+#[derive(Serialize, Clone, Debug)]
+pub struct Change {
+    pub section: &'static str,
+    pub id: String,
+    /// `create` or `update`
+    pub action: &'static str,
+    /// Changed fields of an update.
+    pub fields: Vec<&'static str>,
+}
+
+/// What an import would do. Only applied when `errors` is empty.
+#[derive(Serialize, Debug, Default)]
+pub struct ImportPlan {
+    pub changes: Vec<Change>,
+    pub unchanged: usize,
+    pub errors: Vec<RowError>,
+    #[serde(skip)]
+    categories: Vec<CategoryRow>,
+    #[serde(skip)]
+    products: Vec<ProductRow>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("{0}")]
+    Format(String),
+    #[error("import has {} invalid row(s), nothing was changed", .0.errors.len())]
+    Invalid(ImportPlan),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for ImportError {
+    fn into_response(self) -> Response {
+        let message = self.to_string();
+        match self {
+            ImportError::Format(_) => (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": "invalid_import_file", "message": message }))).into_response(),
+            ImportError::Invalid(plan) => (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "error": "invalid_import",
+                "message": message,
+                "errors": plan.errors,
+                "changes": plan.changes,
+            }))).into_response(),
+            ImportError::Database(ref e) => {
+                tracing::error!("Menu import failed: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": "internal_error", "message": message }))).into_response()
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct CategoryRow {
+    id: String,
+    name: String,
+    description: Option<String>,
+    image_url: Option<String>,
+    sort_order: i64,
+    is_visible: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct ProductRow {
+    id: String,
+    name: String,
+    unit_amount: i64,
+    currency: String,
+    category_id: Option<String>,
+    category_name: Option<String>,
+    description: Option<String>,
+    image_url: Option<String>,
+    allergens: Vec<String>,
+    additives: Vec<String>,
+    spice_level: Option<String>,
+    serving_size: Option<String>,
+    dietary_tags: Option<String>,
+    ingredients: Option<String>,
+    is_available: bool,
+}
+
+impl ProductRow {
+    fn changed_fields(&self, old: &ProductRow) -> Vec<&'static str> {
+        [
+            ("name", self.name != old.name),
+            ("unit_amount", self.unit_amount != old.unit_amount),
+            ("currency", self.currency != old.currency),
+            ("category", self.category_id != old.category_id),
+            ("description", self.description != old.description),
+            ("image_url", self.image_url != old.image_url),
+            ("allergens", self.allergens != old.allergens),
+            ("additives", self.additives != old.additives),
+            ("spice_level", self.spice_level != old.spice_level),
+            ("serving_size", self.serving_size != old.serving_size),
+            ("dietary_tags", self.dietary_tags != old.dietary_tags),
+            ("ingredients", self.ingredients != old.ingredients),
+            ("is_available", self.is_available != old.is_available),
+        ]
+        .into_iter()
+        .filter_map(|(field, changed)| changed.then_some(field))
+        .collect()
+    }
+}
+
+impl CategoryRow {
+    fn changed_fields(&self, old: &CategoryRow) -> Vec<&'static str> {
+        [
+            ("name", self.name != old.name),
+            ("description", self.description != old.description),
+            ("image_url", self.image_url != old.image_url),
+            ("sort_order", self.sort_order != old.sort_order),
+            ("is_visible", self.is_visible != old.is_visible),
+        ]
+        .into_iter()
+        .filter_map(|(field, changed)| changed.then_some(field))
+        .collect()
+    }
+}
+
+fn blank_to_none(v: Option<&str>) -> Option<String> {
+    v.map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+async fn current_categories(pool: &SqlitePool) -> Result<Vec<CategoryRow>, sqlx::Error> {
+    let rows = sqlx::query(r#"SELECT id, name, description, image_url, sort_order, is_visible FROM categories ORDER BY sort_order, name"#)
+        .fetch_all(pool)
+        .await?;
+    let mut categories = Vec::with_capacity(rows.len());
+    for r in rows {
+        categories.push(CategoryRow {
+            id: r.try_get("id")?,
+            name: r.try_get("name")?,
+            description: blank_to_none(r.try_get::<Option<String>, _>("description")?.as_deref()),
+            image_url: blank_to_none(r.try_get::<Option<String>, _>("image_url")?.as_deref()),
+            sort_order: r.try_get("sort_order")?,
+            is_visible: r.try_get("is_visible")?,
+        });
+    }
+    Ok(categories)
+}
+
+/// Products in menu order.
+async fn current_products(pool: &SqlitePool) -> Result<Vec<ProductRow>, sqlx::Error> {
+    let mut product_allergens = allergens::allergens_by_product(pool).await?;
+    let mut product_additives = allergens::additives_by_product(pool).await?;
+    let rows = sqlx::query(r#"
+        SELECT p.id, p.name, p.unit_amount, p.currency, p.category_id, c.name AS category_name, p.description, p.image_url,
+               p.spice_level, p.serving_size, p.dietary_tags, p.ingredients, p.is_available
+        FROM products p
+        LEFT JOIN categories c ON c.id = p.category_id
+        ORDER BY c.id IS NULL, c.sort_order, p.name COLLATE NOCASE
+    "#)
+        .fetch_all(pool)
+        .await?;
+    let mut products = Vec::with_capacity(rows.len());
+    for r in rows {
+        let id: String = r.try_get("id")?;
+        let text = |column: &str| r.try_get::<Option<String>, _>(column).map(|v| blank_to_none(v.as_deref()));
+        products.push(ProductRow {
+            name: r.try_get("name")?,
+            unit_amount: r.try_get("unit_amount")?,
+            currency: r.try_get("currency")?,
+            category_id: r.try_get("category_id")?,
+            category_name: r.try_get("category_name")?,
+            description: text("description")?,
+            image_url: text("image_url")?,
+            allergens: product_allergens.remove(&id).unwrap_or_default().into_iter().map(|a| a.code).collect(),
+            additives: product_additives.remove(&id).unwrap_or_default().into_iter().map(|a| a.code).collect(),
+            spice_level: text("spice_level")?,
+            serving_size: text("serving_size")?,
+            dietary_tags: text("dietary_tags")?,
+            ingredients: text("ingredients")?,
+            is_available: r.try_get("is_available")?,
+            id,
+        });
+    }
+    Ok(products)
+}
+
+/// The full menu: categories in order, then every product (including the
+/// unavailable ones) in menu order.
+pub async fn export(pool: &SqlitePool) -> Result<MenuFile, sqlx::Error> {
+    let categories = current_categories(pool).await?.into_iter().map(|c| MenuCategory {
+        id: c.id,
+        name: c.name,
+        description: c.description,
+        image_url: c.image_url,
+        sort_order: Some(c.sort_order),
+        is_visible: Some(c.is_visible),
+    }).collect();
+    let products = current_products(pool).await?.into_iter().map(|p| MenuItem {
+        id: p.id,
+        name: p.name,
+        unit_amount: p.unit_amount,
+        currency: Some(p.currency),
+        category: p.category_id,
+        description: p.description,
+        image_url: p.image_url,
+        allergens: (!p.allergens.is_empty()).then(|| p.allergens.join(",")),
+        additives: (!p.additives.is_empty()).then(|| p.additives.join(",")),
+        spice_level: p.spice_level,
+        serving_size: p.serving_size,
+        dietary_tags: p.dietary_tags,
+        ingredients: p.ingredients,
+        is_available: Some(p.is_available),
+    }).collect();
+    Ok(MenuFile { categories, products })
+}
+
+pub fn to_csv(products: &[MenuItem]) -> Result<String, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for product in products {
+        writer.serialize(product)?;
+    }
+    let bytes = writer.into_inner().map_err(|e| csv::Error::from(e.into_error()))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Read products from CSV with a header line; a record that does not fit the
+/// columns fails the whole file.
+pub fn from_csv(text: &str) -> Result<MenuFile, ImportError> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(text.as_bytes());
+    let mut products = Vec::new();
+    let mut errors = Vec::new();
+    for (i, record) in reader.deserialize::<MenuItem>().enumerate() {
+        match record {
+            Ok(product) => products.push(product),
+            Err(e) => errors.push(RowError { section: "products", row: i + 1, id: None, message: e.to_string() }),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ImportError::Invalid(ImportPlan { errors, ..Default::default() }));
+    }
+    Ok(MenuFile { categories: Vec::new(), products })
+}
+
+/// Validate an import against the current menu and work out the changes.
+/// Products and categories missing from the file are left alone.
+pub async fn plan(pool: &SqlitePool, file: &MenuFile) -> Result<ImportPlan, sqlx::Error> {
+    let mut plan = ImportPlan::default();
+    let existing_categories = current_categories(pool).await?;
+    let existing_products: HashMap<String, ProductRow> = current_products(pool).await?.into_iter().map(|p| (p.id.clone(), p)).collect();
+    let known_allergens: Vec<String> = allergens::list_allergens(pool).await?.into_iter().map(|a| a.code).collect();
+    let known_additives: Vec<String> = allergens::list_additives(pool).await?.into_iter().map(|a| a.code).collect();
+
+    // Categories first, so products can be filed under new ones
+    let mut next_sort_order = existing_categories.iter().map(|c| c.sort_order).max().unwrap_or(0) + 10;
+    let mut categories: Vec<CategoryRow> = Vec::new();
+    for (i, input) in file.categories.iter().enumerate() {
+        let id = input.id.trim().to_string();
+        let name = input.name.trim().to_string();
+        let mut fail = |message: String| plan.errors.push(RowError { section: "categories", row: i + 1, id: Some(id.clone()).filter(|id| !id.is_empty()), message });
+        if id.is_empty() {
+            fail("id must not be empty".into());
+            continue;
+        }
+        if name.is_empty() {
+            fail("name must not be empty".into());
+            continue;
+        }
+        if categories.iter().any(|c| c.id == id) {
+            fail(format!("category {} is listed twice", id));
+            continue;
+        }
+        if let Some(other) = categories.iter().find(|c| c.name.to_lowercase() == name.to_lowercase()) {
+            fail(format!("name '{}' is also used by category {}", name, other.id));
+            continue;
+        }
+        let renamed_in_file = |c: &CategoryRow| file.categories.iter().any(|f| f.id.trim() == c.id);
+        if let Some(other) = existing_categories.iter().find(|c| c.id != id && c.name.to_lowercase() == name.to_lowercase() && !renamed_in_file(c)) {
+            fail(format!("name '{}' is already used by category {}", name, other.id));
+            continue;
+        }
+        let old = existing_categories.iter().find(|c| c.id == id);
+        let sort_order = match (input.sort_order, old) {
+            (Some(s), _) => s,
+            (None, Some(old)) => old.sort_order,
+            (None, None) => {
+                next_sort_order += 10;
+                next_sort_order - 10
+            }
+        };
+        let row = CategoryRow {
+            id,
+            name,
+            description: blank_to_none(input.description.as_deref()),
+            image_url: blank_to_none(input.image_url.as_deref()),
+            sort_order,
+            is_visible: input.is_visible.unwrap_or_else(|| old.is_none_or(|c| c.is_visible)),
+        };
+        match old {
+            None => plan.changes.push(Change { section: "categories", id: row.id.clone(), action: "create", fields: Vec::new() }),
+            Some(old) => match row.changed_fields(old) {
+                fields if fields.is_empty() => plan.unchanged += 1,
+                fields => plan.changes.push(Change { section: "categories", id: row.id.clone(), action: "update", fields }),
+            },
+        }
+        categories.push(row);
+    }
+    let category_for = |raw: &str| -> Option<(String, String)> {
+        let in_file = categories.iter().find(|c| c.id == raw || c.name.to_lowercase() == raw.to_lowercase());
+        let existing = || existing_categories.iter().find(|c| !categories.iter().any(|f| f.id == c.id) && (c.id == raw || c.name.to_lowercase() == raw.to_lowercase()));
+        in_file.or_else(existing).map(|c| (c.id.clone(), c.name.clone()))
+    };
+
+    let mut seen: HashSet<String> = HashSet::new();
+    for (i, input) in file.products.iter().enumerate() {
+        let id = input.id.trim().to_string();
+        let mut problems: Vec<String> = Vec::new();
+        if id.is_empty() {
+            problems.push("id must not be empty".into());
+        } else if !seen.insert(id.clone()) {
+            problems.push(format!("product {} is listed twice", id));
+        }
+        let name = input.name.trim().to_string();
+        if name.is_empty() {
+            problems.push("name must not be empty".into());
+        }
+        if input.unit_amount < 0 {
+            problems.push("unit_amount must not be negative".into());
+        }
+        let currency = blank_to_none(input.currency.as_deref()).unwrap_or_else(|| "EUR".into()).to_uppercase();
+        if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+            problems.push(format!("'{}' is not a three-letter currency code", currency));
+        }
+        let category = match blank_to_none(input.category.as_deref()) {
+            Some(raw) => {
+                let found = category_for(&raw);
+                if found.is_none() {
+                    problems.push(format!("unknown category '{}'", raw));
+                }
+                found
+            }
+            None => None,
+        };
+        let allergen_codes = split_codes(input.allergens.as_deref().unwrap_or(""));
+        let unknown: Vec<&String> = allergen_codes.iter().filter(|c| !known_allergens.contains(c)).collect();
+        if !unknown.is_empty() {
+            problems.push(format!("unknown allergen code(s): {}", unknown.iter().map(|c| c.as_str()).collect::<Vec<_>>().join(", ")));
+        }
+        let additive_codes = split_codes(input.additives.as_deref().unwrap_or(""));
+        let unknown: Vec<&String> = additive_codes.iter().filter(|c| !known_additives.contains(c)).collect();
+        if !unknown.is_empty() {
+            problems.push(format!("unknown additive code(s): {}", unknown.iter().map(|c| c.as_str()).collect::<Vec<_>>().join(", ")));
+        }
+        if !problems.is_empty() {
+            let id = Some(id).filter(|id| !id.is_empty());
+            plan.errors.extend(problems.into_iter().map(|message| RowError { section: "products", row: i + 1, id: id.clone(), message }));
+            continue;
+        }
+
+        let (category_id, category_name) = category.unzip();
+        let row = ProductRow {
+            id,
+            name,
+            unit_amount: input.unit_amount,
+            currency,
+            category_id,
+            category_name,
+            description: blank_to_none(input.description.as_deref()),
+            image_url: blank_to_none(input.image_url.as_deref()),
+            allergens: known_allergens.iter().filter(|k| allergen_codes.contains(k)).cloned().collect(),
+            additives: known_additives.iter().filter(|k| additive_codes.contains(k)).cloned().collect(),
+            spice_level: blank_to_none(input.spice_level.as_deref()),
+            serving_size: blank_to_none(input.serving_size.as_deref()),
+            dietary_tags: blank_to_none(input.dietary_tags.as_deref()),
+            ingredients: blank_to_none(input.ingredients.as_deref()),
+            is_available: input.is_available.unwrap_or(true),
+        };
+        match existing_products.get(&row.id) {
+            None => plan.changes.push(Change { section: "products", id: row.id.clone(), action: "create", fields: Vec::new() }),
+            Some(old) => match row.changed_fields(old) {
+                fields if fields.is_empty() => plan.unchanged += 1,
+                fields => plan.changes.push(Change { section: "products", id: row.id.clone(), action: "update", fields }),
+            },
+        }
+        plan.products.push(row);
+    }
+    plan.categories = categories;
+    Ok(plan)
+}
+
+/// Write the changes of a valid plan in one transaction.
+pub async fn apply(pool: &SqlitePool, plan: &ImportPlan) -> Result<(), ImportError> {
+    let changed = |section: &str, id: &str| plan.changes.iter().any(|c| c.section == section && c.id == id);
+    let mut tx = pool.begin().await?;
+    // Park changed categories (and the menus listing them by name) on a
+    // placeholder first, so swapped names do not trip the unique index
+    for c in plan.categories.iter().filter(|c| changed("categories", &c.id)) {
+        let placeholder = format!("#import:{}", c.id);
+        sqlx::query(r#"UPDATE OR IGNORE menu_categories SET category = ?1 WHERE category = (SELECT name FROM categories WHERE id = ?2)"#)
+            .bind(&placeholder)
+            .bind(&c.id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(r#"UPDATE categories SET name = ? WHERE id = ?"#).bind(&placeholder).bind(&c.id).execute(&mut *tx).await?;
+    }
+    for c in plan.categories.iter().filter(|c| changed("categories", &c.id)) {
+        sqlx::query(r#"
+            INSERT INTO categories (id, name, description, image_url, sort_order, is_visible) VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT (id) DO UPDATE SET
+                name = excluded.name, description = excluded.description, image_url = excluded.image_url,
+                sort_order = excluded.sort_order, is_visible = excluded.is_visible
+        "#)
+            .bind(&c.id)
+            .bind(&c.name)
+            .bind(c.description.as_deref())
+            .bind(c.image_url.as_deref())
+            .bind(c.sort_order)
+            .bind(c.is_visible)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(r#"UPDATE products SET category = ? WHERE category_id = ?"#).bind(&c.name).bind(&c.id).execute(&mut *tx).await?;
+        sqlx::query(r#"UPDATE menu_categories SET category = ? WHERE category = ?"#).bind(&c.name).bind(format!("#import:{}", c.id)).execute(&mut *tx).await?;
+    }
+    for p in plan.products.iter().filter(|p| changed("products", &p.id)) {
+        sqlx::query(r#"
+            INSERT INTO products (id, name, unit_amount, currency, category_id, category, description, image_url, spice_level, serving_size, dietary_tags, ingredients, is_available)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (id) DO UPDATE SET
+                name = excluded.name, unit_amount = excluded.unit_amount, currency = excluded.currency,
+                category_id = excluded.category_id, category = excluded.category, description = excluded.description,
+                image_url = excluded.image_url, spice_level = excluded.spice_level, serving_size = excluded.serving_size,
+                dietary_tags = excluded.dietary_tags, ingredients = excluded.ingredients, is_available = excluded.is_available
+        "#)
+            .bind(&p.id)
+            .bind(&p.name)
+            .bind(p.unit_amount)
+            .bind(&p.currency)
+            .bind(p.category_id.as_deref())
+            .bind(p.category_name.as_deref())
+            .bind(p.description.as_deref())
+            .bind(p.image_url.as_deref())
+            .bind(p.spice_level.as_deref())
+            .bind(p.serving_size.as_deref())
+            .bind(p.dietary_tags.as_deref())
+            .bind(p.ingredients.as_deref())
+            .bind(p.is_available)
+            .execute(&mut *tx)
+            .await?;
+        allergens::set_allergens(&mut tx, &p.id, &p.allergens).await?;
+        allergens::set_additives(&mut tx, &p.id, &p.additives).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{add_product, mock_state};
+
+    fn item(id: &str, category: Option<&str>) -> MenuItem {
+        MenuItem {
+            id: id.into(),
+            name: id.to_uppercase(),
+            unit_amount: 1250,
+            currency: None,
+            category: category.map(Into::into),
+            description: None,
+            image_url: None,
+            allergens: None,
+            additives: None,
+            spice_level: None,
+            serving_size: None,
+            dietary_tags: None,
+            ingredients: None,
+            is_available: None,
+        }
+    }
+
+    fn section(id: &str, name: &str) -> MenuCategory {
+        MenuCategory { id: id.into(), name: name.into(), description: None, image_url: None, sort_order: None, is_visible: None }
+    }
+
+    #[tokio::test]
+    async fn an_export_imports_back_without_changes() {
+        let pool = mock_state().await.pool.clone();
+        add_product(&pool, "pho", 1250).await;
+        let file = MenuFile { categories: vec![section("suppen-test", "Suppen Test")], products: vec![MenuItem { allergens: Some("g,A".into()), ..item("bun", Some("Suppen Test")) }] };
+        apply(&pool, &plan(&pool, &file).await.unwrap()).await.unwrap();
+
+        let exported = export(&pool).await.unwrap();
+        let again = plan(&pool, &exported).await.unwrap();
+        assert!(again.errors.is_empty() && again.changes.is_empty());
+        assert_eq!(again.unchanged, exported.categories.len() + exported.products.len());
+
+        let csv = to_csv(&exported.products).unwrap();
+        let from_file = plan(&pool, &from_csv(&csv).unwrap()).await.unwrap();
+        assert!(from_file.errors.is_empty() && from_file.changes.is_empty());
+        let bun = exported.products.iter().find(|p| p.id == "bun").unwrap();
+        assert_eq!((bun.category.as_deref(), bun.allergens.as_deref(), bun.currency.as_deref()), (Some("suppen-test"), Some("a,g"), Some("EUR")));
+
+        assert!(matches!(from_csv("id,name,unit_amount\npho,Pho,zwölf\n"), Err(ImportError::Invalid(p)) if p.errors[0].row == 1));
+    }
+
+    #[tokio::test]
+    async fn invalid_rows_are_reported_per_record() {
+        let pool = mock_state().await.pool.clone();
+        let file = MenuFile {
+            categories: vec![section("", "Leer"), section("dessert-test", "Dessert Test"), section("dessert-test", "Nachtisch Test")],
+            products: vec![
+                item("pho", Some("Getränke Test")),
+                MenuItem { currency: Some("euro".into()), unit_amount: -1, ..item("bun", None) },
+                MenuItem { allergens: Some("a,z".into()), additives: Some("99".into()), ..item("pho", None) },
+            ],
+        };
+        let plan = plan(&pool, &file).await.unwrap();
+        let rows: Vec<(&str, usize)> = plan.errors.iter().map(|e| (e.section, e.row)).collect();
+        assert_eq!(rows, vec![("categories", 1), ("categories", 3), ("products", 1), ("products", 2), ("products", 2), ("products", 3), ("products", 3), ("products", 3)]);
+        assert!(plan.errors.iter().any(|e| e.message == "unknown category 'Getränke Test'"));
+    }
+
+    #[tokio::test]
+    async fn categories_can_swap_names_in_one_import() {
+        let pool = mock_state().await.pool.clone();
+        let first = MenuFile { categories: vec![section("x-test", "Vorne Test"), section("y-test", "Hinten Test")], products: vec![item("pho", Some("Vorne Test"))] };
+        apply(&pool, &plan(&pool, &first).await.unwrap()).await.unwrap();
+        sqlx::query(r#"INSERT INTO menus (id, name) VALUES ('lunch', 'Lunch')"#).execute(&pool).await.unwrap();
+        sqlx::query(r#"INSERT INTO menu_categories (menu_id, category) VALUES ('lunch', 'Vorne Test')"#).execute(&pool).await.unwrap();
+
+        let swapped = MenuFile { categories: vec![section("x-test", "Hinten Test"), section("y-test", "Vorne Test")], products: Vec::new() };
+        let plan = plan(&pool, &swapped).await.unwrap();
+        assert!(plan.errors.is_empty());
+        assert!(plan.changes.iter().all(|c| c.action == "update" && c.fields == vec!["name"]));
+        apply(&pool, &plan).await.unwrap();
+
+        let product: String = sqlx::query_scalar(r#"SELECT category FROM products WHERE id = 'pho'"#).fetch_one(&pool).await.unwrap();
+        let on_menu: String = sqlx::query_scalar(r#"SELECT category FROM menu_categories WHERE menu_id = 'lunch'"#).fetch_one(&pool).await.unwrap();
+        assert_eq!((product.as_str(), on_menu.as_str()), ("Hinten Test", "Hinten Test"));
+    }
+}
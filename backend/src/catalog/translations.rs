@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+
+use crate::schedule::db_timestamp;
+
+/// A language the menu is offered in. `products` holds the German text;
+/// the others come from `product_translations`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    De,
+    En,
+    Vi,
+}
+
+impl Locale {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Locale::De => "de",
+            Locale::En => "en",
+            Locale::Vi => "vi",
+        }
+    }
+
+    /// Accepts `en`, `EN` or a tag like `en-GB`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let primary = s.trim().split(['-', '_']).next().unwrap_or("").to_lowercase();
+        match primary.as_str() {
+            "de" => Some(Locale::De),
+            "en" => Some(Locale::En),
+            "vi" => Some(Locale::Vi),
+            _ => None,
+        }
+    }
+}
+
+/// Translated text of one product; `None` falls back to the German text.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Translation {
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TranslationError {
+    #[error("unsupported locale '{0}', expected de, en or vi")]
+    UnsupportedLocale(String),
+    #[error("German text is edited on the product itself")]
+    DefaultLocale,
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for TranslationError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            TranslationError::UnsupportedLocale(_) => (StatusCode::BAD_REQUEST, "unsupported_locale"),
+            TranslationError::DefaultLocale => (StatusCode::BAD_REQUEST, "default_locale"),
+            TranslationError::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            TranslationError::Database(ref e) => {
+                tracing::error!("Product translation query failed: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            }
+        };
+        (status, Json(serde_json::json!({ "error": code, "message": self.to_string() }))).into_response()
+    }
+}
+
+pub fn parse_locale(s: &str) -> Result<Locale, TranslationError> {
+    Locale::parse(s).ok_or_else(|| TranslationError::UnsupportedLocale(s.to_string()))
+}
+
+/// A locale other than the default, for the translation endpoints.
+pub fn translated_locale(s: &str) -> Result<Locale, TranslationError> {
+    match parse_locale(s)? {
+        Locale::De => Err(TranslationError::DefaultLocale),
+        locale => Ok(locale),
+    }
+}
+
+/// Translations of every product into `locale`; empty for the default locale.
+pub async fn for_locale(pool: &SqlitePool, locale: Locale) -> Result<HashMap<String, Translation>, sqlx::Error> {
+    if locale == Locale::default() {
+        return Ok(HashMap::new());
+    }
+    let rows = sqlx::query(r#"SELECT product_id, name, description FROM product_translations WHERE locale = ?"#)
+        .bind(locale.as_str())
+        .fetch_all(pool)
+        .await?;
+    let mut by_product = HashMap::new();
+    for r in rows {
+        by_product.insert(r.try_get("product_id")?, Translation {
+            name: r.try_get("name")?,
+            description: r.try_get("description")?,
+        });
+    }
+    Ok(by_product)
+}
+
+/// All translations of one product, keyed by locale.
+pub async fn for_product(pool: &SqlitePool, product_id: &str) -> Result<HashMap<String, Translation>, TranslationError> {
+    ensure_product(pool, product_id).await?;
+    let rows = sqlx::query(r#"SELECT locale, name, description FROM product_translations WHERE product_id = ? ORDER BY locale"#)
+        .bind(product_id)
+        .fetch_all(pool)
+        .await?;
+    let mut by_locale = HashMap::new();
+    for r in rows {
+        by_locale.insert(r.try_get("locale")?, Translation {
+            name: r.try_get("name")?,
+            description: r.try_get("description")?,
+        });
+    }
+    Ok(by_locale)
+}
+
+/// Create or replace the translation of a product; blank fields are stored as NULL.
+pub async fn upsert(pool: &SqlitePool, product_id: &str, locale: &str, input: &Translation) -> Result<(), TranslationError> {
+    let locale = translated_locale(locale)?;
+    ensure_product(pool, product_id).await?;
+    let blank_to_none = |v: &Option<String>| v.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string);
+    sqlx::query(r#"
+        INSERT INTO product_translations (product_id, locale, name, description, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        ON CONFLICT (product_id, locale) DO UPDATE SET
+            name = excluded.name, description = excluded.description, updated_at = excluded.updated_at
+    "#)
+        .bind(product_id)
+        .bind(locale.as_str())
+        .bind(blank_to_none(&input.name))
+        .bind(blank_to_none(&input.description))
+        .bind(db_timestamp(chrono::Utc::now()))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn delete(pool: &SqlitePool, product_id: &str, locale: &str) -> Result<(), TranslationError> {
+    let locale = translated_locale(locale)?;
+    let deleted = sqlx::query(r#"DELETE FROM product_translations WHERE product_id = ? AND locale = ?"#)
+        .bind(product_id)
+        .bind(locale.as_str())
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(TranslationError::NotFound(format!("{} translation of product {}", locale.as_str(), product_id)));
+    }
+    Ok(())
+}
+
+async fn ensure_product(pool: &SqlitePool, product_id: &str) -> Result<(), TranslationError> {
+    let exists: Option<i64> = sqlx::query_scalar(r#"SELECT 1 FROM products WHERE id = ?"#)
+        .bind(product_id)
+        .fetch_optional(pool)
+        .await?;
+    exists.map(|_| ()).ok_or_else(|| TranslationError::NotFound(format!("product {}", product_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{add_product, mock_state};
+
+    fn text(name: &str, description: &str) -> Translation {
+        Translation { name: Some(name.into()), description: Some(description.into()) }
+    }
+
+    #[test]
+    fn locales_are_read_from_language_tags() {
+        assert_eq!(Locale::parse("EN"), Some(Locale::En));
+        assert_eq!(Locale::parse(" vi-VN "), Some(Locale::Vi));
+        assert_eq!(Locale::parse("de_AT"), Some(Locale::De));
+        assert_eq!(Locale::parse("fr"), None);
+        assert!(matches!(translated_locale("de"), Err(TranslationError::DefaultLocale)));
+        assert!(matches!(translated_locale("fr"), Err(TranslationError::UnsupportedLocale(_))));
+    }
+
+    #[tokio::test]
+    async fn translations_are_replaced_per_locale_and_blanks_fall_back() {
+        let pool = mock_state().await.pool.clone();
+        add_product(&pool, "pho", 1250).await;
+
+        upsert(&pool, "pho", "en", &text("Beef noodle soup", "  ")).await.unwrap();
+        upsert(&pool, "pho", "vi", &text("Phở bò", "Nước dùng bò")).await.unwrap();
+        upsert(&pool, "pho", "en", &text("Pho", "Rice noodles in beef broth")).await.unwrap();
+        upsert(&pool, "pho", "EN-gb", &text("Pho", " ")).await.unwrap();
+        assert!(matches!(upsert(&pool, "pho", "de", &text("Pho", "")).await, Err(TranslationError::DefaultLocale)));
+        assert!(matches!(upsert(&pool, "soup", "en", &text("Soup", "")).await, Err(TranslationError::NotFound(_))));
+
+        let english = for_locale(&pool, Locale::En).await.unwrap();
+        assert_eq!((english["pho"].name.as_deref(), english["pho"].description.as_deref()), (Some("Pho"), None));
+        assert!(for_locale(&pool, Locale::De).await.unwrap().is_empty());
+        assert_eq!(for_product(&pool, "pho").await.unwrap().len(), 2);
+
+        delete(&pool, "pho", "vi").await.unwrap();
+        assert!(matches!(delete(&pool, "pho", "vi").await, Err(TranslationError::NotFound(_))));
+        assert!(for_locale(&pool, Locale::Vi).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use uuid::Uuid;
+
+use super::{write, CouponError, RulesInput};
+use crate::schedule::ScheduleConfig;
+
+/// Characters of generated codes: no 0/O, 1/I/L that get mixed up on paper.
+const ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+pub const MAX_CODES: i64 = 10_000;
+const DEFAULT_LENGTH: usize = 8;
+const MAX_PREFIX: usize = 16;
+
+/// A batch of codes to generate, all with the same rules.
+#[derive(Deserialize)]
+pub struct CampaignInput {
+    pub name: String,
+    pub description: Option<String>,
+    /// Put in front of every code, e.g. `FLYER-`.
+    #[serde(default)]
+    pub prefix: String,
+    /// Random characters after the prefix; 4 to 16, default 8.
+    pub length: Option<usize>,
+    pub count: i64,
+    #[serde(flatten)]
+    pub rules: RulesInput,
+}
+
+/// A campaign with the usage of its codes; reversed redemptions are left out.
+#[derive(Serialize, Debug)]
+pub struct Campaign {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub prefix: String,
+    pub code_length: i64,
+    pub created_by: Option<String>,
+    pub created_at: String,
+    pub codes: i64,
+    /// Codes used at least once.
+    pub redeemed_codes: i64,
+    pub redemptions: i64,
+    pub customers: i64,
+    pub discount_cents: i64,
+}
+
+/// One generated code and how it was used.
+#[derive(Serialize, Debug)]
+pub struct CampaignCode {
+    pub code: String,
+    pub remaining_uses: i64,
+    pub redemptions: i64,
+    pub discount_cents: i64,
+    pub last_redeemed_at: Option<String>,
+}
+
+fn random_code(prefix: &str, length: usize) -> String {
+    let mut rng = rand::thread_rng();
+    let random: String = (0..length).map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char).collect();
+    format!("{}{}", prefix, random)
+}
+
+/// Generate `count` unique single-use codes under a new campaign, all in one
+/// transaction. Returns the campaign id.
+pub async fn create(pool: &SqlitePool, cfg: &ScheduleConfig, input: &CampaignInput, created_by: &str) -> Result<String, CouponError> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(CouponError::Invalid("name must not be empty".into()));
+    }
+    let prefix = input.prefix.trim().to_uppercase();
+    if prefix.len() > MAX_PREFIX || !prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(CouponError::Invalid(format!("prefix must be at most {} letters, digits, '-' or '_'", MAX_PREFIX)));
+    }
+    let length = input.length.unwrap_or(DEFAULT_LENGTH);
+    if !(4..=16).contains(&length) {
+        return Err(CouponError::Invalid("length must be between 4 and 16".into()));
+    }
+    if !(1..=MAX_CODES).contains(&input.count) {
+        return Err(CouponError::Invalid(format!("count must be between 1 and {}", MAX_CODES)));
+    }
+    // Keep codes sparse so they cannot be guessed from one another
+    let space = (ALPHABET.len() as f64).powi(length as i32);
+    if space < input.count as f64 * 1000.0 {
+        return Err(CouponError::Invalid(format!("{} random characters are too few for {} codes", length, input.count)));
+    }
+    let rules = input.rules.validate(pool, cfg).await?;
+
+    let id = Uuid::new_v4().to_string();
+    let mut tx = pool.begin().await?;
+    sqlx::query(r#"INSERT INTO coupon_campaigns (id, name, description, prefix, code_length, created_by) VALUES (?, ?, ?, ?, ?, ?)"#)
+        .bind(&id)
+        .bind(name)
+        .bind(input.description.as_deref().map(str::trim).filter(|d| !d.is_empty()))
+        .bind(&prefix)
+        .bind(length as i64)
+        .bind(created_by)
+        .execute(&mut *tx)
+        .await?;
+    let mut created = 0;
+    let mut attempts = 0;
+    while created < input.count {
+        attempts += 1;
+        if attempts > input.count * 10 {
+            return Err(CouponError::Invalid("could not find enough unused codes; use a longer code or another prefix".into()));
+        }
+        // Taken codes (other campaigns, hand-made ones) are skipped and drawn again
+        if write(&mut tx, &random_code(&prefix, length), 1, &rules, Some(&id)).await? {
+            created += 1;
+        }
+    }
+    tx.commit().await?;
+    Ok(id)
+}
+
+const CAMPAIGN_SELECT: &str = r#"
+    SELECT c.id, c.name, c.description, c.prefix, c.code_length, c.created_by, c.created_at,
+           (SELECT COUNT(*) FROM coupons k WHERE k.campaign_id = c.id) AS codes,
+           COUNT(DISTINCT r.code) AS redeemed_codes,
+           COUNT(r.id) AS redemptions,
+           COUNT(DISTINCT COALESCE(r.user_id, lower(r.email))) AS customers,
+           COALESCE(SUM(r.discount_cents), 0) AS discount_cents
+    FROM coupon_campaigns c
+    LEFT JOIN coupons k ON k.campaign_id = c.id
+    LEFT JOIN coupon_redemptions r ON r.code = k.code AND r.reversed_at IS NULL"#;
+
+fn campaign_from_row(r: &sqlx::sqlite::SqliteRow) -> Result<Campaign, sqlx::Error> {
+    Ok(Campaign {
+        id: r.try_get("id")?,
+        name: r.try_get("name")?,
+        description: r.try_get("description")?,
+        prefix: r.try_get("prefix")?,
+        code_length: r.try_get("code_length")?,
+        created_by: r.try_get("created_by")?,
+        created_at: r.try_get("created_at")?,
+        codes: r.try_get("codes")?,
+        redeemed_codes: r.try_get("redeemed_codes")?,
+        redemptions: r.try_get("redemptions")?,
+        customers: r.try_get("customers")?,
+        discount_cents: r.try_get("discount_cents")?,
+    })
+}
+
+/// All campaigns with their usage, newest first.
+pub async fn list(pool: &SqlitePool) -> Result<Vec<Campaign>, sqlx::Error> {
+    sqlx::query(&format!("{} GROUP BY c.id ORDER BY c.created_at DESC", CAMPAIGN_SELECT))
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(campaign_from_row)
+        .collect()
+}
+
+pub async fn get(pool: &SqlitePool, id: &str) -> Result<Campaign, CouponError> {
+    let row = sqlx::query(&format!("{} WHERE c.id = ? GROUP BY c.id", CAMPAIGN_SELECT))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| CouponError::NotFound(format!("campaign {}", id)))?;
+    Ok(campaign_from_row(&row)?)
+}
+
+/// The codes of a campaign in code order.
+pub async fn codes(pool: &SqlitePool, id: &str) -> Result<Vec<CampaignCode>, sqlx::Error> {
+    let rows = sqlx::query(
+        r#"SELECT k.code, k.remaining_uses,
+                  COUNT(r.id) AS redemptions,
+                  COALESCE(SUM(r.discount_cents), 0) AS discount_cents,
+                  MAX(r.redeemed_at) AS last_redeemed_at
+           FROM coupons k
+           LEFT JOIN coupon_redemptions r ON r.code = k.code AND r.reversed_at IS NULL
+           WHERE k.campaign_id = ?
+           GROUP BY k.code
+           ORDER BY k.code"#,
+    )
+    .bind(id)
+    .fetch_all(pool)
+    .await?;
+    rows.iter()
+        .map(|r| {
+            Ok(CampaignCode {
+                code: r.try_get("code")?,
+                remaining_uses: r.try_get("remaining_uses")?,
+                redemptions: r.try_get("redemptions")?,
+                discount_cents: r.try_get("discount_cents")?,
+                last_redeemed_at: r.try_get("last_redeemed_at")?,
+            })
+        })
+        .collect()
+}
+
+pub fn codes_csv(codes: &[CampaignCode]) -> Result<String, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for code in codes {
+        writer.serialize(code)?;
+    }
+    let bytes = writer.into_inner().map_err(|e| csv::Error::from(e.into_error()))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Delete a campaign with all of its codes.
+pub async fn delete(pool: &SqlitePool, id: &str) -> Result<(), CouponError> {
+    let deleted = sqlx::query(r#"DELETE FROM coupon_campaigns WHERE id = ?"#).bind(id).execute(pool).await?.rows_affected();
+    if deleted == 0 {
+        return Err(CouponError::NotFound(format!("campaign {}", id)));
+    }
+    Ok(())
+}
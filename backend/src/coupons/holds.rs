@@ -0,0 +1,301 @@
+use chrono::{Duration, Utc};
+use sqlx::{Row, Sqlite, SqlitePool, Transaction};
+
+use super::redemptions::Customer;
+use super::RejectionReason;
+use crate::pricing::{AppliedDiscount, DiscountKind};
+use crate::schedule::db_timestamp;
+
+/// Gift-code balance held by checkouts that are still waiting for their payment.
+pub async fn held_gift_cents(pool: &SqlitePool, code: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"SELECT COALESCE(SUM(amount_cents), 0) FROM discount_holds
+           WHERE kind = 'gift_code' AND code = ? COLLATE NOCASE AND expires_at > strftime('%Y-%m-%dT%H:%M:%fZ','now')"#,
+    )
+    .bind(code)
+    .fetch_one(pool)
+    .await
+}
+
+/// Checks a coupon hold must pass, as one row of flags for coupon `?2` and
+/// the customer `?4` (user id) / `?5` (email). These repeat the per-customer
+/// rules of `redemptions::check_customer`, counting the customer's live holds,
+/// so they can be decided in the same statement that takes the hold.
+const COUPON_CHECKS: &str = r#"SELECT
+    c.remaining_uses > (SELECT COUNT(*) FROM discount_holds h
+                        WHERE h.kind = 'coupon' AND h.code = c.code AND h.expires_at > strftime('%Y-%m-%dT%H:%M:%fZ','now')) AS has_use,
+    (c.max_uses_per_customer IS NULL AND NOT c.first_order_only) OR ?4 IS NOT NULL OR ?5 IS NOT NULL AS identified,
+    c.max_uses_per_customer IS NULL
+      OR (SELECT COUNT(*) FROM coupon_redemptions r
+          WHERE r.code = c.code AND r.reversed_at IS NULL AND (r.user_id = ?4 OR r.email = ?5 COLLATE NOCASE))
+       + (SELECT COUNT(*) FROM discount_holds h
+          WHERE h.kind = 'coupon' AND h.code = c.code AND h.expires_at > strftime('%Y-%m-%dT%H:%M:%fZ','now')
+            AND (h.user_id = ?4 OR h.email = ?5 COLLATE NOCASE))
+       < c.max_uses_per_customer AS under_limit,
+    NOT c.first_order_only
+      OR (NOT EXISTS (SELECT 1 FROM orders o
+                      WHERE o.status NOT IN ('cancelled', 'refunded')
+                        AND COALESCE(json_extract(o.items_json, '$.type'), '') != 'gift_coupon'
+                        AND (o.user_id = ?4 OR o.email = ?5 COLLATE NOCASE))
+          AND NOT EXISTS (SELECT 1 FROM discount_holds h JOIN coupons fc ON fc.code = h.code
+                          WHERE h.kind = 'coupon' AND fc.first_order_only AND h.expires_at > strftime('%Y-%m-%dT%H:%M:%fZ','now')
+                            AND (h.user_id = ?4 OR h.email = ?5 COLLATE NOCASE))) AS first_order
+FROM coupons c WHERE c.code = ?2"#;
+
+/// Hold `discount` for the pending order `pending_order_id` for `minutes`.
+///
+/// Each hold is one conditional insert, so two checkouts racing for the last
+/// use or the same balance cannot both succeed. A customer's earlier hold on
+/// the same code is replaced rather than counted: an abandoned checkout must
+/// not block the retry. Should both checkouts get paid anyway, finalizing
+/// flags the second one. Call it inside the transaction that writes the
+/// pending order; that write already holds SQLite's write lock.
+pub async fn reserve(
+    tx: &mut Transaction<'_, Sqlite>,
+    pending_order_id: &str,
+    discount: &AppliedDiscount,
+    customer: &Customer,
+    minutes: i64,
+) -> Result<Result<(), RejectionReason>, sqlx::Error> {
+    let expires_at = db_timestamp(Utc::now() + Duration::minutes(minutes));
+    sqlx::query(
+        r#"DELETE FROM discount_holds
+           WHERE kind = ?1 AND code = ?2 COLLATE NOCASE AND pending_order_id != ?3
+             AND (user_id = ?4 OR email = ?5 COLLATE NOCASE)"#,
+    )
+    .bind(match discount.kind {
+        DiscountKind::Coupon => "coupon",
+        DiscountKind::GiftCode => "gift_code",
+    })
+    .bind(&discount.code)
+    .bind(pending_order_id)
+    .bind(customer.user_id.as_deref())
+    .bind(customer.email.as_deref())
+    .execute(&mut **tx)
+    .await?;
+    let coupon_sql = format!(
+        r#"INSERT INTO discount_holds (pending_order_id, kind, code, amount_cents, user_id, email, expires_at)
+           SELECT ?1, 'coupon', ?2, ?3, ?4, ?5, ?6 FROM ({COUPON_CHECKS})
+           WHERE has_use AND identified AND under_limit AND first_order"#
+    );
+    let held = match discount.kind {
+        DiscountKind::Coupon => sqlx::query(&coupon_sql),
+        DiscountKind::GiftCode => sqlx::query(
+            r#"INSERT INTO discount_holds (pending_order_id, kind, code, amount_cents, user_id, email, expires_at)
+               SELECT ?1, 'gift_code', ?2, ?3, ?4, ?5, ?6
+               WHERE (SELECT remaining_cents FROM gift_codes WHERE code = ?2 COLLATE NOCASE)
+                     - (SELECT COALESCE(SUM(amount_cents), 0) FROM discount_holds
+                        WHERE kind = 'gift_code' AND code = ?2 COLLATE NOCASE AND expires_at > strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+                     >= ?3"#,
+        ),
+    }
+    .bind(pending_order_id)
+    .bind(&discount.code)
+    .bind(discount.discount_cents)
+    .bind(customer.user_id.as_deref())
+    .bind(customer.email.as_deref())
+    .bind(expires_at)
+    .execute(&mut **tx)
+    .await?
+    .rows_affected();
+    if held > 0 {
+        return Ok(Ok(()));
+    }
+    if discount.kind == DiscountKind::GiftCode {
+        return Ok(Err(RejectionReason::Reserved));
+    }
+
+    // Same transaction, so the flags are the ones the insert just saw
+    let Some(row) = sqlx::query(COUPON_CHECKS)
+        .bind(pending_order_id)
+        .bind(&discount.code)
+        .bind(discount.discount_cents)
+        .bind(customer.user_id.as_deref())
+        .bind(customer.email.as_deref())
+        .fetch_optional(&mut **tx)
+        .await?
+    else {
+        return Ok(Err(RejectionReason::UnknownCode));
+    };
+    let reason = if !row.try_get::<bool, _>("identified")? {
+        RejectionReason::EmailRequired
+    } else if !row.try_get::<bool, _>("first_order")? {
+        RejectionReason::FirstOrderOnly
+    } else if !row.try_get::<bool, _>("under_limit")? {
+        RejectionReason::CustomerLimitReached
+    } else {
+        RejectionReason::Reserved
+    };
+    Ok(Err(reason))
+}
+
+/// Drop the hold of `pending_order_id`, so the checks below only count the
+/// holds of other checkouts.
+pub async fn release(tx: &mut Transaction<'_, Sqlite>, pending_order_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"DELETE FROM discount_holds WHERE pending_order_id = ?"#)
+        .bind(pending_order_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Take one use of coupon `code`, unless the uses left are all held by other
+/// checkouts. Returns whether a use was taken.
+pub async fn consume_coupon(tx: &mut Transaction<'_, Sqlite>, code: &str) -> Result<bool, sqlx::Error> {
+    let used = sqlx::query(
+        r#"UPDATE coupons SET remaining_uses = remaining_uses - 1
+           WHERE code = ?1
+             AND remaining_uses > (SELECT COUNT(*) FROM discount_holds
+                                   WHERE kind = 'coupon' AND code = ?1 AND expires_at > strftime('%Y-%m-%dT%H:%M:%fZ','now'))"#,
+    )
+    .bind(code)
+    .execute(&mut **tx)
+    .await?
+    .rows_affected();
+    Ok(used > 0)
+}
+
+/// Balance of gift code `code` not held by other checkouts.
+pub async fn available_gift_cents(tx: &mut Transaction<'_, Sqlite>, code: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"SELECT COALESCE((SELECT remaining_cents FROM gift_codes WHERE code = ?1 COLLATE NOCASE), 0)
+                  - (SELECT COALESCE(SUM(amount_cents), 0) FROM discount_holds
+                     WHERE kind = 'gift_code' AND code = ?1 COLLATE NOCASE AND expires_at > strftime('%Y-%m-%dT%H:%M:%fZ','now'))"#,
+    )
+    .bind(code)
+    .fetch_one(&mut **tx)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{add_coupon, add_gift_card, add_product, mock_state, pay_order, start_order};
+
+    fn coupon(code: &str) -> AppliedDiscount {
+        AppliedDiscount { code: code.into(), kind: DiscountKind::Coupon, percent_off: Some(10), amount_off: None, discount_cents: 100 }
+    }
+
+    fn gift(code: &str, cents: i64) -> AppliedDiscount {
+        AppliedDiscount { code: code.into(), kind: DiscountKind::GiftCode, percent_off: None, amount_off: None, discount_cents: cents }
+    }
+
+    fn customer(email: &str) -> Customer {
+        Customer::new(None, Some(email))
+    }
+
+    /// Write pending order `id` and try to hold `discount` for it.
+    async fn hold(pool: &SqlitePool, id: &str, discount: &AppliedDiscount, customer: &Customer, minutes: i64) -> Result<(), RejectionReason> {
+        let mut tx = pool.begin().await.unwrap();
+        sqlx::query(r#"INSERT INTO pending_orders (order_id, email, amount_cents) VALUES (?, ?, 1000)"#)
+            .bind(id)
+            .bind(customer.email.as_deref())
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        let held = reserve(&mut tx, id, discount, customer, minutes).await.unwrap();
+        tx.commit().await.unwrap();
+        held
+    }
+
+    #[tokio::test]
+    async fn last_coupon_use_is_held_once() {
+        let pool = mock_state().await.pool.clone();
+        add_coupon(&pool, "LAST", 10, 1).await;
+
+        assert_eq!(hold(&pool, "p-1", &coupon("LAST"), &customer("a@example.com"), 30).await, Ok(()));
+        assert_eq!(hold(&pool, "p-2", &coupon("LAST"), &customer("b@example.com"), 30).await, Err(RejectionReason::Reserved));
+        assert_eq!(hold(&pool, "p-3", &coupon("NOPE"), &customer("b@example.com"), 30).await, Err(RejectionReason::UnknownCode));
+    }
+
+    #[tokio::test]
+    async fn expired_holds_do_not_count() {
+        let pool = mock_state().await.pool.clone();
+        add_coupon(&pool, "LAST", 10, 1).await;
+        add_gift_card(&pool, "GIFT", 1000).await;
+
+        assert_eq!(hold(&pool, "p-1", &coupon("LAST"), &customer("a@example.com"), -1).await, Ok(()));
+        assert_eq!(hold(&pool, "p-2", &gift("GIFT", 800), &customer("a@example.com"), -1).await, Ok(()));
+        assert_eq!(held_gift_cents(&pool, "gift").await.unwrap(), 0);
+        assert_eq!(hold(&pool, "p-3", &coupon("LAST"), &customer("b@example.com"), 30).await, Ok(()));
+        assert_eq!(hold(&pool, "p-4", &gift("GIFT", 800), &customer("b@example.com"), 30).await, Ok(()));
+        assert_eq!(hold(&pool, "p-5", &gift("GIFT", 300), &customer("c@example.com"), 30).await, Err(RejectionReason::Reserved));
+        assert_eq!(held_gift_cents(&pool, "gift").await.unwrap(), 800);
+    }
+
+    #[tokio::test]
+    async fn retried_checkout_replaces_the_customers_hold() {
+        let pool = mock_state().await.pool.clone();
+        add_coupon(&pool, "LAST", 10, 1).await;
+        add_gift_card(&pool, "GIFT", 1000).await;
+
+        assert_eq!(hold(&pool, "p-1", &coupon("LAST"), &customer("a@example.com"), 30).await, Ok(()));
+        assert_eq!(hold(&pool, "p-2", &gift("GIFT", 800), &customer("a@example.com"), 30).await, Ok(()));
+        assert_eq!(hold(&pool, "p-3", &coupon("LAST"), &customer("A@example.com"), 30).await, Ok(()));
+        assert_eq!(hold(&pool, "p-4", &gift("GIFT", 900), &customer("a@example.com"), 30).await, Ok(()));
+        assert_eq!(held_gift_cents(&pool, "GIFT").await.unwrap(), 900);
+        let holders: Vec<String> = sqlx::query_scalar(r#"SELECT pending_order_id FROM discount_holds ORDER BY pending_order_id"#).fetch_all(&pool).await.unwrap();
+        assert_eq!(holders, vec!["p-3", "p-4"]);
+        assert_eq!(hold(&pool, "p-5", &coupon("LAST"), &customer("b@example.com"), 30).await, Err(RejectionReason::Reserved));
+    }
+
+    #[tokio::test]
+    async fn paying_both_checkouts_flags_the_second_use() {
+        let state = mock_state().await;
+        add_product(&state.pool, "soup", 1000).await;
+        add_coupon(&state.pool, "ONCE", 10, 10).await;
+        sqlx::query(r#"UPDATE coupons SET max_uses_per_customer = 1 WHERE code = 'ONCE'"#).execute(&state.pool).await.unwrap();
+
+        let first = start_order(&state, &[("soup", 1)], Some("ONCE")).await;
+        let retried = start_order(&state, &[("soup", 1)], Some("ONCE")).await;
+        let kept = pay_order(&state, &retried).await;
+        let flagged = pay_order(&state, &first).await;
+
+        let issues: Vec<(String, Option<String>)> = sqlx::query_as(r#"SELECT id, discount_issue FROM orders"#).fetch_all(&state.pool).await.unwrap();
+        for (id, issue) in issues {
+            assert_eq!(issue.is_some_and(|i| i.contains("ONCE")), id == flagged, "order {id}");
+        }
+        assert!(kept != flagged);
+    }
+
+    #[tokio::test]
+    async fn per_customer_rules_are_checked_when_holding() {
+        let pool = mock_state().await.pool.clone();
+        add_coupon(&pool, "ONCE", 10, 10).await;
+        add_coupon(&pool, "WELCOME", 10, 10).await;
+        add_coupon(&pool, "HELLO", 10, 10).await;
+        sqlx::query(r#"UPDATE coupons SET max_uses_per_customer = 1 WHERE code = 'ONCE'"#).execute(&pool).await.unwrap();
+        sqlx::query(r#"UPDATE coupons SET first_order_only = 1 WHERE code IN ('WELCOME', 'HELLO')"#).execute(&pool).await.unwrap();
+
+        let anonymous = Customer::new(None, None);
+        assert_eq!(hold(&pool, "p-1", &coupon("ONCE"), &anonymous, 30).await, Err(RejectionReason::EmailRequired));
+        assert_eq!(hold(&pool, "p-2", &coupon("ONCE"), &customer("a@example.com"), 30).await, Ok(()));
+        // A paid use counts; the customer's own checkout in flight is replaced instead
+        sqlx::query(r#"INSERT INTO orders (id, email, total_cents, status) VALUES ('order-0', 'a@example.com', 900, 'paid')"#).execute(&pool).await.unwrap();
+        sqlx::query(r#"INSERT INTO coupon_redemptions (id, code, order_id, email, discount_cents) VALUES ('r-1', 'ONCE', 'order-0', 'a@example.com', 100)"#).execute(&pool).await.unwrap();
+        assert_eq!(hold(&pool, "p-3", &coupon("ONCE"), &customer("A@example.com"), 30).await, Err(RejectionReason::CustomerLimitReached));
+        assert_eq!(hold(&pool, "p-4", &coupon("WELCOME"), &customer("a@example.com"), 30).await, Err(RejectionReason::FirstOrderOnly));
+        // A first order in flight with one first-order code rules out the others
+        assert_eq!(hold(&pool, "p-5", &coupon("WELCOME"), &customer("c@example.com"), 30).await, Ok(()));
+        assert_eq!(hold(&pool, "p-6", &coupon("HELLO"), &customer("c@example.com"), 30).await, Err(RejectionReason::FirstOrderOnly));
+    }
+
+    #[tokio::test]
+    async fn consuming_leaves_uses_held_by_others() {
+        let pool = mock_state().await.pool.clone();
+        add_coupon(&pool, "PAIR", 10, 2).await;
+        hold(&pool, "p-1", &coupon("PAIR"), &customer("a@example.com"), 30).await.unwrap();
+        hold(&pool, "p-2", &coupon("PAIR"), &customer("b@example.com"), 30).await.unwrap();
+
+        let mut tx = pool.begin().await.unwrap();
+        release(&mut tx, "p-1").await.unwrap();
+        assert!(consume_coupon(&mut tx, "PAIR").await.unwrap());
+        assert!(!consume_coupon(&mut tx, "PAIR").await.unwrap(), "the last use belongs to p-2");
+        release(&mut tx, "p-2").await.unwrap();
+        assert!(consume_coupon(&mut tx, "PAIR").await.unwrap());
+        tx.commit().await.unwrap();
+
+        let left: i64 = sqlx::query_scalar(r#"SELECT remaining_uses FROM coupons WHERE code = 'PAIR'"#).fetch_one(&pool).await.unwrap();
+        assert_eq!(left, 0);
+    }
+}
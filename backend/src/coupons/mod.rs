@@ -0,0 +1,551 @@
+//! Discount codes and the rules that decide whether one applies to a cart.
+
+use std::collections::HashMap;
+
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, Sqlite, SqlitePool, Transaction};
+
+use crate::catalog::categories::{self, CategoryError};
+use crate::pricing::PricedLine;
+use crate::schedule::{db_timestamp, ScheduleConfig};
+
+pub mod campaigns;
+pub mod holds;
+pub mod redemptions;
+
+/// A coupon with its rules. Codes are stored uppercase.
+#[derive(Serialize, Clone, Debug)]
+pub struct Coupon {
+    pub code: String,
+    pub percent_off: Option<i64>,
+    pub amount_off: Option<i64>,
+    pub remaining_uses: i64,
+    /// Uses held by checkouts waiting for their payment, see `holds`.
+    pub held_uses: i64,
+    /// UTC; `None` means valid right away.
+    pub starts_at: Option<String>,
+    /// UTC, exclusive; `None` means it never expires.
+    pub expires_at: Option<String>,
+    pub min_subtotal_cents: Option<i64>,
+    pub max_discount_cents: Option<i64>,
+    /// Only the listed products and categories are discounted.
+    pub is_scoped: bool,
+    pub product_ids: Vec<String>,
+    pub category_ids: Vec<String>,
+    /// Uses allowed per customer; `None` means no limit.
+    pub max_uses_per_customer: Option<i64>,
+    /// Only for customers without an earlier order.
+    pub first_order_only: bool,
+    /// Set for codes generated by a campaign, see `campaigns`.
+    pub campaign_id: Option<String>,
+}
+
+/// Why a code was not applied.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectionReason {
+    UnknownCode,
+    NotYetValid,
+    Expired,
+    UsedUp,
+    /// The last uses or the balance are held by other checkouts right now.
+    Reserved,
+    BelowMinimum,
+    NotApplicable,
+    /// The code has per-customer rules and nobody is signed in or gave an email.
+    EmailRequired,
+    CustomerLimitReached,
+    FirstOrderOnly,
+}
+
+impl RejectionReason {
+    pub fn message(self) -> &'static str {
+        match self {
+            RejectionReason::UnknownCode => "This code does not exist.",
+            RejectionReason::NotYetValid => "This code is not valid yet.",
+            RejectionReason::Expired => "This code has expired.",
+            RejectionReason::UsedUp => "This code has already been used up.",
+            RejectionReason::Reserved => "This code is being redeemed in another checkout. Please try again in a few minutes.",
+            RejectionReason::BelowMinimum => "The order does not reach the minimum value for this code.",
+            RejectionReason::NotApplicable => "This code does not apply to any dish in the cart.",
+            RejectionReason::EmailRequired => "Please sign in or enter your email to use this code.",
+            RejectionReason::CustomerLimitReached => "You have already used this code as often as allowed.",
+            RejectionReason::FirstOrderOnly => "This code is only valid for your first order.",
+        }
+    }
+}
+
+/// A rejected code as reported to the customer.
+#[derive(Serialize, Clone, Debug)]
+pub struct CouponRejection {
+    pub code: String,
+    pub reason: RejectionReason,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_subtotal_cents: Option<i64>,
+}
+
+impl CouponRejection {
+    pub fn new(code: &str, reason: RejectionReason) -> Self {
+        Self { code: code.to_string(), reason, message: reason.message().to_string(), min_subtotal_cents: None }
+    }
+}
+
+fn parse_timestamp(raw: Option<&str>) -> Option<DateTime<Utc>> {
+    raw.and_then(|t| DateTime::parse_from_rfc3339(t).ok()).map(|t| t.with_timezone(&Utc))
+}
+
+impl Coupon {
+    /// Checks that do not depend on the cart: validity window and remaining
+    /// uses, not counting those held by other checkouts.
+    pub fn check(&self, now: DateTime<Utc>) -> Result<(), RejectionReason> {
+        if parse_timestamp(self.starts_at.as_deref()).is_some_and(|t| now < t) {
+            return Err(RejectionReason::NotYetValid);
+        }
+        if parse_timestamp(self.expires_at.as_deref()).is_some_and(|t| now >= t) {
+            return Err(RejectionReason::Expired);
+        }
+        if self.remaining_uses <= 0 {
+            return Err(RejectionReason::UsedUp);
+        }
+        if self.remaining_uses <= self.held_uses {
+            return Err(RejectionReason::Reserved);
+        }
+        // Older rows may carry no discount at all
+        if self.amount_off.is_none() && self.percent_off.is_none() {
+            return Err(RejectionReason::NotApplicable);
+        }
+        Ok(())
+    }
+
+    /// The discount on a priced cart. `categories` maps each product in the
+    /// cart to its category. The minimum applies to the whole subtotal; the
+    /// discount itself only to the lines in scope, capped by `max_discount_cents`.
+    pub fn discount_for(&self, lines: &[PricedLine], categories: &HashMap<String, Option<String>>, now: DateTime<Utc>) -> Result<i64, RejectionReason> {
+        self.check(now)?;
+        let subtotal: i64 = lines.iter().map(|l| l.line_total_cents).sum();
+        if self.min_subtotal_cents.is_some_and(|min| subtotal < min) {
+            return Err(RejectionReason::BelowMinimum);
+        }
+        let eligible: i64 = lines
+            .iter()
+            .filter(|l| !self.is_scoped || self.covers(&l.product_id, categories.get(&l.product_id).and_then(Option::as_deref)))
+            .map(|l| l.line_total_cents)
+            .sum();
+        if eligible <= 0 {
+            return Err(RejectionReason::NotApplicable);
+        }
+
+        // A fixed amount wins over a percentage when both are set.
+        let discount = match (self.amount_off, self.percent_off) {
+            (Some(amount), _) => amount,
+            (None, Some(percent)) => ((percent.min(100) as f64 / 100.0) * eligible as f64).round() as i64,
+            (None, None) => return Err(RejectionReason::NotApplicable),
+        };
+        let discount = match self.max_discount_cents {
+            Some(cap) => discount.min(cap),
+            None => discount,
+        };
+        Ok(discount.min(eligible))
+    }
+
+    fn covers(&self, product_id: &str, category_id: Option<&str>) -> bool {
+        self.product_ids.iter().any(|p| p == product_id) || category_id.is_some_and(|c| self.category_ids.iter().any(|id| id == c))
+    }
+}
+
+fn coupon_from_row(r: &sqlx::sqlite::SqliteRow, scope: &mut HashMap<String, (Vec<String>, Vec<String>)>) -> Result<Coupon, sqlx::Error> {
+    let code: String = r.try_get("code")?;
+    let (product_ids, category_ids) = scope.remove(&code).unwrap_or_default();
+    Ok(Coupon {
+        percent_off: r.try_get::<Option<i64>, _>("percent_off")?.filter(|p| *p > 0),
+        amount_off: r.try_get::<Option<i64>, _>("amount_off")?.filter(|a| *a > 0),
+        remaining_uses: r.try_get("remaining_uses")?,
+        held_uses: r.try_get("held_uses")?,
+        starts_at: r.try_get("starts_at")?,
+        expires_at: r.try_get("expires_at")?,
+        min_subtotal_cents: r.try_get("min_subtotal_cents")?,
+        max_discount_cents: r.try_get("max_discount_cents")?,
+        is_scoped: r.try_get("is_scoped")?,
+        product_ids,
+        category_ids,
+        max_uses_per_customer: r.try_get("max_uses_per_customer")?,
+        first_order_only: r.try_get("first_order_only")?,
+        campaign_id: r.try_get("campaign_id")?,
+        code,
+    })
+}
+
+const COUPON_COLUMNS: &str = "code, percent_off, amount_off, remaining_uses, starts_at, expires_at, min_subtotal_cents, max_discount_cents, is_scoped, max_uses_per_customer, first_order_only, campaign_id,
+    (SELECT COUNT(*) FROM discount_holds h WHERE h.kind = 'coupon' AND h.code = coupons.code AND h.expires_at > strftime('%Y-%m-%dT%H:%M:%fZ','now')) AS held_uses";
+
+/// Product and category lists keyed by code; `code` limits them to one coupon.
+async fn load_scope(pool: &SqlitePool, code: Option<&str>) -> Result<HashMap<String, (Vec<String>, Vec<String>)>, sqlx::Error> {
+    let mut scope: HashMap<String, (Vec<String>, Vec<String>)> = HashMap::new();
+    for r in sqlx::query(r#"SELECT code, product_id FROM coupon_products WHERE ?1 IS NULL OR code = ?1 ORDER BY product_id"#).bind(code).fetch_all(pool).await? {
+        scope.entry(r.try_get("code")?).or_default().0.push(r.try_get("product_id")?);
+    }
+    for r in sqlx::query(r#"SELECT code, category_id FROM coupon_categories WHERE ?1 IS NULL OR code = ?1 ORDER BY category_id"#).bind(code).fetch_all(pool).await? {
+        scope.entry(r.try_get("code")?).or_default().1.push(r.try_get("category_id")?);
+    }
+    Ok(scope)
+}
+
+/// The coupon for a code as typed by the customer (case-insensitive).
+pub async fn load(pool: &SqlitePool, raw_code: &str) -> Result<Option<Coupon>, sqlx::Error> {
+    let code = raw_code.trim().to_uppercase();
+    let Some(row) = sqlx::query(&format!("SELECT {} FROM coupons WHERE code = ?", COUPON_COLUMNS))
+        .bind(&code)
+        .fetch_optional(pool)
+        .await?
+    else {
+        return Ok(None);
+    };
+    let mut scope = load_scope(pool, Some(&code)).await?;
+    coupon_from_row(&row, &mut scope).map(Some)
+}
+
+/// Hand-made coupons; generated codes are listed per campaign.
+pub async fn list(pool: &SqlitePool) -> Result<Vec<Coupon>, sqlx::Error> {
+    let mut scope = load_scope(pool, None).await?;
+    sqlx::query(&format!("SELECT {} FROM coupons WHERE campaign_id IS NULL ORDER BY code", COUPON_COLUMNS))
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|r| coupon_from_row(r, &mut scope))
+        .collect()
+}
+
+/// Discount and conditions of a coupon as entered by an admin; shared by
+/// single codes and campaigns.
+#[derive(Deserialize)]
+pub struct RulesInput {
+    pub percent_off: Option<i64>,
+    pub amount_off: Option<i64>,
+    /// RFC 3339, or a date meaning the start of that day in restaurant time.
+    pub starts_at: Option<String>,
+    /// RFC 3339, or a date meaning the code is valid through the end of that day.
+    pub expires_at: Option<String>,
+    pub min_subtotal_cents: Option<i64>,
+    pub max_discount_cents: Option<i64>,
+    #[serde(default)]
+    pub product_ids: Vec<String>,
+    /// Category ids or names.
+    #[serde(default)]
+    pub categories: Vec<String>,
+    pub max_uses_per_customer: Option<i64>,
+    #[serde(default)]
+    pub first_order_only: bool,
+}
+
+/// Coupon as created or replaced by an admin.
+#[derive(Deserialize)]
+pub struct CouponInput {
+    pub code: String,
+    pub remaining_uses: i64,
+    #[serde(flatten)]
+    pub rules: RulesInput,
+}
+
+/// Validated rules, ready to be written to any number of codes.
+pub struct Rules {
+    percent_off: Option<i64>,
+    amount_off: Option<i64>,
+    starts_at: Option<String>,
+    expires_at: Option<String>,
+    min_subtotal_cents: Option<i64>,
+    max_discount_cents: Option<i64>,
+    max_uses_per_customer: Option<i64>,
+    first_order_only: bool,
+    product_ids: Vec<String>,
+    category_ids: Vec<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CouponError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<CategoryError> for CouponError {
+    fn from(e: CategoryError) -> Self {
+        match e {
+            CategoryError::Database(e) => CouponError::Database(e),
+            e => CouponError::Invalid(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for CouponError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            CouponError::Invalid(_) => (StatusCode::BAD_REQUEST, "invalid_coupon"),
+            CouponError::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            CouponError::Database(ref e) => {
+                tracing::error!("Coupon update failed: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            }
+        };
+        (status, Json(serde_json::json!({ "error": code, "message": self.to_string() }))).into_response()
+    }
+}
+
+/// A window bound as stored: RFC 3339 as given, or a local date turned into
+/// its midnight (`end_of_day` takes the following midnight).
+fn parse_bound(cfg: &ScheduleConfig, field: &str, raw: Option<&str>, end_of_day: bool) -> Result<Option<DateTime<Utc>>, CouponError> {
+    let Some(raw) = raw.map(str::trim).filter(|r| !r.is_empty()) else {
+        return Ok(None);
+    };
+    if let Ok(at) = DateTime::parse_from_rfc3339(raw) {
+        return Ok(Some(at.with_timezone(&Utc)));
+    }
+    let date = NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .map_err(|_| CouponError::Invalid(format!("{} must be an RFC 3339 timestamp or YYYY-MM-DD", field)))?;
+    let date = if end_of_day { date.succ_opt().unwrap_or(date) } else { date };
+    cfg.to_utc(date, NaiveTime::MIN)
+        .map(Some)
+        .ok_or_else(|| CouponError::Invalid(format!("{} does not exist in restaurant time", field)))
+}
+
+impl RulesInput {
+    /// Check the values and resolve products and categories.
+    pub async fn validate(&self, pool: &SqlitePool, cfg: &ScheduleConfig) -> Result<Rules, CouponError> {
+        let percent_off = self.percent_off.filter(|p| *p != 0);
+        let amount_off = self.amount_off.filter(|a| *a != 0);
+        if percent_off.is_some_and(|p| !(1..=100).contains(&p)) {
+            return Err(CouponError::Invalid("percent_off must be between 1 and 100".into()));
+        }
+        if amount_off.is_some_and(|a| a < 0) {
+            return Err(CouponError::Invalid("amount_off must be positive".into()));
+        }
+        if percent_off.is_none() && amount_off.is_none() {
+            return Err(CouponError::Invalid("set percent_off or amount_off".into()));
+        }
+        if self.min_subtotal_cents.is_some_and(|m| m < 0) {
+            return Err(CouponError::Invalid("min_subtotal_cents must not be negative".into()));
+        }
+        if self.max_discount_cents.is_some_and(|m| m <= 0) {
+            return Err(CouponError::Invalid("max_discount_cents must be positive".into()));
+        }
+        if self.max_uses_per_customer.is_some_and(|m| m <= 0) {
+            return Err(CouponError::Invalid("max_uses_per_customer must be positive".into()));
+        }
+        let starts_at = parse_bound(cfg, "starts_at", self.starts_at.as_deref(), false)?;
+        let expires_at = parse_bound(cfg, "expires_at", self.expires_at.as_deref(), true)?;
+        if let (Some(from), Some(until)) = (starts_at, expires_at) {
+            if from >= until {
+                return Err(CouponError::Invalid("expires_at must be after starts_at".into()));
+            }
+        }
+
+        let mut product_ids: Vec<String> = Vec::new();
+        for id in self.product_ids.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let exists = sqlx::query(r#"SELECT 1 FROM products WHERE id = ?"#).bind(id).fetch_optional(pool).await?.is_some();
+            if !exists {
+                return Err(CouponError::NotFound(format!("product {}", id)));
+            }
+            if !product_ids.iter().any(|p| p == id) {
+                product_ids.push(id.to_string());
+            }
+        }
+        let mut category_ids: Vec<String> = Vec::new();
+        for raw in &self.categories {
+            if let Some(c) = categories::resolve(pool, raw).await? {
+                if !category_ids.contains(&c.id) {
+                    category_ids.push(c.id);
+                }
+            }
+        }
+
+        Ok(Rules {
+            percent_off,
+            amount_off,
+            starts_at: starts_at.map(db_timestamp),
+            expires_at: expires_at.map(db_timestamp),
+            min_subtotal_cents: self.min_subtotal_cents.filter(|m| *m > 0),
+            max_discount_cents: self.max_discount_cents,
+            max_uses_per_customer: self.max_uses_per_customer,
+            first_order_only: self.first_order_only,
+            product_ids,
+            category_ids,
+        })
+    }
+}
+
+/// Write one code with `rules`. A campaign code is only inserted; otherwise an
+/// existing hand-made code is replaced. `false` comes back when the code is
+/// taken (by any code for campaigns, by a campaign code otherwise).
+pub async fn write(tx: &mut Transaction<'_, Sqlite>, code: &str, remaining_uses: i64, rules: &Rules, campaign_id: Option<&str>) -> Result<bool, sqlx::Error> {
+    let on_conflict = match campaign_id {
+        Some(_) => "DO NOTHING",
+        None => "DO UPDATE SET percent_off = excluded.percent_off, amount_off = excluded.amount_off,
+             remaining_uses = excluded.remaining_uses, starts_at = excluded.starts_at, expires_at = excluded.expires_at,
+             min_subtotal_cents = excluded.min_subtotal_cents, max_discount_cents = excluded.max_discount_cents,
+             is_scoped = excluded.is_scoped, max_uses_per_customer = excluded.max_uses_per_customer,
+             first_order_only = excluded.first_order_only
+           WHERE coupons.campaign_id IS NULL",
+    };
+    let written = sqlx::query(&format!(
+        r#"INSERT INTO coupons (code, percent_off, amount_off, remaining_uses, starts_at, expires_at, min_subtotal_cents, max_discount_cents, is_scoped, max_uses_per_customer, first_order_only, campaign_id)
+           VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+           ON CONFLICT(code) {}"#,
+        on_conflict
+    ))
+    .bind(code)
+    .bind(rules.percent_off)
+    .bind(rules.amount_off)
+    .bind(remaining_uses.max(0))
+    .bind(&rules.starts_at)
+    .bind(&rules.expires_at)
+    .bind(rules.min_subtotal_cents)
+    .bind(rules.max_discount_cents)
+    .bind(!rules.product_ids.is_empty() || !rules.category_ids.is_empty())
+    .bind(rules.max_uses_per_customer)
+    .bind(rules.first_order_only)
+    .bind(campaign_id)
+    .execute(&mut **tx)
+    .await?
+    .rows_affected();
+    if written == 0 {
+        return Ok(false);
+    }
+    sqlx::query(r#"DELETE FROM coupon_products WHERE code = ?"#).bind(code).execute(&mut **tx).await?;
+    sqlx::query(r#"DELETE FROM coupon_categories WHERE code = ?"#).bind(code).execute(&mut **tx).await?;
+    for id in &rules.product_ids {
+        sqlx::query(r#"INSERT INTO coupon_products (code, product_id) VALUES (?, ?)"#).bind(code).bind(id).execute(&mut **tx).await?;
+    }
+    for id in &rules.category_ids {
+        sqlx::query(r#"INSERT INTO coupon_categories (code, category_id) VALUES (?, ?)"#).bind(code).bind(id).execute(&mut **tx).await?;
+    }
+    Ok(true)
+}
+
+/// Create or replace a coupon with its rules; returns the stored code.
+pub async fn save(pool: &SqlitePool, cfg: &ScheduleConfig, input: &CouponInput) -> Result<String, CouponError> {
+    let code = input.code.trim().to_uppercase();
+    if code.is_empty() {
+        return Err(CouponError::Invalid("code must not be empty".into()));
+    }
+    let rules = input.rules.validate(pool, cfg).await?;
+    let mut tx = pool.begin().await?;
+    if !write(&mut tx, &code, input.remaining_uses, &rules, None).await? {
+        return Err(CouponError::Invalid(format!("{} belongs to a campaign", code)));
+    }
+    tx.commit().await?;
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coupon() -> Coupon {
+        Coupon {
+            code: "TEST".into(),
+            percent_off: Some(10),
+            amount_off: None,
+            remaining_uses: 5,
+            held_uses: 0,
+            starts_at: None,
+            expires_at: None,
+            min_subtotal_cents: None,
+            max_discount_cents: None,
+            is_scoped: false,
+            product_ids: Vec::new(),
+            category_ids: Vec::new(),
+            max_uses_per_customer: None,
+            first_order_only: false,
+            campaign_id: None,
+        }
+    }
+
+    fn line(product_id: &str, unit_amount: i64, quantity: i64) -> PricedLine {
+        PricedLine {
+            product_id: product_id.into(),
+            name: product_id.into(),
+            unit_amount,
+            options: Vec::new(),
+            quantity,
+            line_total_cents: unit_amount * quantity,
+            currency: "EUR".into(),
+        }
+    }
+
+    /// A 12.50 pho (mains) and two 3.35 spring rolls (starters): 19.20 in total.
+    fn cart() -> (Vec<PricedLine>, HashMap<String, Option<String>>) {
+        let lines = vec![line("pho", 1250, 1), line("rolls", 335, 2)];
+        let categories = HashMap::from([("pho".to_string(), Some("mains".to_string())), ("rolls".to_string(), Some("starters".to_string()))]);
+        (lines, categories)
+    }
+
+    fn now() -> DateTime<Utc> {
+        "2026-10-17T12:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn percentage_is_rounded_to_the_cent() {
+        let (lines, categories) = cart();
+        assert_eq!(coupon().discount_for(&lines, &categories, now()), Ok(192));
+        let odd = Coupon { percent_off: Some(15), ..coupon() };
+        assert_eq!(odd.discount_for(&lines, &categories, now()), Ok(288));
+    }
+
+    #[test]
+    fn fixed_amount_wins_over_percentage_and_never_exceeds_the_cart() {
+        let (lines, categories) = cart();
+        let both = Coupon { amount_off: Some(500), ..coupon() };
+        assert_eq!(both.discount_for(&lines, &categories, now()), Ok(500));
+        let huge = Coupon { amount_off: Some(5000), ..coupon() };
+        assert_eq!(huge.discount_for(&lines, &categories, now()), Ok(1920));
+        let everything = Coupon { percent_off: Some(150), ..coupon() };
+        assert_eq!(everything.discount_for(&lines, &categories, now()), Ok(1920));
+    }
+
+    #[test]
+    fn cap_limits_the_discount() {
+        let (lines, categories) = cart();
+        let capped = Coupon { percent_off: Some(50), max_discount_cents: Some(300), ..coupon() };
+        assert_eq!(capped.discount_for(&lines, &categories, now()), Ok(300));
+    }
+
+    #[test]
+    fn minimum_applies_to_the_whole_subtotal() {
+        let (lines, categories) = cart();
+        let scoped = Coupon { min_subtotal_cents: Some(1500), is_scoped: true, product_ids: vec!["rolls".into()], ..coupon() };
+        assert_eq!(scoped.discount_for(&lines, &categories, now()), Ok(67));
+        let too_high = Coupon { min_subtotal_cents: Some(1921), ..coupon() };
+        assert_eq!(too_high.discount_for(&lines, &categories, now()), Err(RejectionReason::BelowMinimum));
+    }
+
+    #[test]
+    fn scoped_coupons_only_discount_their_lines() {
+        let (lines, categories) = cart();
+        let mains = Coupon { percent_off: Some(20), is_scoped: true, category_ids: vec!["mains".into()], ..coupon() };
+        assert_eq!(mains.discount_for(&lines, &categories, now()), Ok(250));
+        let fixed = Coupon { amount_off: Some(1000), is_scoped: true, product_ids: vec!["rolls".into()], ..coupon() };
+        assert_eq!(fixed.discount_for(&lines, &categories, now()), Ok(670));
+        let drinks = Coupon { is_scoped: true, category_ids: vec!["drinks".into()], ..coupon() };
+        assert_eq!(drinks.discount_for(&lines, &categories, now()), Err(RejectionReason::NotApplicable));
+    }
+
+    #[test]
+    fn check_enforces_window_and_uses() {
+        assert_eq!(coupon().check(now()), Ok(()));
+        let later = Coupon { starts_at: Some("2026-10-18T00:00:00Z".into()), ..coupon() };
+        assert_eq!(later.check(now()), Err(RejectionReason::NotYetValid));
+        let expired = Coupon { expires_at: Some("2026-10-17T12:00:00Z".into()), ..coupon() };
+        assert_eq!(expired.check(now()), Err(RejectionReason::Expired));
+        let used_up = Coupon { remaining_uses: 0, ..coupon() };
+        assert_eq!(used_up.check(now()), Err(RejectionReason::UsedUp));
+        let held = Coupon { remaining_uses: 2, held_uses: 2, ..coupon() };
+        assert_eq!(held.check(now()), Err(RejectionReason::Reserved));
+        let empty = Coupon { percent_off: None, ..coupon() };
+        assert_eq!(empty.check(now()), Err(RejectionReason::NotApplicable));
+    }
+}
@@ -0,0 +1,209 @@
+use serde::Serialize;
+use sqlx::{Row, Sqlite, SqlitePool, Transaction};
+use uuid::Uuid;
+
+use super::{Coupon, RejectionReason};
+
+/// Who is ordering: the signed-in user and/or the email given at checkout.
+#[derive(Clone, Debug, Default)]
+pub struct Customer {
+    pub user_id: Option<String>,
+    pub email: Option<String>,
+}
+
+impl Customer {
+    pub fn new(user_id: Option<String>, email: Option<&str>) -> Self {
+        let email = email.map(str::trim).filter(|e| !e.is_empty()).map(str::to_string);
+        Self { user_id, email }
+    }
+
+    fn is_anonymous(&self) -> bool {
+        self.user_id.is_none() && self.email.is_none()
+    }
+}
+
+/// One use of a coupon by a paid order.
+#[derive(Serialize, Debug)]
+pub struct Redemption {
+    pub id: String,
+    pub code: String,
+    pub order_id: String,
+    pub user_id: Option<String>,
+    pub email: Option<String>,
+    pub discount_cents: i64,
+    pub redeemed_at: String,
+    /// Set once the order was fully refunded; the use no longer counts.
+    pub reversed_at: Option<String>,
+}
+
+/// Redemption totals of one coupon; reversed uses are left out.
+#[derive(Serialize, Debug)]
+pub struct CouponUsage {
+    pub code: String,
+    pub redemptions: i64,
+    pub customers: i64,
+    pub discount_cents: i64,
+    pub reversed: i64,
+    pub last_redeemed_at: Option<String>,
+}
+
+/// Per-customer rules: uses per customer and first order only. A customer
+/// must be identifiable (signed in or an email) for codes that have either rule.
+pub async fn check_customer(pool: &SqlitePool, coupon: &Coupon, customer: &Customer) -> Result<Result<(), RejectionReason>, sqlx::Error> {
+    if coupon.max_uses_per_customer.is_none() && !coupon.first_order_only {
+        return Ok(Ok(()));
+    }
+    if customer.is_anonymous() {
+        return Ok(Err(RejectionReason::EmailRequired));
+    }
+
+    if let Some(limit) = coupon.max_uses_per_customer {
+        // The customer's own checkout still waiting for payment does not count;
+        // a new checkout replaces its hold (see `holds::reserve`)
+        let used: i64 = sqlx::query_scalar(
+            r#"SELECT COUNT(*) FROM coupon_redemptions
+               WHERE code = ?1 AND reversed_at IS NULL AND (user_id = ?2 OR email = ?3 COLLATE NOCASE)"#,
+        )
+        .bind(&coupon.code)
+        .bind(customer.user_id.as_deref())
+        .bind(customer.email.as_deref())
+        .fetch_one(pool)
+        .await?;
+        if used >= limit {
+            return Ok(Err(RejectionReason::CustomerLimitReached));
+        }
+    }
+
+    if coupon.first_order_only {
+        // Cancelled and refunded orders and gift coupon purchases do not count
+        let orders: i64 = sqlx::query_scalar(
+            r#"SELECT COUNT(*) FROM orders
+               WHERE status NOT IN ('cancelled', 'refunded')
+                 AND COALESCE(json_extract(items_json, '$.type'), '') != 'gift_coupon'
+                 AND (user_id = ?1 OR email = ?2 COLLATE NOCASE)"#,
+        )
+        .bind(customer.user_id.as_deref())
+        .bind(customer.email.as_deref())
+        .fetch_one(pool)
+        .await?;
+        if orders > 0 {
+            return Ok(Err(RejectionReason::FirstOrderOnly));
+        }
+    }
+    Ok(Ok(()))
+}
+
+/// The per-customer rule that the use of `code` by the finalized order
+/// `order_id` breaks, if any. Two checkouts of one customer can both get paid
+/// when the second replaced the hold of the first.
+pub async fn broken_rule(tx: &mut Transaction<'_, Sqlite>, code: &str, order_id: &str, customer: &Customer) -> Result<Option<RejectionReason>, sqlx::Error> {
+    if customer.is_anonymous() {
+        return Ok(None);
+    }
+    let Some(row) = sqlx::query(
+        r#"SELECT
+             c.max_uses_per_customer IS NOT NULL
+               AND (SELECT COUNT(*) FROM coupon_redemptions r
+                    WHERE r.code = c.code AND r.reversed_at IS NULL AND (r.user_id = ?2 OR r.email = ?3 COLLATE NOCASE))
+                   > c.max_uses_per_customer AS over_limit,
+             c.first_order_only
+               AND EXISTS (SELECT 1 FROM orders o
+                           WHERE o.id != ?4 AND o.status NOT IN ('cancelled', 'refunded')
+                             AND COALESCE(json_extract(o.items_json, '$.type'), '') != 'gift_coupon'
+                             AND (o.user_id = ?2 OR o.email = ?3 COLLATE NOCASE)) AS not_first
+           FROM coupons c WHERE c.code = ?1"#,
+    )
+    .bind(code.to_uppercase())
+    .bind(customer.user_id.as_deref())
+    .bind(customer.email.as_deref())
+    .bind(order_id)
+    .fetch_optional(&mut **tx)
+    .await?
+    else {
+        return Ok(None);
+    };
+    if row.try_get::<bool, _>("over_limit")? {
+        Ok(Some(RejectionReason::CustomerLimitReached))
+    } else if row.try_get::<bool, _>("not_first")? {
+        Ok(Some(RejectionReason::FirstOrderOnly))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Log the use of `code` by a finalized order.
+pub async fn record(tx: &mut Transaction<'_, Sqlite>, code: &str, order_id: &str, user_id: Option<&str>, email: &str, discount_cents: i64) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"INSERT OR IGNORE INTO coupon_redemptions (id, code, order_id, user_id, email, discount_cents) VALUES (?, ?, ?, ?, ?, ?)"#)
+        .bind(Uuid::new_v4().to_string())
+        .bind(code.to_uppercase())
+        .bind(order_id)
+        .bind(user_id)
+        .bind(Some(email.trim()).filter(|e| !e.is_empty()))
+        .bind(discount_cents)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Stop counting the coupon use of a fully refunded order.
+pub async fn reverse(tx: &mut Transaction<'_, Sqlite>, order_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"UPDATE coupon_redemptions SET reversed_at = strftime('%Y-%m-%dT%H:%M:%fZ','now') WHERE order_id = ? AND reversed_at IS NULL"#)
+        .bind(order_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Usage of every coupon that was ever redeemed, most used first.
+pub async fn usage(pool: &SqlitePool) -> Result<Vec<CouponUsage>, sqlx::Error> {
+    let rows = sqlx::query(
+        r#"SELECT code,
+                  SUM(reversed_at IS NULL) AS redemptions,
+                  COUNT(DISTINCT CASE WHEN reversed_at IS NULL THEN COALESCE(user_id, lower(email)) END) AS customers,
+                  COALESCE(SUM(CASE WHEN reversed_at IS NULL THEN discount_cents END), 0) AS discount_cents,
+                  SUM(reversed_at IS NOT NULL) AS reversed,
+                  MAX(redeemed_at) AS last_redeemed_at
+           FROM coupon_redemptions
+           GROUP BY code
+           ORDER BY redemptions DESC, code"#,
+    )
+    .fetch_all(pool)
+    .await?;
+    rows.iter()
+        .map(|r| {
+            Ok(CouponUsage {
+                code: r.try_get("code")?,
+                redemptions: r.try_get("redemptions")?,
+                customers: r.try_get("customers")?,
+                discount_cents: r.try_get("discount_cents")?,
+                reversed: r.try_get("reversed")?,
+                last_redeemed_at: r.try_get("last_redeemed_at")?,
+            })
+        })
+        .collect()
+}
+
+/// Every use of one coupon, newest first.
+pub async fn list(pool: &SqlitePool, code: &str) -> Result<Vec<Redemption>, sqlx::Error> {
+    let rows = sqlx::query(
+        r#"SELECT id, code, order_id, user_id, email, discount_cents, redeemed_at, reversed_at
+           FROM coupon_redemptions WHERE code = ? ORDER BY redeemed_at DESC"#,
+    )
+    .bind(code.trim().to_uppercase())
+    .fetch_all(pool)
+    .await?;
+    rows.iter()
+        .map(|r| {
+            Ok(Redemption {
+                id: r.try_get("id")?,
+                code: r.try_get("code")?,
+                order_id: r.try_get("order_id")?,
+                user_id: r.try_get("user_id")?,
+                email: r.try_get("email")?,
+                discount_cents: r.try_get("discount_cents")?,
+                redeemed_at: r.try_get("redeemed_at")?,
+                reversed_at: r.try_get("reversed_at")?,
+            })
+        })
+        .collect()
+}
@@ -1,4 +1,6 @@
 use sqlx::{sqlite::SqlitePool, Pool, Row, Sqlite};
+#[cfg(test)]
+use sqlx::sqlite::SqlitePoolOptions;
 
 const PRAGMA_TEMPLATE: &str = "PRAGMA table_info({table})";
 
@@ -10,6 +12,21 @@ pub async fn init_pool(database_url: &str) -> anyhow::Result<SqlitePool> {
     Ok(pool)
 }
 
+/// Fresh migrated in-memory database for tests. A single connection that is
+/// never recycled, since every connection to `:memory:` is its own database.
+#[cfg(test)]
+pub async fn memory_pool() -> anyhow::Result<SqlitePool> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await?;
+    sqlx::migrate!("./migrations").run(&pool).await?;
+    ensure_legacy_schema(&pool).await?;
+    Ok(pool)
+}
+
 /// Cleanup stale pending orders/gifts older than 24 hours
 pub async fn cleanup_stale_pending(pool: &SqlitePool) -> anyhow::Result<()> {
     // Delete pending orders older than 24 hours
@@ -37,7 +54,7 @@ async fn ensure_legacy_schema(pool: &SqlitePool) -> anyhow::Result<()> {
 async fn column_exists(pool: &SqlitePool, table: &str, column: &str) -> anyhow::Result<bool> {
     let pragma = PRAGMA_TEMPLATE.replace("{table}", table);
     let rows = sqlx::query(&pragma).fetch_all(pool).await?;
-    Ok(rows.iter().any(|row| row.try_get::<String, _>("name").map_or(false, |name| name == column)))
+    Ok(rows.iter().any(|row| row.try_get::<String, _>("name").is_ok_and(|name| name == column)))
 }
 
 async fn ensure_orders_currency(pool: &SqlitePool) -> anyhow::Result<()> {
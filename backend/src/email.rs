@@ -1,4 +1,5 @@
 use crate::state::AppState;
+use crate::catalog::translations::Locale;
 use anyhow::Result;
 use lettre::{Message, SmtpTransport, Transport};
 use lettre::transport::smtp::authentication::Credentials;
@@ -115,7 +116,118 @@ async fn send_email_with_html(state: &AppState, to: &str, subject: &str, body: &
 
 // HTML Email Templates
 #[allow(dead_code)]
-pub fn order_confirmation_html(order_id: &str, email: &str, items_html: &str, subtotal: f64, discount: f64, total: f64, app_url: &str) -> String {
+#[allow(clippy::too_many_arguments)]
+/// Fixed wording of the order confirmation in each menu language.
+pub struct OrderEmailText {
+    pub subject: &'static str,
+    heading: &'static str,
+    intro: &'static str,
+    order_id: &'static str,
+    email: &'static str,
+    date: &'static str,
+    date_format: &'static str,
+    /// chrono format of the pickup time in restaurant time.
+    pub pickup_format: &'static str,
+    pickup_label: &'static str,
+    items_title: &'static str,
+    item: &'static str,
+    qty: &'static str,
+    price: &'static str,
+    total: &'static str,
+    subtotal: &'static str,
+    discount_label: &'static str,
+    total_paid: &'static str,
+    invoice_note: &'static str,
+    invoice_cta: &'static str,
+    thanks: &'static str,
+    rights: &'static str,
+}
+
+impl OrderEmailText {
+    pub fn for_locale(locale: Locale) -> &'static OrderEmailText {
+        match locale {
+            Locale::De => &ORDER_EMAIL_DE,
+            Locale::En => &ORDER_EMAIL_EN,
+            Locale::Vi => &ORDER_EMAIL_VI,
+        }
+    }
+}
+
+static ORDER_EMAIL_EN: OrderEmailText = OrderEmailText {
+    subject: "Your Order Confirmation",
+    heading: "Order Confirmed",
+    intro: "Thank you for your order! We're preparing it now.",
+    order_id: "Order ID",
+    email: "Email",
+    date: "Date",
+    date_format: "%B %d, %Y",
+    pickup_format: "%A, %-d %B at %H:%M",
+    pickup_label: "Pickup",
+    items_title: "Items Ordered",
+    item: "Item",
+    qty: "Qty",
+    price: "Price",
+    total: "Total",
+    subtotal: "Subtotal",
+    discount_label: "Discount",
+    total_paid: "Total Paid",
+    invoice_note: "A confirmation email has been sent. You can view your complete invoice anytime.",
+    invoice_cta: "View Invoice",
+    thanks: "Thank you for dining with us!",
+    rights: "All Rights Reserved",
+};
+
+static ORDER_EMAIL_DE: OrderEmailText = OrderEmailText {
+    subject: "Ihre Bestellbestätigung",
+    heading: "Bestellung bestätigt",
+    intro: "Vielen Dank für Ihre Bestellung! Wir bereiten sie gerade zu.",
+    order_id: "Bestellnummer",
+    email: "E-Mail",
+    date: "Datum",
+    date_format: "%d.%m.%Y",
+    pickup_format: "%d.%m.%Y um %H:%M Uhr",
+    pickup_label: "Abholung",
+    items_title: "Ihre Bestellung",
+    item: "Gericht",
+    qty: "Menge",
+    price: "Preis",
+    total: "Summe",
+    subtotal: "Zwischensumme",
+    discount_label: "Rabatt",
+    total_paid: "Gesamtbetrag",
+    invoice_note: "Ihre vollständige Rechnung können Sie jederzeit online einsehen.",
+    invoice_cta: "Rechnung ansehen",
+    thanks: "Vielen Dank für Ihren Besuch!",
+    rights: "Alle Rechte vorbehalten",
+};
+
+static ORDER_EMAIL_VI: OrderEmailText = OrderEmailText {
+    subject: "Xác nhận đơn hàng của bạn",
+    heading: "Đã xác nhận đơn hàng",
+    intro: "Cảm ơn bạn đã đặt món! Chúng tôi đang chuẩn bị đơn hàng.",
+    order_id: "Mã đơn hàng",
+    email: "Email",
+    date: "Ngày",
+    date_format: "%d/%m/%Y",
+    pickup_format: "%H:%M, %d/%m/%Y",
+    pickup_label: "Nhận món",
+    items_title: "Các món đã đặt",
+    item: "Món",
+    qty: "SL",
+    price: "Giá",
+    total: "Thành tiền",
+    subtotal: "Tạm tính",
+    discount_label: "Giảm giá",
+    total_paid: "Tổng thanh toán",
+    invoice_note: "Bạn có thể xem hóa đơn đầy đủ bất cứ lúc nào.",
+    invoice_cta: "Xem hóa đơn",
+    thanks: "Cảm ơn bạn đã dùng bữa cùng chúng tôi!",
+    rights: "Bảo lưu mọi quyền",
+};
+
+#[allow(clippy::too_many_arguments)]
+pub fn order_confirmation_html(locale: Locale, order_id: &str, email: &str, items_html: &str, subtotal: f64, discount: f64, total: f64, pickup: Option<&str>, order_url: &str) -> String {
+    let t = OrderEmailText::for_locale(locale);
     format!(
         r#"<!DOCTYPE html>
 <html>
@@ -270,29 +382,30 @@ pub fn order_confirmation_html(order_id: &str, email: &str, items_html: &str, su
 <body>
     <div class="container">
         <div class="header">
-            <h1>✓ Order Confirmed</h1>
+            <h1>✓ {heading}</h1>
         </div>
         <div class="content">
             <p style="color: #c8c8c8; font-size: 15px; margin-bottom: 15px;">
-                Thank you for your order! We're preparing it now.
+                {intro}
             </p>
 
             <div class="order-id-box">
-                <span class="order-id-label">Order ID</span>
+                <span class="order-id-label">{order_id_label}</span>
                 <div class="order-id-value">{}</div>
             </div>
 
-            <div class="info-line"><strong>Email:</strong> {}</div>
-            <div class="info-line"><strong>Date:</strong> {}</div>
+            <div class="info-line"><strong>{email_label}:</strong> {}</div>
+            <div class="info-line"><strong>{date_label}:</strong> {}</div>
+            {}
 
-            <h3 class="section-title">Items Ordered</h3>
+            <h3 class="section-title">{items_title}</h3>
             <table>
                 <thead>
                     <tr>
-                        <th>Item</th>
-                        <th>Qty</th>
-                        <th>Price</th>
-                        <th>Total</th>
+                        <th>{item_col}</th>
+                        <th>{qty_col}</th>
+                        <th>{price_col}</th>
+                        <th>{total_col}</th>
                     </tr>
                 </thead>
                 <tbody>
@@ -302,45 +415,64 @@ pub fn order_confirmation_html(order_id: &str, email: &str, items_html: &str, su
 
             <div class="totals">
                 <div class="total-row">
-                    <span>Subtotal:</span>
+                    <span>{subtotal_label}:</span>
                     <span>€{:.2}</span>
                 </div>
                 {}
                 <div class="total-row grand-total">
-                    <span>Total Paid:</span>
+                    <span>{total_paid_label}:</span>
                     <span>€{:.2}</span>
                 </div>
             </div>
 
             <p style="text-align: center; color: #999; font-size: 13px; margin: 20px 0;">
-                A confirmation email has been sent. You can view your complete invoice anytime.
+                {invoice_note}
             </p>
 
             <div style="text-align: center;">
-                <a href="{}/thank-you/{}" class="cta-button">View Invoice</a>
+                <a href="{}" class="cta-button">{invoice_cta}</a>
             </div>
         </div>
         <div class="footer">
-            <p style="margin-bottom: 8px;">Thank you for dining with us!</p>
-            <p style="color: #666; font-size: 11px;">© {} - All Rights Reserved</p>
+            <p style="margin-bottom: 8px;">{thanks}</p>
+            <p style="color: #666; font-size: 11px;">© {} - {rights}</p>
         </div>
     </div>
 </body>
 </html>"#,
         order_id,
         email,
-        chrono::Local::now().format("%B %d, %Y").to_string(),
+        chrono::Local::now().format(t.date_format),
+        match pickup {
+            Some(p) => format!("<div class=\"info-line\"><strong>{}:</strong> {}</div>", t.pickup_label, html_escape(p)),
+            None => String::new(),
+        },
         items_html,
         subtotal,
         if discount > 0.01 { 
-            format!("<div class=\"total-row discount\"><span>Discount:</span><span>-€{:.2}</span></div>", discount) 
+            format!("<div class=\"total-row discount\"><span>{}:</span><span>-€{:.2}</span></div>", t.discount_label, discount) 
         } else { 
             String::new() 
         },
         total,
-        app_url,
-        order_id,
-        chrono::Local::now().format("%Y").to_string()
+        order_url,
+        chrono::Local::now().format("%Y"),
+        heading = t.heading,
+        intro = t.intro,
+        order_id_label = t.order_id,
+        email_label = t.email,
+        date_label = t.date,
+        items_title = t.items_title,
+        item_col = t.item,
+        qty_col = t.qty,
+        price_col = t.price,
+        total_col = t.total,
+        subtotal_label = t.subtotal,
+        total_paid_label = t.total_paid,
+        invoice_note = t.invoice_note,
+        invoice_cta = t.invoice_cta,
+        thanks = t.thanks,
+        rights = t.rights,
     )
 }
 
@@ -571,7 +703,669 @@ pub fn gift_coupon_html(code: &str, value: f64, _email: &str, app_url: &str) ->
         app_url,
         code,
         app_url,
-        chrono::Local::now().format("%Y").to_string()
+        chrono::Local::now().format("%Y")
+    )
+}
+
+
+pub fn refund_notice_html(order_id: &str, amount: f64, reason: Option<&str>, full_refund: bool, order_url: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <style>
+        * {{ margin: 0; padding: 0; box-sizing: border-box; }}
+        body {{
+            font-family: 'DM Sans', -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif;
+            background: #0a0a0a;
+            color: #fff;
+            line-height: 1.6;
+        }}
+        .container {{
+            max-width: 600px;
+            margin: 0 auto;
+            background: linear-gradient(135deg, #1a1a1a 0%, #0f0f0f 100%);
+            border: 1px solid #2a2a2a;
+            border-radius: 12px;
+            overflow: hidden;
+            box-shadow: 0 10px 40px rgba(0, 0, 0, 0.5);
+        }}
+        .header {{
+            background: linear-gradient(135deg, #6b0b0a 0%, #3f0505 100%);
+            padding: 40px 20px;
+            text-align: center;
+        }}
+        .header h1 {{
+            margin: 0;
+            color: #f7f5e7;
+            font-family: 'Kaushan Script', cursive;
+            font-size: 32px;
+            font-weight: 700;
+            letter-spacing: 2px;
+        }}
+        .content {{
+            padding: 40px 30px;
+        }}
+        .amount-box {{
+            background: #1a1a1a;
+            border: 2px solid #8c3231;
+            border-radius: 8px;
+            padding: 20px;
+            margin: 20px 0;
+            text-align: center;
+        }}
+        .amount-label {{
+            color: #999;
+            font-size: 12px;
+            text-transform: uppercase;
+            letter-spacing: 2px;
+            margin-bottom: 8px;
+            display: block;
+        }}
+        .amount-value {{
+            color: #4ade80;
+            font-size: 28px;
+            font-weight: 700;
+        }}
+        .info-line {{
+            color: #b8b8b8;
+            font-size: 13px;
+            margin: 8px 0;
+            line-height: 1.6;
+        }}
+        .cta-button {{
+            display: inline-block;
+            background: linear-gradient(135deg, #8c3231 0%, #6b0b0a 100%);
+            color: #f7f5e7;
+            padding: 14px 32px;
+            border-radius: 8px;
+            text-decoration: none;
+            font-weight: 700;
+            letter-spacing: 1px;
+            margin-top: 25px;
+            font-size: 14px;
+            text-transform: uppercase;
+        }}
+        .footer {{
+            background: #1a1a1a;
+            padding: 25px;
+            text-align: center;
+            color: #999;
+            font-size: 12px;
+            border-top: 1px solid #2a2a2a;
+        }}
+        .footer p {{ margin: 0; }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <div class="header">
+            <h1>Refund Issued</h1>
+        </div>
+        <div class="content">
+            <p style="color: #c8c8c8; font-size: 15px; margin-bottom: 15px;">
+                {}
+            </p>
+
+            <div class="amount-box">
+                <span class="amount-label">Amount Refunded</span>
+                <div class="amount-value">€{:.2}</div>
+            </div>
+
+            <div class="info-line"><strong>Order ID:</strong> {}</div>
+            {}
+            <div class="info-line"><strong>Date:</strong> {}</div>
+
+            <p style="text-align: center; color: #999; font-size: 13px; margin: 20px 0;">
+                Depending on your bank it can take a few business days until the money shows up in your account.
+            </p>
+
+            <div style="text-align: center;">
+                <a href="{}" class="cta-button">View Order</a>
+            </div>
+        </div>
+        <div class="footer">
+            <p style="margin-bottom: 8px;">We hope to welcome you again soon!</p>
+            <p style="color: #666; font-size: 11px;">© {} - All Rights Reserved</p>
+        </div>
+    </div>
+</body>
+</html>"#,
+        if full_refund {
+            "Your order has been refunded in full."
+        } else {
+            "Part of your order has been refunded."
+        },
+        amount,
+        order_id,
+        match reason {
+            Some(r) if !r.trim().is_empty() => format!("<div class=\"info-line\"><strong>Reason:</strong> {}</div>", html_escape(r)),
+            _ => String::new(),
+        },
+        chrono::Local::now().format("%B %d, %Y"),
+        order_url,
+        chrono::Local::now().format("%Y")
+    )
+}
+
+pub fn order_ready_html(order_id: &str, order_url: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <style>
+        * {{ margin: 0; padding: 0; box-sizing: border-box; }}
+        body {{
+            font-family: 'DM Sans', -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif;
+            background: #0a0a0a;
+            color: #fff;
+            line-height: 1.6;
+        }}
+        .container {{
+            max-width: 600px;
+            margin: 0 auto;
+            background: linear-gradient(135deg, #1a1a1a 0%, #0f0f0f 100%);
+            border: 1px solid #2a2a2a;
+            border-radius: 12px;
+            overflow: hidden;
+            box-shadow: 0 10px 40px rgba(0, 0, 0, 0.5);
+        }}
+        .header {{
+            background: linear-gradient(135deg, #6b0b0a 0%, #3f0505 100%);
+            padding: 40px 20px;
+            text-align: center;
+        }}
+        .header h1 {{
+            margin: 0;
+            color: #f7f5e7;
+            font-family: 'Kaushan Script', cursive;
+            font-size: 32px;
+            font-weight: 700;
+            letter-spacing: 2px;
+        }}
+        .content {{
+            padding: 40px 30px;
+        }}
+        .amount-box {{
+            background: #1a1a1a;
+            border: 2px solid #8c3231;
+            border-radius: 8px;
+            padding: 20px;
+            margin: 20px 0;
+            text-align: center;
+        }}
+        .amount-label {{
+            color: #999;
+            font-size: 12px;
+            text-transform: uppercase;
+            letter-spacing: 2px;
+            margin-bottom: 8px;
+            display: block;
+        }}
+        .amount-value {{
+            color: #4ade80;
+            font-size: 18px;
+            font-weight: 700;
+        }}
+        .info-line {{
+            color: #b8b8b8;
+            font-size: 13px;
+            margin: 8px 0;
+            line-height: 1.6;
+        }}
+        .cta-button {{
+            display: inline-block;
+            background: linear-gradient(135deg, #8c3231 0%, #6b0b0a 100%);
+            color: #f7f5e7;
+            padding: 14px 32px;
+            border-radius: 8px;
+            text-decoration: none;
+            font-weight: 700;
+            letter-spacing: 1px;
+            margin-top: 25px;
+            font-size: 14px;
+            text-transform: uppercase;
+        }}
+        .footer {{
+            background: #1a1a1a;
+            padding: 25px;
+            text-align: center;
+            color: #999;
+            font-size: 12px;
+            border-top: 1px solid #2a2a2a;
+        }}
+        .footer p {{ margin: 0; }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <div class="header">
+            <h1>Ready for Pickup</h1>
+        </div>
+        <div class="content">
+            <p style="color: #c8c8c8; font-size: 15px; margin-bottom: 15px;">
+                Good news: your order is ready. Come by and pick it up while it's hot!
+            </p>
+
+            <div class="amount-box">
+                <span class="amount-label">Order ID</span>
+                <div class="amount-value">{}</div>
+            </div>
+
+            <p style="text-align: center; color: #999; font-size: 13px; margin: 20px 0;">
+                Please mention your order number at the counter.
+            </p>
+
+            <div style="text-align: center;">
+                <a href="{}" class="cta-button">View Order</a>
+            </div>
+        </div>
+        <div class="footer">
+            <p style="margin-bottom: 8px;">Enjoy your meal!</p>
+            <p style="color: #666; font-size: 11px;">© {} - All Rights Reserved</p>
+        </div>
+    </div>
+</body>
+</html>"#,
+        order_id,
+        order_url,
+        chrono::Local::now().format("%Y")
     )
 }
 
+pub fn reservation_confirmation_html(name: &str, party_size: i64, when: &str, manage_url: &str, changed: bool) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <style>
+        * {{ margin: 0; padding: 0; box-sizing: border-box; }}
+        body {{
+            font-family: 'DM Sans', -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif;
+            background: #0a0a0a;
+            color: #fff;
+            line-height: 1.6;
+        }}
+        .container {{
+            max-width: 600px;
+            margin: 0 auto;
+            background: linear-gradient(135deg, #1a1a1a 0%, #0f0f0f 100%);
+            border: 1px solid #2a2a2a;
+            border-radius: 12px;
+            overflow: hidden;
+            box-shadow: 0 10px 40px rgba(0, 0, 0, 0.5);
+        }}
+        .header {{
+            background: linear-gradient(135deg, #6b0b0a 0%, #3f0505 100%);
+            padding: 40px 20px;
+            text-align: center;
+        }}
+        .header h1 {{
+            margin: 0;
+            color: #f7f5e7;
+            font-family: 'Kaushan Script', cursive;
+            font-size: 32px;
+            font-weight: 700;
+            letter-spacing: 2px;
+        }}
+        .content {{
+            padding: 40px 30px;
+        }}
+        .amount-box {{
+            background: #1a1a1a;
+            border: 2px solid #8c3231;
+            border-radius: 8px;
+            padding: 20px;
+            margin: 20px 0;
+            text-align: center;
+        }}
+        .amount-label {{
+            color: #999;
+            font-size: 12px;
+            text-transform: uppercase;
+            letter-spacing: 2px;
+            margin-bottom: 8px;
+            display: block;
+        }}
+        .amount-value {{
+            color: #4ade80;
+            font-size: 18px;
+            font-weight: 700;
+        }}
+        .info-line {{
+            color: #b8b8b8;
+            font-size: 13px;
+            margin: 8px 0;
+            line-height: 1.6;
+        }}
+        .cta-button {{
+            display: inline-block;
+            background: linear-gradient(135deg, #8c3231 0%, #6b0b0a 100%);
+            color: #f7f5e7;
+            padding: 14px 32px;
+            border-radius: 8px;
+            text-decoration: none;
+            font-weight: 700;
+            letter-spacing: 1px;
+            margin-top: 25px;
+            font-size: 14px;
+            text-transform: uppercase;
+        }}
+        .footer {{
+            background: #1a1a1a;
+            padding: 25px;
+            text-align: center;
+            color: #999;
+            font-size: 12px;
+            border-top: 1px solid #2a2a2a;
+        }}
+        .footer p {{ margin: 0; }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <div class="header">
+            <h1>{}</h1>
+        </div>
+        <div class="content">
+            <p style="color: #c8c8c8; font-size: 15px; margin-bottom: 15px;">
+                Hello {}, {}
+            </p>
+
+            <div class="amount-box">
+                <span class="amount-label">Table for {}</span>
+                <div class="amount-value">{}</div>
+            </div>
+
+            <p style="text-align: center; color: #999; font-size: 13px; margin: 20px 0;">
+                Plans changed? You can cancel your reservation using the button below.
+            </p>
+
+            <div style="text-align: center;">
+                <a href="{}" class="cta-button">View or Cancel</a>
+            </div>
+        </div>
+        <div class="footer">
+            <p style="margin-bottom: 8px;">We look forward to seeing you!</p>
+            <p style="color: #666; font-size: 11px;">© {} - All Rights Reserved</p>
+        </div>
+    </div>
+</body>
+</html>"#,
+        if changed { "Reservation Changed" } else { "Reservation Confirmed" },
+        html_escape(name),
+        if changed { "your reservation has been moved to a new time." } else { "we have reserved a table for you." },
+        party_size,
+        when,
+        manage_url,
+        chrono::Local::now().format("%Y")
+    )
+}
+
+pub fn reservation_reminder_html(name: &str, party_size: i64, when: &str, manage_url: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <style>
+        * {{ margin: 0; padding: 0; box-sizing: border-box; }}
+        body {{
+            font-family: 'DM Sans', -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif;
+            background: #0a0a0a;
+            color: #fff;
+            line-height: 1.6;
+        }}
+        .container {{
+            max-width: 600px;
+            margin: 0 auto;
+            background: linear-gradient(135deg, #1a1a1a 0%, #0f0f0f 100%);
+            border: 1px solid #2a2a2a;
+            border-radius: 12px;
+            overflow: hidden;
+            box-shadow: 0 10px 40px rgba(0, 0, 0, 0.5);
+        }}
+        .header {{
+            background: linear-gradient(135deg, #6b0b0a 0%, #3f0505 100%);
+            padding: 40px 20px;
+            text-align: center;
+        }}
+        .header h1 {{
+            margin: 0;
+            color: #f7f5e7;
+            font-family: 'Kaushan Script', cursive;
+            font-size: 32px;
+            font-weight: 700;
+            letter-spacing: 2px;
+        }}
+        .content {{
+            padding: 40px 30px;
+        }}
+        .amount-box {{
+            background: #1a1a1a;
+            border: 2px solid #8c3231;
+            border-radius: 8px;
+            padding: 20px;
+            margin: 20px 0;
+            text-align: center;
+        }}
+        .amount-label {{
+            color: #999;
+            font-size: 12px;
+            text-transform: uppercase;
+            letter-spacing: 2px;
+            margin-bottom: 8px;
+            display: block;
+        }}
+        .amount-value {{
+            color: #4ade80;
+            font-size: 18px;
+            font-weight: 700;
+        }}
+        .info-line {{
+            color: #b8b8b8;
+            font-size: 13px;
+            margin: 8px 0;
+            line-height: 1.6;
+        }}
+        .cta-button {{
+            display: inline-block;
+            background: linear-gradient(135deg, #8c3231 0%, #6b0b0a 100%);
+            color: #f7f5e7;
+            padding: 14px 32px;
+            border-radius: 8px;
+            text-decoration: none;
+            font-weight: 700;
+            letter-spacing: 1px;
+            margin-top: 25px;
+            font-size: 14px;
+            text-transform: uppercase;
+        }}
+        .footer {{
+            background: #1a1a1a;
+            padding: 25px;
+            text-align: center;
+            color: #999;
+            font-size: 12px;
+            border-top: 1px solid #2a2a2a;
+        }}
+        .footer p {{ margin: 0; }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <div class="header">
+            <h1>See You Soon</h1>
+        </div>
+        <div class="content">
+            <p style="color: #c8c8c8; font-size: 15px; margin-bottom: 15px;">
+                Hello {}, a quick reminder of your reservation.
+            </p>
+
+            <div class="amount-box">
+                <span class="amount-label">Table for {}</span>
+                <div class="amount-value">{}</div>
+            </div>
+
+            <p style="text-align: center; color: #999; font-size: 13px; margin: 20px 0;">
+                Can no longer make it? Please cancel so we can give the table to someone else.
+            </p>
+
+            <div style="text-align: center;">
+                <a href="{}" class="cta-button">View or Cancel</a>
+            </div>
+        </div>
+        <div class="footer">
+            <p style="margin-bottom: 8px;">We look forward to seeing you!</p>
+            <p style="color: #666; font-size: 11px;">© {} - All Rights Reserved</p>
+        </div>
+    </div>
+</body>
+</html>"#,
+        html_escape(name),
+        party_size,
+        when,
+        manage_url,
+        chrono::Local::now().format("%Y")
+    )
+}
+
+pub fn reservation_cancelled_html(name: &str, when: &str, reason: Option<&str>, by_restaurant: bool) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <style>
+        * {{ margin: 0; padding: 0; box-sizing: border-box; }}
+        body {{
+            font-family: 'DM Sans', -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif;
+            background: #0a0a0a;
+            color: #fff;
+            line-height: 1.6;
+        }}
+        .container {{
+            max-width: 600px;
+            margin: 0 auto;
+            background: linear-gradient(135deg, #1a1a1a 0%, #0f0f0f 100%);
+            border: 1px solid #2a2a2a;
+            border-radius: 12px;
+            overflow: hidden;
+            box-shadow: 0 10px 40px rgba(0, 0, 0, 0.5);
+        }}
+        .header {{
+            background: linear-gradient(135deg, #6b0b0a 0%, #3f0505 100%);
+            padding: 40px 20px;
+            text-align: center;
+        }}
+        .header h1 {{
+            margin: 0;
+            color: #f7f5e7;
+            font-family: 'Kaushan Script', cursive;
+            font-size: 32px;
+            font-weight: 700;
+            letter-spacing: 2px;
+        }}
+        .content {{
+            padding: 40px 30px;
+        }}
+        .amount-box {{
+            background: #1a1a1a;
+            border: 2px solid #8c3231;
+            border-radius: 8px;
+            padding: 20px;
+            margin: 20px 0;
+            text-align: center;
+        }}
+        .amount-label {{
+            color: #999;
+            font-size: 12px;
+            text-transform: uppercase;
+            letter-spacing: 2px;
+            margin-bottom: 8px;
+            display: block;
+        }}
+        .amount-value {{
+            color: #4ade80;
+            font-size: 18px;
+            font-weight: 700;
+        }}
+        .info-line {{
+            color: #b8b8b8;
+            font-size: 13px;
+            margin: 8px 0;
+            line-height: 1.6;
+        }}
+        .cta-button {{
+            display: inline-block;
+            background: linear-gradient(135deg, #8c3231 0%, #6b0b0a 100%);
+            color: #f7f5e7;
+            padding: 14px 32px;
+            border-radius: 8px;
+            text-decoration: none;
+            font-weight: 700;
+            letter-spacing: 1px;
+            margin-top: 25px;
+            font-size: 14px;
+            text-transform: uppercase;
+        }}
+        .footer {{
+            background: #1a1a1a;
+            padding: 25px;
+            text-align: center;
+            color: #999;
+            font-size: 12px;
+            border-top: 1px solid #2a2a2a;
+        }}
+        .footer p {{ margin: 0; }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <div class="header">
+            <h1>Reservation Cancelled</h1>
+        </div>
+        <div class="content">
+            <p style="color: #c8c8c8; font-size: 15px; margin-bottom: 15px;">
+                Hello {}, {}
+            </p>
+
+            <div class="amount-box">
+                <span class="amount-label">Cancelled</span>
+                <div class="amount-value">{}</div>
+            </div>
+            {}
+        </div>
+        <div class="footer">
+            <p style="margin-bottom: 8px;">We hope to welcome you another time.</p>
+            <p style="color: #666; font-size: 11px;">© {} - All Rights Reserved</p>
+        </div>
+    </div>
+</body>
+</html>"#,
+        html_escape(name),
+        if by_restaurant {
+            "unfortunately we had to cancel your reservation. We are sorry for the inconvenience."
+        } else {
+            "your reservation has been cancelled as requested."
+        },
+        when,
+        match reason {
+            Some(r) if !r.trim().is_empty() => format!("<div class=\"info-line\"><strong>Reason:</strong> {}</div>", html_escape(r)),
+            _ => String::new(),
+        },
+        chrono::Local::now().format("%Y")
+    )
+}
+
+pub(crate) fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
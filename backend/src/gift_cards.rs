@@ -0,0 +1,295 @@
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use serde::Serialize;
+use sqlx::{Row, Sqlite, SqlitePool, Transaction};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// What moved money on or off a gift card.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionKind {
+    /// The amount paid for the card.
+    Issue,
+    /// Extra value granted on purchase.
+    Bonus,
+    /// Spent on an order (negative).
+    Redeem,
+    /// Given back when an order paid with the card is refunded.
+    Refund,
+    /// Changed by hand, or voided when the card purchase was refunded.
+    Adjustment,
+}
+
+impl TransactionKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TransactionKind::Issue => "issue",
+            TransactionKind::Bonus => "bonus",
+            TransactionKind::Redeem => "redeem",
+            TransactionKind::Refund => "refund",
+            TransactionKind::Adjustment => "adjustment",
+        }
+    }
+}
+
+/// One ledger entry to post.
+pub struct Entry<'a> {
+    pub kind: TransactionKind,
+    /// Signed; negative takes money off the card.
+    pub amount_cents: i64,
+    pub order_id: Option<&'a str>,
+    pub note: Option<&'a str>,
+    pub created_by: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GiftCardTransaction {
+    pub id: String,
+    pub kind: String,
+    pub amount_cents: i64,
+    /// Card balance right after this entry.
+    pub balance_cents: i64,
+    pub order_id: Option<String>,
+    pub note: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Error, Debug)]
+pub enum GiftCardError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for GiftCardError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            GiftCardError::Invalid(_) => (StatusCode::BAD_REQUEST, "invalid_adjustment"),
+            GiftCardError::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            GiftCardError::Database(ref e) => {
+                tracing::error!("Gift card update failed: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            }
+        };
+        (status, Json(serde_json::json!({ "error": code, "message": self.to_string() }))).into_response()
+    }
+}
+
+/// The stored spelling of a code typed in any case.
+async fn find(tx: &mut Transaction<'_, Sqlite>, raw_code: &str) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar(r#"SELECT code FROM gift_codes WHERE code = ? COLLATE NOCASE"#)
+        .bind(raw_code.trim())
+        .fetch_optional(&mut **tx)
+        .await
+}
+
+/// Append `entry` to the card's ledger and return the new balance, or `None`
+/// for an unknown code. The balance in `gift_codes.remaining_cents` is only
+/// ever rewritten here, from the sum of the ledger.
+pub async fn post(tx: &mut Transaction<'_, Sqlite>, raw_code: &str, entry: Entry<'_>) -> Result<Option<i64>, sqlx::Error> {
+    let Some(code) = find(tx, raw_code).await? else { return Ok(None) };
+    sqlx::query(r#"INSERT INTO gift_card_transactions (id, code, kind, amount_cents, order_id, note, created_by) VALUES (?, ?, ?, ?, ?, ?, ?)"#)
+        .bind(Uuid::new_v4().to_string())
+        .bind(&code)
+        .bind(entry.kind.as_str())
+        .bind(entry.amount_cents)
+        .bind(entry.order_id)
+        .bind(entry.note)
+        .bind(entry.created_by)
+        .execute(&mut **tx)
+        .await?;
+    let balance = sqlx::query_scalar(
+        r#"UPDATE gift_codes SET remaining_cents = (SELECT COALESCE(SUM(amount_cents), 0) FROM gift_card_transactions WHERE code = ?1)
+           WHERE code = ?1 RETURNING remaining_cents"#,
+    )
+    .bind(&code)
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(Some(balance))
+}
+
+/// Create a card worth `amount_cents + bonus_cents` bought by `order_id`.
+pub async fn issue(tx: &mut Transaction<'_, Sqlite>, code: &str, amount_cents: i64, bonus_cents: i64, email: &str, order_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"INSERT INTO gift_codes (id, code, value_cents, remaining_cents, purchaser_email, customer_email) VALUES (?, ?, ?, 0, ?, ?)"#)
+        .bind(Uuid::new_v4().to_string())
+        .bind(code)
+        .bind(amount_cents + bonus_cents)
+        .bind(email)
+        .bind(email)
+        .execute(&mut **tx)
+        .await?;
+    let order_id = Some(order_id);
+    post(tx, code, Entry { kind: TransactionKind::Issue, amount_cents, order_id, note: None, created_by: "system" }).await?;
+    if bonus_cents > 0 {
+        post(tx, code, Entry { kind: TransactionKind::Bonus, amount_cents: bonus_cents, order_id, note: None, created_by: "system" }).await?;
+    }
+    Ok(())
+}
+
+/// Spend up to `amount_cents` of the card on `order_id`; returns what was taken.
+/// Spent cards stay in place with a zero balance.
+pub async fn redeem(tx: &mut Transaction<'_, Sqlite>, raw_code: &str, amount_cents: i64, order_id: &str) -> Result<i64, sqlx::Error> {
+    let Some(balance): Option<i64> = sqlx::query_scalar(r#"SELECT remaining_cents FROM gift_codes WHERE code = ? COLLATE NOCASE"#)
+        .bind(raw_code.trim())
+        .fetch_optional(&mut **tx)
+        .await?
+    else {
+        return Ok(0);
+    };
+    let spent = amount_cents.min(balance);
+    if spent < amount_cents {
+        tracing::warn!("Gift card {} covered only {}¢ of {}¢ for order {}", raw_code, spent, amount_cents, order_id);
+    }
+    if spent > 0 {
+        post(tx, raw_code, Entry { kind: TransactionKind::Redeem, amount_cents: -spent, order_id: Some(order_id), note: None, created_by: "system" }).await?;
+    }
+    Ok(spent)
+}
+
+/// Give `amount_cents` of a refunded order back to the card it was paid with,
+/// if there is such a card.
+pub async fn refund(tx: &mut Transaction<'_, Sqlite>, raw_code: &str, amount_cents: i64, order_id: &str, refunded_by: &str) -> Result<(), sqlx::Error> {
+    let entry = Entry { kind: TransactionKind::Refund, amount_cents, order_id: Some(order_id), note: None, created_by: refunded_by };
+    post(tx, raw_code, entry).await?;
+    Ok(())
+}
+
+/// Bring back, empty, a card that was deleted once spent before the ledger
+/// existed, so a refund can be posted to it.
+pub async fn reopen(tx: &mut Transaction<'_, Sqlite>, raw_code: &str, value_cents: i64, email: &str) -> Result<(), sqlx::Error> {
+    if find(tx, raw_code).await?.is_none() {
+        sqlx::query(r#"INSERT INTO gift_codes (id, code, value_cents, remaining_cents, customer_email) VALUES (?, ?, ?, 0, ?)"#)
+            .bind(Uuid::new_v4().to_string())
+            .bind(raw_code.trim())
+            .bind(value_cents)
+            .bind(email)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Take the whole balance off a card whose purchase was refunded.
+pub async fn void(tx: &mut Transaction<'_, Sqlite>, code: &str, order_id: &str, refunded_by: &str) -> Result<(), sqlx::Error> {
+    let balance: i64 = sqlx::query_scalar(r#"SELECT remaining_cents FROM gift_codes WHERE code = ?"#)
+        .bind(code)
+        .fetch_optional(&mut **tx)
+        .await?
+        .unwrap_or(0);
+    if balance > 0 {
+        let entry = Entry { kind: TransactionKind::Adjustment, amount_cents: -balance, order_id: Some(order_id), note: Some("purchase refunded"), created_by: refunded_by };
+        post(tx, code, entry).await?;
+    }
+    Ok(())
+}
+
+/// Change a balance by hand. The note is required and the balance cannot go
+/// below zero.
+pub async fn adjust(pool: &SqlitePool, raw_code: &str, amount_cents: i64, note: &str, admin: &str) -> Result<i64, GiftCardError> {
+    let note = note.trim();
+    if amount_cents == 0 {
+        return Err(GiftCardError::Invalid("amount_cents must not be zero".into()));
+    }
+    if note.is_empty() {
+        return Err(GiftCardError::Invalid("note must say why the balance changes".into()));
+    }
+    let mut tx = pool.begin().await?;
+    let balance = post(&mut tx, raw_code, Entry { kind: TransactionKind::Adjustment, amount_cents, order_id: None, note: Some(note), created_by: admin })
+        .await?
+        .ok_or_else(|| GiftCardError::NotFound(format!("gift card {}", raw_code.trim())))?;
+    if balance < 0 {
+        return Err(GiftCardError::Invalid(format!("the card only holds {}¢", balance - amount_cents)));
+    }
+    tx.commit().await?;
+    Ok(balance)
+}
+
+/// Every entry of a card, oldest first, with the running balance.
+pub async fn history(pool: &SqlitePool, raw_code: &str) -> Result<Vec<GiftCardTransaction>, GiftCardError> {
+    let code: String = sqlx::query_scalar(r#"SELECT code FROM gift_codes WHERE code = ? COLLATE NOCASE"#)
+        .bind(raw_code.trim())
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| GiftCardError::NotFound(format!("gift card {}", raw_code.trim())))?;
+    let rows = sqlx::query(
+        r#"SELECT id, kind, amount_cents, order_id, note, created_by, created_at,
+                  SUM(amount_cents) OVER (ORDER BY created_at, rowid) AS balance_cents
+           FROM gift_card_transactions WHERE code = ?
+           ORDER BY created_at, rowid"#,
+    )
+    .bind(&code)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|r| {
+            Ok(GiftCardTransaction {
+                id: r.try_get("id")?,
+                kind: r.try_get("kind")?,
+                amount_cents: r.try_get("amount_cents")?,
+                balance_cents: r.try_get("balance_cents")?,
+                order_id: r.try_get("order_id")?,
+                note: r.try_get("note")?,
+                created_by: r.try_get("created_by")?,
+                created_at: r.try_get("created_at")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{add_gift_card, mock_state};
+
+    async fn kinds(pool: &SqlitePool) -> Vec<(String, i64, i64)> {
+        history(pool, "gift").await.unwrap().into_iter().map(|t| (t.kind, t.amount_cents, t.balance_cents)).collect()
+    }
+
+    #[tokio::test]
+    async fn balance_follows_the_ledger() {
+        let pool = mock_state().await.pool.clone();
+        add_gift_card(&pool, "GIFT", 2000).await;
+
+        let mut tx = pool.begin().await.unwrap();
+        assert_eq!(redeem(&mut tx, "gift", 1500, "order-1").await.unwrap(), 1500);
+        assert_eq!(redeem(&mut tx, "gift", 1500, "order-2").await.unwrap(), 500, "redeem stops at the balance");
+        refund(&mut tx, "gift", 700, "order-2", "admin").await.unwrap();
+        tx.commit().await.unwrap();
+
+        assert_eq!(
+            kinds(&pool).await,
+            vec![("issue".into(), 2000, 2000), ("redeem".into(), -1500, 500), ("redeem".into(), -500, 0), ("refund".into(), 700, 700)]
+        );
+        let balance: i64 = sqlx::query_scalar(r#"SELECT remaining_cents FROM gift_codes WHERE code = 'GIFT'"#).fetch_one(&pool).await.unwrap();
+        assert_eq!(balance, 700);
+    }
+
+    #[tokio::test]
+    async fn adjustments_need_a_note_and_cannot_overdraw() {
+        let pool = mock_state().await.pool.clone();
+        add_gift_card(&pool, "GIFT", 1000).await;
+
+        assert!(matches!(adjust(&pool, "gift", 500, "  ", "admin").await, Err(GiftCardError::Invalid(_))));
+        assert!(matches!(adjust(&pool, "gift", 0, "nothing", "admin").await, Err(GiftCardError::Invalid(_))));
+        assert!(matches!(adjust(&pool, "gift", -1001, "too much", "admin").await, Err(GiftCardError::Invalid(_))));
+        assert!(matches!(adjust(&pool, "nope", 100, "goodwill", "admin").await, Err(GiftCardError::NotFound(_))));
+        assert_eq!(adjust(&pool, "gift", -1000, "lost card", "admin").await.unwrap(), 0);
+        assert_eq!(kinds(&pool).await.len(), 2, "refused adjustments leave no entry");
+    }
+
+    #[tokio::test]
+    async fn ledger_is_append_only() {
+        let pool = mock_state().await.pool.clone();
+        add_gift_card(&pool, "GIFT", 1000).await;
+
+        assert!(sqlx::query(r#"UPDATE gift_card_transactions SET amount_cents = 99999"#).execute(&pool).await.is_err());
+        assert!(sqlx::query(r#"DELETE FROM gift_card_transactions"#).execute(&pool).await.is_err());
+        assert!(sqlx::query(r#"DELETE FROM gift_codes WHERE code = 'GIFT'"#).execute(&pool).await.is_err(), "cards with history stay");
+        assert_eq!(kinds(&pool).await, vec![("issue".into(), 1000, 1000)]);
+    }
+}
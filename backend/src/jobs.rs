@@ -0,0 +1,382 @@
+use std::sync::Arc;
+
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use uuid::Uuid;
+
+use crate::schedule::db_timestamp;
+use crate::state::AppState;
+
+/// How often the runner looks for due jobs.
+const POLL_SECONDS: u64 = 30;
+
+/// Finished jobs are kept this long for the admin view.
+const KEEP_DONE_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobKind {
+    CleanupPending,
+    ReservationReminders,
+    ReservationNoShows,
+}
+
+impl JobKind {
+    pub const ALL: [JobKind; 3] = [JobKind::CleanupPending, JobKind::ReservationReminders, JobKind::ReservationNoShows];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobKind::CleanupPending => "cleanup_pending",
+            JobKind::ReservationReminders => "reservation_reminders",
+            JobKind::ReservationNoShows => "reservation_no_shows",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == s)
+    }
+
+    /// Time between runs of a recurring job.
+    fn every(self) -> Duration {
+        match self {
+            JobKind::CleanupPending => Duration::hours(1),
+            JobKind::ReservationReminders => Duration::minutes(15),
+            JobKind::ReservationNoShows => Duration::minutes(5),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Job {
+    pub id: String,
+    pub kind: String,
+    pub payload: Option<String>,
+    pub recurring: bool,
+    pub status: String,
+    pub run_at: String,
+    pub attempts: i64,
+    pub max_attempts: i64,
+    pub last_error: Option<String>,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum JobError {
+    #[error("job {0} not found")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for JobError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            JobError::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            JobError::Conflict(_) => (StatusCode::CONFLICT, "job_conflict"),
+            JobError::Database(ref e) => {
+                tracing::error!("Job query failed: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            }
+        };
+        (status, Json(serde_json::json!({ "error": code, "message": self.to_string() }))).into_response()
+    }
+}
+
+/// Start the background runner. Jobs left `running` by a previous process
+/// are picked up again, and every recurring job gets a queued run.
+pub fn spawn(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        if let Err(e) = recover(&state.pool).await {
+            tracing::error!("Could not prepare the job queue: {:?}", e);
+        }
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(POLL_SECONDS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = run_due(&state).await {
+                tracing::error!("Job runner failed: {:?}", e);
+            }
+        }
+    });
+}
+
+async fn recover(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let resumed = sqlx::query(r#"UPDATE jobs SET status = 'pending' WHERE status = 'running'"#)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if resumed > 0 {
+        tracing::warn!("Re-queued {} interrupted job(s)", resumed);
+    }
+    for kind in JobKind::ALL {
+        schedule_recurring(pool, kind, Utc::now()).await?;
+    }
+    Ok(())
+}
+
+/// Queue the next run of a recurring job unless one is already queued.
+async fn schedule_recurring(pool: &SqlitePool, kind: JobKind, run_at: chrono::DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"INSERT OR IGNORE INTO jobs (id, kind, recurring, run_at) VALUES (?, ?, 1, ?)"#)
+        .bind(Uuid::new_v4().to_string())
+        .bind(kind.as_str())
+        .bind(db_timestamp(run_at))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Run every job that is due, one at a time.
+async fn run_due(state: &AppState) -> Result<(), sqlx::Error> {
+    loop {
+        let now = db_timestamp(Utc::now());
+        let claimed = sqlx::query(r#"
+            UPDATE jobs SET status = 'running', attempts = attempts + 1, started_at = ?1
+            WHERE id = (SELECT id FROM jobs WHERE status = 'pending' AND run_at <= ?1 ORDER BY run_at LIMIT 1)
+            RETURNING id, kind, recurring, attempts, max_attempts
+        "#)
+            .bind(&now)
+            .fetch_optional(&state.pool)
+            .await?;
+        let Some(row) = claimed else { return Ok(()) };
+
+        let id: String = row.try_get("id")?;
+        let kind_name: String = row.try_get("kind")?;
+        let recurring: bool = row.try_get("recurring")?;
+        let attempts: i64 = row.try_get("attempts")?;
+        let max_attempts: i64 = row.try_get("max_attempts")?;
+
+        let kind = JobKind::parse(&kind_name);
+        let result = match kind {
+            Some(kind) => run(state, kind).await,
+            None => Err(anyhow::anyhow!("unknown job kind '{}'", kind_name)),
+        };
+        let finished = Utc::now();
+
+        match result {
+            Ok(()) => {
+                sqlx::query(r#"UPDATE jobs SET status = 'done', last_error = NULL, finished_at = ? WHERE id = ?"#)
+                    .bind(db_timestamp(finished))
+                    .bind(&id)
+                    .execute(&state.pool)
+                    .await?;
+            }
+            Err(e) if kind.is_some() && attempts < max_attempts => {
+                let retry_at = finished + Duration::minutes(attempts * attempts);
+                tracing::warn!("Job {} ({}) failed on attempt {}, retrying at {}: {:#}", id, kind_name, attempts, retry_at, e);
+                sqlx::query(r#"UPDATE jobs SET status = 'pending', run_at = ?, last_error = ? WHERE id = ?"#)
+                    .bind(db_timestamp(retry_at))
+                    .bind(format!("{:#}", e))
+                    .bind(&id)
+                    .execute(&state.pool)
+                    .await?;
+                continue;
+            }
+            Err(e) => {
+                tracing::error!("Job {} ({}) failed for good after {} attempt(s): {:#}", id, kind_name, attempts, e);
+                sqlx::query(r#"UPDATE jobs SET status = 'failed', last_error = ?, finished_at = ? WHERE id = ?"#)
+                    .bind(format!("{:#}", e))
+                    .bind(db_timestamp(finished))
+                    .bind(&id)
+                    .execute(&state.pool)
+                    .await?;
+            }
+        }
+        if let (Some(kind), true) = (kind, recurring) {
+            schedule_recurring(&state.pool, kind, finished + kind.every()).await?;
+        }
+    }
+}
+
+async fn run(state: &AppState, kind: JobKind) -> anyhow::Result<()> {
+    match kind {
+        JobKind::CleanupPending => {
+            crate::db::cleanup_stale_pending(&state.pool).await?;
+            crate::orders::refund::reconcile_claims(state, None).await?;
+            let pruned = sqlx::query(r#"DELETE FROM jobs WHERE status = 'done' AND finished_at < ?"#)
+                .bind(db_timestamp(Utc::now() - Duration::days(KEEP_DONE_DAYS)))
+                .execute(&state.pool)
+                .await?
+                .rows_affected();
+            if pruned > 0 {
+                tracing::info!("Pruned {} finished job(s)", pruned);
+            }
+        }
+        JobKind::ReservationReminders => {
+            let sent = crate::reservations::send_reminders(state).await?;
+            if sent > 0 {
+                tracing::info!("Sent {} reservation reminder(s)", sent);
+            }
+        }
+        JobKind::ReservationNoShows => {
+            for id in crate::reservations::mark_no_shows(state).await? {
+                tracing::info!("Reservation {} marked as no-show", id);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Jobs for the admin view, newest first.
+pub async fn list(pool: &SqlitePool, status: Option<&str>, kind: Option<&str>, limit: i64) -> Result<Vec<Job>, sqlx::Error> {
+    let rows = sqlx::query(r#"
+        SELECT id, kind, payload, recurring, status, run_at, attempts, max_attempts, last_error, started_at, finished_at, created_at
+        FROM jobs
+        WHERE (?1 IS NULL OR status = ?1) AND (?2 IS NULL OR kind = ?2)
+        ORDER BY run_at DESC
+        LIMIT ?3
+    "#)
+        .bind(status)
+        .bind(kind)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|r| Job {
+        id: r.try_get("id").unwrap_or_default(),
+        kind: r.try_get("kind").unwrap_or_default(),
+        payload: r.try_get("payload").ok().flatten(),
+        recurring: r.try_get("recurring").unwrap_or(false),
+        status: r.try_get("status").unwrap_or_default(),
+        run_at: r.try_get("run_at").unwrap_or_default(),
+        attempts: r.try_get("attempts").unwrap_or(0),
+        max_attempts: r.try_get("max_attempts").unwrap_or(0),
+        last_error: r.try_get("last_error").ok().flatten(),
+        started_at: r.try_get("started_at").ok().flatten(),
+        finished_at: r.try_get("finished_at").ok().flatten(),
+        created_at: r.try_get("created_at").unwrap_or_default(),
+    }).collect())
+}
+
+/// Per kind: what is queued, when it last finished and the latest failure.
+pub async fn summary(pool: &SqlitePool) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query(r#"
+        SELECT kind,
+               SUM(status = 'pending') AS pending,
+               SUM(status = 'running') AS running,
+               SUM(status = 'failed') AS failed,
+               MIN(CASE WHEN status = 'pending' THEN run_at END) AS next_run_at,
+               MAX(CASE WHEN status = 'done' THEN finished_at END) AS last_success_at,
+               (SELECT j.last_error FROM jobs j WHERE j.kind = jobs.kind AND j.last_error IS NOT NULL ORDER BY COALESCE(j.finished_at, j.started_at) DESC LIMIT 1) AS last_error
+        FROM jobs
+        GROUP BY kind
+        ORDER BY kind
+    "#)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|r| serde_json::json!({
+        "kind": r.try_get::<String, _>("kind").unwrap_or_default(),
+        "pending": r.try_get::<i64, _>("pending").unwrap_or(0),
+        "running": r.try_get::<i64, _>("running").unwrap_or(0),
+        "failed": r.try_get::<i64, _>("failed").unwrap_or(0),
+        "next_run_at": r.try_get::<Option<String>, _>("next_run_at").ok().flatten(),
+        "last_success_at": r.try_get::<Option<String>, _>("last_success_at").ok().flatten(),
+        "last_error": r.try_get::<Option<String>, _>("last_error").ok().flatten(),
+    })).collect())
+}
+
+/// Put a failed job back in the queue to run right away.
+pub async fn retry(pool: &SqlitePool, id: &str) -> Result<(), JobError> {
+    let status: Option<String> = sqlx::query_scalar(r#"SELECT status FROM jobs WHERE id = ?"#)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+    match status.as_deref() {
+        None => return Err(JobError::NotFound(id.to_string())),
+        Some("failed") => {}
+        Some(other) => return Err(JobError::Conflict(format!("only failed jobs can be retried, this one is {}", other))),
+    }
+    // A recurring job has already queued its next run, which the unique index guards
+    sqlx::query(r#"UPDATE jobs SET status = 'pending', attempts = 0, run_at = ?, finished_at = NULL WHERE id = ? AND status = 'failed'"#)
+        .bind(db_timestamp(Utc::now()))
+        .bind(id)
+        .execute(pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.is_unique_violation() => JobError::Conflict("a run of this job is already queued".into()),
+            e => JobError::Database(e),
+        })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_state;
+
+    async fn count(pool: &SqlitePool, status: &str) -> i64 {
+        sqlx::query_scalar(r#"SELECT COUNT(DISTINCT kind) FROM jobs WHERE status = ?"#).bind(status).fetch_one(pool).await.unwrap()
+    }
+
+    async fn add_job(pool: &SqlitePool, id: &str, kind: &str, max_attempts: i64) {
+        sqlx::query(r#"INSERT INTO jobs (id, kind, run_at, max_attempts) VALUES (?, ?, ?, ?)"#)
+            .bind(id)
+            .bind(kind)
+            .bind(db_timestamp(Utc::now() - Duration::minutes(1)))
+            .bind(max_attempts)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn recurring_jobs_are_queued_once_and_requeue_themselves() {
+        let state = mock_state().await;
+        sqlx::query(r#"INSERT INTO jobs (id, kind, recurring, status, run_at) VALUES ('interrupted', 'cleanup_pending', 1, 'running', ?)"#)
+            .bind(db_timestamp(Utc::now()))
+            .execute(&state.pool)
+            .await
+            .unwrap();
+        recover(&state.pool).await.unwrap();
+        recover(&state.pool).await.unwrap();
+        let queued: i64 = sqlx::query_scalar(r#"SELECT COUNT(*) FROM jobs"#).fetch_one(&state.pool).await.unwrap();
+        assert_eq!((queued, count(&state.pool, "pending").await), (3, 3), "one run per kind, the interrupted one included");
+
+        run_due(&state).await.unwrap();
+        assert_eq!((count(&state.pool, "done").await, count(&state.pool, "pending").await), (3, 3));
+        let next_due: i64 = sqlx::query_scalar(r#"SELECT COUNT(*) FROM jobs WHERE status = 'pending' AND run_at <= ?"#)
+            .bind(db_timestamp(Utc::now()))
+            .fetch_one(&state.pool)
+            .await
+            .unwrap();
+        assert_eq!(next_due, 0, "the next runs lie in the future");
+    }
+
+    #[tokio::test]
+    async fn failed_runs_back_off_and_can_be_retried() {
+        let state = mock_state().await;
+        // Reminders cannot be sent without SMTP, so the job fails
+        sqlx::query(r#"INSERT INTO reservations (id, table_id, name, email, party_size, starts_at, ends_at, manage_token, created_at) VALUES ('r-1', 'table-1', 'Guest', 'guest@example.com', 2, ?, ?, 'tok', '2020-01-01T00:00:00.000Z')"#)
+            .bind(db_timestamp(Utc::now() + Duration::hours(2)))
+            .bind(db_timestamp(Utc::now() + Duration::hours(4)))
+            .execute(&state.pool)
+            .await
+            .unwrap();
+        add_job(&state.pool, "retried", "reservation_reminders", 2).await;
+        add_job(&state.pool, "unknown", "polish_cutlery", 5).await;
+
+        run_due(&state).await.unwrap();
+        let jobs = list(&state.pool, None, None, 10).await.unwrap();
+        let job = |id: &str| jobs.iter().find(|j| j.id == id).unwrap();
+        assert_eq!((job("retried").status.as_str(), job("retried").attempts), ("pending", 1));
+        assert!(job("retried").last_error.as_deref().is_some_and(|e| e.contains("reminder")));
+        assert!(job("retried").run_at > db_timestamp(Utc::now()));
+        assert_eq!((job("unknown").status.as_str(), job("unknown").attempts), ("failed", 1), "unknown kinds are not retried");
+        let reminder_sent: Option<String> = sqlx::query_scalar(r#"SELECT reminder_sent_at FROM reservations WHERE id = 'r-1'"#).fetch_one(&state.pool).await.unwrap();
+        assert_eq!(reminder_sent, None, "the claim is released for the next run");
+
+        sqlx::query(r#"UPDATE jobs SET run_at = ? WHERE id = 'retried'"#).bind(db_timestamp(Utc::now())).execute(&state.pool).await.unwrap();
+        run_due(&state).await.unwrap();
+        let failed = list(&state.pool, Some("failed"), Some("reservation_reminders"), 10).await.unwrap();
+        assert_eq!((failed.len(), failed[0].attempts), (1, 2));
+
+        assert!(matches!(retry(&state.pool, "missing").await, Err(JobError::NotFound(_))));
+        retry(&state.pool, "retried").await.unwrap();
+        assert!(matches!(retry(&state.pool, "retried").await, Err(JobError::Conflict(_))));
+        let summary = summary(&state.pool).await.unwrap();
+        let reminders = summary.iter().find(|s| s["kind"] == "reservation_reminders").unwrap();
+        assert_eq!((reminders["pending"].as_i64(), reminders["failed"].as_i64()), (Some(1), Some(0)));
+    }
+}
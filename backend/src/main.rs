@@ -9,6 +9,16 @@ mod db;
 mod routes;
 mod payments;
 mod email;
+mod pricing;
+mod coupons;
+mod gift_cards;
+mod catalog;
+mod orders;
+mod schedule;
+mod reservations;
+mod jobs;
+#[cfg(test)]
+mod test_support;
 
 #[tokio::main]
 async fn main() {
@@ -33,9 +43,21 @@ async fn main() {
     let smtp_password = std::env::var("SMTP_PASSWORD").ok();
     let smtp_from = std::env::var("SMTP_FROM").ok();
 
+    let prep_minutes = std::env::var("ORDER_PREP_MINUTES").ok().and_then(|v| v.parse().ok()).unwrap_or(20);
+    let discount_hold_minutes = std::env::var("DISCOUNT_HOLD_MINUTES").ok().and_then(|v| v.parse().ok()).filter(|m| *m > 0).unwrap_or(30);
+    let schedule = schedule::ScheduleConfig::from_env();
+
     let paypal_client_id = std::env::var("PAYPAL_CLIENT_ID").ok();
     let paypal_secret = std::env::var("PAYPAL_SECRET").ok();
     let paypal_api_base = std::env::var("PAYPAL_API_BASE").unwrap_or_else(|_| "https://api-m.sandbox.paypal.com".into());
+    let payments = payments::providers_from_config(payments::PaymentConfig {
+        providers: std::env::var("PAYMENT_PROVIDER").ok(),
+        backend_url: backend_url.clone(),
+        paypal_client_id: paypal_client_id.clone(),
+        paypal_secret: paypal_secret.clone(),
+        paypal_api_base,
+        paypal_webhook_id: std::env::var("PAYPAL_WEBHOOK_ID").ok(),
+    }).expect("payment providers");
 
     // Log configuration status (without exposing sensitive data)
     tracing::info!("Backend starting with configuration:");
@@ -44,12 +66,13 @@ async fn main() {
     tracing::info!("Backend URL: {}", backend_url);
     tracing::info!("SMTP configured: {}", smtp_host.is_some() && smtp_username.is_some() && smtp_password.is_some() && smtp_from.is_some());
     tracing::info!("PayPal configured: {}", paypal_client_id.is_some() && paypal_secret.is_some());
+    tracing::info!("Payment providers: {} (default {})", payments.names().join(", "), payments.default_provider().name());
+    tracing::info!("Restaurant timezone: {}, pickup slots of {} min", schedule.timezone, schedule.slot_minutes);
 
     let state = Arc::new(state::AppState {
         pool,
         jwt_secret,
         app_url,
-        backend_url,
         smtp_host,
         smtp_port,
         smtp_username,
@@ -57,20 +80,16 @@ async fn main() {
         smtp_from,
         paypal_client_id,
         paypal_secret,
-        paypal_api_base,
+        payments,
+        prep_minutes,
+        discount_hold_minutes,
+        schedule,
+        images: catalog::images::ImageConfig::from_env(),
+        order_events: tokio::sync::broadcast::channel(orders::feed::FEED_CAPACITY).0,
     });
 
-    // Spawn background cleanup task
-    let cleanup_pool = state.pool.clone();
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(3600)); // Run every hour
-        loop {
-            interval.tick().await;
-            if let Err(e) = db::cleanup_stale_pending(&cleanup_pool).await {
-                tracing::error!("Cleanup task failed: {:?}", e);
-            }
-        }
-    });
+    // Background jobs: pending-order cleanup, reservation reminders and no-shows
+    jobs::spawn(state.clone());
 
     let app_router: Router<_> = routes::build_router(state);
 
@@ -0,0 +1,146 @@
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+
+use crate::pricing::SelectedOption;
+use crate::state::AppState;
+
+/// Capacity of the in-process kitchen feed. A subscriber that falls further
+/// behind is disconnected and catches up from the database on reconnect.
+pub const FEED_CAPACITY: usize = 256;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TicketItem {
+    pub product_id: String,
+    pub name: Option<String>,
+    pub quantity: i64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<SelectedOption>,
+}
+
+/// What the kitchen needs to cook an order.
+#[derive(Serialize, Clone, Debug)]
+pub struct Ticket {
+    pub order_id: String,
+    pub email: Option<String>,
+    pub total_cents: i64,
+    pub created_at: String,
+    /// Chosen pickup slot (UTC); `None` means as soon as possible.
+    pub pickup_at: Option<String>,
+    pub items: Vec<TicketItem>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedEventKind {
+    NewOrder,
+    StatusChanged,
+}
+
+/// One entry of the kitchen feed. `cursor` is the `order_status_events` rowid,
+/// so it only ever grows and doubles as the SSE event id.
+#[derive(Serialize, Clone, Debug)]
+pub struct FeedEvent {
+    pub cursor: i64,
+    pub kind: FeedEventKind,
+    pub order_id: String,
+    pub from_status: Option<String>,
+    pub to_status: String,
+    pub at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticket: Option<Ticket>,
+}
+
+/// Options stored on an `order_items` row; rows without options have `NULL`.
+pub fn parse_options(options_json: Option<String>) -> Vec<SelectedOption> {
+    options_json.and_then(|j| serde_json::from_str(&j).ok()).unwrap_or_default()
+}
+
+/// Remaining (not refunded) lines of an order.
+pub async fn ticket_items(pool: &SqlitePool, order_id: &str) -> Result<Vec<TicketItem>, sqlx::Error> {
+    let rows = sqlx::query(r#"SELECT oi.product_id, p.name, oi.quantity - oi.refunded_quantity AS quantity, oi.options_json FROM order_items oi LEFT JOIN products p ON oi.product_id = p.id WHERE oi.order_id = ?"#)
+        .bind(order_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter()
+        .map(|r| TicketItem {
+            product_id: r.try_get("product_id").unwrap_or_default(),
+            name: r.try_get("name").ok().flatten(),
+            quantity: r.try_get("quantity").unwrap_or(0),
+            options: parse_options(r.try_get("options_json").ok().flatten()),
+        })
+        .filter(|i| i.quantity > 0)
+        .collect())
+}
+
+/// Feed events after `cursor`, oldest first. Orders without kitchen lines
+/// (gift coupon purchases) are left out.
+pub async fn events_since(pool: &SqlitePool, cursor: i64, limit: i64) -> Result<Vec<FeedEvent>, sqlx::Error> {
+    let rows = sqlx::query(r#"
+        SELECT e.rowid AS cursor, e.order_id, e.from_status, e.to_status, e.created_at, o.email, o.total_cents, o.created_at AS order_created_at, o.pickup_at
+        FROM order_status_events e
+        JOIN orders o ON o.id = e.order_id
+        WHERE e.rowid > ? AND EXISTS (SELECT 1 FROM order_items oi WHERE oi.order_id = e.order_id)
+        ORDER BY e.rowid ASC
+        LIMIT ?
+    "#)
+        .bind(cursor)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+    let mut events = Vec::with_capacity(rows.len());
+    for r in rows {
+        let order_id: String = r.try_get("order_id")?;
+        let from_status: Option<String> = r.try_get("from_status").ok().flatten();
+        let kind = if from_status.is_none() { FeedEventKind::NewOrder } else { FeedEventKind::StatusChanged };
+        let ticket = match kind {
+            FeedEventKind::NewOrder => Some(Ticket {
+                order_id: order_id.clone(),
+                email: r.try_get("email").ok().flatten(),
+                total_cents: r.try_get("total_cents").unwrap_or(0),
+                created_at: r.try_get("order_created_at").unwrap_or_default(),
+                pickup_at: r.try_get("pickup_at").ok().flatten(),
+                items: ticket_items(pool, &order_id).await?,
+            }),
+            FeedEventKind::StatusChanged => None,
+        };
+        events.push(FeedEvent {
+            cursor: r.try_get("cursor")?,
+            kind,
+            order_id,
+            from_status,
+            to_status: r.try_get("to_status").unwrap_or_default(),
+            at: r.try_get("created_at").unwrap_or_default(),
+            ticket,
+        });
+    }
+    Ok(events)
+}
+
+/// Newest cursor, for subscribers that only want events from now on.
+pub async fn latest_cursor(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(r#"SELECT COALESCE(MAX(rowid), 0) FROM order_status_events"#)
+        .fetch_one(pool)
+        .await
+}
+
+/// Broadcast the latest status event of `order_id`.
+/// Call after the transaction that changed the order has committed.
+pub async fn publish(state: &AppState, order_id: &str) {
+    let newest = sqlx::query_scalar::<_, Option<i64>>(r#"SELECT MAX(rowid) FROM order_status_events WHERE order_id = ?"#)
+        .bind(order_id)
+        .fetch_one(&state.pool)
+        .await;
+    let Ok(Some(newest)) = newest else { return };
+
+    // Re-read through the same query the catch-up endpoint uses so both agree
+    match events_since(&state.pool, newest - 1, 1).await {
+        Ok(events) => {
+            for event in events.into_iter().filter(|e| e.cursor == newest) {
+                // No subscribers is fine: nobody has the kitchen screen open
+                let _ = state.order_events.send(event);
+            }
+        }
+        Err(e) => tracing::error!("Failed to publish kitchen feed event for order {}: {:?}", order_id, e),
+    }
+}
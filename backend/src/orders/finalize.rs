@@ -0,0 +1,370 @@
+use sqlx::{Row, Sqlite, Transaction};
+use uuid::Uuid;
+
+use crate::catalog::stock;
+use crate::coupons::{holds, redemptions};
+use crate::catalog::translations::{self, Locale};
+use crate::gift_cards;
+use crate::email::{gift_coupon_html, html_escape, order_confirmation_html, send_html_email, OrderEmailText};
+use crate::payments::{CapturedPayment, PaymentStatus};
+use crate::pricing::options_label;
+use crate::schedule::db_timestamp;
+use crate::state::AppState;
+
+use super::StoredItems;
+use super::status::{record_initial, OrderStatus};
+use super::tracking;
+
+/// Bonus granted on top of the purchased gift coupon value.
+const GIFT_BONUS_PERCENT: f64 = 0.10;
+
+#[derive(Debug, Clone)]
+pub struct FinalizedOrder {
+    pub order_id: String,
+    /// `false` when the payment had already been finalized by another entry point.
+    pub created: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FinalizeError {
+    #[error("payment {0} is not completed (status {1:?})")]
+    NotCaptured(String, PaymentStatus),
+    #[error("no pending order or gift for payment {0}")]
+    PendingNotFound(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// What was finalized, kept around so notifications can be sent after commit.
+enum Finalized {
+    Order { order_id: String, email: String, items: StoredItems, total_cents: i64, tracking_token: String, pickup_at: Option<String>, locale: Locale },
+    Gift { order_id: String, email: String, code: String, value_cents: i64 },
+}
+
+/// Turn a captured payment into exactly one `orders` row.
+///
+/// Shared by the PayPal return redirect, the PayPal webhook, the gift return
+/// and pay-at-pickup checkout. The pending order (or pending gift) is consumed
+/// inside a single transaction; `orders.payment_order_id` is unique, so a
+/// second call for the same payment returns the existing order instead of
+/// creating another one.
+pub async fn finalize_payment(state: &AppState, payment_order_id: &str, capture: &CapturedPayment) -> Result<FinalizedOrder, FinalizeError> {
+    let status = match capture.status {
+        PaymentStatus::Completed => OrderStatus::Paid,
+        PaymentStatus::AwaitingPayment => OrderStatus::AwaitingPayment,
+        ref other => return Err(FinalizeError::NotCaptured(payment_order_id.to_string(), other.clone())),
+    };
+
+    if let Some(order_id) = find_finalized(state, payment_order_id).await? {
+        tracing::info!("Payment {} already finalized as order {}", payment_order_id, order_id);
+        return Ok(FinalizedOrder { order_id, created: false });
+    }
+
+    let mut tx = state.pool.begin().await?;
+    let finalized = match consume_pending(&mut tx, state, payment_order_id, capture, status).await {
+        Ok(found) => found,
+        Err(e) if is_unique_violation(&e) => None,
+        Err(e) => return Err(e.into()),
+    };
+    let Some(finalized) = finalized else {
+        drop(tx);
+        // Another entry point may have consumed the pending row in the meantime
+        if let Some(order_id) = find_finalized(state, payment_order_id).await? {
+            tracing::info!("Payment {} was finalized concurrently as order {}", payment_order_id, order_id);
+            return Ok(FinalizedOrder { order_id, created: false });
+        }
+        return Err(FinalizeError::PendingNotFound(payment_order_id.to_string()));
+    };
+    tx.commit().await?;
+
+    let order_id = match &finalized {
+        Finalized::Order { order_id, .. } | Finalized::Gift { order_id, .. } => order_id.clone(),
+    };
+    tracing::info!("Payment {} finalized as order {}", payment_order_id, order_id);
+    super::feed::publish(state, &order_id).await;
+    send_confirmation(state, finalized).await;
+
+    Ok(FinalizedOrder { order_id, created: true })
+}
+
+/// `orders.paid_at` of a new order: now if the payment was captured, unset while it awaits payment.
+fn paid_at(status: OrderStatus) -> Option<String> {
+    (status == OrderStatus::Paid).then(|| db_timestamp(chrono::Utc::now()))
+}
+
+/// The order already produced for a provider payment id, if any.
+pub async fn find_finalized(state: &AppState, payment_order_id: &str) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(r#"SELECT id FROM orders WHERE payment_order_id = ?"#)
+        .bind(payment_order_id)
+        .fetch_optional(&state.pool)
+        .await
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db) if db.is_unique_violation())
+}
+
+async fn consume_pending(
+    tx: &mut Transaction<'_, Sqlite>,
+    state: &AppState,
+    payment_order_id: &str,
+    capture: &CapturedPayment,
+    status: OrderStatus,
+) -> Result<Option<Finalized>, sqlx::Error> {
+    if let Some(f) = finalize_pending_order(tx, state, payment_order_id, capture, status).await? {
+        return Ok(Some(f));
+    }
+    finalize_pending_gift(tx, payment_order_id, capture, status).await
+}
+
+async fn finalize_pending_order(
+    tx: &mut Transaction<'_, Sqlite>,
+    state: &AppState,
+    payment_order_id: &str,
+    capture: &CapturedPayment,
+    status: OrderStatus,
+) -> Result<Option<Finalized>, sqlx::Error> {
+    let Some(row) = sqlx::query(r#"SELECT user_id, email, amount_cents, items_json, payment_provider, notify_ready, pickup_at, locale FROM pending_orders WHERE order_id = ?"#)
+        .bind(payment_order_id)
+        .fetch_optional(&mut **tx)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let user_id: Option<String> = row.try_get("user_id").ok().flatten();
+    let email: String = row.try_get::<Option<String>, _>("email").ok().flatten().unwrap_or_default();
+    let amount_cents: i64 = row.try_get("amount_cents").unwrap_or(0);
+    let items_json: String = row.try_get::<Option<String>, _>("items_json").ok().flatten().unwrap_or_default();
+    let items = StoredItems::parse(&items_json);
+    let provider: Option<String> = row.try_get("payment_provider").ok().flatten();
+    let notify_ready: bool = row.try_get("notify_ready").unwrap_or(false);
+    let pickup_at: Option<String> = row.try_get("pickup_at").ok().flatten();
+    let locale = row.try_get::<String, _>("locale").ok().and_then(|l| Locale::parse(&l)).unwrap_or_default();
+
+    // The money is taken either way, so the order is still created; staff see
+    // the note and settle the difference with the customer
+    let mut issues = Vec::new();
+    if let Some(captured_cents) = capture.amount_cents.filter(|&c| c != amount_cents) {
+        tracing::warn!("Captured amount {}¢ for {} differs from pending amount {}¢", captured_cents, payment_order_id, amount_cents);
+        issues.push(format!("captured {}¢ but the order total is {}¢", captured_cents, amount_cents));
+    }
+
+    let order_id = Uuid::new_v4().to_string();
+    let tracking_token = tracking::new_token();
+    sqlx::query(r#"INSERT INTO orders (id, user_id, email, total_cents, currency, coupon_code, items_json, status, paid_at, payment_provider, payment_capture_id, payment_order_id, tracking_token, notify_ready, pickup_at, locale) VALUES (?, ?, ?, ?, 'EUR', ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#)
+        .bind(&order_id)
+        .bind(&user_id)
+        .bind(&email)
+        .bind(amount_cents)
+        .bind(items.coupon_code.clone())
+        .bind(items_json.clone())
+        .bind(status.as_str())
+        .bind(paid_at(status))
+        .bind(&provider)
+        .bind(capture.capture_id.clone())
+        .bind(payment_order_id)
+        .bind(&tracking_token)
+        .bind(notify_ready)
+        .bind(&pickup_at)
+        .bind(locale.as_str())
+        .execute(&mut **tx)
+        .await?;
+    record_initial(tx, &order_id, status, "system").await?;
+    tracking::set_initial_estimate(tx, &order_id, state.prep_minutes).await?;
+
+    for line in &items.cart {
+        let options_json = (!line.options.is_empty()).then(|| serde_json::to_string(&line.options).unwrap_or_default());
+        sqlx::query(r#"INSERT INTO order_items (id, order_id, product_id, quantity, unit_amount, options_json) VALUES (?, ?, ?, ?, ?, ?)"#)
+            .bind(Uuid::new_v4().to_string())
+            .bind(&order_id)
+            .bind(&line.product_id)
+            .bind(line.quantity)
+            .bind(line.unit_amount)
+            .bind(options_json)
+            .execute(&mut **tx)
+            .await?;
+    }
+
+    let stock_day = stock::order_stock_day(&state.schedule, pickup_at.as_deref());
+    for line in &items.cart {
+        stock::record_sale(tx, stock_day, &line.product_id, line.quantity).await?;
+    }
+    sqlx::query(r#"UPDATE orders SET stock_day = ? WHERE id = ?"#)
+        .bind(stock_day.to_string())
+        .bind(&order_id)
+        .execute(&mut **tx)
+        .await?;
+
+    // The hold only guarded the checkout; spending is re-checked here against
+    // the holds of other checkouts, in case this one's hold had lapsed
+    holds::release(tx, payment_order_id).await?;
+    if let Some(code) = items.coupon_code.as_deref() {
+        let kind = items.discount_kind.as_deref();
+        if kind != Some("gift_code") {
+            if holds::consume_coupon(tx, &code.to_uppercase()).await? {
+                redemptions::record(tx, code, &order_id, user_id.as_deref(), &email, items.discount_cents).await?;
+                let customer = redemptions::Customer::new(user_id.clone(), Some(&email));
+                if let Some(rule) = redemptions::broken_rule(tx, code, &order_id, &customer).await? {
+                    tracing::warn!("Order {} used coupon {} against its per-customer rule: {:?}", order_id, code, rule);
+                    issues.push(format!("coupon {}: {} {}¢ discount given anyway", code, rule.message(), items.discount_cents));
+                }
+            } else if kind == Some("coupon") {
+                tracing::warn!("Coupon {} had no use left when order {} was paid", code, order_id);
+                issues.push(format!("coupon {} had no use left at payment; {}¢ discount not covered", code, items.discount_cents));
+            }
+        }
+        if kind != Some("coupon") && items.discount_cents > 0 {
+            let available = holds::available_gift_cents(tx, code).await?.max(0);
+            let spent = gift_cards::redeem(tx, code, items.discount_cents.min(available), &order_id).await?;
+            if spent < items.discount_cents && kind == Some("gift_code") {
+                tracing::warn!("Gift code {} covered only {}¢ of {}¢ when order {} was paid", code, spent, items.discount_cents, order_id);
+                issues.push(format!("gift code {} covered only {}¢ of the {}¢ discount at payment", code, spent, items.discount_cents));
+            }
+        }
+    }
+    if !issues.is_empty() {
+        sqlx::query(r#"UPDATE orders SET discount_issue = ? WHERE id = ?"#)
+            .bind(issues.join("; "))
+            .bind(&order_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+
+    sqlx::query(r#"DELETE FROM pending_orders WHERE order_id = ?"#)
+        .bind(payment_order_id)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(Some(Finalized::Order { order_id, email, items, total_cents: amount_cents, tracking_token, pickup_at, locale }))
+}
+
+async fn finalize_pending_gift(
+    tx: &mut Transaction<'_, Sqlite>,
+    payment_order_id: &str,
+    capture: &CapturedPayment,
+    status: OrderStatus,
+) -> Result<Option<Finalized>, sqlx::Error> {
+    let Some(row) = sqlx::query(r#"SELECT email, amount_cents, payment_provider FROM pending_gifts WHERE order_id = ?"#)
+        .bind(payment_order_id)
+        .fetch_optional(&mut **tx)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let email: String = row.try_get::<Option<String>, _>("email").ok().flatten().unwrap_or_default();
+    let base_amount: i64 = row.try_get("amount_cents").unwrap_or(0);
+    let provider: Option<String> = row.try_get("payment_provider").ok().flatten();
+    let bonus = ((base_amount as f64) * GIFT_BONUS_PERCENT).round() as i64;
+    let total_value = base_amount + bonus;
+    let code = Uuid::new_v4().to_string().replace('-', "");
+
+    tracing::info!("Creating gift code: {}, value: {}¢ (base: {}¢ + bonus: {}¢)", code, total_value, base_amount, bonus);
+
+    let order_id = Uuid::new_v4().to_string();
+    let gift_json = serde_json::json!({
+        "type": "gift_coupon",
+        "code": &code,
+        "cart": [
+            {
+                "productId": "gift_coupon",
+                "name": format!("Gift Coupon - €{:.2}", base_amount as f64 / 100.0),
+                "unitAmount": base_amount,
+                "quantity": 1,
+                "currency": "EUR"
+            }
+        ],
+        "base_amount_cents": base_amount,
+        "bonus_cents": bonus,
+        "total_value_cents": total_value,
+        "discount_cents": 0
+    }).to_string();
+
+    // The order records what was paid; the bonus only lives on the gift code
+    sqlx::query(r#"INSERT INTO orders (id, email, total_cents, currency, items_json, status, paid_at, payment_provider, payment_capture_id, payment_order_id, tracking_token) VALUES (?, ?, ?, 'EUR', ?, ?, ?, ?, ?, ?, ?)"#)
+        .bind(&order_id)
+        .bind(&email)
+        .bind(base_amount)
+        .bind(gift_json)
+        .bind(status.as_str())
+        .bind(paid_at(status))
+        .bind(&provider)
+        .bind(capture.capture_id.clone())
+        .bind(payment_order_id)
+        .bind(tracking::new_token())
+        .execute(&mut **tx)
+        .await?;
+    record_initial(tx, &order_id, status, "system").await?;
+
+    gift_cards::issue(tx, &code, base_amount, bonus, &email, &order_id).await?;
+
+    sqlx::query(r#"DELETE FROM pending_gifts WHERE order_id = ?"#)
+        .bind(payment_order_id)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(Some(Finalized::Gift { order_id, email, code, value_cents: total_value }))
+}
+
+/// "Friday, 18 October at 12:15" (or the German/Vietnamese form) in restaurant time.
+fn pickup_label(state: &AppState, pickup_at: &str, text: &OrderEmailText) -> Option<String> {
+    let at = chrono::DateTime::parse_from_rfc3339(pickup_at).ok()?;
+    Some(at.with_timezone(&state.schedule.timezone).format(text.pickup_format).to_string())
+}
+
+async fn send_confirmation(state: &AppState, finalized: Finalized) {
+    match finalized {
+        Finalized::Order { order_id, email, items, total_cents, tracking_token, pickup_at, locale } => {
+            if email.is_empty() {
+                return;
+            }
+            let text = OrderEmailText::for_locale(locale);
+            let translated = translations::for_locale(&state.pool, locale).await.unwrap_or_else(|e| {
+                tracing::warn!("Could not load {} product names for the confirmation of {}: {:?}", locale.as_str(), order_id, e);
+                Default::default()
+            });
+            let mut items_html = String::new();
+            for line in &items.cart {
+                let options = if line.options.is_empty() {
+                    String::new()
+                } else {
+                    format!("<br><small style=\"color: #999;\">{}</small>", html_escape(&options_label(&line.options)))
+                };
+                items_html.push_str(&format!(
+                    "<tr><td>{}{}</td><td>{}</td><td>€{:.2}</td><td>€{:.2}</td></tr>",
+                    html_escape(translated.get(&line.product_id).and_then(|t| t.name.as_deref()).or(line.name.as_deref()).unwrap_or("Product")),
+                    options,
+                    line.quantity,
+                    line.unit_amount as f64 / 100.0,
+                    line.line_total_cents() as f64 / 100.0
+                ));
+            }
+            let html_body = order_confirmation_html(
+                locale,
+                &order_id,
+                &email,
+                &items_html,
+                items.subtotal_cents() as f64 / 100.0,
+                items.discount_cents as f64 / 100.0,
+                total_cents as f64 / 100.0,
+                pickup_at.as_deref().and_then(|at| pickup_label(state, at, text)).as_deref(),
+                &tracking::order_url(&state.app_url, &order_id, Some(&tracking_token)),
+            );
+            match send_html_email(state, &email, text.subject, &html_body).await {
+                Ok(_) => tracing::info!("Order confirmation email (HTML) sent successfully to {}", email),
+                Err(e) => tracing::error!("Failed to send order confirmation email to {}: {:?}", email, e),
+            }
+        }
+        Finalized::Gift { email, code, value_cents, .. } => {
+            if email.is_empty() {
+                return;
+            }
+            let html_body = gift_coupon_html(&code, value_cents as f64 / 100.0, &email, &state.app_url);
+            match send_html_email(state, &email, "Your Gift Coupon", &html_body).await {
+                Ok(_) => tracing::info!("Gift coupon email (HTML) sent successfully to {}", email),
+                Err(e) => tracing::error!("Failed to send gift coupon email to {}: {:?}", email, e),
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use serde::Deserialize;
+
+use crate::pricing::SelectedOption;
+
+pub mod finalize;
+pub mod refund;
+pub mod status;
+pub mod feed;
+pub mod tracking;
+
+/// A cart line as stored in `pending_orders.items_json` / `orders.items_json`.
+///
+/// Current rows are written from `pricing::PricedLine` (camelCase); older rows
+/// used snake_case keys, so both spellings are accepted.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredLine {
+    #[serde(alias = "product_id")]
+    pub product_id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(alias = "unit_amount")]
+    pub unit_amount: i64,
+    #[serde(default = "default_quantity")]
+    pub quantity: i64,
+    #[serde(default)]
+    pub options: Vec<SelectedOption>,
+}
+
+fn default_quantity() -> i64 { 1 }
+
+impl StoredLine {
+    pub fn line_total_cents(&self) -> i64 {
+        self.unit_amount * self.quantity
+    }
+}
+
+/// The `items_json` document of a pending or finalized order.
+#[derive(Deserialize, Default, Debug)]
+pub struct StoredItems {
+    #[serde(default)]
+    pub cart: Vec<StoredLine>,
+    pub coupon_code: Option<String>,
+    #[serde(default)]
+    pub discount_cents: i64,
+    /// `coupon` or `gift_code`; missing on rows written before pricing recorded it.
+    pub discount_kind: Option<String>,
+    /// Set on gift coupon purchases: the code that was issued.
+    #[serde(rename = "code")]
+    pub gift_code: Option<String>,
+}
+
+impl StoredItems {
+    /// Parse leniently: malformed JSON or lines yield an empty document rather than an error.
+    pub fn parse(items_json: &str) -> Self {
+        serde_json::from_str(items_json).unwrap_or_default()
+    }
+
+    pub fn subtotal_cents(&self) -> i64 {
+        self.cart.iter().map(StoredLine::line_total_cents).sum()
+    }
+}
@@ -0,0 +1,672 @@
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+use crate::coupons::redemptions;
+use crate::gift_cards;
+use crate::email::{refund_notice_html, send_html_email};
+use crate::schedule::db_timestamp;
+use crate::state::AppState;
+
+use super::StoredItems;
+use super::status::{pays_at_pickup, transition, OrderStatus, StatusError};
+
+/// A line to refund, addressed by `order_items.id`.
+#[derive(Deserialize, Debug)]
+pub struct RefundLine {
+    pub order_item_id: String,
+    pub quantity: i64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RefundRequest {
+    /// Lines to refund; omit to refund whatever is left of the order.
+    #[serde(default)]
+    pub items: Option<Vec<RefundLine>>,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RefundOutcome {
+    pub refund_id: String,
+    pub order_id: String,
+    pub amount_cents: i64,
+    pub refunded_total_cents: i64,
+    pub order_status: OrderStatus,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RefundError {
+    #[error("order {0} not found")]
+    OrderNotFound(String),
+    #[error("order is {0} and cannot be refunded")]
+    NotRefundable(String),
+    #[error("order was not paid online")]
+    NoCapture,
+    /// The order was paid through a provider this server is not running.
+    #[error("order was paid through {0}, which is not available")]
+    ProviderUnavailable(String),
+    #[error("order item {0} is not part of this order")]
+    UnknownItem(String),
+    #[error("cannot refund {requested} of order item {order_item_id}, only {remaining} left")]
+    InvalidQuantity { order_item_id: String, requested: i64, remaining: i64 },
+    #[error("nothing left to refund")]
+    NothingToRefund,
+    #[error("another refund of this order is in progress, or the order changed meanwhile")]
+    Conflict,
+    #[error("payment provider error: {0}")]
+    Provider(anyhow::Error),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for RefundError {
+    fn into_response(self) -> Response {
+        let (status, code) = match &self {
+            RefundError::OrderNotFound(_) => (StatusCode::NOT_FOUND, "order_not_found"),
+            RefundError::NotRefundable(_) | RefundError::NoCapture | RefundError::NothingToRefund => (StatusCode::CONFLICT, "not_refundable"),
+            RefundError::Conflict => (StatusCode::CONFLICT, "refund_conflict"),
+            RefundError::ProviderUnavailable(_) => (StatusCode::SERVICE_UNAVAILABLE, "provider_unavailable"),
+            RefundError::UnknownItem(_) | RefundError::InvalidQuantity { .. } => (StatusCode::BAD_REQUEST, "invalid_items"),
+            RefundError::Provider(e) => {
+                tracing::error!("Refund rejected by payment provider: {:?}", e);
+                (StatusCode::BAD_GATEWAY, "refund_failed")
+            }
+            RefundError::Database(e) => {
+                tracing::error!("Refund failed with database error: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            }
+        };
+        (status, Json(serde_json::json!({ "error": code, "message": self.to_string() }))).into_response()
+    }
+}
+
+struct ItemRow {
+    id: String,
+    unit_amount: i64,
+    remaining: i64,
+}
+
+/// Refund a whole order or some of its lines through the provider that took the payment.
+///
+/// Line refunds are scaled by the discount the order received, so refunding every
+/// line adds up to what was paid. Once an order is fully refunded the coupon use or
+/// gift-code balance it consumed is given back.
+pub async fn refund_order(state: &AppState, order_id: &str, request: &RefundRequest, requested_by: &str) -> Result<RefundOutcome, RefundError> {
+    // Earlier refunds whose booking broke off must not block or be counted twice
+    reconcile_claims(state, Some(order_id)).await?;
+    let order = sqlx::query(r#"SELECT email, total_cents, currency, status, coupon_code, items_json, payment_provider, payment_capture_id FROM orders WHERE id = ?"#)
+        .bind(order_id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| RefundError::OrderNotFound(order_id.to_string()))?;
+
+    let status: String = order.try_get("status").unwrap_or_default();
+    let recorded_provider: Option<String> = order.try_get("payment_provider").ok().flatten();
+    let pay_at_pickup = pays_at_pickup(recorded_provider.as_deref());
+    let current_status = OrderStatus::parse(&status).filter(|s| s.is_refundable(pay_at_pickup)).ok_or(RefundError::NotRefundable(status))?;
+    let capture_id: String = order.try_get::<Option<String>, _>("payment_capture_id").ok().flatten().ok_or(RefundError::NoCapture)?;
+    // Orders from before providers were recorded were all paid through PayPal
+    let provider_name = recorded_provider.unwrap_or_else(|| "paypal".into());
+    let Some(provider) = state.payments.get(&provider_name) else {
+        return Err(RefundError::ProviderUnavailable(provider_name));
+    };
+    let email: String = order.try_get::<Option<String>, _>("email").ok().flatten().unwrap_or_default();
+    let total_cents: i64 = order.try_get("total_cents").unwrap_or(0);
+    let currency: String = order.try_get("currency").unwrap_or_else(|_| "EUR".into());
+    let items = StoredItems::parse(&order.try_get::<Option<String>, _>("items_json").ok().flatten().unwrap_or_default());
+
+    // Refunds still in flight are caught by the claim below
+    let already_refunded: i64 = sqlx::query_scalar(r#"SELECT COALESCE(SUM(amount_cents), 0) FROM refunds WHERE order_id = ? AND NOT pending"#)
+        .bind(order_id)
+        .fetch_one(&state.pool)
+        .await?;
+    let remaining_cents = total_cents - already_refunded;
+    if remaining_cents <= 0 {
+        return Err(RefundError::NothingToRefund);
+    }
+
+    let item_rows: Vec<ItemRow> = sqlx::query(r#"SELECT id, unit_amount, quantity - refunded_quantity AS remaining FROM order_items WHERE order_id = ?"#)
+        .bind(order_id)
+        .fetch_all(&state.pool)
+        .await?
+        .into_iter()
+        .map(|r| ItemRow {
+            id: r.try_get("id").unwrap_or_default(),
+            unit_amount: r.try_get("unit_amount").unwrap_or(0),
+            remaining: r.try_get("remaining").unwrap_or(0),
+        })
+        .collect();
+
+    // (order_item_id, quantity) pairs to mark as refunded
+    let mut refunded_lines: Vec<(String, i64)> = Vec::new();
+    let amount_cents = match &request.items {
+        None => {
+            refunded_lines.extend(item_rows.iter().filter(|i| i.remaining > 0).map(|i| (i.id.clone(), i.remaining)));
+            remaining_cents
+        }
+        Some(lines) => {
+            let mut gross = 0;
+            for line in lines {
+                let item = item_rows.iter().find(|i| i.id == line.order_item_id)
+                    .ok_or_else(|| RefundError::UnknownItem(line.order_item_id.clone()))?;
+                let already = refunded_lines.iter().filter(|(id, _)| *id == item.id).map(|(_, q)| q).sum::<i64>();
+                if line.quantity <= 0 || line.quantity + already > item.remaining {
+                    return Err(RefundError::InvalidQuantity {
+                        order_item_id: item.id.clone(),
+                        requested: line.quantity,
+                        remaining: item.remaining - already,
+                    });
+                }
+                gross += item.unit_amount * line.quantity;
+                refunded_lines.push((item.id.clone(), line.quantity));
+            }
+            let subtotal = items.subtotal_cents();
+            let net = if subtotal > 0 { (gross as f64 * total_cents as f64 / subtotal as f64).round() as i64 } else { gross };
+            net.min(remaining_cents)
+        }
+    };
+    if amount_cents <= 0 {
+        return Err(RefundError::NothingToRefund);
+    }
+
+    let refund_id = Uuid::new_v4().to_string();
+    let lines_json = serde_json::to_string(&refunded_lines.iter().map(|(id, q)| serde_json::json!({"order_item_id": id, "quantity": q})).collect::<Vec<_>>()).unwrap_or_default();
+    claim(state, &refund_id, order_id, amount_cents, &currency, request.reason.as_deref(), &provider_name, &lines_json, requested_by).await?;
+
+    let refund = match provider.refund(&capture_id, amount_cents, &currency, request.reason.as_deref()).await {
+        Ok(refund) => refund,
+        Err(e) => {
+            if let Err(db) = sqlx::query(r#"DELETE FROM refunds WHERE id = ? AND pending"#).bind(&refund_id).execute(&state.pool).await {
+                tracing::error!("Could not release refund claim {} of order {}: {:?}", refund_id, order_id, db);
+            }
+            return Err(RefundError::Provider(e));
+        }
+    };
+
+    // The money has moved at this point. Keep the provider's refund id on the
+    // claim first, so `reconcile_claims` can finish the booking if it fails.
+    if let Err(e) = sqlx::query(r#"UPDATE refunds SET amount_cents = ?, provider_refund_id = ?, provider_status = ? WHERE id = ? AND pending"#)
+        .bind(refund.amount_cents)
+        .bind(&refund.refund_id)
+        .bind(refund.status.as_str())
+        .bind(&refund_id)
+        .execute(&state.pool)
+        .await
+    {
+        tracing::error!("Could not note provider refund {} on claim {}: {:?}", refund.refund_id, refund_id, e);
+    }
+    let mut tx = state.pool.begin().await?;
+    let record = RefundRecord {
+        claim_id: Some(&refund_id),
+        amount_cents: refund.amount_cents,
+        provider_refund_id: &refund.refund_id,
+        provider_status: refund.status.as_str(),
+        reason: request.reason.as_deref(),
+        lines: &refunded_lines,
+        actor: requested_by,
+    };
+    let (refunded_total_cents, fully_refunded) = match record_refund(&mut tx, order_id, record).await? {
+        Recorded::Applied { refunded_total_cents, fully_refunded } => (refunded_total_cents, fully_refunded),
+        // `reconcile_claims` booked the claim first
+        Recorded::Duplicate => refunded_so_far(&mut tx, order_id).await?,
+        Recorded::ClaimPending => {
+            // Only refunds without a claim can be skipped; this one holds the claim
+            tracing::error!("Refund {} of order {} was not booked", refund.refund_id, order_id);
+            return Err(RefundError::Conflict);
+        }
+    };
+    tx.commit().await?;
+    // A partial refund leaves the kitchen status alone; only a full one ends the order
+    let order_status = if fully_refunded { OrderStatus::Refunded } else { current_status };
+    if fully_refunded {
+        super::feed::publish(state, order_id).await;
+    }
+
+    tracing::info!("Refunded {}¢ of order {} ({}), requested by {}", amount_cents, order_id, order_status, requested_by);
+
+    if !email.is_empty() {
+        let html_body = refund_notice_html(
+            order_id,
+            amount_cents as f64 / 100.0,
+            request.reason.as_deref(),
+            fully_refunded,
+            &super::tracking::order_url_for(state, order_id).await,
+        );
+        if let Err(e) = send_html_email(state, &email, "Your Refund", &html_body).await {
+            tracing::error!("Failed to send refund email to {}: {:?}", email, e);
+        }
+    }
+
+    Ok(RefundOutcome {
+        refund_id,
+        order_id: order_id.to_string(),
+        amount_cents,
+        refunded_total_cents,
+        order_status,
+    })
+}
+
+/// A refund the provider has confirmed, to be booked against its order.
+pub struct RefundRecord<'a> {
+    /// The pending row written by `refund_order`; `None` for refunds made
+    /// directly at the provider and reported by webhook.
+    pub claim_id: Option<&'a str>,
+    pub amount_cents: i64,
+    pub provider_refund_id: &'a str,
+    pub provider_status: &'a str,
+    pub reason: Option<&'a str>,
+    /// `(order_item_id, quantity)` pairs given back; empty when unknown.
+    pub lines: &'a [(String, i64)],
+    pub actor: &'a str,
+}
+
+/// What `record_refund` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recorded {
+    Applied { refunded_total_cents: i64, fully_refunded: bool },
+    /// The provider refund, or the claim, is already booked.
+    Duplicate,
+    /// A refund of the order is still being sent by `refund_order` and may be
+    /// this very one; try again once it is finished.
+    ClaimPending,
+}
+
+/// Book a confirmed refund: the `refunds` row, the refunded lines and, once
+/// the order is fully refunded, its `refunded` status, the coupon use or
+/// gift-code balance it consumed and the gift card it bought.
+///
+/// Shared by `refund_order` and the PayPal refund webhook so both leave an
+/// order in the same state. Call it inside a transaction.
+pub async fn record_refund(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, order_id: &str, refund: RefundRecord<'_>) -> Result<Recorded, sqlx::Error> {
+    match refund.claim_id {
+        Some(claim_id) => {
+            let booked = sqlx::query(r#"UPDATE refunds SET pending = 0, amount_cents = ?, provider_refund_id = ?, provider_status = ? WHERE id = ? AND pending"#)
+                .bind(refund.amount_cents)
+                .bind(refund.provider_refund_id)
+                .bind(refund.provider_status)
+                .bind(claim_id)
+                .execute(&mut **tx)
+                .await?
+                .rows_affected();
+            if booked == 0 {
+                return Ok(Recorded::Duplicate);
+            }
+        }
+        None => {
+            let inserted = sqlx::query(
+                r#"INSERT OR IGNORE INTO refunds (id, order_id, amount_cents, reason, provider, provider_refund_id, provider_status, created_by)
+                   SELECT ?1, ?2, ?3, ?4, (SELECT payment_provider FROM orders WHERE id = ?2), ?5, ?6, ?7
+                   WHERE NOT EXISTS (SELECT 1 FROM refunds WHERE order_id = ?2 AND pending)"#,
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(order_id)
+            .bind(refund.amount_cents)
+            .bind(refund.reason)
+            .bind(refund.provider_refund_id)
+            .bind(refund.provider_status)
+            .bind(refund.actor)
+            .execute(&mut **tx)
+            .await?
+            .rows_affected();
+            if inserted == 0 {
+                let known: bool = sqlx::query_scalar(r#"SELECT EXISTS (SELECT 1 FROM refunds WHERE provider_refund_id = ?)"#)
+                    .bind(refund.provider_refund_id)
+                    .fetch_one(&mut **tx)
+                    .await?;
+                return Ok(if known { Recorded::Duplicate } else { Recorded::ClaimPending });
+            }
+        }
+    }
+
+    for (item_id, quantity) in refund.lines {
+        sqlx::query(r#"UPDATE order_items SET refunded_quantity = refunded_quantity + ? WHERE id = ?"#)
+            .bind(quantity)
+            .bind(item_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+
+    let (refunded_total_cents, fully_refunded) = refunded_so_far(tx, order_id).await?;
+    if fully_refunded {
+        let order = sqlx::query(r#"SELECT email, items_json FROM orders WHERE id = ?"#)
+            .bind(order_id)
+            .fetch_one(&mut **tx)
+            .await?;
+        // Lines a provider-side refund did not name are gone as well
+        sqlx::query(r#"UPDATE order_items SET refunded_quantity = quantity WHERE order_id = ?"#)
+            .bind(order_id)
+            .execute(&mut **tx)
+            .await?;
+        match transition(tx, order_id, OrderStatus::Refunded, refund.actor, refund.reason).await {
+            Ok(_) => {}
+            Err(StatusError::Database(e)) => return Err(e),
+            // Someone changed the order meanwhile; the refund itself still has to be recorded
+            Err(e) => tracing::warn!("Refund of order {} recorded without status change: {}", order_id, e),
+        }
+        let email: String = order.try_get::<Option<String>, _>("email")?.unwrap_or_default();
+        let items = StoredItems::parse(&order.try_get::<Option<String>, _>("items_json")?.unwrap_or_default());
+        restore_discount(tx, order_id, &items, &email, refund.actor).await?;
+        if let Some(code) = items.gift_code.as_deref() {
+            // Refunded gift purchase: the code it created must not stay spendable
+            gift_cards::void(tx, code, order_id, refund.actor).await?;
+        }
+    }
+    Ok(Recorded::Applied { refunded_total_cents, fully_refunded })
+}
+
+/// Booked refunds of an order so far, and whether they cover its total.
+async fn refunded_so_far(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, order_id: &str) -> Result<(i64, bool), sqlx::Error> {
+    let row = sqlx::query(
+        r#"SELECT total_cents, (SELECT COALESCE(SUM(amount_cents), 0) FROM refunds WHERE order_id = orders.id AND NOT pending) AS refunded_cents
+           FROM orders WHERE id = ?"#,
+    )
+    .bind(order_id)
+    .fetch_one(&mut **tx)
+    .await?;
+    let refunded_cents: i64 = row.try_get("refunded_cents")?;
+    Ok((refunded_cents, refunded_cents >= row.try_get::<i64, _>("total_cents")?))
+}
+
+/// Minutes after which a claim the provider never confirmed is dropped. The
+/// provider call gives up long before; if the refund went through anyway,
+/// its webhook books it like a refund made at the provider.
+pub const CLAIM_EXPIRY_MINUTES: i64 = 10;
+
+/// Tidy up claims left behind by `refund_order` (for one order, or all):
+/// book those the provider confirmed but that were never booked, and drop
+/// those the provider never confirmed once they expire. Returns how many
+/// claims were settled.
+pub async fn reconcile_claims(state: &AppState, order_id: Option<&str>) -> Result<u64, sqlx::Error> {
+    let sent = sqlx::query(
+        r#"SELECT id, order_id, amount_cents, reason, provider_refund_id, provider_status, items_json, created_by
+           FROM refunds WHERE pending AND provider_refund_id IS NOT NULL AND (?1 IS NULL OR order_id = ?1)"#,
+    )
+    .bind(order_id)
+    .fetch_all(&state.pool)
+    .await?;
+    let mut settled = 0;
+    for row in sent {
+        let claim_id: String = row.try_get("id")?;
+        let claim_order: String = row.try_get("order_id")?;
+        let provider_refund_id: String = row.try_get("provider_refund_id")?;
+        let provider_status: String = row.try_get::<Option<String>, _>("provider_status")?.unwrap_or_default();
+        let reason: Option<String> = row.try_get("reason")?;
+        let actor: String = row.try_get::<Option<String>, _>("created_by")?.unwrap_or_else(|| "system".into());
+        let lines: Vec<(String, i64)> = serde_json::from_str::<Vec<serde_json::Value>>(&row.try_get::<Option<String>, _>("items_json")?.unwrap_or_default())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|l| Some((l.get("order_item_id")?.as_str()?.to_string(), l.get("quantity")?.as_i64()?)))
+            .collect();
+
+        let mut tx = state.pool.begin().await?;
+        let record = RefundRecord {
+            claim_id: Some(&claim_id),
+            amount_cents: row.try_get("amount_cents")?,
+            provider_refund_id: &provider_refund_id,
+            provider_status: &provider_status,
+            reason: reason.as_deref(),
+            lines: &lines,
+            actor: &actor,
+        };
+        let recorded = record_refund(&mut tx, &claim_order, record).await?;
+        tx.commit().await?;
+        if let Recorded::Applied { fully_refunded, .. } = recorded {
+            tracing::warn!("Booked refund {} of order {} left unfinished by an earlier request", provider_refund_id, claim_order);
+            if fully_refunded {
+                super::feed::publish(state, &claim_order).await;
+            }
+            settled += 1;
+        }
+    }
+
+    let expired = sqlx::query(
+        r#"DELETE FROM refunds WHERE pending AND provider_refund_id IS NULL AND created_at < ?1 AND (?2 IS NULL OR order_id = ?2)"#,
+    )
+    .bind(db_timestamp(Utc::now() - Duration::minutes(CLAIM_EXPIRY_MINUTES)))
+    .bind(order_id)
+    .execute(&state.pool)
+    .await?
+    .rows_affected();
+    if expired > 0 {
+        tracing::warn!("Dropped {} refund claim(s) the provider never confirmed", expired);
+    }
+    Ok(settled + expired)
+}
+
+/// Write the refund as pending before it is sent to the provider.
+///
+/// The insert only goes through while no other refund of the order is pending,
+/// `amount_cents` fits into what is left of the order and every line still has
+/// the quantity to refund, so concurrent refunds of one order are serialized
+/// here rather than both reaching the provider.
+#[allow(clippy::too_many_arguments)]
+async fn claim(
+    state: &AppState,
+    refund_id: &str,
+    order_id: &str,
+    amount_cents: i64,
+    currency: &str,
+    reason: Option<&str>,
+    provider: &str,
+    lines_json: &str,
+    requested_by: &str,
+) -> Result<(), RefundError> {
+    let claimed = sqlx::query(
+        r#"INSERT INTO refunds (id, order_id, amount_cents, currency, reason, provider, items_json, created_by, pending)
+           SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 1
+           WHERE NOT EXISTS (SELECT 1 FROM refunds WHERE order_id = ?2 AND pending)
+             AND (SELECT total_cents FROM orders WHERE id = ?2)
+                 - (SELECT COALESCE(SUM(amount_cents), 0) FROM refunds WHERE order_id = ?2) >= ?3
+             AND NOT EXISTS (
+                 SELECT 1 FROM (SELECT json_extract(value, '$.order_item_id') AS item_id, SUM(json_extract(value, '$.quantity')) AS quantity
+                                FROM json_each(?7) GROUP BY item_id) l
+                 LEFT JOIN order_items oi ON oi.id = l.item_id AND oi.order_id = ?2
+                 WHERE oi.id IS NULL OR oi.quantity - oi.refunded_quantity < l.quantity)"#,
+    )
+    .bind(refund_id)
+    .bind(order_id)
+    .bind(amount_cents)
+    .bind(currency)
+    .bind(reason)
+    .bind(provider)
+    .bind(lines_json)
+    .bind(requested_by)
+    .execute(&state.pool)
+    .await?
+    .rows_affected();
+    if claimed == 0 {
+        return Err(RefundError::Conflict);
+    }
+    Ok(())
+}
+
+/// Give back the coupon use or gift-code balance the order consumed.
+async fn restore_discount(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, order_id: &str, items: &StoredItems, email: &str, refunded_by: &str) -> Result<(), sqlx::Error> {
+    let Some(code) = items.coupon_code.as_deref() else { return Ok(()) };
+    let kind = items.discount_kind.as_deref();
+
+    if kind != Some("gift_code") {
+        sqlx::query(r#"UPDATE coupons SET remaining_uses = remaining_uses + 1 WHERE code = ?"#)
+            .bind(code.to_uppercase())
+            .execute(&mut **tx)
+            .await?;
+        redemptions::reverse(tx, order_id).await?;
+    }
+    if kind != Some("coupon") && items.discount_cents > 0 {
+        if kind == Some("gift_code") {
+            gift_cards::reopen(tx, code, items.discount_cents, email).await?;
+        }
+        gift_cards::refund(tx, code, items.discount_cents, order_id, refunded_by).await?;
+    }
+    Ok(())
+}
+
+/// Refunds already issued for an order, newest first.
+pub async fn list_refunds(state: &AppState, order_id: &str) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query(r#"SELECT id, amount_cents, currency, reason, provider, provider_refund_id, provider_status, items_json, created_by, created_at, pending FROM refunds WHERE order_id = ? ORDER BY created_at DESC"#)
+        .bind(order_id)
+        .fetch_all(&state.pool)
+        .await?;
+    Ok(rows.into_iter().map(|r| {
+        let items_json: String = r.try_get::<Option<String>, _>("items_json").ok().flatten().unwrap_or_default();
+        serde_json::json!({
+            "id": r.try_get::<String, _>("id").unwrap_or_default(),
+            "amount_cents": r.try_get::<i64, _>("amount_cents").unwrap_or(0),
+            "currency": r.try_get::<String, _>("currency").unwrap_or_default(),
+            "reason": r.try_get::<Option<String>, _>("reason").ok().flatten(),
+            "provider": r.try_get::<Option<String>, _>("provider").ok().flatten(),
+            "provider_refund_id": r.try_get::<Option<String>, _>("provider_refund_id").ok().flatten(),
+            "provider_status": r.try_get::<Option<String>, _>("provider_status").ok().flatten(),
+            "items": serde_json::from_str::<serde_json::Value>(&items_json).unwrap_or(serde_json::json!([])),
+            "created_by": r.try_get::<Option<String>, _>("created_by").ok().flatten(),
+            "created_at": r.try_get::<String, _>("created_at").unwrap_or_default(),
+            "pending": r.try_get::<bool, _>("pending").unwrap_or(false),
+        })
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{add_coupon, add_gift_card, add_product, mock_state, place_order};
+
+    async fn item_id(state: &AppState, order_id: &str, product_id: &str) -> String {
+        sqlx::query_scalar(r#"SELECT id FROM order_items WHERE order_id = ? AND product_id = ?"#)
+            .bind(order_id)
+            .bind(product_id)
+            .fetch_one(&state.pool)
+            .await
+            .unwrap()
+    }
+
+    fn lines(items: &[(&str, i64)]) -> RefundRequest {
+        let items = items.iter().map(|(id, quantity)| RefundLine { order_item_id: id.to_string(), quantity: *quantity }).collect();
+        RefundRequest { items: Some(items), reason: None }
+    }
+
+    async fn shop(state: &AppState) {
+        add_product(&state.pool, "pho", 1250).await;
+        add_product(&state.pool, "rolls", 335).await;
+        add_coupon(&state.pool, "TEN", 10, 5).await;
+    }
+
+    #[tokio::test]
+    async fn line_refunds_add_up_to_what_was_paid() {
+        let state = mock_state().await;
+        shop(&state).await;
+        let order_id = place_order(&state, &[("pho", 1), ("rolls", 2)], Some("TEN")).await;
+        let rolls = item_id(&state, &order_id, "rolls").await;
+
+        // 335¢ less the order's 10% discount
+        let first = refund_order(&state, &order_id, &lines(&[(&rolls, 1)]), "admin@example.com").await.unwrap();
+        assert_eq!((first.amount_cents, first.refunded_total_cents, first.order_status), (302, 302, OrderStatus::Paid));
+        assert!(matches!(
+            refund_order(&state, &order_id, &lines(&[(&rolls, 2)]), "admin@example.com").await,
+            Err(RefundError::InvalidQuantity { remaining: 1, .. })
+        ));
+
+        let rest = refund_order(&state, &order_id, &RefundRequest { items: None, reason: None }, "admin@example.com").await.unwrap();
+        assert_eq!((rest.amount_cents, rest.refunded_total_cents, rest.order_status), (1426, 1728, OrderStatus::Refunded));
+        let uses: i64 = sqlx::query_scalar(r#"SELECT remaining_uses FROM coupons WHERE code = 'TEN'"#).fetch_one(&state.pool).await.unwrap();
+        assert_eq!(uses, 5, "a fully refunded order gives its coupon use back");
+        assert!(matches!(
+            refund_order(&state, &order_id, &RefundRequest { items: None, reason: None }, "admin@example.com").await,
+            Err(RefundError::NotRefundable(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn refund_goes_through_the_provider_of_the_order() {
+        let state = mock_state().await;
+        shop(&state).await;
+        let order_id = place_order(&state, &[("pho", 1)], None).await;
+        sqlx::query(r#"UPDATE orders SET payment_provider = 'paypal' WHERE id = ?"#).bind(&order_id).execute(&state.pool).await.unwrap();
+
+        let result = refund_order(&state, &order_id, &RefundRequest { items: None, reason: None }, "admin@example.com").await;
+        assert!(matches!(result, Err(RefundError::ProviderUnavailable(ref p)) if p == "paypal"));
+        let refunds: i64 = sqlx::query_scalar(r#"SELECT COUNT(*) FROM refunds"#).fetch_one(&state.pool).await.unwrap();
+        assert_eq!(refunds, 0);
+    }
+
+    async fn insert_claim(state: &AppState, order_id: &str, provider_refund_id: Option<&str>, minutes_ago: i64) {
+        sqlx::query(
+            r#"INSERT INTO refunds (id, order_id, amount_cents, provider, provider_refund_id, provider_status, items_json, created_by, pending, created_at)
+               VALUES ('claim-1', ?, 1250, 'mock', ?, 'completed', '[]', 'admin@example.com', 1, ?)"#,
+        )
+        .bind(order_id)
+        .bind(provider_refund_id)
+        .bind(db_timestamp(Utc::now() - Duration::minutes(minutes_ago)))
+        .execute(&state.pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn confirmed_claim_is_booked_later() {
+        let state = mock_state().await;
+        shop(&state).await;
+        let order_id = place_order(&state, &[("pho", 1)], None).await;
+        // The provider refunded, then the booking broke off
+        insert_claim(&state, &order_id, Some("MOCK-REFUND-9"), 0).await;
+
+        assert!(matches!(
+            refund_order(&state, &order_id, &RefundRequest { items: None, reason: None }, "admin@example.com").await,
+            Err(RefundError::NotRefundable(_))
+        ));
+        let (status, pending): (String, i64) = sqlx::query_as(r#"SELECT o.status, (SELECT COUNT(*) FROM refunds WHERE pending) FROM orders o WHERE o.id = ?"#)
+            .bind(&order_id)
+            .fetch_one(&state.pool)
+            .await
+            .unwrap();
+        assert_eq!((status.as_str(), pending), ("refunded", 0));
+        assert_eq!(reconcile_claims(&state, None).await.unwrap(), 0, "nothing is booked twice");
+    }
+
+    #[tokio::test]
+    async fn unconfirmed_claim_blocks_until_it_expires() {
+        let state = mock_state().await;
+        shop(&state).await;
+        let order_id = place_order(&state, &[("pho", 1)], None).await;
+        let whole = RefundRequest { items: None, reason: None };
+
+        insert_claim(&state, &order_id, None, 1).await;
+        assert!(matches!(refund_order(&state, &order_id, &whole, "admin@example.com").await, Err(RefundError::Conflict)));
+
+        sqlx::query(r#"DELETE FROM refunds"#).execute(&state.pool).await.unwrap();
+        insert_claim(&state, &order_id, None, CLAIM_EXPIRY_MINUTES + 1).await;
+        let outcome = refund_order(&state, &order_id, &whole, "admin@example.com").await.unwrap();
+        assert_eq!((outcome.amount_cents, outcome.order_status), (1250, OrderStatus::Refunded));
+    }
+
+    #[tokio::test]
+    async fn full_refund_gives_the_gift_code_balance_back() {
+        let state = mock_state().await;
+        shop(&state).await;
+        add_gift_card(&state.pool, "GIFT", 1000).await;
+        let order_id = place_order(&state, &[("pho", 1)], Some("GIFT")).await;
+        let balance = || sqlx::query_scalar::<_, i64>(r#"SELECT remaining_cents FROM gift_codes WHERE code = 'GIFT'"#).fetch_one(&state.pool);
+        assert_eq!(balance().await.unwrap(), 0);
+
+        let outcome = refund_order(&state, &order_id, &RefundRequest { items: None, reason: Some("kitchen closed".into()) }, "admin@example.com").await.unwrap();
+        assert_eq!((outcome.amount_cents, outcome.order_status), (250, OrderStatus::Refunded));
+        assert_eq!(balance().await.unwrap(), 1000);
+
+        let listed = list_refunds(&state, &order_id).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!((listed[0]["amount_cents"].as_i64(), listed[0]["reason"].as_str(), listed[0]["pending"].as_bool()), (Some(250), Some("kitchen closed"), Some(false)));
+    }
+
+    #[tokio::test]
+    async fn pay_at_pickup_orders_are_not_refunded() {
+        let state = mock_state().await;
+        shop(&state).await;
+        let order_id = place_order(&state, &[("pho", 1)], None).await;
+        sqlx::query(r#"UPDATE orders SET payment_provider = 'pickup', status = 'picked_up' WHERE id = ?"#).bind(&order_id).execute(&state.pool).await.unwrap();
+
+        let result = refund_order(&state, &order_id, &RefundRequest { items: None, reason: None }, "admin@example.com").await;
+        assert!(matches!(result, Err(RefundError::NotRefundable(ref s)) if s == "picked_up"));
+    }
+}
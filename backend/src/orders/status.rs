@@ -0,0 +1,315 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection};
+use uuid::Uuid;
+
+use crate::catalog::stock;
+use crate::payments::pickup;
+
+/// Where an order is in its lifecycle. Stored as snake_case text in `orders.status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    /// Created without an online payment (pay at pickup, manual orders).
+    AwaitingPayment,
+    Paid,
+    /// Seen and accepted by the kitchen.
+    Accepted,
+    Preparing,
+    Ready,
+    PickedUp,
+    Cancelled,
+    /// Fully refunded. A partial refund leaves the status alone and only shows in `refunds`.
+    Refunded,
+}
+
+impl OrderStatus {
+    pub const ALL: [OrderStatus; 8] = [
+        OrderStatus::AwaitingPayment,
+        OrderStatus::Paid,
+        OrderStatus::Accepted,
+        OrderStatus::Preparing,
+        OrderStatus::Ready,
+        OrderStatus::PickedUp,
+        OrderStatus::Cancelled,
+        OrderStatus::Refunded,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OrderStatus::AwaitingPayment => "awaiting_payment",
+            OrderStatus::Paid => "paid",
+            OrderStatus::Accepted => "accepted",
+            OrderStatus::Preparing => "preparing",
+            OrderStatus::Ready => "ready",
+            OrderStatus::PickedUp => "picked_up",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Refunded => "refunded",
+        }
+    }
+
+    /// Parse a stored status. `completed` and `pending` are the pre-state-machine spellings.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "completed" => Some(OrderStatus::Paid),
+            "pending" => Some(OrderStatus::AwaitingPayment),
+            other => OrderStatus::ALL.into_iter().find(|st| st.as_str() == other),
+        }
+    }
+
+    /// The only place that decides which status changes are legal.
+    ///
+    /// Kitchen progress runs accepted → preparing → ready → picked_up. Only
+    /// pay-at-pickup orders (`pay_at_pickup`) enter the kitchen while still awaiting
+    /// payment; picking them up settles the bill. An online payment that is still
+    /// pending must be paid first. A full refund is reachable from anything that
+    /// took money online; partial refunds do not change the status. Money taken at
+    /// the counter is given back at the counter, so pay-at-pickup orders never refund.
+    pub fn can_transition_to(self, next: OrderStatus, pay_at_pickup: bool) -> bool {
+        use OrderStatus::*;
+        match (self, next) {
+            (AwaitingPayment, Accepted) => pay_at_pickup,
+            (Paid | Accepted | Preparing | Ready | PickedUp | Cancelled, Refunded) => !pay_at_pickup,
+            _ => matches!(
+                (self, next),
+                (AwaitingPayment, Paid | Cancelled)
+                    | (Paid, Accepted | Cancelled)
+                    | (Accepted, Preparing | Cancelled)
+                    | (Preparing, Ready | Cancelled)
+                    | (Ready, PickedUp)
+            ),
+        }
+    }
+
+    pub fn allowed_next(self, pay_at_pickup: bool) -> Vec<OrderStatus> {
+        OrderStatus::ALL.into_iter().filter(|s| self.can_transition_to(*s, pay_at_pickup)).collect()
+    }
+
+    /// Whether money was taken online for an order in this state and may be given back.
+    pub fn is_refundable(self, pay_at_pickup: bool) -> bool {
+        self.can_transition_to(OrderStatus::Refunded, pay_at_pickup)
+    }
+
+    /// Whether leaving this status for `cancelled` or `refunded` gives the order's
+    /// portions back: only while the kitchen has not started on them.
+    fn portions_untouched(self) -> bool {
+        matches!(self, OrderStatus::AwaitingPayment | OrderStatus::Paid | OrderStatus::Accepted)
+    }
+
+    /// Whether reaching this status means the customer has paid: online payments
+    /// when captured (or marked paid by staff), pay-at-pickup orders at hand-over.
+    fn settles_payment(self, pay_at_pickup: bool) -> bool {
+        self == OrderStatus::Paid || (pay_at_pickup && self == OrderStatus::PickedUp)
+    }
+}
+
+/// Whether an order with this `orders.payment_provider` is paid at the counter.
+pub fn pays_at_pickup(provider: Option<&str>) -> bool {
+    provider == Some(pickup::NAME)
+}
+
+impl std::fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StatusError {
+    #[error("order {0} not found")]
+    NotFound(String),
+    #[error("order has unknown status '{0}'")]
+    Unknown(String),
+    #[error("cannot move order from {from} to {to}")]
+    InvalidTransition { from: OrderStatus, to: OrderStatus },
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Record the status an order was created with.
+pub async fn record_initial(conn: &mut SqliteConnection, order_id: &str, status: OrderStatus, actor: &str) -> Result<(), sqlx::Error> {
+    insert_event(conn, order_id, None, status, actor, None).await
+}
+
+/// Move an order to `to`, enforcing `can_transition_to` and writing an
+/// `order_status_events` row. Stamps `orders.paid_at` when the move settles
+/// the payment, and gives the portions back when an order is cancelled or
+/// refunded before the kitchen started on it. Returns the previous status.
+///
+/// Pass a transaction when the change must commit together with other writes.
+pub async fn transition(conn: &mut SqliteConnection, order_id: &str, to: OrderStatus, actor: &str, note: Option<&str>) -> Result<OrderStatus, StatusError> {
+    let row = sqlx::query(r#"SELECT status, payment_provider FROM orders WHERE id = ?"#)
+        .bind(order_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| StatusError::NotFound(order_id.to_string()))?;
+    let current: String = row.try_get("status")?;
+    let provider: Option<String> = row.try_get("payment_provider")?;
+    let from = OrderStatus::parse(&current).ok_or_else(|| StatusError::Unknown(current.clone()))?;
+
+    let pay_at_pickup = pays_at_pickup(provider.as_deref());
+    if !from.can_transition_to(to, pay_at_pickup) {
+        return Err(StatusError::InvalidTransition { from, to });
+    }
+
+    // Guard on the status we read so two staff members cannot both advance the same step
+    let updated = sqlx::query(r#"UPDATE orders SET status = ? WHERE id = ? AND status = ?"#)
+        .bind(to.as_str())
+        .bind(order_id)
+        .bind(&current)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+    if updated == 0 {
+        return Err(StatusError::InvalidTransition { from, to });
+    }
+    if to.settles_payment(pay_at_pickup) {
+        sqlx::query(r#"UPDATE orders SET paid_at = COALESCE(paid_at, strftime('%Y-%m-%dT%H:%M:%fZ','now')) WHERE id = ?"#)
+            .bind(order_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    if matches!(to, OrderStatus::Cancelled | OrderStatus::Refunded) && from.portions_untouched() {
+        stock::release_order(conn, order_id).await?;
+    }
+
+    insert_event(conn, order_id, Some(from), to, actor, note).await?;
+    tracing::info!("Order {} moved from {} to {} by {}", order_id, from, to, actor);
+    Ok(from)
+}
+
+async fn insert_event(conn: &mut SqliteConnection, order_id: &str, from: Option<OrderStatus>, to: OrderStatus, actor: &str, note: Option<&str>) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"INSERT INTO order_status_events (id, order_id, from_status, to_status, actor, note) VALUES (?, ?, ?, ?, ?, ?)"#)
+        .bind(Uuid::new_v4().to_string())
+        .bind(order_id)
+        .bind(from.map(|s| s.as_str()))
+        .bind(to.as_str())
+        .bind(actor)
+        .bind(note)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+pub struct StatusEvent {
+    pub from_status: Option<String>,
+    pub to_status: String,
+    pub actor: String,
+    pub note: Option<String>,
+    pub created_at: String,
+}
+
+/// Status history of an order, oldest first.
+pub async fn history(conn: &mut SqliteConnection, order_id: &str) -> Result<Vec<StatusEvent>, sqlx::Error> {
+    let rows = sqlx::query(r#"SELECT from_status, to_status, actor, note, created_at FROM order_status_events WHERE order_id = ? ORDER BY created_at ASC, rowid ASC"#)
+        .bind(order_id)
+        .fetch_all(conn)
+        .await?;
+    Ok(rows.into_iter().map(|r| StatusEvent {
+        from_status: r.try_get("from_status").ok().flatten(),
+        to_status: r.try_get("to_status").unwrap_or_default(),
+        actor: r.try_get("actor").unwrap_or_default(),
+        note: r.try_get("note").ok().flatten(),
+        created_at: r.try_get("created_at").unwrap_or_default(),
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OrderStatus::{self, *};
+    use super::*;
+    use crate::test_support::mock_state;
+
+    #[test]
+    fn kitchen_path_runs_forward_only() {
+        let path = [Paid, Accepted, Preparing, Ready, PickedUp];
+        for pair in path.windows(2) {
+            assert!(pair[0].can_transition_to(pair[1], false), "{} -> {}", pair[0], pair[1]);
+            assert!(!pair[1].can_transition_to(pair[0], false), "{} -> {}", pair[1], pair[0]);
+        }
+        assert!(!Paid.can_transition_to(Preparing, false));
+        assert!(!Accepted.can_transition_to(Ready, false));
+    }
+
+    #[test]
+    fn only_pay_at_pickup_orders_are_accepted_before_payment() {
+        assert!(AwaitingPayment.can_transition_to(Accepted, true));
+        assert!(!AwaitingPayment.can_transition_to(Accepted, false));
+        assert!(AwaitingPayment.can_transition_to(Paid, false));
+        assert!(!AwaitingPayment.can_transition_to(Refunded, true));
+    }
+
+    #[test]
+    fn cancelling_stops_once_the_food_is_ready() {
+        for status in [AwaitingPayment, Paid, Accepted, Preparing] {
+            assert!(status.can_transition_to(Cancelled, false), "{}", status);
+        }
+        assert!(!Ready.can_transition_to(Cancelled, false));
+        assert!(!PickedUp.can_transition_to(Cancelled, false));
+    }
+
+    #[test]
+    fn full_refunds_are_reachable_from_anything_paid_online() {
+        for status in [Paid, Accepted, Preparing, Ready, PickedUp, Cancelled] {
+            assert!(status.is_refundable(false), "{}", status);
+            assert!(!status.is_refundable(true), "{}", status);
+        }
+        assert!(!AwaitingPayment.is_refundable(false));
+        assert!(!Refunded.is_refundable(false));
+    }
+
+    #[test]
+    fn terminal_states_have_no_next_step() {
+        assert!(Refunded.allowed_next(true).is_empty());
+        assert_eq!(Cancelled.allowed_next(false), vec![Refunded]);
+        assert_eq!(Ready.allowed_next(false), vec![PickedUp, Refunded]);
+    }
+
+    #[test]
+    fn parse_reads_stored_and_legacy_spellings() {
+        for status in OrderStatus::ALL {
+            assert_eq!(OrderStatus::parse(status.as_str()), Some(status));
+        }
+        assert_eq!(OrderStatus::parse("completed"), Some(Paid));
+        assert_eq!(OrderStatus::parse("pending"), Some(AwaitingPayment));
+        assert_eq!(OrderStatus::parse("shipped"), None);
+    }
+
+    #[tokio::test]
+    async fn transition_records_an_event_and_refuses_illegal_moves() {
+        let state = mock_state().await;
+        let mut conn = state.pool.acquire().await.unwrap();
+        sqlx::query(r#"INSERT INTO orders (id, total_cents, status, payment_provider) VALUES ('order-1', 1000, 'paid', 'mock')"#)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+
+        assert_eq!(transition(&mut conn, "order-1", Accepted, "cook@example.com", None).await.unwrap(), Paid);
+        let err = transition(&mut conn, "order-1", Ready, "cook@example.com", None).await.unwrap_err();
+        assert!(matches!(err, StatusError::InvalidTransition { from: Accepted, to: Ready }));
+
+        let events = history(&mut conn, "order-1").await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].from_status.as_deref(), events[0].to_status.as_str()), (Some("paid"), "accepted"));
+    }
+
+    #[tokio::test]
+    async fn pay_at_pickup_orders_are_paid_at_hand_over() {
+        let state = mock_state().await;
+        let mut conn = state.pool.acquire().await.unwrap();
+        sqlx::query(r#"INSERT INTO orders (id, total_cents, status, payment_provider) VALUES ('order-1', 1000, 'awaiting_payment', 'pickup')"#)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+
+        for to in [Accepted, Preparing, Ready] {
+            transition(&mut conn, "order-1", to, "cook@example.com", None).await.unwrap();
+        }
+        let paid_at: Option<String> = sqlx::query_scalar(r#"SELECT paid_at FROM orders WHERE id = 'order-1'"#).fetch_one(&mut *conn).await.unwrap();
+        assert_eq!(paid_at, None);
+
+        transition(&mut conn, "order-1", PickedUp, "cook@example.com", None).await.unwrap();
+        let paid_at: Option<String> = sqlx::query_scalar(r#"SELECT paid_at FROM orders WHERE id = 'order-1'"#).fetch_one(&mut *conn).await.unwrap();
+        assert!(paid_at.is_some());
+    }
+}
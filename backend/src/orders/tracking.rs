@@ -0,0 +1,173 @@
+use sqlx::{Row, SqliteConnection};
+use uuid::Uuid;
+
+use crate::email::{order_ready_html, send_html_email};
+use crate::state::AppState;
+
+use super::status::OrderStatus;
+
+/// Extra minutes added to the estimate for every order already in the kitchen.
+const MINUTES_PER_QUEUED_ORDER: i64 = 3;
+
+/// Secret that has to accompany `GET /api/orders/:id`.
+pub fn new_token() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// Compare a tracking token without leaking how much of it matched.
+pub fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Customer-facing tracking page of an order.
+pub fn order_url(app_url: &str, order_id: &str, token: Option<&str>) -> String {
+    let base = format!("{}/thank-you/{}", app_url.trim_end_matches('/'), order_id);
+    match token {
+        Some(t) => format!("{}?t={}", base, t),
+        None => base,
+    }
+}
+
+/// `order_url` for an order that is already stored.
+pub async fn order_url_for(state: &AppState, order_id: &str) -> String {
+    let token: Option<String> = sqlx::query_scalar(r#"SELECT tracking_token FROM orders WHERE id = ?"#)
+        .bind(order_id)
+        .fetch_optional(&state.pool)
+        .await
+        .ok()
+        .flatten()
+        .flatten();
+    order_url(&state.app_url, order_id, token.as_deref())
+}
+
+/// Statuses for which an estimated ready time means something to the customer.
+pub fn shows_estimate(status: OrderStatus) -> bool {
+    matches!(status, OrderStatus::AwaitingPayment | OrderStatus::Paid | OrderStatus::Accepted | OrderStatus::Preparing)
+}
+
+/// Set the first estimate for a new order: its pickup slot if one was chosen,
+/// otherwise base preparation time plus a few minutes for every order the
+/// kitchen is already working on.
+pub async fn set_initial_estimate(conn: &mut SqliteConnection, order_id: &str, prep_minutes: i64) -> Result<(), sqlx::Error> {
+    let scheduled = sqlx::query(r#"UPDATE orders SET estimated_ready_at = pickup_at WHERE id = ? AND pickup_at IS NOT NULL"#)
+        .bind(order_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+    if scheduled > 0 {
+        return Ok(());
+    }
+    let queued: i64 = sqlx::query_scalar(r#"SELECT COUNT(*) FROM orders WHERE status IN ('paid', 'accepted', 'preparing') AND id != ?"#)
+        .bind(order_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    set_estimate(conn, order_id, prep_minutes + queued * MINUTES_PER_QUEUED_ORDER).await
+}
+
+/// Replace the estimate with "`minutes` from now", e.g. when the kitchen accepts the order.
+pub async fn set_estimate(conn: &mut SqliteConnection, order_id: &str, minutes: i64) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"UPDATE orders SET estimated_ready_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now', ?) WHERE id = ?"#)
+        .bind(format!("+{} minutes", minutes.max(0)))
+        .bind(order_id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Email the customer once the order reached `ready`, if they asked for it.
+/// `ready_notified_at` is claimed first so the email goes out at most once.
+pub async fn notify_ready(state: &AppState, order_id: &str) {
+    let claimed = sqlx::query(r#"UPDATE orders SET ready_notified_at = strftime('%Y-%m-%dT%H:%M:%fZ','now') WHERE id = ? AND status = 'ready' AND notify_ready = 1 AND ready_notified_at IS NULL AND email != '' RETURNING email, tracking_token"#)
+        .bind(order_id)
+        .fetch_optional(&state.pool)
+        .await;
+    let row = match claimed {
+        Ok(Some(row)) => row,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("Failed to claim ready notification for order {}: {:?}", order_id, e);
+            return;
+        }
+    };
+    let email: String = row.try_get("email").unwrap_or_default();
+    let token: Option<String> = row.try_get("tracking_token").ok().flatten();
+
+    let html_body = order_ready_html(order_id, &order_url(&state.app_url, order_id, token.as_deref()));
+    match send_html_email(state, &email, "Your order is ready for pickup", &html_body).await {
+        Ok(_) => tracing::info!("Ready notification sent to {} for order {}", email, order_id),
+        Err(e) => tracing::error!("Failed to send ready notification to {}: {:?}", email, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_state;
+
+    async fn add_order(state: &AppState, id: &str, status: &str, pickup_at: Option<&str>) {
+        sqlx::query(r#"INSERT INTO orders (id, email, total_cents, status, pickup_at, notify_ready, tracking_token) VALUES (?, 'a@example.com', 1000, ?, ?, 1, ?)"#)
+            .bind(id)
+            .bind(status)
+            .bind(pickup_at)
+            .bind(new_token())
+            .execute(&state.pool)
+            .await
+            .unwrap();
+    }
+
+    async fn minutes_until_ready(state: &AppState, id: &str) -> i64 {
+        sqlx::query_scalar(r#"SELECT CAST(ROUND((julianday(estimated_ready_at) - julianday('now')) * 1440) AS INTEGER) FROM orders WHERE id = ?"#)
+            .bind(id)
+            .fetch_one(&state.pool)
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn tokens_must_match_exactly() {
+        let token = new_token();
+        assert!(token_matches(&token, &token));
+        assert!(!token_matches(&token[1..], &token));
+        assert!(!token_matches(&new_token(), &token));
+        assert_eq!(order_url("https://shop.example/", "o-1", Some("abc")), "https://shop.example/thank-you/o-1?t=abc");
+        assert_eq!(order_url("https://shop.example", "o-1", None), "https://shop.example/thank-you/o-1");
+    }
+
+    #[tokio::test]
+    async fn estimate_counts_the_kitchen_queue_unless_a_slot_was_chosen() {
+        let state = mock_state().await;
+        add_order(&state, "busy-1", "accepted", None).await;
+        add_order(&state, "busy-2", "preparing", None).await;
+        add_order(&state, "done", "picked_up", None).await;
+        add_order(&state, "asap", "paid", None).await;
+        add_order(&state, "slot", "paid", Some("2026-10-19T11:15:00Z")).await;
+
+        let mut conn = state.pool.acquire().await.unwrap();
+        set_initial_estimate(&mut conn, "asap", state.prep_minutes).await.unwrap();
+        set_initial_estimate(&mut conn, "slot", state.prep_minutes).await.unwrap();
+        drop(conn);
+
+        // 20 minutes of preparation, 3 for each of the two orders in the kitchen and the slot order
+        assert_eq!(minutes_until_ready(&state, "asap").await, 29);
+        let slot: String = sqlx::query_scalar(r#"SELECT estimated_ready_at FROM orders WHERE id = 'slot'"#).fetch_one(&state.pool).await.unwrap();
+        assert_eq!(slot, "2026-10-19T11:15:00Z");
+    }
+
+    #[tokio::test]
+    async fn ready_notification_is_claimed_once_and_only_when_ready() {
+        let state = mock_state().await;
+        add_order(&state, "o-1", "preparing", None).await;
+        let claimed = || sqlx::query_scalar::<_, Option<String>>(r#"SELECT ready_notified_at FROM orders WHERE id = 'o-1'"#).fetch_one(&state.pool);
+
+        notify_ready(&state, "o-1").await;
+        assert_eq!(claimed().await.unwrap(), None);
+
+        sqlx::query(r#"UPDATE orders SET status = 'ready' WHERE id = 'o-1'"#).execute(&state.pool).await.unwrap();
+        notify_ready(&state, "o-1").await;
+        let first = claimed().await.unwrap();
+        assert!(first.is_some());
+        notify_ready(&state, "o-1").await;
+        assert_eq!(claimed().await.unwrap(), first);
+    }
+}
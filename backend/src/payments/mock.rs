@@ -0,0 +1,168 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::{join_url, CapturedPayment, CreatePayment, CreatedPayment, PaymentProvider, PaymentStatus, RefundResult, WebhookTransmission};
+
+struct MockOrder {
+    amount_cents: i64,
+    refunded_cents: i64,
+    status: PaymentStatus,
+}
+
+/// In-memory provider for local development and tests.
+///
+/// Ids are sequential (`MOCK-ORDER-1`, `MOCK-CAPTURE-1`, ...) and the approval
+/// URL points straight back at the backend return path, so the whole checkout
+/// round-trip works without network access. Orders are forgotten on restart.
+///
+/// Webhooks are signed locally: `paypal-transmission-sig` must be the hex
+/// HMAC-SHA256 of `{transmission_id}|{transmission_time}|{body}` keyed with
+/// `PAYPAL_WEBHOOK_ID`.
+pub struct MockProvider {
+    backend_url: String,
+    webhook_id: Option<String>,
+    counter: AtomicU64,
+    orders: Mutex<HashMap<String, MockOrder>>,
+}
+
+impl MockProvider {
+    pub fn new(backend_url: String, webhook_id: Option<String>) -> Self {
+        Self { backend_url, webhook_id, counter: AtomicU64::new(0), orders: Mutex::new(HashMap::new()) }
+    }
+
+    fn next_id(&self, prefix: &str) -> String {
+        format!("MOCK-{}-{}", prefix, self.counter.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+}
+
+fn capture_id_for(order_id: &str) -> String {
+    order_id.replacen("MOCK-ORDER-", "MOCK-CAPTURE-", 1)
+}
+
+fn order_id_for(capture_id: &str) -> String {
+    capture_id.replacen("MOCK-CAPTURE-", "MOCK-ORDER-", 1)
+}
+
+/// Signature the mock provider expects on webhook deliveries.
+pub(crate) fn mock_webhook_signature(webhook_id: &str, transmission_id: &str, transmission_time: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(webhook_id.as_bytes()).expect("hmac accepts any key length");
+    mac.update(format!("{}|{}|{}", transmission_id, transmission_time, body).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[async_trait]
+impl PaymentProvider for MockProvider {
+    fn name(&self) -> &'static str { "mock" }
+
+    async fn create_order(&self, request: &CreatePayment) -> Result<CreatedPayment> {
+        if request.amount_cents < 0 {
+            return Err(anyhow::anyhow!("mock payment amount must not be negative"));
+        }
+        let id = self.next_id("ORDER");
+        self.orders.lock().unwrap().insert(id.clone(), MockOrder {
+            amount_cents: request.amount_cents,
+            refunded_cents: 0,
+            status: PaymentStatus::Created,
+        });
+        let approval_url = format!("{}?token={}", join_url(&self.backend_url, &request.return_path), id);
+        tracing::info!("Mock payment {} created for {}¢", id, request.amount_cents);
+        Ok(CreatedPayment { id, approval_url: Some(approval_url), status: PaymentStatus::Created })
+    }
+
+    async fn capture(&self, order_id: &str) -> Result<CapturedPayment> {
+        let mut orders = self.orders.lock().unwrap();
+        let order = orders.get_mut(order_id).ok_or_else(|| anyhow::anyhow!("unknown mock order {}", order_id))?;
+        if order.status == PaymentStatus::Created || order.status == PaymentStatus::Approved {
+            order.status = PaymentStatus::Completed;
+        }
+        Ok(CapturedPayment {
+            capture_id: Some(capture_id_for(order_id)),
+            status: order.status.clone(),
+            amount_cents: Some(order.amount_cents),
+        })
+    }
+
+    async fn refund(&self, capture_id: &str, amount_cents: i64, _currency: &str, _reason: Option<&str>) -> Result<RefundResult> {
+        let mut orders = self.orders.lock().unwrap();
+        let order_id = order_id_for(capture_id);
+        let order = orders.get_mut(&order_id).ok_or_else(|| anyhow::anyhow!("unknown mock capture {}", capture_id))?;
+        if amount_cents <= 0 || order.refunded_cents + amount_cents > order.amount_cents {
+            return Err(anyhow::anyhow!("mock refund of {}¢ exceeds captured amount", amount_cents));
+        }
+        order.refunded_cents += amount_cents;
+        order.status = if order.refunded_cents == order.amount_cents { PaymentStatus::Refunded } else { PaymentStatus::PartiallyRefunded };
+        Ok(RefundResult { refund_id: self.next_id("REFUND"), status: PaymentStatus::Completed, amount_cents })
+    }
+
+    async fn fetch_status(&self, order_id: &str) -> Result<PaymentStatus> {
+        let orders = self.orders.lock().unwrap();
+        orders.get(order_id)
+            .map(|o| o.status.clone())
+            .ok_or_else(|| anyhow::anyhow!("unknown mock order {}", order_id))
+    }
+
+    async fn verify_webhook(&self, transmission: &WebhookTransmission, body: &str) -> Result<bool> {
+        let webhook_id = self.webhook_id.as_deref().ok_or_else(|| anyhow::anyhow!("missing PAYPAL_WEBHOOK_ID"))?;
+        let (Some(id), Some(time), Some(sig)) = (
+            transmission.transmission_id.as_deref(),
+            transmission.transmission_time.as_deref(),
+            transmission.transmission_sig.as_deref(),
+        ) else {
+            return Ok(false);
+        };
+        Ok(mock_webhook_signature(webhook_id, id, time, body).eq_ignore_ascii_case(sig))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &str = r#"{"id":"WH-1"}"#;
+    /// HMAC-SHA256 of `t-1|2026-10-17T10:00:00Z|{BODY}` keyed with `WH-TEST`.
+    const SIGNATURE: &str = "fd8a51ba5630bce6a65270494113f26e44f5cf05a4b7d37c6d4aa346aac9d7d0";
+
+    fn transmission(sig: Option<&str>) -> WebhookTransmission {
+        WebhookTransmission {
+            transmission_id: Some("t-1".into()),
+            transmission_time: Some("2026-10-17T10:00:00Z".into()),
+            transmission_sig: sig.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn provider(webhook_id: Option<&str>) -> MockProvider {
+        MockProvider::new("http://127.0.0.1:8080".into(), webhook_id.map(str::to_string))
+    }
+
+    #[test]
+    fn signature_is_hmac_of_id_time_and_body() {
+        assert_eq!(mock_webhook_signature("WH-TEST", "t-1", "2026-10-17T10:00:00Z", BODY), SIGNATURE);
+    }
+
+    #[tokio::test]
+    async fn verify_webhook_accepts_matching_signature() {
+        let mock = provider(Some("WH-TEST"));
+        assert!(mock.verify_webhook(&transmission(Some(SIGNATURE)), BODY).await.unwrap());
+        assert!(mock.verify_webhook(&transmission(Some(&SIGNATURE.to_uppercase())), BODY).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn verify_webhook_rejects_tampered_or_unsigned_deliveries() {
+        let mock = provider(Some("WH-TEST"));
+        assert!(!mock.verify_webhook(&transmission(Some(SIGNATURE)), r#"{"id":"WH-2"}"#).await.unwrap());
+        assert!(!provider(Some("OTHER")).verify_webhook(&transmission(Some(SIGNATURE)), BODY).await.unwrap());
+        assert!(!mock.verify_webhook(&transmission(None), BODY).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn verify_webhook_needs_a_webhook_id() {
+        assert!(provider(None).verify_webhook(&transmission(Some(SIGNATURE)), BODY).await.is_err());
+    }
+}
@@ -1,114 +1,248 @@
-use serde::{Deserialize, Serialize};
 use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::{sqlite::SqlitePool, Row};
+use std::sync::Arc;
 
-use crate::state::AppState;
+pub mod paypal;
+pub mod mock;
+pub mod pickup;
 
-#[derive(Serialize)]
-struct PayPalOrderAmount { currency_code: String, value: String }
+/// Lifecycle of a payment as reported by a provider.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    /// Created at the provider, waiting for the customer to approve it.
+    Created,
+    /// Approved by the customer, not captured yet.
+    Approved,
+    Completed,
+    /// No online payment; the customer pays when collecting the order.
+    AwaitingPayment,
+    Refunded,
+    PartiallyRefunded,
+    Denied,
+    Other(String),
+}
 
-#[derive(Serialize)]
-struct PayPalPurchaseUnit { amount: PayPalOrderAmount, description: Option<String> }
+impl PaymentStatus {
+    /// Map a PayPal-style status string (`COMPLETED`, `APPROVED`, ...).
+    pub fn from_provider(status: &str) -> Self {
+        match status.to_ascii_uppercase().as_str() {
+            "CREATED" | "SAVED" | "PAYER_ACTION_REQUIRED" => PaymentStatus::Created,
+            "APPROVED" => PaymentStatus::Approved,
+            "COMPLETED" => PaymentStatus::Completed,
+            "REFUNDED" => PaymentStatus::Refunded,
+            "PARTIALLY_REFUNDED" => PaymentStatus::PartiallyRefunded,
+            "DENIED" | "DECLINED" | "VOIDED" | "FAILED" => PaymentStatus::Denied,
+            other => PaymentStatus::Other(other.to_string()),
+        }
+    }
 
-#[derive(Serialize)]
-struct PayPalOrderCreate { intent: String, purchase_units: Vec<PayPalPurchaseUnit>, application_context: PayPalAppCtx }
+    pub fn as_str(&self) -> &str {
+        match self {
+            PaymentStatus::Created => "created",
+            PaymentStatus::Approved => "approved",
+            PaymentStatus::Completed => "completed",
+            PaymentStatus::AwaitingPayment => "awaiting_payment",
+            PaymentStatus::Refunded => "refunded",
+            PaymentStatus::PartiallyRefunded => "partially_refunded",
+            PaymentStatus::Denied => "denied",
+            PaymentStatus::Other(s) => s,
+        }
+    }
+}
 
-#[derive(Serialize)]
-struct PayPalAppCtx { return_url: String, cancel_url: String }
+pub struct CreatePayment {
+    pub amount_cents: i64,
+    pub currency: String,
+    pub description: Option<String>,
+    /// Backend path the customer is sent back to after approving, e.g. `/api/paypal/return`.
+    pub return_path: String,
+    pub cancel_path: String,
+}
 
-#[derive(Deserialize)]
-struct PayPalTokenResp { access_token: String, token_type: String }
+#[derive(Debug, Clone)]
+pub struct CreatedPayment {
+    /// Provider order id; used as `pending_orders.order_id`.
+    pub id: String,
+    /// Where to send the customer to approve the payment, if the provider needs that step.
+    pub approval_url: Option<String>,
+    pub status: PaymentStatus,
+}
 
-#[derive(Deserialize)]
-pub struct PayPalOrderLink { pub rel: String, pub href: String }
+#[derive(Debug, Clone)]
+pub struct CapturedPayment {
+    pub capture_id: Option<String>,
+    pub status: PaymentStatus,
+    pub amount_cents: Option<i64>,
+}
 
-#[derive(Deserialize)]
-pub struct PayPalOrderResp { pub id: String, pub links: Vec<PayPalOrderLink> }
+#[derive(Debug, Clone)]
+pub struct RefundResult {
+    pub refund_id: String,
+    pub status: PaymentStatus,
+    pub amount_cents: i64,
+}
 
-fn join_url(base: &str, path: &str) -> String {
-    format!(
-        "{}/{}",
-        base.trim_end_matches('/'),
-        path.trim_start_matches('/')
-    )
+/// Transmission headers PayPal sends with every webhook delivery.
+#[derive(Debug, Clone, Default)]
+pub struct WebhookTransmission {
+    pub transmission_id: Option<String>,
+    pub transmission_time: Option<String>,
+    pub transmission_sig: Option<String>,
+    pub cert_url: Option<String>,
+    pub auth_algo: Option<String>,
 }
 
-pub async fn get_paypal_access_token(state: &AppState) -> Result<String> {
-    let client_id = state.paypal_client_id.as_ref().ok_or_else(|| anyhow::anyhow!("missing paypal client id"))?;
-    let secret = state.paypal_secret.as_ref().ok_or_else(|| anyhow::anyhow!("missing paypal secret"))?;
-    let url = format!("{}/v1/oauth2/token", state.paypal_api_base);
-    let resp = reqwest::Client::new()
-        .post(url)
-        .basic_auth(client_id, Some(secret))
-        .form(&[("grant_type", "client_credentials")])
-        .send().await?;
-    let body: PayPalTokenResp = resp.json().await?;
-    Ok(format!("{} {}", body.token_type, body.access_token))
-}
-
-pub async fn create_paypal_order(state: &AppState, value_eur: i64, return_path: &str, cancel_path: &str, description: Option<String>) -> Result<PayPalOrderResp> {
-    let bearer = get_paypal_access_token(state).await?;
-    let url = format!("{}/v2/checkout/orders", state.paypal_api_base);
-    let return_url = join_url(&state.backend_url, return_path);
-    let cancel_url = join_url(&state.backend_url, cancel_path);
-    let body = PayPalOrderCreate {
-        intent: "CAPTURE".into(),
-        purchase_units: vec![PayPalPurchaseUnit {
-            amount: PayPalOrderAmount { currency_code: "EUR".into(), value: format!("{:.2}", value_eur as f64 / 100.0) },
-            description,
-        }],
-        application_context: PayPalAppCtx {
-            return_url,
-            cancel_url,
+impl WebhookTransmission {
+    pub fn from_headers(headers: &axum::http::HeaderMap) -> Self {
+        let get = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(|v| v.to_string());
+        Self {
+            transmission_id: get("paypal-transmission-id"),
+            transmission_time: get("paypal-transmission-time"),
+            transmission_sig: get("paypal-transmission-sig"),
+            cert_url: get("paypal-cert-url"),
+            auth_algo: get("paypal-auth-algo"),
         }
-    };
-    let resp = reqwest::Client::new()
-        .post(url)
-        .bearer_auth(bearer.trim_start_matches("Bearer "))
-        .json(&body)
-        .send().await?;
-    let body: PayPalOrderResp = resp.json().await?;
-    Ok(body)
-}
-
-pub fn find_approval_url(order: &PayPalOrderResp) -> Option<String> {
-    order.links.iter().find(|l| l.rel == "approve").map(|l| l.href.clone())
-}
-
-#[derive(Deserialize, Debug)]
-#[allow(dead_code)]
-pub struct PayPalCaptureResp { pub id: String, pub status: String }
-
-pub async fn capture_paypal_order(state: &AppState, order_id: &str) -> Result<PayPalCaptureResp> {
-    let bearer = get_paypal_access_token(state).await?;
-    let url = format!("{}/v2/checkout/orders/{}/capture", state.paypal_api_base, order_id);
-    
-    tracing::info!("Capturing PayPal order {} from URL: {}", order_id, url);
-    
-    let resp = reqwest::Client::new()
-        .post(url)
-        .bearer_auth(bearer.trim_start_matches("Bearer "))
-        .header("Content-Type", "application/json")
-        .send().await?;
-    
-    let status = resp.status();
-    let text = resp.text().await?;
-    
-    tracing::info!("PayPal capture response status: {}, body: {}", status, text);
-    
-    if !status.is_success() {
-        return Err(anyhow::anyhow!("PayPal capture failed with status {}: {}", status, text));
     }
-    
-    match serde_json::from_str::<PayPalCaptureResp>(&text) {
-        Ok(body) => {
-            tracing::info!("Successfully parsed PayPal capture response: {:?}", body);
-            Ok(body)
+}
+
+/// A payment backend. The configured providers live in `AppState::payments`.
+#[async_trait]
+pub trait PaymentProvider: Send + Sync {
+    /// Short identifier stored on orders, e.g. `paypal`.
+    fn name(&self) -> &'static str;
+
+    async fn create_order(&self, request: &CreatePayment) -> Result<CreatedPayment>;
+
+    async fn capture(&self, order_id: &str) -> Result<CapturedPayment>;
+
+    /// Refund `amount_cents` of a capture. Partial refunds pass less than the captured amount.
+    async fn refund(&self, capture_id: &str, amount_cents: i64, currency: &str, reason: Option<&str>) -> Result<RefundResult>;
+
+    async fn fetch_status(&self, order_id: &str) -> Result<PaymentStatus>;
+
+    /// Check that a webhook body was really sent by the provider. `Ok(false)` means
+    /// the signature did not match; `Err` means verification could not be performed.
+    async fn verify_webhook(&self, transmission: &WebhookTransmission, body: &str) -> Result<bool>;
+}
+
+pub struct PaymentConfig {
+    /// Comma-separated `PAYMENT_PROVIDER` list; the first entry is the checkout default.
+    pub providers: Option<String>,
+    pub backend_url: String,
+    pub paypal_client_id: Option<String>,
+    pub paypal_secret: Option<String>,
+    pub paypal_api_base: String,
+    /// Id of the webhook registered in the PayPal dashboard; required to verify deliveries.
+    pub paypal_webhook_id: Option<String>,
+}
+
+/// The providers a checkout may choose from, keyed by `PaymentProvider::name`.
+/// Orders keep the name in `payment_provider`, and capture, refund and webhooks
+/// look the provider up again by that name.
+#[derive(Clone)]
+pub struct PaymentProviders {
+    providers: Vec<Arc<dyn PaymentProvider>>,
+}
+
+impl PaymentProviders {
+    /// Registry of `providers`; the first one is the default. Panics when empty.
+    pub fn new(providers: Vec<Arc<dyn PaymentProvider>>) -> Self {
+        assert!(!providers.is_empty(), "at least one payment provider");
+        Self { providers }
+    }
+
+    /// Provider used when a checkout does not ask for one.
+    pub fn default_provider(&self) -> &Arc<dyn PaymentProvider> {
+        &self.providers[0]
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn PaymentProvider>> {
+        self.providers.iter().find(|p| p.name() == name)
+    }
+
+    /// Provider recorded on an order or pending payment. Rows from before providers
+    /// were recorded have no name and were paid through PayPal.
+    pub fn recorded(&self, name: Option<&str>) -> Option<&Arc<dyn PaymentProvider>> {
+        self.get(name.unwrap_or("paypal"))
+    }
+
+    /// First provider that takes the money online, for purchases that cannot be paid at the counter.
+    pub fn online(&self) -> Option<&Arc<dyn PaymentProvider>> {
+        self.providers.iter().find(|p| p.name() != pickup::NAME)
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.providers.iter().map(|p| p.name()).collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn PaymentProvider>> {
+        self.providers.iter()
+    }
+
+    /// Provider that created the payment `payment_order_id`, found through the
+    /// pending checkout, the pending gift purchase or the finished order.
+    pub async fn for_payment(&self, pool: &SqlitePool, payment_order_id: &str) -> Result<Option<&Arc<dyn PaymentProvider>>> {
+        let row = sqlx::query(
+            r#"SELECT payment_provider FROM pending_orders WHERE order_id = ?1
+               UNION ALL SELECT payment_provider FROM pending_gifts WHERE order_id = ?1
+               UNION ALL SELECT payment_provider FROM orders WHERE payment_order_id = ?1
+               LIMIT 1"#,
+        )
+        .bind(payment_order_id)
+        .fetch_optional(pool)
+        .await?;
+        match row {
+            Some(row) => Ok(self.recorded(row.try_get::<Option<String>, _>("payment_provider")?.as_deref())),
+            None => Ok(None),
         }
-        Err(e) => {
-            tracing::error!("Failed to parse PayPal response as PayPalCaptureResp: {}", e);
-            tracing::error!("Response text was: {}", text);
-            Err(anyhow::anyhow!("Failed to parse PayPal response: {} - Response was: {}", e, text))
+    }
+}
+
+/// Build the providers listed in `PAYMENT_PROVIDER` (`paypal`, `mock`, `pickup`,
+/// comma-separated). Defaults to PayPal alone, which fails loudly at checkout if
+/// credentials are missing.
+pub fn providers_from_config(config: PaymentConfig) -> Result<PaymentProviders> {
+    let list = config.providers.as_deref().unwrap_or("paypal");
+    let mut providers: Vec<Arc<dyn PaymentProvider>> = Vec::new();
+    for name in list.split(',').map(|p| p.trim().to_ascii_lowercase()).filter(|p| !p.is_empty()) {
+        let provider: Arc<dyn PaymentProvider> = match name.as_str() {
+            "paypal" => Arc::new(paypal::PayPalProvider::new(
+                config.paypal_client_id.clone(),
+                config.paypal_secret.clone(),
+                config.paypal_api_base.clone(),
+                config.backend_url.clone(),
+                config.paypal_webhook_id.clone(),
+            )),
+            "mock" => Arc::new(mock::MockProvider::new(config.backend_url.clone(), config.paypal_webhook_id.clone())),
+            pickup::NAME => Arc::new(pickup::PickupProvider::new()),
+            other => return Err(anyhow::anyhow!("unknown PAYMENT_PROVIDER '{}'", other)),
+        };
+        if providers.iter().any(|p| p.name() == provider.name()) {
+            return Err(anyhow::anyhow!("PAYMENT_PROVIDER lists '{}' twice", name));
         }
+        providers.push(provider);
+    }
+    if providers.is_empty() {
+        return Err(anyhow::anyhow!("PAYMENT_PROVIDER lists no provider"));
     }
+    Ok(PaymentProviders::new(providers))
+}
+
+pub(crate) fn join_url(base: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
 }
 
+pub(crate) fn cents_to_value(cents: i64) -> String {
+    format!("{:.2}", cents as f64 / 100.0)
+}
+
+pub(crate) fn value_to_cents(value: &str) -> Option<i64> {
+    value.parse::<f64>().ok().map(|v| (v * 100.0).round() as i64)
+}
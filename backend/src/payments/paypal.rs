@@ -0,0 +1,320 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::{cents_to_value, join_url, value_to_cents, CapturedPayment, CreatePayment, CreatedPayment, PaymentProvider, PaymentStatus, RefundResult, WebhookTransmission};
+
+/// Upper bound for one PayPal API call; refund claims outlive it (see
+/// `orders::refund::CLAIM_EXPIRY_MINUTES`).
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(Serialize)]
+struct PayPalOrderAmount { currency_code: String, value: String }
+
+#[derive(Serialize)]
+struct PayPalPurchaseUnit { amount: PayPalOrderAmount, description: Option<String> }
+
+#[derive(Serialize)]
+struct PayPalOrderCreate { intent: String, purchase_units: Vec<PayPalPurchaseUnit>, application_context: PayPalAppCtx }
+
+#[derive(Serialize)]
+struct PayPalAppCtx { return_url: String, cancel_url: String }
+
+#[derive(Deserialize)]
+struct PayPalTokenResp { access_token: String }
+
+#[derive(Deserialize)]
+struct PayPalOrderLink { rel: String, href: String }
+
+#[derive(Deserialize)]
+struct PayPalOrderResp { id: String, status: Option<String>, #[serde(default)] links: Vec<PayPalOrderLink> }
+
+#[derive(Deserialize, Debug)]
+struct PayPalCaptureResp {
+    status: String,
+    #[serde(default)]
+    purchase_units: Vec<PayPalCapturedUnit>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PayPalCapturedUnit { payments: Option<PayPalCapturedPayments> }
+
+#[derive(Deserialize, Debug)]
+struct PayPalCapturedPayments { #[serde(default)] captures: Vec<PayPalCapture> }
+
+#[derive(Deserialize, Debug)]
+struct PayPalCapture { id: String, amount: Option<PayPalAmount> }
+
+#[derive(Deserialize, Debug)]
+struct PayPalAmount { value: String }
+
+#[derive(Serialize)]
+struct PayPalRefundRequest { amount: PayPalOrderAmount, note_to_payer: Option<String> }
+
+#[derive(Deserialize)]
+struct PayPalRefundResp { id: String, status: String }
+
+#[derive(Serialize)]
+struct PayPalVerifyRequest<'a> {
+    auth_algo: &'a str,
+    cert_url: &'a str,
+    transmission_id: &'a str,
+    transmission_sig: &'a str,
+    transmission_time: &'a str,
+    webhook_id: &'a str,
+    webhook_event: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct PayPalVerifyResp { verification_status: String }
+
+/// PayPal Orders v2 REST API.
+pub struct PayPalProvider {
+    client_id: Option<String>,
+    secret: Option<String>,
+    api_base: String,
+    backend_url: String,
+    webhook_id: Option<String>,
+    http: reqwest::Client,
+}
+
+impl PayPalProvider {
+    pub fn new(client_id: Option<String>, secret: Option<String>, api_base: String, backend_url: String, webhook_id: Option<String>) -> Self {
+        let http = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build().expect("PayPal HTTP client");
+        Self { client_id, secret, api_base, backend_url, webhook_id, http }
+    }
+
+    async fn access_token(&self) -> Result<String> {
+        let client_id = self.client_id.as_ref().ok_or_else(|| anyhow::anyhow!("missing paypal client id"))?;
+        let secret = self.secret.as_ref().ok_or_else(|| anyhow::anyhow!("missing paypal secret"))?;
+        let url = join_url(&self.api_base, "/v1/oauth2/token");
+        let resp = self.http
+            .post(url)
+            .basic_auth(client_id, Some(secret))
+            .form(&[("grant_type", "client_credentials")])
+            .send().await?;
+        let body: PayPalTokenResp = resp.json().await?;
+        Ok(body.access_token)
+    }
+}
+
+#[async_trait]
+impl PaymentProvider for PayPalProvider {
+    fn name(&self) -> &'static str { "paypal" }
+
+    async fn create_order(&self, request: &CreatePayment) -> Result<CreatedPayment> {
+        let bearer = self.access_token().await?;
+        let url = join_url(&self.api_base, "/v2/checkout/orders");
+        let body = PayPalOrderCreate {
+            intent: "CAPTURE".into(),
+            purchase_units: vec![PayPalPurchaseUnit {
+                amount: PayPalOrderAmount { currency_code: request.currency.clone(), value: cents_to_value(request.amount_cents) },
+                description: request.description.clone(),
+            }],
+            application_context: PayPalAppCtx {
+                return_url: join_url(&self.backend_url, &request.return_path),
+                cancel_url: join_url(&self.backend_url, &request.cancel_path),
+            }
+        };
+        let resp = self.http
+            .post(url)
+            .bearer_auth(bearer)
+            .json(&body)
+            .send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("PayPal order creation failed with status {}: {}", status, text));
+        }
+        let order: PayPalOrderResp = resp.json().await?;
+        let approval_url = order.links.iter().find(|l| l.rel == "approve").map(|l| l.href.clone());
+        Ok(CreatedPayment {
+            status: PaymentStatus::from_provider(order.status.as_deref().unwrap_or("CREATED")),
+            id: order.id,
+            approval_url,
+        })
+    }
+
+    async fn capture(&self, order_id: &str) -> Result<CapturedPayment> {
+        let bearer = self.access_token().await?;
+        let url = join_url(&self.api_base, &format!("/v2/checkout/orders/{}/capture", order_id));
+
+        tracing::info!("Capturing PayPal order {} from URL: {}", order_id, url);
+
+        let resp = self.http
+            .post(url)
+            .bearer_auth(bearer)
+            .header("Content-Type", "application/json")
+            .send().await?;
+
+        let status = resp.status();
+        let text = resp.text().await?;
+
+        tracing::info!("PayPal capture response status: {}, body: {}", status, text);
+
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("PayPal capture failed with status {}: {}", status, text));
+        }
+
+        let body = serde_json::from_str::<PayPalCaptureResp>(&text).map_err(|e| {
+            tracing::error!("Failed to parse PayPal response as PayPalCaptureResp: {}", e);
+            anyhow::anyhow!("Failed to parse PayPal response: {} - Response was: {}", e, text)
+        })?;
+
+        let capture = body.purchase_units.iter()
+            .filter_map(|u| u.payments.as_ref())
+            .flat_map(|p| p.captures.iter())
+            .next();
+        Ok(CapturedPayment {
+            capture_id: capture.map(|c| c.id.clone()),
+            status: PaymentStatus::from_provider(&body.status),
+            amount_cents: capture.and_then(|c| c.amount.as_ref()).and_then(|a| value_to_cents(&a.value)),
+        })
+    }
+
+    async fn refund(&self, capture_id: &str, amount_cents: i64, currency: &str, reason: Option<&str>) -> Result<RefundResult> {
+        let bearer = self.access_token().await?;
+        let url = join_url(&self.api_base, &format!("/v2/payments/captures/{}/refund", capture_id));
+        let body = PayPalRefundRequest {
+            amount: PayPalOrderAmount { currency_code: currency.to_string(), value: cents_to_value(amount_cents) },
+            note_to_payer: reason.map(|r| r.to_string()),
+        };
+        let resp = self.http
+            .post(url)
+            .bearer_auth(bearer)
+            .json(&body)
+            .send().await?;
+        let status = resp.status();
+        let text = resp.text().await?;
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("PayPal refund failed with status {}: {}", status, text));
+        }
+        let body: PayPalRefundResp = serde_json::from_str(&text)?;
+        Ok(RefundResult { refund_id: body.id, status: PaymentStatus::from_provider(&body.status), amount_cents })
+    }
+
+    async fn fetch_status(&self, order_id: &str) -> Result<PaymentStatus> {
+        let bearer = self.access_token().await?;
+        let url = join_url(&self.api_base, &format!("/v2/checkout/orders/{}", order_id));
+        let resp = self.http.get(url).bearer_auth(bearer).send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("PayPal order lookup failed with status {}: {}", status, text));
+        }
+        let order: PayPalOrderResp = resp.json().await?;
+        Ok(PaymentStatus::from_provider(order.status.as_deref().unwrap_or("")))
+    }
+
+    /// Uses PayPal's verify-webhook-signature endpoint under `PAYPAL_API_BASE`,
+    /// so a local stand-in can answer it during development.
+    async fn verify_webhook(&self, transmission: &WebhookTransmission, body: &str) -> Result<bool> {
+        let webhook_id = self.webhook_id.as_deref().ok_or_else(|| anyhow::anyhow!("missing PAYPAL_WEBHOOK_ID"))?;
+        let (Some(transmission_id), Some(transmission_time), Some(transmission_sig), Some(cert_url), Some(auth_algo)) = (
+            transmission.transmission_id.as_deref(),
+            transmission.transmission_time.as_deref(),
+            transmission.transmission_sig.as_deref(),
+            transmission.cert_url.as_deref(),
+            transmission.auth_algo.as_deref(),
+        ) else {
+            return Ok(false);
+        };
+        let bearer = self.access_token().await?;
+        let url = join_url(&self.api_base, "/v1/notifications/verify-webhook-signature");
+        let request = PayPalVerifyRequest {
+            auth_algo,
+            cert_url,
+            transmission_id,
+            transmission_sig,
+            transmission_time,
+            webhook_id,
+            webhook_event: serde_json::from_str(body)?,
+        };
+        let resp = self.http.post(url).bearer_auth(bearer).json(&request).send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("PayPal webhook verification failed with status {}: {}", status, text));
+        }
+        let body: PayPalVerifyResp = resp.json().await?;
+        Ok(body.verification_status.eq_ignore_ascii_case("SUCCESS"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{routing::post, Json, Router};
+    use serde_json::{json, Value};
+
+    use crate::payments::{providers_from_config, PaymentConfig, PaymentProvider, WebhookTransmission};
+
+    /// Stand-in for the PayPal API: issues a token and reports SUCCESS only
+    /// for the signature `good-sig` on webhook `WH-TEST`.
+    async fn stub_api() -> String {
+        let app = Router::new()
+            .route("/v1/oauth2/token", post(|| async { Json(json!({ "access_token": "stub-token" })) }))
+            .route(
+                "/v1/notifications/verify-webhook-signature",
+                post(|Json(request): Json<Value>| async move {
+                    let valid = request["transmission_sig"] == "good-sig"
+                        && request["webhook_id"] == "WH-TEST"
+                        && request["webhook_event"]["id"] == "WH-1";
+                    Json(json!({ "verification_status": if valid { "SUCCESS" } else { "FAILURE" } }))
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    fn provider(api_base: String) -> std::sync::Arc<dyn PaymentProvider> {
+        providers_from_config(PaymentConfig {
+            providers: Some("paypal".into()),
+            backend_url: "http://127.0.0.1:8080".into(),
+            paypal_client_id: Some("client".into()),
+            paypal_secret: Some("secret".into()),
+            paypal_api_base: api_base,
+            paypal_webhook_id: Some("WH-TEST".into()),
+        })
+        .unwrap()
+        .default_provider()
+        .clone()
+    }
+
+    fn transmission(sig: &str) -> WebhookTransmission {
+        WebhookTransmission {
+            transmission_id: Some("t-1".into()),
+            transmission_time: Some("2026-10-17T10:00:00Z".into()),
+            transmission_sig: Some(sig.into()),
+            cert_url: Some("https://api.paypal.com/cert.pem".into()),
+            auth_algo: Some("SHA256withRSA".into()),
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_webhook_trusts_success_from_paypal() {
+        let paypal = provider(stub_api().await);
+        assert!(paypal.verify_webhook(&transmission("good-sig"), r#"{"id":"WH-1"}"#).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn verify_webhook_rejects_failure_from_paypal() {
+        let paypal = provider(stub_api().await);
+        assert!(!paypal.verify_webhook(&transmission("forged-sig"), r#"{"id":"WH-1"}"#).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn verify_webhook_without_transmission_headers_is_rejected_locally() {
+        // Nothing listens here; the check must not need the API
+        let paypal = provider("http://127.0.0.1:9".into());
+        let headers = WebhookTransmission { transmission_sig: Some("good-sig".into()), ..Default::default() };
+        assert!(!paypal.verify_webhook(&headers, r#"{"id":"WH-1"}"#).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn verify_webhook_errors_when_paypal_is_unreachable() {
+        let paypal = provider("http://127.0.0.1:9".into());
+        assert!(paypal.verify_webhook(&transmission("good-sig"), r#"{"id":"WH-1"}"#).await.is_err());
+    }
+}
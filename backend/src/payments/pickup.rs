@@ -0,0 +1,52 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use super::{CapturedPayment, CreatePayment, CreatedPayment, PaymentProvider, PaymentStatus, RefundResult, WebhookTransmission};
+
+/// Provider name stored in `orders.payment_provider`.
+pub const NAME: &str = "pickup";
+
+/// "Pay at pickup": no online payment at all. Checkout creates the order
+/// straight away in `awaiting_payment` and staff settle it at the counter.
+#[derive(Default)]
+pub struct PickupProvider;
+
+impl PickupProvider {
+    pub fn new() -> Self { Self }
+}
+
+#[async_trait]
+impl PaymentProvider for PickupProvider {
+    fn name(&self) -> &'static str { NAME }
+
+    async fn create_order(&self, _request: &CreatePayment) -> Result<CreatedPayment> {
+        Ok(CreatedPayment {
+            id: format!("PICKUP-{}", Uuid::new_v4()),
+            approval_url: None,
+            status: PaymentStatus::AwaitingPayment,
+        })
+    }
+
+    async fn capture(&self, _order_id: &str) -> Result<CapturedPayment> {
+        // Nothing to capture online; the money changes hands at the counter.
+        Ok(CapturedPayment {
+            capture_id: None,
+            status: PaymentStatus::AwaitingPayment,
+            amount_cents: None,
+        })
+    }
+
+    async fn refund(&self, _capture_id: &str, _amount_cents: i64, _currency: &str, _reason: Option<&str>) -> Result<RefundResult> {
+        Err(anyhow::anyhow!("pay-at-pickup orders have no online payment to refund"))
+    }
+
+    async fn fetch_status(&self, _order_id: &str) -> Result<PaymentStatus> {
+        Ok(PaymentStatus::AwaitingPayment)
+    }
+
+    async fn verify_webhook(&self, _transmission: &WebhookTransmission, _body: &str) -> Result<bool> {
+        // No provider talks to us, so nothing can be a genuine delivery.
+        Ok(false)
+    }
+}
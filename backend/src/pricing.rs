@@ -0,0 +1,383 @@
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqlitePool, Row};
+
+use crate::catalog::options::price_options;
+use crate::coupons::{self, holds, redemptions::{self, Customer}, CouponRejection, RejectionReason};
+
+/// Upper bound for a single cart line; anything above is treated as a typo or abuse.
+const MAX_LINE_QUANTITY: i64 = 99;
+
+/// Cart line as sent by the browser. Only `productId`, `quantity` and
+/// `optionIds` are read; name, unit price and currency are always reloaded
+/// from `products`, option surcharges from `options`.
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CartLine {
+    pub product_id: String,
+    pub quantity: i64,
+    #[serde(default)]
+    pub option_ids: Vec<String>,
+}
+
+/// An option chosen on a cart line, as priced at checkout.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectedOption {
+    pub id: String,
+    /// Name of the option group, e.g. "Schärfegrad".
+    pub group: String,
+    pub name: String,
+    pub price_delta_cents: i64,
+}
+
+/// "mittel scharf, Extra Tofu (+€2.00)" for emails and kitchen tickets.
+pub fn options_label(options: &[SelectedOption]) -> String {
+    options
+        .iter()
+        .map(|o| match o.price_delta_cents {
+            0 => o.name.clone(),
+            d if d > 0 => format!("{} (+€{:.2})", o.name, d as f64 / 100.0),
+            d => format!("{} (-€{:.2})", o.name, -d as f64 / 100.0),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A cart line priced from the database. `unit_amount` includes the option surcharges.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PricedLine {
+    pub product_id: String,
+    pub name: String,
+    pub unit_amount: i64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<SelectedOption>,
+    pub quantity: i64,
+    pub line_total_cents: i64,
+    pub currency: String,
+}
+
+/// Discount resolved from a coupon or gift code against a subtotal.
+#[derive(Serialize, Clone, Debug)]
+pub struct AppliedDiscount {
+    pub code: String,
+    pub kind: DiscountKind,
+    pub percent_off: Option<i64>,
+    pub amount_off: Option<i64>,
+    pub discount_cents: i64,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscountKind {
+    Coupon,
+    GiftCode,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PriceBreakdown {
+    pub lines: Vec<PricedLine>,
+    pub subtotal_cents: i64,
+    pub discount_cents: i64,
+    pub total_cents: i64,
+    pub currency: String,
+    pub coupon_code: Option<String>,
+    pub discount: Option<AppliedDiscount>,
+    /// Set when a code was given but does not apply to this cart.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coupon_rejection: Option<CouponRejection>,
+}
+
+impl PriceBreakdown {
+    /// JSON stored in `pending_orders.items_json` / `orders.items_json`.
+    pub fn to_items_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "cart": self.lines,
+            "coupon_code": self.coupon_code,
+            "discount_kind": self.discount.as_ref().map(|d| d.kind),
+            "subtotal_cents": self.subtotal_cents,
+            "discount_cents": self.discount_cents,
+            "total_cents": self.total_cents,
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PricingError {
+    #[error("cart is empty")]
+    EmptyCart,
+    #[error("invalid quantity {quantity} for product {product_id}")]
+    InvalidQuantity { product_id: String, quantity: i64 },
+    #[error("unknown product {0}")]
+    UnknownProduct(String),
+    #[error("product {0} is currently unavailable")]
+    UnavailableProduct(String),
+    #[error("product {0} is sold out")]
+    SoldOut(String),
+    #[error("product {0} is not on the menu at that time")]
+    OffMenu(String),
+    #[error("only {remaining} portion(s) of {product_id} left")]
+    InsufficientStock { product_id: String, remaining: i64 },
+    #[error("option {option_id} is not offered for product {product_id}")]
+    UnknownOption { product_id: String, option_id: String },
+    #[error("option {option_id} is currently unavailable")]
+    UnavailableOption { product_id: String, option_id: String },
+    #[error("{group}: {message}")]
+    InvalidSelection { product_id: String, group: String, message: String },
+    #[error("{}", .0.message)]
+    CouponRejected(CouponRejection),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl PricingError {
+    fn code(&self) -> &'static str {
+        match self {
+            PricingError::EmptyCart => "empty_cart",
+            PricingError::InvalidQuantity { .. } => "invalid_quantity",
+            PricingError::UnknownProduct(_) => "unknown_product",
+            PricingError::UnavailableProduct(_) => "unavailable_product",
+            PricingError::SoldOut(_) => "sold_out",
+            PricingError::OffMenu(_) => "off_menu",
+            PricingError::InsufficientStock { .. } => "insufficient_stock",
+            PricingError::UnknownOption { .. } => "unknown_option",
+            PricingError::UnavailableOption { .. } => "unavailable_option",
+            PricingError::InvalidSelection { .. } => "invalid_option_selection",
+            PricingError::CouponRejected(_) => "coupon_rejected",
+            PricingError::Database(_) => "internal_error",
+        }
+    }
+
+    fn product_id(&self) -> Option<&str> {
+        match self {
+            PricingError::InvalidQuantity { product_id, .. }
+            | PricingError::UnknownOption { product_id, .. }
+            | PricingError::UnavailableOption { product_id, .. }
+            | PricingError::InvalidSelection { product_id, .. }
+            | PricingError::InsufficientStock { product_id, .. } => Some(product_id),
+            PricingError::UnknownProduct(id) | PricingError::UnavailableProduct(id) | PricingError::SoldOut(id) | PricingError::OffMenu(id) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for PricingError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            PricingError::Database(ref e) => {
+                tracing::error!("Pricing failed with database error: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            _ => StatusCode::BAD_REQUEST,
+        };
+        let mut body = serde_json::json!({
+            "error": self.code(),
+            "message": self.to_string(),
+            "product_id": self.product_id(),
+        });
+        if let PricingError::CouponRejected(ref rejection) = self {
+            body["coupon"] = serde_json::json!(rejection);
+            body["reason"] = serde_json::json!(rejection.reason);
+        }
+        (status, Json(body)).into_response()
+    }
+}
+
+/// Price a cart from the `products` table and apply an optional coupon or gift code.
+///
+/// Client-supplied prices are never used. Unknown or unavailable products or
+/// options, selections outside a group's min/max and non-positive quantities
+/// reject the whole cart. A code that does not apply leaves the cart at full
+/// price and is reported in `coupon_rejection`; per-customer coupon rules are
+/// only checked when `customer` is given.
+pub async fn price_cart(pool: &SqlitePool, cart: &[CartLine], code: Option<&str>, customer: Option<&Customer>) -> Result<PriceBreakdown, PricingError> {
+    if cart.is_empty() {
+        return Err(PricingError::EmptyCart);
+    }
+
+    let mut lines = Vec::with_capacity(cart.len());
+    let mut categories = HashMap::new();
+    for item in cart {
+        if item.quantity <= 0 || item.quantity > MAX_LINE_QUANTITY {
+            return Err(PricingError::InvalidQuantity { product_id: item.product_id.clone(), quantity: item.quantity });
+        }
+
+        let row = sqlx::query(r#"SELECT name, unit_amount, currency, is_available, category_id FROM products WHERE id = ?"#)
+            .bind(&item.product_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| PricingError::UnknownProduct(item.product_id.clone()))?;
+
+        let is_available: i64 = row.try_get("is_available").unwrap_or(1);
+        if is_available == 0 {
+            return Err(PricingError::UnavailableProduct(item.product_id.clone()));
+        }
+
+        categories.insert(item.product_id.clone(), row.try_get::<Option<String>, _>("category_id")?);
+        let options = price_options(pool, &item.product_id, &item.option_ids).await?;
+        let unit_amount: i64 = row.get::<i64, _>("unit_amount") + options.iter().map(|o| o.price_delta_cents).sum::<i64>();
+        lines.push(PricedLine {
+            product_id: item.product_id.clone(),
+            name: row.get("name"),
+            unit_amount,
+            options,
+            quantity: item.quantity,
+            line_total_cents: unit_amount * item.quantity,
+            currency: row.get("currency"),
+        });
+    }
+
+    let subtotal_cents: i64 = lines.iter().map(|l| l.line_total_cents).sum();
+
+    let (discount, coupon_rejection) = match code.map(str::trim).filter(|c| !c.is_empty()) {
+        Some(code) => match resolve_discount(pool, code, Some((&lines, &categories)), customer).await? {
+            Ok(discount) => (Some(discount), None),
+            Err(rejection) => (None, Some(rejection)),
+        },
+        None => (None, None),
+    };
+    let discount_cents = discount.as_ref().map(|d| d.discount_cents).unwrap_or(0);
+
+    Ok(PriceBreakdown {
+        lines,
+        subtotal_cents,
+        discount_cents,
+        total_cents: subtotal_cents - discount_cents,
+        currency: "EUR".into(),
+        coupon_code: discount.as_ref().map(|d| d.code.clone()),
+        discount,
+        coupon_rejection,
+    })
+}
+
+/// Priced lines and the category of each product in them.
+pub type CartScope<'a> = (&'a [PricedLine], &'a HashMap<String, Option<String>>);
+
+/// Look up a gift code (case-insensitive) or coupon and work out the discount
+/// it grants on `cart`. Without a cart only the code itself is checked (window,
+/// remaining uses) and the discount is zero. With a `customer`, the coupon's
+/// per-customer limits are checked too. The discount never exceeds the subtotal.
+pub async fn resolve_discount(pool: &SqlitePool, raw_code: &str, cart: Option<CartScope<'_>>, customer: Option<&Customer>) -> Result<Result<AppliedDiscount, CouponRejection>, sqlx::Error> {
+    let raw_code = raw_code.trim();
+    let subtotal_cents: i64 = cart.map(|(lines, _)| lines.iter().map(|l| l.line_total_cents).sum()).unwrap_or(0);
+
+    if let Some(g) = sqlx::query(r#"SELECT remaining_cents FROM gift_codes WHERE code = ? COLLATE NOCASE"#)
+        .bind(raw_code)
+        .fetch_optional(pool)
+        .await?
+    {
+        let remaining: i64 = g.get("remaining_cents");
+        if remaining <= 0 {
+            return Ok(Err(CouponRejection::new(raw_code, RejectionReason::UsedUp)));
+        }
+        // Balance other checkouts are about to spend is not available
+        let available = remaining - holds::held_gift_cents(pool, raw_code).await?;
+        if available <= 0 {
+            return Ok(Err(CouponRejection::new(raw_code, RejectionReason::Reserved)));
+        }
+        return Ok(Ok(AppliedDiscount {
+            code: raw_code.to_string(),
+            kind: DiscountKind::GiftCode,
+            percent_off: None,
+            amount_off: Some(available),
+            discount_cents: available.min(subtotal_cents),
+        }));
+    }
+
+    let Some(coupon) = coupons::load(pool, raw_code).await? else {
+        return Ok(Err(CouponRejection::new(raw_code, RejectionReason::UnknownCode)));
+    };
+    let now = chrono::Utc::now();
+    let mut discount_cents = match cart {
+        Some((lines, categories)) => coupon.discount_for(lines, categories, now),
+        None => coupon.check(now).map(|_| 0),
+    };
+    if let (Ok(_), Some(customer)) = (&discount_cents, customer) {
+        if let Err(reason) = redemptions::check_customer(pool, &coupon, customer).await? {
+            discount_cents = Err(reason);
+        }
+    }
+    Ok(match discount_cents {
+        Ok(discount_cents) => Ok(AppliedDiscount {
+            code: coupon.code,
+            kind: DiscountKind::Coupon,
+            percent_off: coupon.percent_off,
+            amount_off: coupon.amount_off,
+            discount_cents: discount_cents.min(subtotal_cents),
+        }),
+        Err(reason) => Err(CouponRejection {
+            min_subtotal_cents: coupon.min_subtotal_cents.filter(|_| reason == RejectionReason::BelowMinimum),
+            ..CouponRejection::new(&coupon.code, reason)
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{add_coupon, add_gift_card, add_product, mock_state};
+
+    fn cart(lines: &[(&str, i64)]) -> Vec<CartLine> {
+        lines.iter().map(|(id, quantity)| CartLine { product_id: id.to_string(), quantity: *quantity, option_ids: Vec::new() }).collect()
+    }
+
+    async fn menu() -> SqlitePool {
+        let pool = mock_state().await.pool.clone();
+        add_product(&pool, "pho", 1250).await;
+        add_product(&pool, "rolls", 335).await;
+        pool
+    }
+
+    #[tokio::test]
+    async fn prices_come_from_the_products_table() {
+        let pool = menu().await;
+        let priced = price_cart(&pool, &cart(&[("pho", 1), ("rolls", 2)]), None, None).await.unwrap();
+        assert_eq!(priced.lines.iter().map(|l| (l.unit_amount, l.line_total_cents)).collect::<Vec<_>>(), vec![(1250, 1250), (335, 670)]);
+        assert_eq!((priced.subtotal_cents, priced.discount_cents, priced.total_cents), (1920, 0, 1920));
+    }
+
+    #[tokio::test]
+    async fn invalid_carts_are_refused() {
+        let pool = menu().await;
+        assert!(matches!(price_cart(&pool, &[], None, None).await, Err(PricingError::EmptyCart)));
+        assert!(matches!(price_cart(&pool, &cart(&[("pho", 0)]), None, None).await, Err(PricingError::InvalidQuantity { .. })));
+        assert!(matches!(price_cart(&pool, &cart(&[("pho", MAX_LINE_QUANTITY + 1)]), None, None).await, Err(PricingError::InvalidQuantity { .. })));
+        assert!(matches!(price_cart(&pool, &cart(&[("steak", 1)]), None, None).await, Err(PricingError::UnknownProduct(_))));
+
+        sqlx::query(r#"UPDATE products SET is_available = 0 WHERE id = 'rolls'"#).execute(&pool).await.unwrap();
+        assert!(matches!(price_cart(&pool, &cart(&[("rolls", 1)]), None, None).await, Err(PricingError::UnavailableProduct(_))));
+    }
+
+    #[tokio::test]
+    async fn coupon_discount_is_taken_off_the_total() {
+        let pool = menu().await;
+        add_coupon(&pool, "TEN", 10, 3).await;
+        let priced = price_cart(&pool, &cart(&[("pho", 1), ("rolls", 2)]), Some(" ten "), None).await.unwrap();
+        assert_eq!((priced.discount_cents, priced.total_cents), (192, 1728));
+        let discount = priced.discount.unwrap();
+        assert_eq!((discount.code.as_str(), discount.kind), ("TEN", DiscountKind::Coupon));
+    }
+
+    #[tokio::test]
+    async fn gift_code_covers_at_most_the_subtotal() {
+        let pool = menu().await;
+        add_gift_card(&pool, "GIFTCARD", 5000).await;
+        let priced = price_cart(&pool, &cart(&[("pho", 1)]), Some("giftcard"), None).await.unwrap();
+        assert_eq!((priced.discount_cents, priced.total_cents), (1250, 0));
+        assert_eq!(priced.discount.unwrap().kind, DiscountKind::GiftCode);
+    }
+
+    #[tokio::test]
+    async fn rejected_code_is_reported_instead_of_applied() {
+        let pool = menu().await;
+        add_coupon(&pool, "GONE", 10, 0).await;
+        for (code, reason) in [("NOPE", RejectionReason::UnknownCode), ("GONE", RejectionReason::UsedUp)] {
+            let priced = price_cart(&pool, &cart(&[("pho", 1)]), Some(code), None).await.unwrap();
+            assert_eq!(priced.total_cents, 1250);
+            assert_eq!(priced.coupon_rejection.map(|r| r.reason), Some(reason));
+        }
+    }
+}
@@ -0,0 +1,642 @@
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection};
+use uuid::Uuid;
+
+use crate::email::{reservation_cancelled_html, reservation_confirmation_html, reservation_reminder_html, send_html_email};
+use crate::schedule::{db_timestamp, parse_time, windows_for};
+use crate::state::AppState;
+
+/// Online bookings have to be made at least this far ahead.
+const BOOKING_LEAD_MINUTES: i64 = 30;
+
+/// Reminders go out this long before the booking, for bookings made earlier than that.
+const REMINDER_HOURS: i64 = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReservationStatus {
+    Booked,
+    Seated,
+    Cancelled,
+    NoShow,
+}
+
+impl ReservationStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReservationStatus::Booked => "booked",
+            ReservationStatus::Seated => "seated",
+            ReservationStatus::Cancelled => "cancelled",
+            ReservationStatus::NoShow => "no_show",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [ReservationStatus::Booked, ReservationStatus::Seated, ReservationStatus::Cancelled, ReservationStatus::NoShow]
+            .into_iter()
+            .find(|st| st.as_str() == s)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Reservation {
+    pub id: String,
+    pub table_id: Option<String>,
+    pub table_name: Option<String>,
+    pub name: String,
+    pub email: String,
+    pub phone: Option<String>,
+    pub party_size: i64,
+    pub starts_at: String,
+    pub ends_at: String,
+    pub status: String,
+    pub notes: Option<String>,
+    pub cancel_reason: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BookingRequest {
+    pub name: String,
+    pub email: String,
+    pub phone: Option<String>,
+    pub party_size: i64,
+    /// `starts_at` of an available time from the availability endpoint.
+    pub starts_at: String,
+    pub notes: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MoveRequest {
+    pub starts_at: Option<String>,
+    pub table_id: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AvailableTime {
+    /// Local `HH:MM`.
+    pub time: String,
+    pub starts_at: String,
+    pub available: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReservationError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{0}")]
+    Unavailable(String),
+    #[error("reservation {0} not found")]
+    NotFound(String),
+    #[error("reservation is {0}")]
+    WrongStatus(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl ReservationError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ReservationError::Invalid(_) => "invalid_reservation",
+            ReservationError::Unavailable(_) => "not_available",
+            ReservationError::NotFound(_) => "not_found",
+            ReservationError::WrongStatus(_) => "invalid_status_change",
+            ReservationError::Database(_) => "internal_error",
+        }
+    }
+}
+
+impl IntoResponse for ReservationError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            ReservationError::Invalid(_) => StatusCode::BAD_REQUEST,
+            ReservationError::Unavailable(_) | ReservationError::WrongStatus(_) => StatusCode::CONFLICT,
+            ReservationError::NotFound(_) => StatusCode::NOT_FOUND,
+            ReservationError::Database(ref e) => {
+                tracing::error!("Reservation failed with database error: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, Json(serde_json::json!({ "error": self.code(), "message": self.to_string() }))).into_response()
+    }
+}
+
+fn parse_start(s: &str) -> Result<DateTime<Utc>, ReservationError> {
+    DateTime::parse_from_rfc3339(s.trim())
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|_| ReservationError::Invalid(format!("'{}' is not a valid time", s)))
+}
+
+fn length(state: &AppState) -> Duration {
+    Duration::minutes(state.schedule.reservation_minutes)
+}
+
+/// Link from the emails to the page where the guest can view or cancel.
+pub fn manage_url(app_url: &str, id: &str, token: &str) -> String {
+    format!("{}/reservations/{}?t={}", app_url.trim_end_matches('/'), id, token)
+}
+
+/// Times a table can be booked on a local day: every slot step of a service
+/// window that starts before the last order time and ends by closing.
+async fn start_times(state: &AppState, date: NaiveDate) -> Result<Vec<DateTime<Utc>>, sqlx::Error> {
+    let cfg = &state.schedule;
+    let step = Duration::minutes(cfg.slot_minutes);
+    let length = length(state);
+    let mut starts = Vec::new();
+    for window in windows_for(&state.pool, date).await? {
+        let mut time = window.opens;
+        while time < window.last_order && time + length <= window.closes && time >= window.opens {
+            if let Some(at) = cfg.to_utc(date, time) {
+                starts.push(at);
+            }
+            time += step;
+        }
+    }
+    Ok(starts)
+}
+
+/// Smallest active table that fits the party and is free for the whole stay.
+async fn free_table(conn: &mut SqliteConnection, party: i64, start: &str, end: &str, exclude: Option<&str>) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar(r#"
+        SELECT t.id FROM tables t
+        WHERE t.active = 1 AND t.seats >= ?1 AND t.min_party <= ?1
+          AND NOT EXISTS (
+            SELECT 1 FROM reservations r
+            WHERE r.table_id = t.id AND r.status IN ('booked', 'seated')
+              AND r.starts_at < ?3 AND r.ends_at > ?2 AND r.id != COALESCE(?4, '')
+          )
+        ORDER BY t.seats, t.name
+        LIMIT 1
+    "#)
+        .bind(party)
+        .bind(start)
+        .bind(end)
+        .bind(exclude)
+        .fetch_optional(conn)
+        .await
+}
+
+async fn table_is_free(conn: &mut SqliteConnection, table_id: &str, start: &str, end: &str, exclude: &str) -> Result<bool, sqlx::Error> {
+    let clash: Option<String> = sqlx::query_scalar(r#"SELECT id FROM reservations WHERE table_id = ? AND status IN ('booked', 'seated') AND starts_at < ? AND ends_at > ? AND id != ? LIMIT 1"#)
+        .bind(table_id)
+        .bind(end)
+        .bind(start)
+        .bind(exclude)
+        .fetch_optional(conn)
+        .await?;
+    Ok(clash.is_none())
+}
+
+/// Whether another booking of `party` guests starting at `start` stays within
+/// every pacing rule that covers that time.
+async fn within_rules(conn: &mut SqliteConnection, state: &AppState, start: DateTime<Utc>, party: i64) -> Result<bool, sqlx::Error> {
+    let cfg = &state.schedule;
+    let date = cfg.local_date(start);
+    let time = cfg.local_time(start).format("%H:%M").to_string();
+    let weekday = date.weekday().num_days_from_monday() as i64;
+
+    let rules = sqlx::query(r#"SELECT starts_at, ends_at, max_bookings, max_covers FROM reservation_rules WHERE (weekday IS NULL OR weekday = ?) AND starts_at <= ? AND ends_at > ?"#)
+        .bind(weekday)
+        .bind(&time)
+        .bind(&time)
+        .fetch_all(&mut *conn)
+        .await?;
+    for rule in rules {
+        let bounds = |col: &str| rule.try_get::<String, _>(col).ok().and_then(|t| parse_time(&t)).and_then(|t| cfg.to_utc(date, t));
+        let (Some(from), Some(to)) = (bounds("starts_at"), bounds("ends_at")) else { continue };
+        let row = sqlx::query(r#"SELECT COUNT(*) AS bookings, COALESCE(SUM(party_size), 0) AS covers FROM reservations WHERE status IN ('booked', 'seated') AND starts_at >= ? AND starts_at < ?"#)
+            .bind(db_timestamp(from))
+            .bind(db_timestamp(to))
+            .fetch_one(&mut *conn)
+            .await?;
+        let bookings: i64 = row.try_get("bookings")?;
+        let covers: i64 = row.try_get("covers")?;
+        if rule.try_get::<Option<i64>, _>("max_bookings")?.is_some_and(|max| bookings + 1 > max) {
+            return Ok(false);
+        }
+        if rule.try_get::<Option<i64>, _>("max_covers")?.is_some_and(|max| covers + party > max) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Bookable times of a local day for a party.
+pub async fn availability(state: &AppState, date: NaiveDate, party: i64) -> Result<Vec<AvailableTime>, ReservationError> {
+    if party < 1 {
+        return Err(ReservationError::Invalid("party_size must be at least 1".into()));
+    }
+    let earliest = Utc::now() + Duration::minutes(BOOKING_LEAD_MINUTES);
+    let starts = start_times(state, date).await?;
+    let mut conn = state.pool.acquire().await?;
+    let mut times = Vec::new();
+    for start in starts {
+        if start < earliest {
+            continue;
+        }
+        let (from, to) = (db_timestamp(start), db_timestamp(start + length(state)));
+        let available = free_table(&mut conn, party, &from, &to, None).await?.is_some()
+            && within_rules(&mut conn, state, start, party).await?;
+        times.push(AvailableTime {
+            time: state.schedule.local_time(start).format("%H:%M").to_string(),
+            starts_at: from,
+            available,
+        });
+    }
+    Ok(times)
+}
+
+const SELECT_RESERVATION: &str = r#"
+    SELECT r.id, r.table_id, t.name AS table_name, r.name, r.email, r.phone, r.party_size, r.starts_at, r.ends_at,
+           r.status, r.notes, r.cancel_reason, r.created_at, r.manage_token
+    FROM reservations r
+    LEFT JOIN tables t ON t.id = r.table_id
+"#;
+
+fn from_row(r: &sqlx::sqlite::SqliteRow) -> Reservation {
+    Reservation {
+        id: r.try_get("id").unwrap_or_default(),
+        table_id: r.try_get("table_id").ok().flatten(),
+        table_name: r.try_get("table_name").ok().flatten(),
+        name: r.try_get("name").unwrap_or_default(),
+        email: r.try_get("email").unwrap_or_default(),
+        phone: r.try_get("phone").ok().flatten(),
+        party_size: r.try_get("party_size").unwrap_or(0),
+        starts_at: r.try_get("starts_at").unwrap_or_default(),
+        ends_at: r.try_get("ends_at").unwrap_or_default(),
+        status: r.try_get("status").unwrap_or_default(),
+        notes: r.try_get("notes").ok().flatten(),
+        cancel_reason: r.try_get("cancel_reason").ok().flatten(),
+        created_at: r.try_get("created_at").unwrap_or_default(),
+    }
+}
+
+/// A reservation and its manage token.
+async fn load(state: &AppState, id: &str) -> Result<(Reservation, String), ReservationError> {
+    let row = sqlx::query(&format!("{} WHERE r.id = ?", SELECT_RESERVATION))
+        .bind(id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| ReservationError::NotFound(id.to_string()))?;
+    Ok((from_row(&row), row.try_get("manage_token")?))
+}
+
+/// A reservation for the guest holding its manage token. A wrong token looks
+/// exactly like an unknown reservation.
+pub async fn load_for_guest(state: &AppState, id: &str, token: &str) -> Result<Reservation, ReservationError> {
+    let (reservation, expected) = load(state, id).await?;
+    if !crate::orders::tracking::token_matches(token, &expected) {
+        return Err(ReservationError::NotFound(id.to_string()));
+    }
+    Ok(reservation)
+}
+
+/// Reservations starting on a local day, earliest first.
+pub async fn list_day(state: &AppState, date: NaiveDate) -> Result<Vec<Reservation>, ReservationError> {
+    let cfg = &state.schedule;
+    let bound = |d: NaiveDate| cfg.to_utc(d, NaiveTime::MIN).map(db_timestamp).unwrap_or_else(|| format!("{}T00:00:00.000Z", d));
+    let next = date.succ_opt().unwrap_or(date);
+    let rows = sqlx::query(&format!("{} WHERE r.starts_at >= ? AND r.starts_at < ? ORDER BY r.starts_at, t.name", SELECT_RESERVATION))
+        .bind(bound(date))
+        .bind(bound(next))
+        .fetch_all(&state.pool)
+        .await?;
+    Ok(rows.iter().map(from_row).collect())
+}
+
+fn local_label(state: &AppState, starts_at: &str) -> String {
+    DateTime::parse_from_rfc3339(starts_at)
+        .map(|t| t.with_timezone(&state.schedule.timezone).format("%A, %-d %B %Y at %H:%M").to_string())
+        .unwrap_or_else(|_| starts_at.to_string())
+}
+
+async fn send_confirmation(state: &AppState, reservation: &Reservation, token: &str, changed: bool) {
+    let html_body = reservation_confirmation_html(
+        &reservation.name,
+        reservation.party_size,
+        &local_label(state, &reservation.starts_at),
+        &manage_url(&state.app_url, &reservation.id, token),
+        changed,
+    );
+    let subject = if changed { "Your reservation has been changed" } else { "Your reservation is confirmed" };
+    if let Err(e) = send_html_email(state, &reservation.email, subject, &html_body).await {
+        tracing::error!("Failed to send reservation email to {}: {:?}", reservation.email, e);
+    }
+}
+
+/// Book a table. The time must be one `availability` offers; pacing rules and
+/// table conflicts are checked again inside the transaction.
+pub async fn book(state: &AppState, req: &BookingRequest) -> Result<Reservation, ReservationError> {
+    let name = req.name.trim();
+    let email = req.email.trim();
+    if name.is_empty() {
+        return Err(ReservationError::Invalid("name is required".into()));
+    }
+    if !email.contains('@') {
+        return Err(ReservationError::Invalid("a valid email is required".into()));
+    }
+    if req.party_size < 1 {
+        return Err(ReservationError::Invalid("party_size must be at least 1".into()));
+    }
+
+    let start = parse_start(&req.starts_at)?;
+    if start < Utc::now() + Duration::minutes(BOOKING_LEAD_MINUTES) {
+        return Err(ReservationError::Unavailable("this time can no longer be booked online".into()));
+    }
+    if !start_times(state, state.schedule.local_date(start)).await?.contains(&start) {
+        return Err(ReservationError::Unavailable("we do not take reservations at this time".into()));
+    }
+    let (from, to) = (db_timestamp(start), db_timestamp(start + length(state)));
+
+    let id = Uuid::new_v4().to_string();
+    let token = crate::orders::tracking::new_token();
+    let mut tx = state.pool.begin().await?;
+    if !within_rules(&mut tx, state, start, req.party_size).await? {
+        return Err(ReservationError::Unavailable("this time is fully booked".into()));
+    }
+    let Some(table_id) = free_table(&mut tx, req.party_size, &from, &to, None).await? else {
+        return Err(ReservationError::Unavailable(format!("no table for {} is free at this time", req.party_size)));
+    };
+    sqlx::query(r#"INSERT INTO reservations (id, table_id, name, email, phone, party_size, starts_at, ends_at, notes, manage_token) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#)
+        .bind(&id)
+        .bind(&table_id)
+        .bind(name)
+        .bind(email)
+        .bind(req.phone.as_deref().map(str::trim).filter(|p| !p.is_empty()))
+        .bind(req.party_size)
+        .bind(&from)
+        .bind(&to)
+        .bind(req.notes.as_deref().map(str::trim).filter(|n| !n.is_empty()))
+        .bind(&token)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    let (reservation, _) = load(state, &id).await?;
+    tracing::info!("Reservation {} booked for {} guests at {} on {}", id, reservation.party_size, reservation.starts_at, table_id);
+    send_confirmation(state, &reservation, &token, false).await;
+    Ok(reservation)
+}
+
+async fn set_status(state: &AppState, id: &str, from: ReservationStatus, to: ReservationStatus, reason: Option<&str>) -> Result<(), ReservationError> {
+    let updated = sqlx::query(r#"UPDATE reservations SET status = ?, cancel_reason = COALESCE(?, cancel_reason), updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now') WHERE id = ? AND status = ?"#)
+        .bind(to.as_str())
+        .bind(reason)
+        .bind(id)
+        .bind(from.as_str())
+        .execute(&state.pool)
+        .await?
+        .rows_affected();
+    if updated == 0 {
+        let (current, _) = load(state, id).await?;
+        return Err(ReservationError::WrongStatus(current.status));
+    }
+    Ok(())
+}
+
+/// Cancel a booking, by the guest or by the restaurant, and tell the guest.
+pub async fn cancel(state: &AppState, id: &str, reason: Option<&str>, by_restaurant: bool) -> Result<Reservation, ReservationError> {
+    set_status(state, id, ReservationStatus::Booked, ReservationStatus::Cancelled, reason).await?;
+    let (reservation, _) = load(state, id).await?;
+    tracing::info!("Reservation {} cancelled by {}", id, if by_restaurant { "the restaurant" } else { "the guest" });
+
+    let html_body = reservation_cancelled_html(&reservation.name, &local_label(state, &reservation.starts_at), reason, by_restaurant);
+    if let Err(e) = send_html_email(state, &reservation.email, "Your reservation has been cancelled", &html_body).await {
+        tracing::error!("Failed to send reservation cancellation to {}: {:?}", reservation.email, e);
+    }
+    Ok(reservation)
+}
+
+pub async fn seat(state: &AppState, id: &str) -> Result<Reservation, ReservationError> {
+    set_status(state, id, ReservationStatus::Booked, ReservationStatus::Seated, None).await?;
+    Ok(load(state, id).await?.0)
+}
+
+pub async fn mark_no_show(state: &AppState, id: &str) -> Result<Reservation, ReservationError> {
+    set_status(state, id, ReservationStatus::Booked, ReservationStatus::NoShow, None).await?;
+    Ok(load(state, id).await?.0)
+}
+
+/// Move a booking to another time and/or table. Staff may overrule pacing
+/// rules, but not opening hours or double-booked tables.
+pub async fn move_booking(state: &AppState, id: &str, req: &MoveRequest) -> Result<Reservation, ReservationError> {
+    let (current, token) = load(state, id).await?;
+    if ReservationStatus::parse(&current.status) != Some(ReservationStatus::Booked) {
+        return Err(ReservationError::WrongStatus(current.status));
+    }
+
+    let start = match req.starts_at.as_deref() {
+        Some(s) => {
+            let start = parse_start(s)?;
+            if !start_times(state, state.schedule.local_date(start)).await?.contains(&start) {
+                return Err(ReservationError::Unavailable("the restaurant does not take reservations at this time".into()));
+            }
+            start
+        }
+        None => parse_start(&current.starts_at)?,
+    };
+    let (from, to) = (db_timestamp(start), db_timestamp(start + length(state)));
+
+    let mut tx = state.pool.begin().await?;
+    let table_id = match req.table_id.as_deref().or(current.table_id.as_deref()) {
+        Some(table_id) => {
+            let seats: Option<i64> = sqlx::query_scalar(r#"SELECT seats FROM tables WHERE id = ? AND active = 1"#)
+                .bind(table_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            match seats {
+                None => return Err(ReservationError::Invalid(format!("table {} does not exist", table_id))),
+                Some(seats) if seats < current.party_size => {
+                    return Err(ReservationError::Unavailable(format!("table {} seats only {}", table_id, seats)))
+                }
+                Some(_) => {}
+            }
+            if table_is_free(&mut tx, table_id, &from, &to, id).await? {
+                Some(table_id.to_string())
+            } else if req.table_id.is_none() {
+                // The old table is taken at the new time: pick another one
+                free_table(&mut tx, current.party_size, &from, &to, Some(id)).await?
+            } else {
+                None
+            }
+        }
+        None => free_table(&mut tx, current.party_size, &from, &to, Some(id)).await?,
+    };
+    let Some(table_id) = table_id else {
+        return Err(ReservationError::Unavailable("no suitable table is free at this time".into()));
+    };
+    sqlx::query(r#"UPDATE reservations SET table_id = ?, starts_at = ?, ends_at = ?, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now') WHERE id = ?"#)
+        .bind(&table_id)
+        .bind(&from)
+        .bind(&to)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    let (moved, _) = load(state, id).await?;
+    tracing::info!("Reservation {} moved to {} on {}", id, moved.starts_at, table_id);
+    if moved.starts_at != current.starts_at {
+        send_confirmation(state, &moved, &token, true).await;
+    }
+    Ok(moved)
+}
+
+/// Send the day-before reminder for bookings starting within the next
+/// `REMINDER_HOURS`. A reminder that cannot be sent is released again so the
+/// next run retries it.
+pub async fn send_reminders(state: &AppState) -> anyhow::Result<usize> {
+    let now = Utc::now();
+    let ids: Vec<String> = sqlx::query_scalar(r#"
+        SELECT id FROM reservations
+        WHERE status = 'booked' AND reminder_sent_at IS NULL
+          AND starts_at > ? AND starts_at <= ?
+          AND datetime(created_at) <= datetime(starts_at, ?)
+        ORDER BY starts_at
+    "#)
+        .bind(db_timestamp(now))
+        .bind(db_timestamp(now + Duration::hours(REMINDER_HOURS)))
+        .bind(format!("-{} hours", REMINDER_HOURS))
+        .fetch_all(&state.pool)
+        .await?;
+
+    let mut sent = 0;
+    let mut failed = Vec::new();
+    for id in ids {
+        let claimed = sqlx::query(r#"UPDATE reservations SET reminder_sent_at = ? WHERE id = ? AND reminder_sent_at IS NULL"#)
+            .bind(db_timestamp(now))
+            .bind(&id)
+            .execute(&state.pool)
+            .await?
+            .rows_affected();
+        if claimed == 0 {
+            continue;
+        }
+        let (reservation, token) = load(state, &id).await?;
+        let html_body = reservation_reminder_html(
+            &reservation.name,
+            reservation.party_size,
+            &local_label(state, &reservation.starts_at),
+            &manage_url(&state.app_url, &reservation.id, &token),
+        );
+        match send_html_email(state, &reservation.email, "Reminder: your reservation", &html_body).await {
+            Ok(()) => sent += 1,
+            Err(e) => {
+                sqlx::query(r#"UPDATE reservations SET reminder_sent_at = NULL WHERE id = ?"#).bind(&id).execute(&state.pool).await?;
+                failed.push(format!("{}: {}", id, e));
+            }
+        }
+    }
+    if !failed.is_empty() {
+        anyhow::bail!("{} reminder(s) could not be sent ({})", failed.len(), failed.join("; "));
+    }
+    Ok(sent)
+}
+
+/// Mark bookings nobody was seated for as `no_show` once the grace period
+/// after their start has passed.
+pub async fn mark_no_shows(state: &AppState) -> Result<Vec<String>, sqlx::Error> {
+    let cutoff = Utc::now() - Duration::minutes(state.schedule.no_show_grace_minutes);
+    sqlx::query_scalar(r#"UPDATE reservations SET status = 'no_show', updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now') WHERE status = 'booked' AND starts_at <= ? RETURNING id"#)
+        .bind(db_timestamp(cutoff))
+        .fetch_all(&state.pool)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_state;
+
+    /// 17:30 on a Monday in Berlin, far enough ahead to be bookable online.
+    const DINNER: &str = "2030-06-03T15:30:00.000Z";
+
+    fn request(party_size: i64, starts_at: &str) -> BookingRequest {
+        BookingRequest { name: "Guest".into(), email: "guest@example.com".into(), phone: None, party_size, starts_at: starts_at.into(), notes: None }
+    }
+
+    #[tokio::test]
+    async fn booking_takes_the_smallest_free_table() {
+        let state = mock_state().await;
+
+        let tables: Vec<Option<String>> = [
+            book(&state, &request(2, DINNER)).await.unwrap(),
+            book(&state, &request(2, DINNER)).await.unwrap(),
+            book(&state, &request(2, DINNER)).await.unwrap(),
+            book(&state, &request(5, DINNER)).await.unwrap(),
+        ]
+        .into_iter()
+        .map(|r| r.table_id)
+        .collect();
+        assert_eq!(tables, ["table-1", "table-2", "table-3", "table-6"].map(|t| Some(t.to_string())));
+        assert_eq!(book(&state, &request(2, DINNER)).await.unwrap().ends_at, "2030-06-03T17:00:00.000Z");
+        assert!(matches!(book(&state, &request(9, DINNER)).await, Err(ReservationError::Unavailable(_))));
+
+        // Times outside the service windows and odd minutes are not offered
+        assert!(matches!(book(&state, &request(2, "2030-06-03T13:30:00.000Z")).await, Err(ReservationError::Unavailable(_))));
+        assert!(matches!(book(&state, &request(2, "2030-06-03T15:35:00.000Z")).await, Err(ReservationError::Unavailable(_))));
+        assert!(matches!(book(&state, &request(2, "2020-06-01T15:30:00.000Z")).await, Err(ReservationError::Unavailable(_))));
+        assert!(matches!(book(&state, &request(0, DINNER)).await, Err(ReservationError::Invalid(_))));
+    }
+
+    #[tokio::test]
+    async fn pacing_rules_limit_bookings_and_covers() {
+        let state = mock_state().await;
+        sqlx::query(r#"INSERT INTO reservation_rules (id, weekday, starts_at, ends_at, max_bookings, max_covers) VALUES ('rule-1', 0, '17:00', '19:00', 3, 8)"#)
+            .execute(&state.pool)
+            .await
+            .unwrap();
+
+        book(&state, &request(4, DINNER)).await.unwrap();
+        book(&state, &request(3, "2030-06-03T16:00:00.000Z")).await.unwrap();
+        assert!(matches!(book(&state, &request(2, DINNER)).await, Err(ReservationError::Unavailable(_))), "9 covers");
+        book(&state, &request(1, DINNER)).await.unwrap();
+        assert!(matches!(book(&state, &request(1, DINNER)).await, Err(ReservationError::Unavailable(_))), "4 bookings");
+        book(&state, &request(2, "2030-06-03T17:00:00.000Z")).await.unwrap();
+
+        let times = availability(&state, NaiveDate::from_ymd_opt(2030, 6, 3).unwrap(), 1).await.unwrap();
+        let open = |time: &str| times.iter().find(|t| t.time == time).map(|t| t.available);
+        assert_eq!((open("17:30"), open("19:00"), open("16:00")), (Some(false), Some(true), None));
+    }
+
+    #[tokio::test]
+    async fn guests_need_the_token_and_only_booked_tables_change() {
+        let state = mock_state().await;
+        let booked = book(&state, &request(2, DINNER)).await.unwrap();
+        let (_, token) = load(&state, &booked.id).await.unwrap();
+        assert!(matches!(load_for_guest(&state, &booked.id, "wrong").await, Err(ReservationError::NotFound(_))));
+        assert_eq!(load_for_guest(&state, &booked.id, &token).await.unwrap().id, booked.id);
+
+        let other = book(&state, &request(2, DINNER)).await.unwrap();
+        let to_taken = MoveRequest { starts_at: None, table_id: other.table_id.clone() };
+        assert!(matches!(move_booking(&state, &booked.id, &to_taken).await, Err(ReservationError::Unavailable(_))));
+        let later = MoveRequest { starts_at: Some("2030-06-03T17:30:00.000Z".into()), table_id: None };
+        let moved = move_booking(&state, &booked.id, &later).await.unwrap();
+        assert_eq!((moved.table_id, moved.starts_at.as_str()), (booked.table_id, "2030-06-03T17:30:00.000Z"));
+
+        let cancelled = cancel(&state, &booked.id, Some("ill"), false).await.unwrap();
+        assert_eq!((cancelled.status.as_str(), cancelled.cancel_reason.as_deref()), ("cancelled", Some("ill")));
+        assert!(matches!(seat(&state, &booked.id).await, Err(ReservationError::WrongStatus(s)) if s == "cancelled"));
+    }
+
+    #[tokio::test]
+    async fn unseated_bookings_become_no_shows_after_the_grace_period() {
+        let state = mock_state().await;
+        for (id, minutes_ago, status) in [("late", 45, "booked"), ("seated", 45, "seated"), ("soon", 10, "booked")] {
+            sqlx::query(r#"INSERT INTO reservations (id, table_id, name, email, party_size, starts_at, ends_at, status, manage_token) VALUES (?, 'table-1', 'Guest', 'guest@example.com', 2, ?, ?, ?, ?)"#)
+                .bind(id)
+                .bind(db_timestamp(Utc::now() - Duration::minutes(minutes_ago)))
+                .bind(db_timestamp(Utc::now() + Duration::minutes(45)))
+                .bind(status)
+                .bind(id)
+                .execute(&state.pool)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(mark_no_shows(&state).await.unwrap(), vec!["late".to_string()]);
+        assert!(mark_no_shows(&state).await.unwrap().is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use axum::{routing::{get, post, delete, patch}, extract::{Query, Path}, Json, Router, http::HeaderMap, Extension};
+use axum::{routing::{get, post, delete, patch}, extract::{Query, Path}, Json, Router, http::{HeaderMap, StatusCode}, response::{IntoResponse, Response}, Extension};
 use serde::Serialize;
 use serde::Deserialize;
 use jsonwebtoken::{DecodingKey, Validation, decode};
@@ -7,12 +7,17 @@ use sqlx::{Row, Column, FromRow};
 use argon2::password_hash::PasswordHasher;
 
 use crate::state::AppState;
+use crate::catalog::{allergens, categories, images};
+use crate::coupons;
+use crate::gift_cards;
+use crate::routes::webhooks::EventOutcome;
+use crate::orders::refund::{self, RefundOutcome, RefundRequest};
 
 #[derive(Deserialize)]
 #[allow(dead_code)]
 struct Claims { sub: String, email: String, exp: usize }
 
-fn extract_email_from_token(headers: &HeaderMap, state: &AppState) -> Option<String> {
+pub(crate) fn extract_email_from_token(headers: &HeaderMap, state: &AppState) -> Option<String> {
     let auth = headers.get(axum::http::header::AUTHORIZATION).and_then(|v| v.to_str().ok());
     if let Some(bearer) = auth {
         if let Some(token) = bearer.strip_prefix("Bearer ") {
@@ -24,26 +29,43 @@ fn extract_email_from_token(headers: &HeaderMap, state: &AppState) -> Option<Str
     None
 }
 
-async fn is_admin_user(email: &str, state: &AppState) -> bool {
+pub(crate) async fn is_admin_user(email: &str, state: &AppState) -> bool {
+    user_role(email, state).await.as_deref() == Some("admin")
+}
+
+/// Staff run the kitchen workflow; admins can do everything staff can.
+pub(crate) async fn is_staff_user(email: &str, state: &AppState) -> bool {
+    matches!(user_role(email, state).await.as_deref(), Some("staff") | Some("admin"))
+}
+
+/// Email of the signed-in admin, or the 401/403 to answer with.
+pub(crate) async fn require_admin(headers: &HeaderMap, state: &AppState) -> Result<String, Response> {
+    let email = extract_email_from_token(headers, state).ok_or_else(|| StatusCode::UNAUTHORIZED.into_response())?;
+    if !is_admin_user(&email, state).await { return Err(StatusCode::FORBIDDEN.into_response()); }
+    Ok(email)
+}
+
+/// Email of the signed-in staff member (or admin), or the 401/403 to answer with.
+pub(crate) async fn require_staff(headers: &HeaderMap, state: &AppState) -> Result<String, Response> {
+    let email = extract_email_from_token(headers, state).ok_or_else(|| StatusCode::UNAUTHORIZED.into_response())?;
+    if !is_staff_user(&email, state).await { return Err(StatusCode::FORBIDDEN.into_response()); }
+    Ok(email)
+}
+
+async fn user_role(email: &str, state: &AppState) -> Option<String> {
     let result = sqlx::query("SELECT role FROM users WHERE email = ?")
         .bind(email)
         .fetch_optional(&state.pool)
         .await;
 
     match result {
-        Ok(Some(row)) => {
-            let role: String = row.get("role");
-            role == "admin"
-        }
-        _ => false,
-        }
+        Ok(Some(row)) => row.try_get("role").ok(),
+        _ => None,
     }
-
-#[allow(dead_code)]
-fn require_admin(headers: &HeaderMap, state: &AppState) -> bool {
-    extract_email_from_token(headers, state).is_some()
 }
 
+const ROLES: [&str; 3] = ["customer", "staff", "admin"];
+
 #[derive(Serialize)]
 struct Tables { tables: Vec<String> }
 
@@ -56,6 +78,8 @@ pub fn router() -> Router {
         .route("/api/admin/query", get(query_table))
         .route("/api/admin/coupons", get(list_coupons).post(add_coupon))
         .route("/api/admin/coupons/:code", delete(delete_coupon))
+        .route("/api/admin/coupons/redemptions", get(coupon_usage))
+        .route("/api/admin/coupons/:code/redemptions", get(coupon_redemptions))
         .route("/api/admin/columns", get(columns_for_table))
         .route("/api/admin/insert", post(generic_insert))
         .route("/api/admin/delete", post(generic_delete))
@@ -64,12 +88,18 @@ pub fn router() -> Router {
         // Dashboard endpoints
         .route("/api/admin/stats", get(get_stats))
         .route("/api/admin/orders", get(get_orders))
+        .route("/api/admin/orders/:id/refunds", get(list_order_refunds).post(refund_order))
         .route("/api/admin/pending-orders", get(get_pending_orders))
         .route("/api/admin/pending-orders/:order_id", delete(delete_pending_order))
+        .route("/api/admin/pending-orders/:order_id/payment-status", get(pending_order_payment_status))
         .route("/api/admin/cleanup", post(cleanup_stale_pending))
+        .route("/api/admin/webhook-events", get(list_webhook_events))
+        .route("/api/admin/webhook-events/:id/replay", post(replay_webhook_event))
         .route("/api/admin/products", get(list_products).post(add_product))
         .route("/api/admin/products/:id", patch(update_product).delete(delete_product))
         .route("/api/admin/gift-coupons", get(list_gift_coupons))
+        .route("/api/admin/gift-coupons/:code/transactions", get(gift_card_history))
+        .route("/api/admin/gift-coupons/:code/adjustments", post(adjust_gift_card))
 }
 
 async fn list_tables(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap) -> Result<Json<Tables>, axum::http::StatusCode> {
@@ -87,7 +117,7 @@ async fn list_tables(Extension(state): Extension<Arc<AppState>>, headers: Header
 async fn query_table(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Query(params): Query<QueryParams>) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
     let email = extract_email_from_token(&headers, &state).ok_or(axum::http::StatusCode::UNAUTHORIZED)?;
     if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN); }
-    let limit = params.limit.unwrap_or(50).max(1).min(500);
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
     let sql = format!("SELECT * FROM {} LIMIT {}", params.table.replace('"', ""), limit);
     let rows = sqlx::query(&sql).fetch_all(&state.pool).await.map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
     let mut out = Vec::new();
@@ -119,21 +149,34 @@ async fn query_table(Extension(state): Extension<Arc<AppState>>, headers: Header
     Ok(Json(serde_json::Value::Array(out)))
 }
 
-#[derive(Deserialize)]
-struct AddCouponPayload { code: String, percent_off: Option<i64>, amount_off: Option<i64>, remaining_uses: i64 }
+async fn add_coupon(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<coupons::CouponInput>) -> Result<Json<serde_json::Value>, axum::response::Response> {
+    use axum::response::IntoResponse;
+    let email = extract_email_from_token(&headers, &state).ok_or_else(|| axum::http::StatusCode::UNAUTHORIZED.into_response())?;
+    if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN.into_response()); }
+    let code = coupons::save(&state.pool, &state.schedule, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({"ok": true, "code": code})))
+}
 
-async fn add_coupon(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<AddCouponPayload>) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
+/// Uses, customers and discount given per coupon.
+async fn coupon_usage(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
     let email = extract_email_from_token(&headers, &state).ok_or(axum::http::StatusCode::UNAUTHORIZED)?;
     if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN); }
-    let _ = sqlx::query(r#"INSERT OR REPLACE INTO coupons (code, percent_off, amount_off, remaining_uses) VALUES (?, ?, ?, ?)"#)
-        .bind(payload.code.trim().to_uppercase())
-        .bind(payload.percent_off)
-        .bind(payload.amount_off)
-        .bind(payload.remaining_uses.max(0))
-        .execute(&state.pool)
-        .await
-        .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
-    Ok(Json(serde_json::json!({"ok": true})))
+    let usage = coupons::redemptions::usage(&state.pool).await.map_err(|e| {
+        tracing::error!("Failed to load coupon usage: {:?}", e);
+        axum::http::StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(serde_json::json!({ "coupons": usage })))
+}
+
+/// Every order that used a coupon, with who placed it.
+async fn coupon_redemptions(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(code): Path<String>) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
+    let email = extract_email_from_token(&headers, &state).ok_or(axum::http::StatusCode::UNAUTHORIZED)?;
+    if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN); }
+    let redemptions = coupons::redemptions::list(&state.pool, &code).await.map_err(|e| {
+        tracing::error!("Failed to load redemptions of {}: {:?}", code, e);
+        axum::http::StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(serde_json::json!({ "code": code.trim().to_uppercase(), "redemptions": redemptions })))
 }
 
 async fn delete_coupon(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(code): Path<String>) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
@@ -238,6 +281,7 @@ async fn update_user_role(
 
     // Update user role, defaulting to 'customer' if no role provided
     let role = payload.role.as_deref().unwrap_or("customer");
+    if !ROLES.contains(&role) { return Err(axum::http::StatusCode::BAD_REQUEST); }
 
     sqlx::query(r#"UPDATE users SET role = ? WHERE email = ?"#)
         .bind(role)
@@ -276,6 +320,7 @@ struct OrderInfo {
     id: String,
     email: Option<String>,
     total_cents: i64,
+    status: String,
     created_at: String,
 }
 
@@ -301,7 +346,7 @@ async fn get_orders(Extension(state): Extension<Arc<AppState>>, headers: HeaderM
     let email = extract_email_from_token(&headers, &state).ok_or(axum::http::StatusCode::UNAUTHORIZED)?;
     if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN); }
     
-    let orders = sqlx::query_as::<_, OrderInfo>("SELECT id, email, total_cents, created_at FROM orders ORDER BY created_at DESC LIMIT 100")
+    let orders = sqlx::query_as::<_, OrderInfo>("SELECT id, email, total_cents, status, created_at FROM orders ORDER BY created_at DESC LIMIT 100")
         .fetch_all(&state.pool)
         .await
         .unwrap_or_default();
@@ -314,12 +359,10 @@ async fn get_pending_orders(Extension(state): Extension<Arc<AppState>>, headers:
     if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN); }
     
     // Handle case where table might not exist
-    let pending_orders = match sqlx::query_as::<_, PendingOrderInfo>("SELECT order_id AS id, email, amount_cents AS total_cents, created_at FROM pending_orders ORDER BY created_at DESC LIMIT 100")
+    let pending_orders = sqlx::query_as::<_, PendingOrderInfo>("SELECT order_id AS id, email, amount_cents AS total_cents, created_at FROM pending_orders ORDER BY created_at DESC LIMIT 100")
         .fetch_all(&state.pool)
-        .await {
-            Ok(data) => data,
-            Err(_) => Vec::new(), // Table might not exist
-        };
+        .await
+        .unwrap_or_default(); // Table might not exist
     
     Ok(Json(PendingOrdersResponse { pending_orders }))
 }
@@ -363,6 +406,7 @@ struct ProductInfo {
     serving_size: Option<String>,
     dietary_tags: Option<String>,
     ingredients: Option<String>,
+    is_available: bool,
 }
 
 #[derive(Serialize)]
@@ -385,6 +429,7 @@ struct AddProductRequest {
     serving_size: Option<String>,
     dietary_tags: Option<String>,
     ingredients: Option<String>,
+    is_available: Option<bool>,
 }
 
 #[derive(Deserialize)]
@@ -401,6 +446,7 @@ struct UpdateProductRequest {
     serving_size: Option<String>,
     dietary_tags: Option<String>,
     ingredients: Option<String>,
+    is_available: Option<bool>,
 }
 
 async fn list_products(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap) -> Result<Json<ProductsResponse>, axum::http::StatusCode> {
@@ -408,7 +454,7 @@ async fn list_products(Extension(state): Extension<Arc<AppState>>, headers: Head
     if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN); }
     
     let products = sqlx::query_as::<_, ProductInfo>(
-        "SELECT id, name, unit_amount AS price_cents, image_url, description, category, allergens, additives, spice_level, serving_size, dietary_tags, ingredients FROM products ORDER BY category, name COLLATE NOCASE ASC LIMIT 200"
+        "SELECT p.id, p.name, p.unit_amount AS price_cents, p.image_url, p.description, p.category, p.allergens, p.additives, p.spice_level, p.serving_size, p.dietary_tags, p.ingredients, p.is_available FROM products p LEFT JOIN categories c ON c.id = p.category_id ORDER BY c.id IS NULL, c.sort_order, p.name COLLATE NOCASE ASC LIMIT 200"
     )
         .fetch_all(&state.pool)
         .await
@@ -421,15 +467,21 @@ async fn add_product(
     Extension(state): Extension<Arc<AppState>>,
     headers: HeaderMap,
     Json(payload): Json<AddProductRequest>
-) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
-    let email = extract_email_from_token(&headers, &state).ok_or(axum::http::StatusCode::UNAUTHORIZED)?;
-    if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN); }
+) -> Result<Json<serde_json::Value>, axum::response::Response> {
+    use axum::response::IntoResponse;
+    let email = extract_email_from_token(&headers, &state).ok_or_else(|| axum::http::StatusCode::UNAUTHORIZED.into_response())?;
+    if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN.into_response()); }
+
+    let allergens = allergens::parse_allergens(&state.pool, payload.allergens.as_deref().unwrap_or("")).await.map_err(IntoResponse::into_response)?;
+    let additives = allergens::parse_additives(&state.pool, payload.additives.as_deref().unwrap_or("")).await.map_err(IntoResponse::into_response)?;
+    let category = categories::resolve(&state.pool, payload.category.as_deref().unwrap_or("")).await.map_err(IntoResponse::into_response)?;
     
     let currency = payload.currency.unwrap_or_else(|| "EUR".to_string());
+    let mut tx = state.pool.begin().await.map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
     
     sqlx::query(
-        r#"INSERT INTO products (id, name, unit_amount, currency, image_url, description, category, allergens, additives, spice_level, serving_size, dietary_tags, ingredients) 
-           VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#
+        r#"INSERT INTO products (id, name, unit_amount, currency, image_url, description, category_id, category, allergens, additives, spice_level, serving_size, dietary_tags, ingredients, is_available) 
+           VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#
     )
         .bind(&payload.id)
         .bind(&payload.name)
@@ -437,16 +489,23 @@ async fn add_product(
         .bind(&currency)
         .bind(payload.image_url.as_ref())
         .bind(payload.description.as_ref())
-        .bind(payload.category.as_ref())
-        .bind(payload.allergens.as_ref())
-        .bind(payload.additives.as_ref())
+        .bind(category.as_ref().map(|c| &c.id))
+        .bind(category.as_ref().map(|c| &c.name))
+        .bind(None::<String>)
+        .bind(None::<String>)
         .bind(payload.spice_level.as_ref())
         .bind(payload.serving_size.as_ref())
         .bind(payload.dietary_tags.as_ref())
         .bind(payload.ingredients.as_ref())
-        .execute(&state.pool)
+        .bind(payload.is_available.unwrap_or(true))
+        .execute(&mut *tx)
         .await
-        .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+        .map_err(|_| axum::http::StatusCode::BAD_REQUEST.into_response())?;
+    allergens::set_allergens(&mut tx, &payload.id, &allergens).await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+    allergens::set_additives(&mut tx, &payload.id, &additives).await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+    tx.commit().await.map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
     
     Ok(Json(serde_json::json!({"ok": true, "id": payload.id})))
 }
@@ -456,9 +515,24 @@ async fn update_product(
     headers: HeaderMap,
     Path(product_id): Path<String>,
     Json(payload): Json<UpdateProductRequest>
-) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
-    let email = extract_email_from_token(&headers, &state).ok_or(axum::http::StatusCode::UNAUTHORIZED)?;
-    if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN); }
+) -> Result<Json<serde_json::Value>, axum::response::Response> {
+    use axum::response::IntoResponse;
+    let email = extract_email_from_token(&headers, &state).ok_or_else(|| axum::http::StatusCode::UNAUTHORIZED.into_response())?;
+    if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN.into_response()); }
+
+    // Reject unknown codes before anything is written
+    let allergens = match payload.allergens.as_deref() {
+        Some(raw) => Some(allergens::parse_allergens(&state.pool, raw).await.map_err(IntoResponse::into_response)?),
+        None => None,
+    };
+    let additives = match payload.additives.as_deref() {
+        Some(raw) => Some(allergens::parse_additives(&state.pool, raw).await.map_err(IntoResponse::into_response)?),
+        None => None,
+    };
+    let category = match payload.category.as_deref() {
+        Some(raw) => Some(categories::resolve(&state.pool, raw).await.map_err(IntoResponse::into_response)?),
+        None => None,
+    };
     
     // Use individual UPDATE queries for each field (simpler and safer)
     if let Some(ref name) = payload.name {
@@ -467,7 +541,7 @@ async fn update_product(
             .bind(&product_id)
             .execute(&state.pool)
             .await
-            .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+            .map_err(|_| axum::http::StatusCode::BAD_REQUEST.into_response())?;
     }
     if let Some(unit_amount) = payload.unit_amount {
         sqlx::query("UPDATE products SET unit_amount = ? WHERE id = ?")
@@ -475,7 +549,7 @@ async fn update_product(
             .bind(&product_id)
             .execute(&state.pool)
             .await
-            .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+            .map_err(|_| axum::http::StatusCode::BAD_REQUEST.into_response())?;
     }
     if let Some(ref currency) = payload.currency {
         sqlx::query("UPDATE products SET currency = ? WHERE id = ?")
@@ -483,7 +557,7 @@ async fn update_product(
             .bind(&product_id)
             .execute(&state.pool)
             .await
-            .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+            .map_err(|_| axum::http::StatusCode::BAD_REQUEST.into_response())?;
     }
     if let Some(ref image_url) = payload.image_url {
         sqlx::query("UPDATE products SET image_url = ? WHERE id = ?")
@@ -491,7 +565,7 @@ async fn update_product(
             .bind(&product_id)
             .execute(&state.pool)
             .await
-            .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+            .map_err(|_| axum::http::StatusCode::BAD_REQUEST.into_response())?;
     }
     if let Some(ref description) = payload.description {
         sqlx::query("UPDATE products SET description = ? WHERE id = ?")
@@ -499,31 +573,16 @@ async fn update_product(
             .bind(&product_id)
             .execute(&state.pool)
             .await
-            .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
-    }
-    if let Some(ref category) = payload.category {
-        sqlx::query("UPDATE products SET category = ? WHERE id = ?")
-            .bind(category)
-            .bind(&product_id)
-            .execute(&state.pool)
-            .await
-            .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+            .map_err(|_| axum::http::StatusCode::BAD_REQUEST.into_response())?;
     }
-    if let Some(ref allergens) = payload.allergens {
-        sqlx::query("UPDATE products SET allergens = ? WHERE id = ?")
-            .bind(allergens)
+    if let Some(ref category) = category {
+        sqlx::query("UPDATE products SET category_id = ?, category = ? WHERE id = ?")
+            .bind(category.as_ref().map(|c| &c.id))
+            .bind(category.as_ref().map(|c| &c.name))
             .bind(&product_id)
             .execute(&state.pool)
             .await
-            .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
-    }
-    if let Some(ref additives) = payload.additives {
-        sqlx::query("UPDATE products SET additives = ? WHERE id = ?")
-            .bind(additives)
-            .bind(&product_id)
-            .execute(&state.pool)
-            .await
-            .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+            .map_err(|_| axum::http::StatusCode::BAD_REQUEST.into_response())?;
     }
     if let Some(ref spice_level) = payload.spice_level {
         sqlx::query("UPDATE products SET spice_level = ? WHERE id = ?")
@@ -531,7 +590,7 @@ async fn update_product(
             .bind(&product_id)
             .execute(&state.pool)
             .await
-            .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+            .map_err(|_| axum::http::StatusCode::BAD_REQUEST.into_response())?;
     }
     if let Some(ref serving_size) = payload.serving_size {
         sqlx::query("UPDATE products SET serving_size = ? WHERE id = ?")
@@ -539,7 +598,7 @@ async fn update_product(
             .bind(&product_id)
             .execute(&state.pool)
             .await
-            .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+            .map_err(|_| axum::http::StatusCode::BAD_REQUEST.into_response())?;
     }
     if let Some(ref dietary_tags) = payload.dietary_tags {
         sqlx::query("UPDATE products SET dietary_tags = ? WHERE id = ?")
@@ -547,7 +606,7 @@ async fn update_product(
             .bind(&product_id)
             .execute(&state.pool)
             .await
-            .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+            .map_err(|_| axum::http::StatusCode::BAD_REQUEST.into_response())?;
     }
     if let Some(ref ingredients) = payload.ingredients {
         sqlx::query("UPDATE products SET ingredients = ? WHERE id = ?")
@@ -555,7 +614,15 @@ async fn update_product(
             .bind(&product_id)
             .execute(&state.pool)
             .await
-            .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+            .map_err(|_| axum::http::StatusCode::BAD_REQUEST.into_response())?;
+    }
+    if let Some(is_available) = payload.is_available {
+        sqlx::query("UPDATE products SET is_available = ? WHERE id = ?")
+            .bind(is_available)
+            .bind(&product_id)
+            .execute(&state.pool)
+            .await
+            .map_err(|_| axum::http::StatusCode::BAD_REQUEST.into_response())?;
     }
     
     // Verify product exists
@@ -566,7 +633,18 @@ async fn update_product(
         .unwrap_or(0);
     
     if exists == 0 {
-        return Err(axum::http::StatusCode::NOT_FOUND);
+        return Err(axum::http::StatusCode::NOT_FOUND.into_response());
+    }
+
+    if let Some(ref codes) = allergens {
+        let mut conn = state.pool.acquire().await.map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+        allergens::set_allergens(&mut conn, &product_id, codes).await
+            .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+    }
+    if let Some(ref codes) = additives {
+        let mut conn = state.pool.acquire().await.map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+        allergens::set_additives(&mut conn, &product_id, codes).await
+            .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
     }
     
     Ok(Json(serde_json::json!({"ok": true})))
@@ -580,6 +658,7 @@ async fn delete_product(
     let email = extract_email_from_token(&headers, &state).ok_or(axum::http::StatusCode::UNAUTHORIZED)?;
     if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN); }
     
+    let image_files = images::variant_files(&state.pool, &product_id).await.unwrap_or_default();
     let result = sqlx::query("DELETE FROM products WHERE id = ?")
         .bind(&product_id)
         .execute(&state.pool)
@@ -589,31 +668,22 @@ async fn delete_product(
     if result.rows_affected() == 0 {
         return Err(axum::http::StatusCode::NOT_FOUND);
     }
+    // The image rows went with the product; the files stay on disk otherwise
+    images::remove_files(&state.images.products_dir(), &image_files);
     
     Ok(Json(serde_json::json!({"ok": true})))
 }
 
-#[derive(Serialize, FromRow)]
-struct CouponInfo {
-    code: String,
-    percent_off: Option<i64>,
-    amount_off: Option<i64>,
-    remaining_uses: i64,
-}
-
 #[derive(Serialize)]
 struct CouponsResponse {
-    coupons: Vec<CouponInfo>,
+    coupons: Vec<coupons::Coupon>,
 }
 
 async fn list_coupons(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap) -> Result<Json<CouponsResponse>, axum::http::StatusCode> {
     let email = extract_email_from_token(&headers, &state).ok_or(axum::http::StatusCode::UNAUTHORIZED)?;
     if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN); }
     
-    let coupons = sqlx::query_as::<_, CouponInfo>("SELECT code, percent_off, amount_off, remaining_uses FROM coupons ORDER BY code")
-        .fetch_all(&state.pool)
-        .await
-        .unwrap_or_default();
+    let coupons = coupons::list(&state.pool).await.unwrap_or_default();
     
     Ok(Json(CouponsResponse { coupons }))
 }
@@ -656,6 +726,32 @@ async fn list_gift_coupons(Extension(state): Extension<Arc<AppState>>, headers:
     Ok(Json(GiftCouponsResponse { gift_coupons }))
 }
 
+/// Every balance change of one gift card, with the running balance.
+async fn gift_card_history(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(code): Path<String>) -> Result<Json<serde_json::Value>, axum::response::Response> {
+    use axum::response::IntoResponse;
+    let email = extract_email_from_token(&headers, &state).ok_or_else(|| axum::http::StatusCode::UNAUTHORIZED.into_response())?;
+    if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN.into_response()); }
+    let transactions = gift_cards::history(&state.pool, &code).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "code": code.trim(), "transactions": transactions })))
+}
+
+#[derive(Deserialize)]
+struct GiftCardAdjustment { amount_cents: i64, note: String }
+
+async fn adjust_gift_card(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(code): Path<String>,
+    Json(payload): Json<GiftCardAdjustment>,
+) -> Result<Json<serde_json::Value>, axum::response::Response> {
+    use axum::response::IntoResponse;
+    let email = extract_email_from_token(&headers, &state).ok_or_else(|| axum::http::StatusCode::UNAUTHORIZED.into_response())?;
+    if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN.into_response()); }
+    let balance = gift_cards::adjust(&state.pool, &code, payload.amount_cents, &payload.note, &email).await.map_err(IntoResponse::into_response)?;
+    tracing::info!("Gift card {} adjusted by {}¢ by {}", code.trim(), payload.amount_cents, email);
+    Ok(Json(serde_json::json!({ "ok": true, "remaining_cents": balance })))
+}
+
 #[derive(Deserialize)]
 struct AddUserPayload { email: String, password: String, role: Option<String> }
 
@@ -671,6 +767,7 @@ async fn add_user(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap
         .to_string();
     
     let role = payload.role.unwrap_or_else(|| "customer".to_string());
+    if !ROLES.contains(&role.as_str()) { return Err(axum::http::StatusCode::BAD_REQUEST); }
     
     sqlx::query(r#"INSERT INTO users (id, email, password_hash, role) VALUES (?, ?, ?, ?)"#)
         .bind(&id)
@@ -701,6 +798,33 @@ async fn delete_user(Extension(state): Extension<Arc<AppState>>, headers: Header
     Ok(Json(serde_json::json!({"ok": true})))
 }
 
+async fn refund_order(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(order_id): Path<String>,
+    Json(payload): Json<RefundRequest>
+) -> Result<Json<RefundOutcome>, axum::response::Response> {
+    use axum::response::IntoResponse;
+    let email = extract_email_from_token(&headers, &state).ok_or_else(|| axum::http::StatusCode::UNAUTHORIZED.into_response())?;
+    if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN.into_response()); }
+
+    refund::refund_order(&state, &order_id, &payload, &email).await
+        .map(Json)
+        .map_err(|e| e.into_response())
+}
+
+async fn list_order_refunds(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(order_id): Path<String>
+) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
+    let email = extract_email_from_token(&headers, &state).ok_or(axum::http::StatusCode::UNAUTHORIZED)?;
+    if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN); }
+
+    let refunds = refund::list_refunds(&state, &order_id).await.map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(serde_json::json!({"order_id": order_id, "refunds": refunds})))
+}
+
 // Cleanup endpoints for pending orders
 async fn delete_pending_order(
     Extension(state): Extension<Arc<AppState>>,
@@ -723,6 +847,28 @@ async fn delete_pending_order(
     Ok(Json(serde_json::json!({"ok": true, "message": format!("Deleted pending order {}", order_id)})))
 }
 
+async fn pending_order_payment_status(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(order_id): Path<String>
+) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
+    let email = extract_email_from_token(&headers, &state).ok_or(axum::http::StatusCode::UNAUTHORIZED)?;
+    if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN); }
+
+    let provider = state.payments.for_payment(&state.pool, &order_id).await
+        .map_err(|e| {
+            tracing::error!("Failed to look up payment provider of {}: {:?}", order_id, e);
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+    let status = provider.fetch_status(&order_id).await.map_err(|e| {
+        tracing::error!("Failed to fetch payment status for {}: {:?}", order_id, e);
+        axum::http::StatusCode::BAD_GATEWAY
+    })?;
+
+    Ok(Json(serde_json::json!({"order_id": order_id, "provider": provider.name(), "status": status})))
+}
+
 async fn cleanup_stale_pending(
     Extension(state): Extension<Arc<AppState>>,
     headers: HeaderMap
@@ -754,3 +900,69 @@ async fn cleanup_stale_pending(
     })))
 }
 
+#[derive(Deserialize)]
+struct WebhookEventsQuery { status: Option<String>, limit: Option<i64> }
+
+#[derive(Serialize, FromRow)]
+struct WebhookEventInfo {
+    id: String,
+    provider: String,
+    event_type: String,
+    resource_id: Option<String>,
+    status: String,
+    error: Option<String>,
+    attempts: i64,
+    received_at: String,
+    processed_at: Option<String>,
+}
+
+async fn list_webhook_events(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(q): Query<WebhookEventsQuery>
+) -> Result<Json<Vec<WebhookEventInfo>>, axum::http::StatusCode> {
+    let email = extract_email_from_token(&headers, &state).ok_or(axum::http::StatusCode::UNAUTHORIZED)?;
+    if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN); }
+
+    let limit = q.limit.unwrap_or(100).clamp(1, 500);
+    let events = sqlx::query_as::<_, WebhookEventInfo>(r#"
+        SELECT id, provider, event_type, resource_id, status, error, attempts, received_at, processed_at
+        FROM webhook_events
+        WHERE ? IS NULL OR status = ?
+        ORDER BY received_at DESC
+        LIMIT ?
+    "#)
+        .bind(q.status.as_deref())
+        .bind(q.status.as_deref())
+        .bind(limit)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(events))
+}
+
+async fn replay_webhook_event(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>
+) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
+    let email = extract_email_from_token(&headers, &state).ok_or(axum::http::StatusCode::UNAUTHORIZED)?;
+    if !is_admin_user(&email, &state).await { return Err(axum::http::StatusCode::FORBIDDEN); }
+
+    let exists: Option<String> = sqlx::query_scalar(r#"SELECT id FROM webhook_events WHERE id = ?"#)
+        .bind(&id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+    if exists.is_none() {
+        return Err(axum::http::StatusCode::NOT_FOUND);
+    }
+
+    tracing::info!("Admin {} replaying webhook event {}", email, id);
+    match crate::routes::webhooks::process_event(&state, &id).await {
+        Ok(EventOutcome::Processed(order_id)) => Ok(Json(serde_json::json!({"ok": true, "status": "processed", "order_id": order_id}))),
+        Ok(EventOutcome::Ignored(reason)) => Ok(Json(serde_json::json!({"ok": true, "status": "ignored", "reason": reason}))),
+        Err(e) => Ok(Json(serde_json::json!({"ok": false, "status": "failed", "error": e.to_string()}))),
+    }
+}
@@ -0,0 +1,91 @@
+use axum::{routing::{get, post}, extract::Query, http::{header, HeaderMap, StatusCode}, response::{IntoResponse, Response}, Json, Router, Extension};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::catalog::transfer::{self, ImportError, MenuFile};
+use crate::state::AppState;
+use crate::routes::admin::require_admin;
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    /// `json` (default) or `csv`
+    format: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ImportQuery {
+    /// `json` or `csv`; taken from the Content-Type when omitted.
+    format: Option<String>,
+    /// Validate and report the changes without writing anything.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/admin/catalog/export", get(export))
+        .route("/api/admin/catalog/import", post(import))
+}
+
+fn is_csv(format: Option<&str>, headers: &HeaderMap) -> Result<bool, ImportError> {
+    match format.map(|f| f.trim().to_lowercase()) {
+        Some(f) if f == "csv" => Ok(true),
+        Some(f) if f == "json" => Ok(false),
+        Some(f) => Err(ImportError::Format(format!("unsupported format '{}', expected json or csv", f))),
+        None => Ok(headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("csv"))),
+    }
+}
+
+async fn export(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Query(q): Query<ExportQuery>) -> Result<Response, Response> {
+    require_admin(&headers, &state).await?;
+    let csv = is_csv(Some(q.format.as_deref().unwrap_or("json")), &headers).map_err(IntoResponse::into_response)?;
+    let menu = transfer::export(&state.pool).await.map_err(|e| ImportError::from(e).into_response())?;
+    if !csv {
+        return Ok(Json(menu).into_response());
+    }
+    let body = transfer::to_csv(&menu.products).map_err(|e| {
+        tracing::error!("Failed to write menu CSV: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+    Ok((
+        [(header::CONTENT_TYPE, "text/csv; charset=utf-8"), (header::CONTENT_DISPOSITION, "attachment; filename=\"menu.csv\"")],
+        body,
+    ).into_response())
+}
+
+/// Import a menu file: every row is validated first, and the changes are
+/// written in one transaction only when all rows are valid.
+async fn import(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Query(q): Query<ImportQuery>, body: String) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let file = if is_csv(q.format.as_deref(), &headers).map_err(IntoResponse::into_response)? {
+        transfer::from_csv(&body).map_err(IntoResponse::into_response)?
+    } else {
+        serde_json::from_str::<MenuFile>(&body)
+            .map_err(|e| ImportError::Format(format!("invalid menu JSON: {}", e)).into_response())?
+    };
+    let plan = transfer::plan(&state.pool, &file).await.map_err(|e| ImportError::from(e).into_response())?;
+    if q.dry_run {
+        return Ok(Json(serde_json::json!({
+            "ok": true,
+            "dry_run": true,
+            "valid": plan.errors.is_empty(),
+            "changes": plan.changes,
+            "unchanged": plan.unchanged,
+            "errors": plan.errors,
+        })));
+    }
+    if !plan.errors.is_empty() {
+        return Err(ImportError::Invalid(plan).into_response());
+    }
+    transfer::apply(&state.pool, &plan).await.map_err(IntoResponse::into_response)?;
+    tracing::info!("Menu import applied: {} change(s)", plan.changes.len());
+    Ok(Json(serde_json::json!({
+        "ok": true,
+        "dry_run": false,
+        "changes": plan.changes,
+        "unchanged": plan.unchanged,
+    })))
+}
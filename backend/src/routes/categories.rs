@@ -0,0 +1,72 @@
+use axum::{routing::{get, put}, extract::{Path, Query}, http::HeaderMap, response::{IntoResponse, Response}, Json, Router, Extension};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::catalog::categories::{self, CategoryError, CategoryInput};
+use crate::catalog::translations::{self, Locale};
+use crate::state::AppState;
+use crate::routes::admin::require_admin;
+
+#[derive(Deserialize)]
+struct LangQuery {
+    lang: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ReorderRequest {
+    ids: Vec<String>,
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/categories", get(public_categories))
+        .route("/api/admin/categories", get(list_categories).post(create_category))
+        .route("/api/admin/categories/order", put(reorder))
+        .route("/api/admin/categories/:id", put(update_category).delete(delete_category))
+}
+
+/// Visible categories in menu order, in the guest's language.
+async fn public_categories(Extension(state): Extension<Arc<AppState>>, Query(q): Query<LangQuery>) -> Result<Json<serde_json::Value>, Response> {
+    let locale = match q.lang.as_deref().filter(|l| !l.trim().is_empty()) {
+        Some(raw) => translations::parse_locale(raw).map_err(IntoResponse::into_response)?,
+        None => Locale::default(),
+    };
+    let categories: Vec<_> = categories::list_categories(&state.pool, true).await
+        .map_err(|e| CategoryError::from(e).into_response())?
+        .iter()
+        .map(|c| c.localized(locale))
+        .collect();
+    Ok(Json(serde_json::json!({ "locale": locale.as_str(), "categories": categories })))
+}
+
+async fn list_categories(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let categories = categories::list_categories(&state.pool, false).await.map_err(|e| CategoryError::from(e).into_response())?;
+    Ok(Json(serde_json::json!({ "categories": categories })))
+}
+
+async fn create_category(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<CategoryInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let id = categories::create_category(&state.pool, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true, "id": id })))
+}
+
+async fn update_category(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>, Json(payload): Json<CategoryInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    categories::update_category(&state.pool, &id, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+async fn delete_category(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    categories::delete_category(&state.pool, &id).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// `{"ids": [...]}`: the listed categories first, in that order.
+async fn reorder(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<ReorderRequest>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    categories::reorder(&state.pool, &payload.ids).await.map_err(IntoResponse::into_response)?;
+    let categories = categories::list_categories(&state.pool, false).await.map_err(|e| CategoryError::from(e).into_response())?;
+    Ok(Json(serde_json::json!({ "ok": true, "categories": categories })))
+}
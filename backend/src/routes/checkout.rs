@@ -1,127 +1,344 @@
-use axum::{routing::post, Json, Router, Extension, http::HeaderMap};
+use axum::{routing::{get, post}, Json, Router, Extension, http::{HeaderMap, StatusCode}, response::{IntoResponse, Response}};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use sqlx::Row;
+use chrono::{DateTime, Utc};
 use jsonwebtoken::{DecodingKey, Validation, decode};
 
-use crate::{state::AppState, payments::{create_paypal_order, find_approval_url}};
-
-#[derive(Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct CartItem { pub product_id: String, pub name: String, pub unit_amount: i64, pub quantity: i64, pub currency: String }
+use crate::state::AppState;
+use crate::payments::{CreatePayment, PaymentStatus};
+use crate::orders::finalize::{finalize_payment, FinalizeError};
+use crate::orders::tracking::order_url_for;
+use crate::catalog::menus;
+use crate::catalog::stock::{self, check_cart};
+use crate::catalog::translations::Locale;
+use crate::coupons::{holds, redemptions::Customer};
+use crate::coupons::CouponRejection;
+use crate::pricing::{price_cart, CartLine, DiscountKind, PriceBreakdown, PricingError};
+use crate::schedule::slots::{self, validate_pickup, SlotError};
+use crate::schedule::{db_timestamp, next_opening, open_state, ready_at, OpenState};
 
 #[derive(Deserialize)]
-pub struct CheckoutRequest { pub cart: Vec<CartItem>, pub coupon: Option<String>, pub email: Option<String> }
+pub struct CheckoutRequest {
+    pub cart: Vec<CartLine>,
+    pub coupon: Option<String>,
+    pub email: Option<String>,
+    /// Email the customer when the kitchen marks the order ready.
+    #[serde(default)]
+    pub notify_ready: bool,
+    /// `starts_at` of a slot from `/api/pickup/slots`; omitted means as soon as possible.
+    pub pickup_at: Option<String>,
+    /// Menu language the customer ordered in (`de`, `en`, `vi`); the
+    /// confirmation email uses it. Anything else falls back to German.
+    pub lang: Option<String>,
+    /// One of `/api/checkout/providers`; omitted means the default provider.
+    pub payment_provider: Option<String>,
+}
 
 #[derive(Serialize)]
-pub struct CheckoutResponse { pub url: String }
+pub struct CheckoutResponse { pub url: String, pub breakdown: PriceBreakdown }
+
+#[derive(Debug, thiserror::Error)]
+pub enum CheckoutError {
+    #[error(transparent)]
+    Pricing(#[from] PricingError),
+    #[error("payment provider error: {0}")]
+    Payment(anyhow::Error),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error(transparent)]
+    Finalize(#[from] FinalizeError),
+    #[error(transparent)]
+    PickupSlot(#[from] SlotError),
+    /// Outside the ordering windows; carries the next opening (UTC), if known.
+    #[error("orders are not being taken right now")]
+    OrderingClosed(Option<String>),
+    #[error("payment provider '{0}' is not offered")]
+    UnknownProvider(String),
+}
+
+impl IntoResponse for CheckoutError {
+    fn into_response(self) -> Response {
+        match self {
+            CheckoutError::Pricing(e) => e.into_response(),
+            CheckoutError::PickupSlot(e) => e.into_response(),
+            CheckoutError::OrderingClosed(next_opening_at) => {
+                (StatusCode::CONFLICT, Json(serde_json::json!({
+                    "error": "ordering_closed",
+                    "message": "We are not taking orders right now. Please choose a pickup time or come back when we are open.",
+                    "next_opening_at": next_opening_at,
+                }))).into_response()
+            }
+            CheckoutError::UnknownProvider(name) => {
+                (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                    "error": "unknown_payment_provider",
+                    "message": format!("Payment method '{}' is not available.", name),
+                }))).into_response()
+            }
+            CheckoutError::Payment(e) => {
+                tracing::error!("Checkout payment creation failed: {:?}", e);
+                (StatusCode::BAD_GATEWAY, Json(serde_json::json!({
+                    "error": "payment_unavailable",
+                    "message": "Payment could not be started. Please try again later.",
+                }))).into_response()
+            }
+            CheckoutError::Finalize(FinalizeError::Database(e)) | CheckoutError::Database(e) => {
+                tracing::error!("Checkout failed with database error: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                    "error": "internal_error",
+                    "message": "Order could not be saved.",
+                }))).into_response()
+            }
+            CheckoutError::Finalize(e) => {
+                tracing::error!("Checkout could not finalize order: {}", e);
+                (StatusCode::BAD_GATEWAY, Json(serde_json::json!({
+                    "error": "payment_unavailable",
+                    "message": "Payment could not be started. Please try again later.",
+                }))).into_response()
+            }
+        }
+    }
+}
 
 pub fn router() -> Router {
-    Router::new().route("/api/checkout", post(start))
+    Router::new()
+        .route("/api/checkout", post(start))
+        .route("/api/checkout/providers", get(providers))
+}
+
+/// Payment methods the customer can choose from; `default` is used when the checkout names none.
+async fn providers(Extension(state): Extension<Arc<AppState>>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "providers": state.payments.names(),
+        "default": state.payments.default_provider().name(),
+    }))
 }
 
 #[derive(Deserialize)]
 #[allow(dead_code)]
 struct Claims { sub: String, email: String, exp: usize }
 
-async fn start(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<CheckoutRequest>) -> Json<CheckoutResponse> {
-    // compute subtotal
-    let subtotal_cents: i64 = payload.cart.iter().map(|i| i.unit_amount * i.quantity).sum();
-
-    // compute discount from coupon (if provided and valid)
-    let mut discount_cents: i64 = 0;
-    let mut applied_coupon: Option<String> = None;
-    if let Some(raw_code) = payload.coupon.as_ref().map(|s| s.trim()).filter(|s| !s.is_empty()) {
-        let code_upper = raw_code.to_uppercase();
-        
-        // First check for gift code (case-insensitive, stored as lowercase UUID)
-        if let Ok(Some(g)) = sqlx::query(r#"SELECT remaining_cents FROM gift_codes WHERE code = ? COLLATE NOCASE"#)
-            .bind(&raw_code)
-            .fetch_optional(&state.pool)
-            .await
-        {
-            let remaining: i64 = g.get::<i64, _>("remaining_cents");
-            if remaining > 0 {
-                discount_cents = remaining.min(subtotal_cents);
-                applied_coupon = Some(raw_code.to_string());
-            }
-        } else if let Ok(Some(row)) = sqlx::query(r#"SELECT percent_off, amount_off, remaining_uses FROM coupons WHERE code = ?"#)
-            .bind(&code_upper)
-            .fetch_optional(&state.pool)
-            .await
-        {
-            let remaining: i64 = row.get::<i64, _>("remaining_uses");
-            if remaining > 0 {
-                let amount_off: Option<i64> = row.try_get("amount_off").ok();
-                let percent_off: Option<i64> = row.try_get("percent_off").ok();
-                
-                // Only apply discount if at least one valid discount type exists
-                let has_valid_discount = 
-                    (amount_off.is_some() && amount_off.unwrap_or(0) > 0) ||
-                    (percent_off.is_some() && percent_off.unwrap_or(0) > 0);
-                
-                if has_valid_discount {
-                // Apply fixed amount discount if available
-                if let Some(amount) = amount_off {
-                        if amount > 0 {
-                    discount_cents = amount;
-                        }
-                }
-                
-                // Apply percentage discount if available (and no fixed amount was applied)
-                if discount_cents == 0 {
-                    if let Some(percent) = percent_off {
-                        if percent > 0 {
-                            discount_cents = ((percent as f64 / 100.0) * subtotal_cents as f64).round() as i64;
-                        }
-                    }
-                }
-                
-                applied_coupon = Some(code_upper);
-                }
+/// User id and email of a valid bearer token, if any.
+pub(crate) fn signed_in_user(headers: &HeaderMap, state: &AppState) -> Option<(String, String)> {
+    let token = headers.get(axum::http::header::AUTHORIZATION).and_then(|v| v.to_str().ok())?.strip_prefix("Bearer ")?;
+    let data = decode::<Claims>(token, &DecodingKey::from_secret(state.jwt_secret.as_bytes()), &Validation::default()).ok()?;
+    Some((data.claims.sub, data.claims.email))
+}
+
+async fn start(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<CheckoutRequest>) -> Result<Json<CheckoutResponse>, CheckoutError> {
+    let user = signed_in_user(&headers, &state);
+    checkout_at(&state, user, payload, Utc::now()).await.map(Json)
+}
+
+/// The checkout of `start` for the signed-in `user` (id, email), placed at `now`.
+pub(crate) async fn checkout_at(state: &AppState, user: Option<(String, String)>, payload: CheckoutRequest, now: DateTime<Utc>) -> Result<CheckoutResponse, CheckoutError> {
+    // Always prefer the authenticated user's email from JWT over any provided email
+    let (user_id, user_email) = user.unzip();
+    let provider = match payload.payment_provider.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(name) => state.payments.get(&name.to_ascii_lowercase()).ok_or_else(|| CheckoutError::UnknownProvider(name.to_string()))?,
+        None => state.payments.default_provider(),
+    };
+    let final_email = user_email.unwrap_or_else(|| payload.email.as_deref().unwrap_or("").to_string());
+    let customer = Customer::new(user_id.clone(), Some(&final_email));
+
+    // Prices always come from the products table, never from the client cart
+    let breakdown = price_cart(&state.pool, &payload.cart, payload.coupon.as_deref(), Some(&customer)).await?;
+    // Never charge full price for a code the customer expects to apply
+    if let Some(rejection) = breakdown.coupon_rejection.clone() {
+        return Err(PricingError::CouponRejected(rejection).into());
+    }
+    let total_cents = breakdown.total_cents;
+
+    let pickup_at = match payload.pickup_at.as_deref().filter(|p| !p.trim().is_empty()) {
+        Some(requested) => {
+            let items = breakdown.lines.iter().map(|l| l.quantity).sum();
+            Some(validate_pickup(state, requested, items, now).await?)
+        }
+        None => {
+            // As soon as possible only works while the kitchen takes orders
+            if open_state(&state.pool, &state.schedule, now).await? != OpenState::AcceptingOrders {
+                let next = next_opening(&state.pool, &state.schedule, now).await?;
+                return Err(CheckoutError::OrderingClosed(next.map(db_timestamp)));
             }
+            None
         }
-    }
+    };
 
-    let total_cents = std::cmp::max(0, subtotal_cents - discount_cents);
+    // Sold-out dishes, used-up portions and dishes off the menu at that time cannot be paid for
+    let ready = pickup_at.as_deref().map_or(now, |at| ready_at(Some(at)));
+    let stock_day = state.schedule.stock_day(ready);
+    check_cart(&state.pool, stock_day, &breakdown.lines).await?;
+    menus::check_cart(&state.pool, &state.schedule, ready, &breakdown.lines).await?;
 
-    // extract user_id and email from JWT if provided
-    let mut user_id: Option<String> = None;
-    let mut user_email: Option<String> = None;
-    if let Some(auth) = headers.get(axum::http::header::AUTHORIZATION).and_then(|v| v.to_str().ok()) {
-        if let Some(token) = auth.strip_prefix("Bearer ") {
-            if let Ok(data) = decode::<Claims>(token, &DecodingKey::from_secret(state.jwt_secret.as_bytes()), &Validation::default()) {
-                user_id = Some(data.claims.sub);
-                user_email = Some(data.claims.email);
-            }
+    let payment = provider.create_order(&CreatePayment {
+        amount_cents: total_cents,
+        currency: breakdown.currency.clone(),
+        description: Some("Cart checkout".into()),
+        return_path: "/api/paypal/return".into(),
+        cancel_path: "/api/paypal/cancel".into(),
+    }).await.map_err(CheckoutError::Payment)?;
+
+    let locale = payload.lang.as_deref().and_then(Locale::parse).unwrap_or_default();
+
+    // The pending order and the holds on its slot, portions and discount are
+    // written together; the provider order stays unused if another checkout
+    // took the last place, portion or the discount first.
+    let mut tx = state.pool.begin().await?;
+    sqlx::query(r#"INSERT OR REPLACE INTO pending_orders (order_id, user_id, email, amount_cents, items_json, payment_provider, notify_ready, pickup_at, locale) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"#)
+        .bind(&payment.id)
+        .bind(user_id.as_deref())
+        .bind(&final_email)
+        .bind(total_cents)
+        .bind(breakdown.to_items_json().to_string())
+        .bind(provider.name())
+        .bind(payload.notify_ready)
+        .bind(pickup_at.as_deref())
+        .bind(locale.as_str())
+        .execute(&mut *tx)
+        .await?;
+    if let Some(at) = pickup_at.as_deref() {
+        slots::reserve(&mut tx, state, at).await?;
+    }
+    stock::hold(&mut tx, &payment.id, stock_day, &breakdown.lines, state.discount_hold_minutes).await?;
+    if let Some(discount) = breakdown.discount.as_ref().filter(|d| d.kind == DiscountKind::Coupon || d.discount_cents > 0) {
+        if let Err(reason) = holds::reserve(&mut tx, &payment.id, discount, &customer, state.discount_hold_minutes).await? {
+            return Err(PricingError::CouponRejected(CouponRejection::new(&discount.code, reason)).into());
         }
     }
+    tx.commit().await?;
 
-    // Prefer PayPal if configured
-    if state.paypal_client_id.is_some() && state.paypal_secret.is_some() {
-        if let Ok(order) = create_paypal_order(&state, total_cents, "/api/paypal/return", "/api/paypal/cancel", Some("Cart checkout".into())).await {
-            // Always prefer the authenticated user's email from JWT over any provided email
-            let final_email = user_email.unwrap_or_else(|| payload.email.as_deref().unwrap_or("").to_string());
-
-            let _ = sqlx::query(r#"INSERT OR REPLACE INTO pending_orders (order_id, user_id, email, amount_cents, items_json) VALUES (?, ?, ?, ?, ?)"#)
-                .bind(&order.id)
-                .bind(user_id.as_deref())
-                .bind(&final_email)
-                .bind(total_cents)
-                .bind(serde_json::json!({
-                    "cart": payload.cart,
-                    "coupon_code": applied_coupon,
-                    "discount_cents": discount_cents
-                }).to_string())
-                .execute(&state.pool)
-                .await;
-
-            if let Some(approval) = find_approval_url(&order) { return Json(CheckoutResponse { url: approval }); }
-        }
+    if payment.status == PaymentStatus::AwaitingPayment {
+        // Pay at pickup: nothing to approve online, so finalize right away
+        let captured = provider.capture(&payment.id).await.map_err(CheckoutError::Payment)?;
+        let order = finalize_payment(state, &payment.id, &captured).await?;
+        let url = order_url_for(state, &order.order_id).await;
+        return Ok(CheckoutResponse { url, breakdown });
     }
-    // Stripe integration removed as it's not implemented
-    // Fallback
-    Json(CheckoutResponse { url: format!("{}/thank-you", state.app_url) })
+
+    let url = payment.approval_url.ok_or_else(|| CheckoutError::Payment(anyhow::anyhow!("provider returned no approval url for {}", payment.id)))?;
+    Ok(CheckoutResponse { url, breakdown })
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payments::pickup::PickupProvider;
+    use crate::test_support::{add_coupon, add_product, mock_provider, mock_state, state_with};
+
+    /// Monday 13:00 in Berlin, inside the lunch window the migrations seed.
+    fn lunchtime() -> DateTime<Utc> {
+        "2026-10-19T11:00:00Z".parse().unwrap()
+    }
+
+    async fn shop(state: &AppState) {
+        add_product(&state.pool, "pho", 1250).await;
+        add_product(&state.pool, "rolls", 335).await;
+        add_coupon(&state.pool, "TEN", 10, 5).await;
+    }
+
+    fn request(coupon: Option<&str>) -> CheckoutRequest {
+        let cart = serde_json::json!([{ "productId": "pho", "quantity": 1 }, { "productId": "rolls", "quantity": 2 }]);
+        CheckoutRequest {
+            cart: serde_json::from_value(cart).unwrap(),
+            coupon: coupon.map(str::to_string),
+            email: Some("guest@example.com".into()),
+            notify_ready: false,
+            pickup_at: None,
+            lang: Some("en".into()),
+            payment_provider: None,
+        }
+    }
+
+    async fn count(state: &AppState, sql: &str) -> i64 {
+        sqlx::query_scalar(sql).fetch_one(&state.pool).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn checkout_capture_and_finalize() {
+        let state = mock_state().await;
+        shop(&state).await;
+
+        let started = checkout_at(&state, None, request(Some("ten")), lunchtime()).await.unwrap();
+        assert_eq!(started.breakdown.total_cents, 1728);
+        let payment_id = started.url.split("token=").nth(1).unwrap().to_string();
+        assert_eq!(count(&state, r#"SELECT COUNT(*) FROM discount_holds"#).await, 1);
+
+        let captured = state.payments.default_provider().capture(&payment_id).await.unwrap();
+        let order = finalize_payment(&state, &payment_id, &captured).await.unwrap();
+        assert!(order.created);
+
+        let (status, total, coupon): (String, i64, Option<String>) =
+            sqlx::query_as(r#"SELECT status, total_cents, coupon_code FROM orders WHERE id = ?"#).bind(&order.order_id).fetch_one(&state.pool).await.unwrap();
+        assert_eq!((status.as_str(), total, coupon.as_deref()), ("paid", 1728, Some("TEN")));
+        let items: Vec<(String, i64, i64)> = sqlx::query_as(r#"SELECT product_id, quantity, unit_amount FROM order_items WHERE order_id = ? ORDER BY product_id"#)
+            .bind(&order.order_id)
+            .fetch_all(&state.pool)
+            .await
+            .unwrap();
+        assert_eq!(items, vec![("pho".into(), 1, 1250), ("rolls".into(), 2, 335)]);
+        assert_eq!(count(&state, r#"SELECT remaining_uses FROM coupons WHERE code = 'TEN'"#).await, 4);
+        assert_eq!(count(&state, r#"SELECT COUNT(*) FROM coupon_redemptions WHERE code = 'TEN' AND email = 'guest@example.com'"#).await, 1);
+        assert_eq!(count(&state, r#"SELECT COUNT(*) FROM pending_orders"#).await, 0);
+        assert_eq!(count(&state, r#"SELECT COUNT(*) FROM discount_holds"#).await, 0);
 
+        // The return page and the webhook can both finalize the same payment
+        let again = finalize_payment(&state, &payment_id, &captured).await.unwrap();
+        assert_eq!((again.order_id, again.created), (order.order_id, false));
+        assert_eq!(count(&state, r#"SELECT COUNT(*) FROM orders"#).await, 1);
+        assert_eq!(count(&state, r#"SELECT remaining_uses FROM coupons WHERE code = 'TEN'"#).await, 4);
+    }
+
+    #[tokio::test]
+    async fn rejected_coupon_stops_the_checkout() {
+        let state = mock_state().await;
+        shop(&state).await;
+
+        let err = checkout_at(&state, None, request(Some("NOPE")), lunchtime()).await.err().unwrap();
+        assert!(matches!(err, CheckoutError::Pricing(PricingError::CouponRejected(_))));
+        assert_eq!(count(&state, r#"SELECT COUNT(*) FROM pending_orders"#).await, 0);
+    }
+
+    #[tokio::test]
+    async fn asap_checkout_waits_for_the_next_window() {
+        let state = mock_state().await;
+        shop(&state).await;
+
+        // Monday 16:00 in Berlin, between lunch and dinner
+        let afternoon = "2026-10-19T14:00:00Z".parse().unwrap();
+        let err = checkout_at(&state, None, request(None), afternoon).await.err().unwrap();
+        assert!(matches!(err, CheckoutError::OrderingClosed(Some(ref next)) if next.as_str() == "2026-10-19T15:30:00.000Z"));
+        assert_eq!(count(&state, r#"SELECT COUNT(*) FROM pending_orders"#).await, 0);
+    }
+
+    #[tokio::test]
+    async fn pay_at_pickup_finalizes_right_away() {
+        let state = state_with(vec![mock_provider(), Arc::new(PickupProvider::new())]).await;
+        shop(&state).await;
+
+        let mut pickup = request(None);
+        pickup.payment_provider = Some("pickup".into());
+        let started = checkout_at(&state, None, pickup, lunchtime()).await.unwrap();
+        assert!(!started.url.contains("token="));
+        let (status, total, provider): (String, i64, String) =
+            sqlx::query_as(r#"SELECT status, total_cents, payment_provider FROM orders"#).fetch_one(&state.pool).await.unwrap();
+        assert_eq!((status.as_str(), total, provider.as_str()), ("awaiting_payment", 1920, "pickup"));
+        assert_eq!(count(&state, r#"SELECT COUNT(*) FROM pending_orders"#).await, 0);
+    }
+
+    #[tokio::test]
+    async fn checkout_uses_the_provider_the_customer_chose() {
+        let state = state_with(vec![Arc::new(PickupProvider::new()), mock_provider()]).await;
+        shop(&state).await;
+
+        let mut online = request(None);
+        online.payment_provider = Some("mock".into());
+        let started = checkout_at(&state, None, online, lunchtime()).await.unwrap();
+        assert!(started.url.contains("token="));
+        let provider: String = sqlx::query_scalar(r#"SELECT payment_provider FROM pending_orders"#).fetch_one(&state.pool).await.unwrap();
+        assert_eq!(provider, "mock");
+
+        let mut unknown = request(None);
+        unknown.payment_provider = Some("stripe".into());
+        let err = checkout_at(&state, None, unknown, lunchtime()).await.err().unwrap();
+        assert!(matches!(err, CheckoutError::UnknownProvider(name) if name == "stripe"));
+    }
+}
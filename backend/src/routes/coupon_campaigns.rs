@@ -0,0 +1,61 @@
+use axum::{routing::get, extract::Path, http::{header, HeaderMap, StatusCode}, response::{IntoResponse, Response}, Json, Router, Extension};
+use std::sync::Arc;
+
+use crate::coupons::{campaigns::{self, CampaignInput}, CouponError};
+use crate::state::AppState;
+use crate::routes::admin::require_admin;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/admin/coupon-campaigns", get(list).post(create))
+        .route("/api/admin/coupon-campaigns/:id", get(detail).delete(remove))
+        .route("/api/admin/coupon-campaigns/:id/export", get(export))
+}
+
+async fn list(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let campaigns = campaigns::list(&state.pool).await.map_err(|e| CouponError::from(e).into_response())?;
+    Ok(Json(serde_json::json!({ "campaigns": campaigns })))
+}
+
+/// Generate the codes of a new campaign; nothing is stored when any rule is invalid.
+async fn create(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<CampaignInput>) -> Result<Json<serde_json::Value>, Response> {
+    let admin = require_admin(&headers, &state).await?;
+    let id = campaigns::create(&state.pool, &state.schedule, &payload, &admin).await.map_err(IntoResponse::into_response)?;
+    let campaign = campaigns::get(&state.pool, &id).await.map_err(IntoResponse::into_response)?;
+    tracing::info!("Campaign {} created by {} with {} codes", id, admin, campaign.codes);
+    Ok(Json(serde_json::json!({ "ok": true, "campaign": campaign })))
+}
+
+/// The campaign with every code and its use.
+async fn detail(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let campaign = campaigns::get(&state.pool, &id).await.map_err(IntoResponse::into_response)?;
+    let codes = campaigns::codes(&state.pool, &id).await.map_err(|e| CouponError::from(e).into_response())?;
+    Ok(Json(serde_json::json!({ "campaign": campaign, "codes": codes })))
+}
+
+async fn export(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>) -> Result<Response, Response> {
+    require_admin(&headers, &state).await?;
+    let campaign = campaigns::get(&state.pool, &id).await.map_err(IntoResponse::into_response)?;
+    let codes = campaigns::codes(&state.pool, &id).await.map_err(|e| CouponError::from(e).into_response())?;
+    let body = campaigns::codes_csv(&codes).map_err(|e| {
+        tracing::error!("Failed to write campaign CSV: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+    let name: String = campaign.name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect();
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"campaign-{}.csv\"", name)),
+        ],
+        body,
+    ).into_response())
+}
+
+/// Delete the campaign and its codes; recorded redemptions are kept.
+async fn remove(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    campaigns::delete(&state.pool, &id).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
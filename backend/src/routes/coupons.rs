@@ -1,20 +1,40 @@
-use axum::{routing::post, Json, Router, Extension, http::StatusCode};
+use axum::{routing::post, Json, Router, Extension, http::{HeaderMap, StatusCode}};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use sqlx::Row;
 
 use crate::state::AppState;
+use crate::coupons::{redemptions::Customer, CouponRejection, RejectionReason};
+use crate::routes::checkout::signed_in_user;
+use crate::pricing::{price_cart, resolve_discount, CartLine, DiscountKind, PriceBreakdown, PricingError};
 
 #[derive(Deserialize)]
-pub struct ApplyCouponRequest { pub code: String, pub cart: Option<Vec<CartItem>> }
-
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-#[allow(dead_code)]
-pub struct CartItem { pub product_id: String, pub name: String, pub unit_amount: i64, pub quantity: i64, pub currency: String }
+pub struct ApplyCouponRequest {
+    pub code: String,
+    pub cart: Option<Vec<CartLine>>,
+    /// Checks per-customer limits for a guest; a bearer token takes precedence.
+    pub email: Option<String>,
+}
 
 #[derive(Serialize)]
-pub struct ApplyCouponResponse { pub valid: bool, pub amount_off: Option<i64>, pub percent_off: Option<i64> }
+pub struct ApplyCouponResponse {
+    pub valid: bool,
+    pub amount_off: Option<i64>,
+    pub percent_off: Option<i64>,
+    /// Why the code was rejected, e.g. `expired` or `below_minimum`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<RejectionReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rejection: Option<CouponRejection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breakdown: Option<PriceBreakdown>,
+}
+
+impl ApplyCouponResponse {
+    fn rejected(rejection: Option<CouponRejection>, breakdown: Option<PriceBreakdown>) -> Self {
+        Self { valid: false, amount_off: None, percent_off: None, reason: rejection.as_ref().map(|r| r.reason), rejection, breakdown }
+    }
+}
 
 #[derive(Serialize)]
 pub struct ValidateQRResponse {
@@ -35,49 +55,40 @@ pub fn router() -> Router {
         .route("/api/coupons/validate", post(validate_qr))
 }
 
-async fn apply(Extension(state): Extension<Arc<AppState>>, Json(payload): Json<ApplyCouponRequest>) -> Json<ApplyCouponResponse> {
+async fn apply(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<ApplyCouponRequest>) -> Result<Json<ApplyCouponResponse>, PricingError> {
     let code = payload.code.trim();
-    let code_upper = code.to_uppercase();
-    
-    // Gift code support: if exists in gift_codes with remaining > 0, apply up to cart total
-    // Use COLLATE NOCASE for case-insensitive matching (gift codes are lowercase UUIDs)
-    if let Ok(Some(g)) = sqlx::query(r#"SELECT remaining_cents FROM gift_codes WHERE code = ? COLLATE NOCASE"#)
-        .bind(&code)
-        .fetch_optional(&state.pool)
-        .await
-    {
-        let remaining: i64 = g.get::<i64, _>("remaining_cents");
-        if remaining > 0 {
-            // derive cart total
-            let total: i64 = payload.cart.as_ref().map(|c| c.iter().map(|i| i.unit_amount * i.quantity).sum()).unwrap_or(0);
-            let apply_amount = remaining.min(total);
-            if apply_amount > 0 { return Json(ApplyCouponResponse { valid: true, amount_off: Some(apply_amount), percent_off: None }); }
+    // Per-customer limits can only be checked once we know who is asking
+    let customer = match signed_in_user(&headers, &state) {
+        Some((user_id, email)) => Some(Customer::new(Some(user_id), Some(&email))),
+        None => payload.email.as_deref().map(|email| Customer::new(None, Some(email))),
+    };
+
+    // Price the cart exactly like checkout does so the preview matches the charged amount
+    let (discount, breakdown) = match payload.cart.as_deref() {
+        Some(cart) if !cart.is_empty() => {
+            let breakdown = price_cart(&state.pool, cart, Some(code), customer.as_ref()).await?;
+            let discount = match breakdown.coupon_rejection.clone() {
+                Some(rejection) => Err(rejection),
+                None => breakdown.discount.clone().ok_or_else(|| CouponRejection::new(code, RejectionReason::UnknownCode)),
+            };
+            (discount, Some(breakdown))
         }
-    }
+        _ => (resolve_discount(&state.pool, code, None, customer.as_ref()).await?, None),
+    };
 
-    // Regular coupon (uppercase)
-    if let Ok(Some(r)) = sqlx::query(r#"SELECT percent_off, amount_off, remaining_uses FROM coupons WHERE code = ?"#)
-        .bind(&code_upper)
-        .fetch_optional(&state.pool)
-        .await
-    {
-        let remaining: i64 = r.get::<i64, _>("remaining_uses");
-        if remaining > 0 {
-            let amount_off: Option<i64> = r.try_get("amount_off").ok();
-            let percent_off: Option<i64> = r.try_get("percent_off").ok();
-            
-            // Only mark as valid if at least one discount type is actually set to a positive value
-            let has_valid_discount = 
-                (amount_off.is_some() && amount_off.unwrap_or(0) > 0) ||
-                (percent_off.is_some() && percent_off.unwrap_or(0) > 0);
-            
-            if has_valid_discount {
-            return Json(ApplyCouponResponse { valid: true, amount_off, percent_off });
-            }
+    let response = match discount {
+        // Gift codes are only valid when there is something left to pay for
+        Ok(d) if d.kind == DiscountKind::GiftCode && d.discount_cents > 0 => {
+            ApplyCouponResponse { valid: true, amount_off: Some(d.discount_cents), percent_off: None, reason: None, rejection: None, breakdown }
         }
-    }
+        Ok(d) if d.kind == DiscountKind::Coupon => {
+            ApplyCouponResponse { valid: true, amount_off: d.amount_off, percent_off: d.percent_off, reason: None, rejection: None, breakdown }
+        }
+        Ok(_) => ApplyCouponResponse::rejected(None, breakdown),
+        Err(rejection) => ApplyCouponResponse::rejected(Some(rejection), breakdown),
+    };
 
-    Json(ApplyCouponResponse { valid: false, amount_off: None, percent_off: None })
+    Ok(Json(response))
 }
 
 async fn validate_qr(
@@ -91,7 +102,7 @@ async fn validate_qr(
     if let Ok(Some(row)) = sqlx::query(
         r#"SELECT id, code, remaining_cents as balance, customer_email FROM gift_codes WHERE code = ? COLLATE NOCASE"#
     )
-    .bind(&code)
+    .bind(code)
     .fetch_optional(&state.pool)
     .await
     {
@@ -114,7 +125,7 @@ async fn validate_qr(
     if let Ok(Some(row)) = sqlx::query(
         r#"SELECT id, code, remaining_uses, amount_off, percent_off FROM coupons WHERE code = ?"#
     )
-    .bind(&code_lower.to_uppercase())
+    .bind(code_lower.to_uppercase())
     .fetch_optional(&state.pool)
     .await
     {
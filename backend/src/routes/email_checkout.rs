@@ -1,16 +1,20 @@
-use axum::{routing::post, Json, Router, Extension, http::StatusCode};
+use axum::{routing::post, response::{IntoResponse, Response}, Json, Router, Extension, http::{HeaderMap, StatusCode}};
+use chrono::Utc;
 use serde::Deserialize;
 use std::sync::Arc;
 
 use crate::state::AppState;
 use crate::email::send_html_email;
+use crate::pricing::CartLine;
+use crate::routes::admin::require_staff;
+use crate::routes::checkout::{checkout_at, CheckoutRequest};
 
+/// An order taken by staff at the counter; the customer pays through the emailed link.
 #[derive(Deserialize)]
 pub struct SendCheckoutEmailRequest {
     pub customer_email: String,
-    pub order_id: String,
-    pub total_amount: i64,
-    pub items: Vec<serde_json::Value>,
+    pub cart: Vec<CartLine>,
+    pub coupon: Option<String>,
 }
 
 pub fn router() -> Router {
@@ -19,14 +23,29 @@ pub fn router() -> Router {
 
 async fn send_checkout_email(
     Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
     Json(payload): Json<SendCheckoutEmailRequest>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let checkout_url = format!(
-        "{}/checkout?order_id={}",
-        state.app_url, payload.order_id
-    );
+) -> Result<Json<serde_json::Value>, Response> {
+    require_staff(&headers, &state).await?;
+    // The link is paid online, so the order goes through the same server-side
+    // pricing, holds and pending order as a checkout by the customer
+    let Some(provider) = state.payments.online() else {
+        tracing::warn!("Checkout email rejected: no online payment provider among {:?}", state.payments.names());
+        return Err(StatusCode::SERVICE_UNAVAILABLE.into_response());
+    };
+    let request = CheckoutRequest {
+        cart: payload.cart,
+        coupon: payload.coupon,
+        email: Some(payload.customer_email.clone()),
+        notify_ready: false,
+        pickup_at: None,
+        lang: None,
+        payment_provider: Some(provider.name().to_string()),
+    };
+    let checkout = checkout_at(&state, None, request, Utc::now()).await.map_err(IntoResponse::into_response)?;
+    let checkout_url = checkout.url;
 
-    let total_euros = (payload.total_amount as f64) / 100.0;
+    let total_euros = (checkout.breakdown.total_cents as f64) / 100.0;
 
     let html_body = format!(
         r#"
@@ -58,7 +77,6 @@ async fn send_checkout_email(
                     <p>
                         <a href="{}" class="button">Complete Payment via PayPal</a>
                     </p>
-                    <p>Order ID: <strong>{}</strong></p>
                     <p style="color: #e67e22; font-weight: bold;">Please complete payment within 2 hours of placing your order.</p>
                 </div>
                 <div class="footer">
@@ -68,7 +86,7 @@ async fn send_checkout_email(
         </body>
         </html>
         "#,
-        total_euros, checkout_url, payload.order_id
+        total_euros, checkout_url
     );
 
     match send_html_email(
@@ -81,13 +99,15 @@ async fn send_checkout_email(
     {
         Ok(_) => {
             tracing::info!(
-                "Checkout email sent to {} for order {}",
+                "Checkout email sent to {} for {} cents",
                 payload.customer_email,
-                payload.order_id
+                checkout.breakdown.total_cents
             );
             Ok(Json(serde_json::json!({
                 "success": true,
-                "message": "Checkout email sent successfully"
+                "message": "Checkout email sent successfully",
+                "url": checkout_url,
+                "breakdown": checkout.breakdown,
             })))
         }
         Err(e) => {
@@ -96,8 +116,12 @@ async fn send_checkout_email(
                 payload.customer_email,
                 e
             );
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            // The pending order exists; staff can still hand the link over
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "error": "email_failed",
+                "message": "The order was created but the checkout email could not be sent.",
+                "url": checkout_url,
+            }))).into_response())
         }
     }
 }
-
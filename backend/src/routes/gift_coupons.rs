@@ -1,11 +1,11 @@
-use axum::{routing::post, Json, Router, Extension, http::HeaderMap};
+use axum::{routing::post, Json, Router, Extension, http::{HeaderMap, StatusCode}};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use sqlx::Row;
 use jsonwebtoken::{decode, DecodingKey, Validation};
 use chrono;
 
-use crate::{state::AppState, payments::{create_paypal_order, find_approval_url}};
+use crate::{state::AppState, payments::{CreatePayment, PaymentStatus}};
 
 #[derive(Deserialize)]
 pub struct BuyGiftRequest { pub amount_eur: i64, pub email: Option<String> }
@@ -20,13 +20,29 @@ pub fn router() -> Router {
     Router::new().route("/api/gift-coupons/buy", post(buy))
 }
 
-async fn buy(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<BuyGiftRequest>) -> Json<BuyGiftResponse> {
+async fn buy(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<BuyGiftRequest>) -> Result<Json<BuyGiftResponse>, StatusCode> {
     let amount_cents = payload.amount_eur * 100;
     let bonus_cents = (amount_cents as f64 * 0.10).round() as i64;
     let _total_value = amount_cents + bonus_cents;
 
-    if state.paypal_client_id.is_some() && state.paypal_secret.is_some() {
-        if let Ok(order) = create_paypal_order(&state, amount_cents, "/api/paypal/gift/return", "/api/paypal/gift/cancel", Some(format!("Gift coupon {} cents (+{} bonus)", amount_cents, bonus_cents))).await {
+    let request = CreatePayment {
+        amount_cents,
+        currency: "EUR".into(),
+        description: Some(format!("Gift coupon {} cents (+{} bonus)", amount_cents, bonus_cents)),
+        return_path: "/api/paypal/gift/return".into(),
+        cancel_path: "/api/paypal/gift/cancel".into(),
+    };
+    // Gift coupons are a prepaid balance; they can't be paid at pickup
+    let Some(provider) = state.payments.online() else {
+        tracing::warn!("Gift coupon purchase rejected: no online payment provider among {:?}", state.payments.names());
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    };
+    match provider.create_order(&request).await {
+        Ok(order) if order.status == PaymentStatus::AwaitingPayment => {
+            tracing::warn!("Gift coupon purchase rejected: provider {} has no online payment", provider.name());
+            Err(StatusCode::SERVICE_UNAVAILABLE)
+        }
+        Ok(order) => {
             // Save pending gift mapping for email delivery after capture
             // Always prefer authenticated user's email from database over provided email
             let user_email = if let Some(auth) = headers.get(axum::http::header::AUTHORIZATION).and_then(|v| v.to_str().ok()) {
@@ -37,7 +53,7 @@ async fn buy(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Jso
 
                         if data.claims.exp < current_time {
                             tracing::warn!("JWT token expired for gift coupon purchase");
-                            return Json(BuyGiftResponse { url: format!("{}/?error=expired_token", state.app_url) });
+                            return Ok(Json(BuyGiftResponse { url: format!("{}/?error=expired_token", state.app_url) }));
                         }
 
                         // Use email from JWT token if available, otherwise query database
@@ -71,22 +87,28 @@ async fn buy(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Jso
 
             tracing::info!("Creating pending gift for order {} with email: '{}'", order.id, user_email);
 
-            let _ = sqlx::query(r#"INSERT OR REPLACE INTO pending_gifts (order_id, email, amount_cents) VALUES (?, ?, ?)"#)
+            sqlx::query(r#"INSERT OR REPLACE INTO pending_gifts (order_id, email, amount_cents, payment_provider) VALUES (?, ?, ?, ?)"#)
                 .bind(&order.id)
                 .bind(&user_email)
                 .bind(amount_cents)
+                .bind(provider.name())
                 .execute(&state.pool)
-                .await;
+                .await
+                .map_err(|e| {
+                    tracing::error!("Failed to store pending gift {}: {:?}", order.id, e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
 
-            if let Some(approval) = find_approval_url(&order) {
-                tracing::info!("PayPal order created successfully: {}", order.id);
-                return Json(BuyGiftResponse { url: approval });
-            }
+            let approval = order.approval_url.ok_or_else(|| {
+                tracing::error!("Payment provider returned no approval url for gift order {}", order.id);
+                StatusCode::BAD_GATEWAY
+            })?;
+            tracing::info!("Gift payment order created successfully: {}", order.id);
+            Ok(Json(BuyGiftResponse { url: approval }))
+        }
+        Err(e) => {
+            tracing::error!("Failed to create payment order for gift coupon: {:?}", e);
+            Err(StatusCode::BAD_GATEWAY)
         }
     }
-
-    tracing::warn!("Failed to create PayPal order for gift coupon");
-    Json(BuyGiftResponse { url: format!("{}/thank-you", state.app_url) })
 }
-
-
@@ -177,7 +177,7 @@ async fn detailed_handler(Extension(state): Extension<Arc<AppState>>) -> Json<He
         ok: db_connected && users_table_exists,
         database: DatabaseStatus {
             connected: db_connected,
-            users_table_exists: users_table_exists,
+            users_table_exists,
             admin_user_exists: admin_exists,
             error: None,
         },
@@ -0,0 +1,102 @@
+use axum::{routing::{get, put}, extract::Path, http::HeaderMap, response::{IntoResponse, Response}, Json, Router, Extension};
+use chrono::Utc;
+use std::sync::Arc;
+
+use crate::schedule::hours::{self, ClosureInput, HoursError, OpeningHoursInput, WEEKDAYS};
+use crate::schedule::{closure_on, next_opening, open_state, windows_for, OpenState};
+use crate::state::AppState;
+use crate::routes::admin::require_admin;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/hours", get(public_hours))
+        .route("/api/admin/opening-hours", get(list_hours).post(create_hours))
+        .route("/api/admin/opening-hours/:id", put(update_hours).delete(delete_hours))
+        .route("/api/admin/closures", get(list_closures).post(create_closure))
+        .route("/api/admin/closures/:id", put(update_closure).delete(delete_closure))
+}
+
+/// Opening hours for the website, plus whether orders are taken right now.
+async fn public_hours(Extension(state): Extension<Arc<AppState>>) -> Result<Json<serde_json::Value>, HoursError> {
+    let cfg = &state.schedule;
+    let now = Utc::now();
+    let today = cfg.local_date(now);
+
+    let open = open_state(&state.pool, cfg, now).await?;
+    let next = next_opening(&state.pool, cfg, now).await?;
+    let weekly = hours::list_hours(&state.pool).await?;
+    let week: Vec<serde_json::Value> = WEEKDAYS.iter().enumerate().map(|(day, name)| serde_json::json!({
+        "weekday": day,
+        "name": name,
+        "windows": weekly.iter().filter(|h| h.weekday == day as i64).map(|h| serde_json::json!({
+            "opens_at": h.opens_at,
+            "closes_at": h.closes_at,
+            "last_order_at": h.last_order_at.as_deref().unwrap_or(&h.closes_at),
+        })).collect::<Vec<_>>(),
+    })).collect();
+    let today_windows: Vec<serde_json::Value> = windows_for(&state.pool, today).await?.into_iter().map(|w| serde_json::json!({
+        "opens_at": w.opens.format("%H:%M").to_string(),
+        "closes_at": w.closes.format("%H:%M").to_string(),
+        "last_order_at": w.last_order.format("%H:%M").to_string(),
+    })).collect();
+
+    Ok(Json(serde_json::json!({
+        "timezone": cfg.timezone.name(),
+        "open_now": open != OpenState::Closed,
+        "accepting_orders": open == OpenState::AcceptingOrders,
+        "closed_today": closure_on(&state.pool, today).await?.map(|reason| serde_json::json!({ "reason": reason })),
+        "next_opening_at": next.map(crate::schedule::db_timestamp),
+        "next_opening_local": next.map(|at| at.with_timezone(&cfg.timezone).format("%Y-%m-%d %H:%M").to_string()),
+        "today": { "date": today.to_string(), "windows": today_windows },
+        "week": week,
+        "closures": hours::list_closures(&state.pool, Some(today)).await?,
+    })))
+}
+
+async fn list_hours(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let hours = hours::list_hours(&state.pool).await.map_err(|e| HoursError::from(e).into_response())?;
+    Ok(Json(serde_json::json!({ "opening_hours": hours })))
+}
+
+async fn create_hours(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<OpeningHoursInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let id = hours::create_hours(&state.pool, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true, "id": id })))
+}
+
+async fn update_hours(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>, Json(payload): Json<OpeningHoursInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    hours::update_hours(&state.pool, &id, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+async fn delete_hours(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    hours::delete_hours(&state.pool, &id).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+async fn list_closures(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let closures = hours::list_closures(&state.pool, None).await.map_err(|e| HoursError::from(e).into_response())?;
+    Ok(Json(serde_json::json!({ "closures": closures })))
+}
+
+async fn create_closure(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<ClosureInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let id = hours::create_closure(&state.pool, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true, "id": id })))
+}
+
+async fn update_closure(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>, Json(payload): Json<ClosureInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    hours::update_closure(&state.pool, &id, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+async fn delete_closure(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    hours::delete_closure(&state.pool, &id).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
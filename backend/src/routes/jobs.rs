@@ -0,0 +1,36 @@
+use axum::{routing::{get, post}, extract::{Path, Query}, http::HeaderMap, response::{IntoResponse, Response}, Json, Router, Extension};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::jobs::{self, JobError};
+use crate::state::AppState;
+use crate::routes::admin::require_admin;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/admin/jobs", get(list_jobs))
+        .route("/api/admin/jobs/:id/retry", post(retry_job))
+}
+
+#[derive(Deserialize)]
+struct JobsQuery {
+    status: Option<String>,
+    kind: Option<String>,
+    limit: Option<i64>,
+}
+
+async fn list_jobs(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Query(q): Query<JobsQuery>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let limit = q.limit.unwrap_or(50).clamp(1, 500);
+    let list = jobs::list(&state.pool, q.status.as_deref(), q.kind.as_deref(), limit)
+        .await
+        .map_err(|e| JobError::from(e).into_response())?;
+    let summary = jobs::summary(&state.pool).await.map_err(|e| JobError::from(e).into_response())?;
+    Ok(Json(serde_json::json!({ "summary": summary, "jobs": list })))
+}
+
+async fn retry_job(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    jobs::retry(&state.pool, &id).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
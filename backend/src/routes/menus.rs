@@ -0,0 +1,58 @@
+use axum::{routing::{delete, get, post, put}, extract::Path, http::HeaderMap, response::{IntoResponse, Response}, Json, Router, Extension};
+use chrono::Utc;
+use std::sync::Arc;
+
+use crate::catalog::menus::{self, MenuError, MenuInput, MenuWindowInput};
+use crate::state::AppState;
+use crate::routes::admin::require_admin;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/menus", get(public_menus))
+        .route("/api/admin/menus", get(list_menus).post(create_menu))
+        .route("/api/admin/menus/:id", put(update_menu).delete(delete_menu))
+        .route("/api/admin/menus/:id/windows", post(add_window))
+        .route("/api/admin/menu-windows/:id", delete(delete_window))
+}
+
+/// Active menus and their times, e.g. for a "lunch Mon–Fri 11:30–14:30" note.
+async fn public_menus(Extension(state): Extension<Arc<AppState>>) -> Result<Json<serde_json::Value>, MenuError> {
+    let menus = menus::list_menus(&state.pool, &state.schedule, Utc::now(), true).await?;
+    Ok(Json(serde_json::json!({ "menus": menus })))
+}
+
+async fn list_menus(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let menus = menus::list_menus(&state.pool, &state.schedule, Utc::now(), false).await.map_err(|e| MenuError::from(e).into_response())?;
+    Ok(Json(serde_json::json!({ "menus": menus })))
+}
+
+async fn create_menu(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<MenuInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let id = menus::create_menu(&state.pool, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true, "id": id })))
+}
+
+async fn update_menu(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>, Json(payload): Json<MenuInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    menus::update_menu(&state.pool, &id, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+async fn delete_menu(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    menus::delete_menu(&state.pool, &id).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+async fn add_window(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(menu_id): Path<String>, Json(payload): Json<MenuWindowInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let id = menus::add_window(&state.pool, &menu_id, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true, "id": id })))
+}
+
+async fn delete_window(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    menus::delete_window(&state.pool, &id).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
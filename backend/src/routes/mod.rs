@@ -5,14 +5,27 @@ use crate::state::AppState;
 
 pub mod health;
 pub mod products;
+pub mod product_options;
+pub mod product_translations;
+pub mod product_images;
 pub mod coupons;
+pub mod coupon_campaigns;
 pub mod checkout;
 pub mod auth;
 pub mod gift_coupons;
 pub mod webhooks;
 pub mod paypal;
 pub mod admin;
+pub mod staff;
+pub mod stock;
 pub mod orders;
+pub mod pickup;
+pub mod hours;
+pub mod menus;
+pub mod categories;
+pub mod catalog;
+pub mod reservations;
+pub mod jobs;
 pub mod test_email;
 pub mod email_checkout;
 
@@ -20,14 +33,27 @@ pub fn build_router(state: Arc<AppState>) -> Router {
     Router::new()
         .merge(health::router())
         .merge(products::router())
+        .merge(product_options::router())
+        .merge(product_translations::router())
+        .merge(product_images::router())
         .merge(coupons::router())
+        .merge(coupon_campaigns::router())
         .merge(checkout::router())
         .merge(auth::router())
         .merge(gift_coupons::router())
         .merge(webhooks::router())
         .merge(paypal::router())
         .merge(admin::router())
+        .merge(staff::router())
+        .merge(stock::router())
         .merge(orders::router())
+        .merge(pickup::router())
+        .merge(hours::router())
+        .merge(menus::router())
+        .merge(categories::router())
+        .merge(catalog::router())
+        .merge(reservations::router())
+        .merge(jobs::router())
         .merge(test_email::router())
         .merge(email_checkout::router())
         .layer(Extension(state))
@@ -1,10 +1,15 @@
-use axum::{routing::{get, post}, Json, Router, Extension, extract::Path, http::StatusCode};
+use axum::{routing::{get, put}, Json, Router, Extension, extract::{Path, Query}, http::{HeaderMap, StatusCode}};
 use serde::{Serialize, Deserialize};
 use std::sync::Arc;
 use sqlx::Row;
-use uuid::Uuid;
 
 use crate::state::AppState;
+use crate::orders::StoredItems;
+use crate::orders::feed::parse_options;
+use crate::pricing::SelectedOption;
+use crate::orders::status::{history, OrderStatus};
+use crate::orders::tracking;
+use crate::routes::admin::{extract_email_from_token, is_staff_user};
 
 #[derive(Serialize, Deserialize)]
 pub struct OrderItem {
@@ -13,6 +18,9 @@ pub struct OrderItem {
     pub unit_amount: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// Chosen options; `unit_amount` already includes their surcharges.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<SelectedOption>,
 }
 
 #[derive(Serialize)]
@@ -24,95 +32,68 @@ pub struct OrderDetails {
     pub discount_cents: i64,
     pub items: Vec<OrderItem>,
     pub created_at: String,
+    pub status: String,
+    /// Status changes, oldest first, so customers can follow the kitchen.
+    pub status_history: Vec<StatusChange>,
+    /// Chosen pickup slot (UTC); `None` means as soon as possible.
+    pub pickup_at: Option<String>,
+    /// Only set while the order is still on its way through the kitchen.
+    pub estimated_ready_at: Option<String>,
+    pub notify_ready: bool,
+    /// Sum of completed refunds; partial refunds do not change `status`.
+    pub refunded_cents: i64,
+}
+
+#[derive(Serialize)]
+pub struct StatusChange {
+    pub status: String,
+    pub at: String,
 }
 
 #[derive(Deserialize)]
-pub struct CreateOrderRequest {
-    pub customer_email: String,
-    pub items: Vec<OrderItem>,
-    pub total_amount: i64,
-    pub qr_code_used: Option<String>,
-    pub discount_applied: Option<i64>,
+pub struct TrackingQuery {
+    pub token: Option<String>,
 }
 
-#[derive(Serialize)]
-pub struct CreateOrderResponse {
-    pub id: String,
-    pub status: String,
+#[derive(Deserialize)]
+pub struct NotificationSettings {
+    pub token: String,
+    pub notify_ready: bool,
 }
 
 pub fn router() -> Router {
     Router::new()
-        .route("/api/orders", post(create_order))
         .route("/api/orders/:id", get(get_order))
+        .route("/api/orders/:id/notifications", put(update_notifications))
 }
 
-async fn create_order(
-    Extension(state): Extension<Arc<AppState>>,
-    Json(payload): Json<CreateOrderRequest>,
-) -> Result<Json<CreateOrderResponse>, StatusCode> {
-    let order_id = Uuid::new_v4().to_string();
-    let now = chrono::Utc::now().to_rfc3339();
-
-    // Prepare items JSON
-    let items_json = serde_json::to_string(&serde_json::json!({
-        "items": payload.items,
-        "discount_applied": payload.discount_applied.unwrap_or(0),
-    }))
-    .unwrap_or_else(|_| "{}".to_string());
-
-    // Insert order
-    if let Err(e) = sqlx::query(
-        r#"
-        INSERT INTO orders (id, email, total_cents, coupon_code, items_json, created_at, status)
-        VALUES (?, ?, ?, ?, ?, ?, ?)
-        "#,
-    )
-    .bind(&order_id)
-    .bind(&payload.customer_email)
-    .bind(payload.total_amount)
-    .bind(&payload.qr_code_used)
-    .bind(&items_json)
-    .bind(&now)
-    .bind("pending")
-    .execute(&state.pool)
-    .await
-    {
-        tracing::error!("Failed to insert order: {:?}", e);
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
-
-    // Insert order items
-    for item in &payload.items {
-        if let Err(e) = sqlx::query(
-            r#"INSERT INTO order_items (id, order_id, product_id, quantity, unit_amount) VALUES (?, ?, ?, ?, ?)"#,
-        )
-        .bind(Uuid::new_v4().to_string())
-        .bind(&order_id)
-        .bind(&item.product_id)
-        .bind(item.quantity)
-        .bind(item.unit_amount)
-        .execute(&state.pool)
-        .await
-        {
-            tracing::error!("Failed to insert order item: {:?}", e);
+/// Whoever holds the tracking token may read the order, as may the customer
+/// who placed it and staff. Anyone else gets a 404, not a hint that it exists.
+async fn can_view_order(state: &AppState, headers: &HeaderMap, token: Option<&str>, expected_token: Option<&str>, order_email: &str) -> bool {
+    if let (Some(given), Some(expected)) = (token, expected_token) {
+        if tracking::token_matches(given, expected) {
+            return true;
         }
     }
-
-    tracing::info!("Order created: {}", order_id);
-
-    Ok(Json(CreateOrderResponse {
-        id: order_id,
-        status: "pending".to_string(),
-    }))
+    match extract_email_from_token(headers, state) {
+        Some(email) => email.eq_ignore_ascii_case(order_email) || is_staff_user(&email, state).await,
+        None => false,
+    }
 }
 
-async fn get_order(Extension(state): Extension<Arc<AppState>>, Path(id): Path<String>) -> Result<Json<OrderDetails>, axum::http::StatusCode> {
+async fn get_order(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(q): Query<TrackingQuery>,
+) -> Result<Json<OrderDetails>, axum::http::StatusCode> {
     tracing::info!("Fetching order: {}", id);
     
     // Fetch order with proper joins to get product names
     let order_row = sqlx::query(r#"
-        SELECT o.id, o.email, o.total_cents, o.coupon_code, o.created_at, o.items_json
+        SELECT o.id, o.email, o.total_cents, o.coupon_code, o.created_at, o.items_json, o.status,
+               o.tracking_token, o.estimated_ready_at, o.notify_ready, o.pickup_at,
+               (SELECT COALESCE(SUM(r.amount_cents), 0) FROM refunds r WHERE r.order_id = o.id AND NOT r.pending) AS refunded_cents
         FROM orders o
         WHERE o.id = ?
     "#)
@@ -133,26 +114,30 @@ async fn get_order(Extension(state): Extension<Arc<AppState>>, Path(id): Path<St
     let coupon_code: Option<String> = order_row.try_get("coupon_code").ok();
     let created_at: String = order_row.try_get("created_at").unwrap_or_default();
     let items_json: String = order_row.try_get("items_json").unwrap_or_default();
+    let status: String = order_row.try_get("status").unwrap_or_default();
+    let tracking_token: Option<String> = order_row.try_get("tracking_token").ok().flatten();
+
+    if !can_view_order(&state, &headers, q.token.as_deref(), tracking_token.as_deref(), &email).await {
+        tracing::warn!("Order {} requested without a valid tracking token", id);
+        return Err(axum::http::StatusCode::NOT_FOUND);
+    }
+    let estimated_ready_at: Option<String> = OrderStatus::parse(&status)
+        .filter(|s| tracking::shows_estimate(*s))
+        .and_then(|_| order_row.try_get("estimated_ready_at").ok().flatten());
+    let notify_ready: bool = order_row.try_get("notify_ready").unwrap_or(false);
+    let pickup_at: Option<String> = order_row.try_get("pickup_at").ok().flatten();
+    let refunded_cents: i64 = order_row.try_get("refunded_cents").unwrap_or(0);
     
     tracing::debug!("Order found - email: {}, total: {}, coupon: {:?}, items_json: {}", 
         email, total_cents, coupon_code, items_json);
     
-    // Extract discount_cents from items_json
-    let mut discount_cents: i64 = 0;
-    if !items_json.is_empty() {
-        if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&items_json) {
-            if let Some(disc) = parsed.get("discount_cents").and_then(|v| v.as_i64()) {
-                discount_cents = disc;
-                tracing::debug!("Extracted discount from items_json: {}", discount_cents);
-            }
-        } else {
-            tracing::warn!("Failed to parse items_json for order {}", id);
-        }
-    }
+    // Discount and fallback lines come from items_json; older webhook rows used snake_case keys
+    let stored = StoredItems::parse(&items_json);
+    let discount_cents = stored.discount_cents;
 
     // Try to fetch order items from database first
     let item_rows = sqlx::query(r#"
-        SELECT oi.product_id, oi.quantity, oi.unit_amount, p.name
+        SELECT oi.product_id, oi.quantity, oi.unit_amount, oi.options_json, p.name
         FROM order_items oi
         LEFT JOIN products p ON oi.product_id = p.id
         WHERE oi.order_id = ?
@@ -175,38 +160,32 @@ async fn get_order(Extension(state): Extension<Arc<AppState>>, Path(id): Path<St
             quantity,
             unit_amount,
             name: Some(name),
+            options: parse_options(r.try_get("options_json").ok().flatten()),
         }
     }).collect();
 
     // If no items found in order_items table, try parsing from items_json (fallback for migration issues)
     if items.is_empty() && !items_json.is_empty() {
         tracing::info!("No items in order_items table, falling back to items_json for order {}", id);
-        if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&items_json) {
-            if let Some(cart_items) = parsed.get("cart").and_then(|v| v.as_array()) {
-                for it in cart_items {
-                    let product_id: String = it.get("productId")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("unknown")
-                        .to_string();
-                    let quantity: i64 = it.get("quantity").and_then(|v| v.as_i64()).unwrap_or(1);
-                    let unit_amount: i64 = it.get("unitAmount").and_then(|v| v.as_i64()).unwrap_or(0);
-                    let name: String = it.get("name")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("Product")
-                        .to_string();
-
-                    items.push(OrderItem {
-                        product_id,
-                        quantity,
-                        unit_amount,
-                        name: Some(name),
-                    });
-                }
-                tracing::info!("Parsed {} items from items_json", items.len());
-            }
-        }
+        items = stored.cart.into_iter().map(|line| OrderItem {
+            product_id: line.product_id,
+            quantity: line.quantity,
+            unit_amount: line.unit_amount,
+            name: Some(line.name.unwrap_or_else(|| "Product".to_string())),
+            options: line.options,
+        }).collect();
+        tracing::info!("Parsed {} items from items_json", items.len());
     }
 
+    // Actors are staff emails, which customers don't need to see
+    let status_history = match state.pool.acquire().await {
+        Ok(mut conn) => history(&mut conn, &id).await.unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+        .into_iter()
+        .map(|e| StatusChange { status: e.to_status, at: e.created_at })
+        .collect();
+
     Ok(Json(OrderDetails {
         id,
         email,
@@ -215,7 +194,43 @@ async fn get_order(Extension(state): Extension<Arc<AppState>>, Path(id): Path<St
         discount_cents,
         items,
         created_at,
+        status,
+        status_history,
+        pickup_at,
+        estimated_ready_at,
+        notify_ready,
+        refunded_cents,
     }))
 }
 
+/// Opt in to (or out of) the "your order is ready" email from the tracking page.
+async fn update_notifications(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<NotificationSettings>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let expected: Option<String> = sqlx::query_scalar(r#"SELECT tracking_token FROM orders WHERE id = ?"#)
+        .bind(&id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .flatten();
+    if !expected.is_some_and(|t| tracking::token_matches(&payload.token, &t)) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    sqlx::query(r#"UPDATE orders SET notify_ready = ? WHERE id = ?"#)
+        .bind(payload.notify_ready)
+        .bind(&id)
+        .execute(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Opting in after the order is already waiting at the counter still sends the email
+    if payload.notify_ready {
+        tracking::notify_ready(&state, &id).await;
+    }
+    Ok(Json(serde_json::json!({ "notify_ready": payload.notify_ready })))
+}
+
 
@@ -1,10 +1,9 @@
 use axum::{routing::get, Router, extract::Query, response::Redirect, Extension};
 use serde::Deserialize;
 use std::sync::Arc;
-use uuid::Uuid;
-use sqlx::Row;
-use crate::{state::AppState, payments::capture_paypal_order};
-use crate::email::{send_html_email, gift_coupon_html, order_confirmation_html};
+use crate::state::AppState;
+use crate::orders::finalize::{finalize_payment, find_finalized};
+use crate::orders::tracking::order_url_for;
 use axum::http::header::HeaderMap;
 
 #[derive(Deserialize)]
@@ -19,7 +18,7 @@ pub fn router() -> Router {
     // In production, capture PayPal order IDs on return and finalize order/gift coupon.
     Router::new()
         .route("/api/paypal/return", get(paypal_return))
-        .route("/api/paypal/cancel", get(|| async { "CANCEL" }))
+        .route("/api/paypal/cancel", get(paypal_cancel))
         .route("/api/paypal/gift/return", get(paypal_gift_return))
         .route("/api/paypal/gift/cancel", get(|| async { "CANCEL" }))
 }
@@ -33,378 +32,69 @@ fn frontend_url(state: &AppState, path: &str) -> String {
 }
 
 async fn paypal_return(Extension(state): Extension<Arc<AppState>>, _headers: HeaderMap, Query(params): Query<ReturnParams>) -> Redirect {
-    if let Some(order_id) = params.token {
-        tracing::info!("PayPal return callback received for order_id: {}", order_id);
-        
-        // First check if pending order exists
-        match sqlx::query(r#"SELECT order_id FROM pending_orders WHERE order_id = ?"#)
-            .bind(&order_id)
-            .fetch_optional(&state.pool)
-            .await
-        {
-            Ok(Some(_)) => {
-                tracing::info!("Found pending order in database: {}", order_id);
-            }
-            Ok(None) => {
-                tracing::warn!("NO pending order found for: {}", order_id);
-                tracing::warn!("Listing all pending orders:");
-                if let Ok(all_orders) = sqlx::query_scalar::<_, String>(r#"SELECT order_id FROM pending_orders"#)
-                    .fetch_all(&state.pool)
-                    .await
-                {
-                    for oid in all_orders {
-                        tracing::warn!("  - {}", oid);
-                    }
-                }
-            }
-            Err(e) => {
-                tracing::error!("Error checking pending orders: {:?}", e);
-            }
-        }
-        
-        match capture_paypal_order(&state, &order_id).await {
-            Ok(captured) => {
-                tracing::info!("PayPal order captured. Status: {}", captured.status);
-                
-                if captured.status == "COMPLETED" {
-                    tracing::info!("PayPal order {} is COMPLETED, proceeding to finalize...", order_id);
-                    
-                    // finalize any pending order
-                    match sqlx::query(r#"SELECT user_id, email, amount_cents, items_json FROM pending_orders WHERE order_id = ?"#)
-                        .bind(&order_id)
-                        .fetch_optional(&state.pool)
-                        .await
-                    {
-                        Ok(row) => {
-                            if let Some(r) = row {
-                                tracing::info!("Found pending order for {}, finalizing...", order_id);
-                                
-                                let _user_id: Option<String> = r.try_get("user_id").ok();
-                                let email: String = r.try_get("email").unwrap_or_default();
-                                let amount_cents: i64 = r.try_get("amount_cents").unwrap_or(0);
-                                let items_json: String = r.try_get("items_json").unwrap_or_default();
-
-                                tracing::info!("Order details - Email: {}, Amount: {}¢, Items: {}", email, amount_cents, items_json);
-
-                                // parse items
-                                let parsed: serde_json::Value = serde_json::from_str(&items_json).unwrap_or(serde_json::json!({}));
-                                tracing::info!("Parsed items_json: {}", serde_json::to_string(&parsed).unwrap_or_default());
-                                
-                                let coupon_code = parsed.get("coupon_code").and_then(|v| v.as_str()).map(|s| s.to_string());
-                                let items = parsed.get("cart").and_then(|v| v.as_array()).cloned().unwrap_or_default();
-                                
-                                tracing::info!("Found {} items in cart", items.len());
-                                for (idx, it) in items.iter().enumerate() {
-                                    tracing::debug!("Item {}: {}", idx, serde_json::to_string(it).unwrap_or_default());
-                                }
-
-                                // Create order record
-                                let order_db_id = Uuid::new_v4().to_string();
-                                tracing::info!("Creating order record: {}", order_db_id);
-                                
-                                match sqlx::query(r#"INSERT INTO orders (id, email, total_cents, currency, coupon_code, items_json, status) VALUES (?, ?, ?, ?, ?, ?, 'completed')"#)
-                                    .bind(&order_db_id)
-                                    .bind(&email)
-                                    .bind(amount_cents)
-                                    .bind("EUR")
-                                    .bind(coupon_code.as_deref())
-                                    .bind(&items_json)
-                                    .execute(&state.pool)
-                                    .await
-                                {
-                                    Ok(_) => {
-                                        tracing::info!("Order record created successfully: {}", order_db_id);
-                                    }
-                                    Err(e) => {
-                                        tracing::error!("Failed to create order record {}: {:?}", order_db_id, e);
-                                        let redirect_url = frontend_url(&state, "/thank-you");
-                                        return Redirect::to(&redirect_url);
-                                    }
-                                }
-
-                                // Insert order items
-                                tracing::info!("Inserting {} order items", items.len());
-                                for it in &items {
-                                    let pid = it.get("productId").and_then(|v| v.as_str()).unwrap_or("");
-                                    let qty = it.get("quantity").and_then(|v| v.as_i64()).unwrap_or(1);
-                                    let unit = it.get("unitAmount").and_then(|v| v.as_i64()).unwrap_or(0);
-                                    tracing::debug!("Inserting item - pid: {}, qty: {}, unit: {}", pid, qty, unit);
-                                    if let Err(e) = sqlx::query(r#"INSERT INTO order_items (id, order_id, product_id, quantity, unit_amount) VALUES (?, ?, ?, ?, ?)"#)
-                                        .bind(Uuid::new_v4().to_string())
-                                        .bind(&order_db_id)
-                                        .bind(pid)
-                                        .bind(qty)
-                                        .bind(unit)
-                                        .execute(&state.pool)
-                                        .await
-                                    {
-                                        tracing::error!("Failed to insert order item (pid: {}, qty: {}, unit: {}): {:?}", pid, qty, unit, e);
-                                    } else {
-                                        tracing::info!("Order item inserted successfully");
-                                    }
-                                }
-
-                                // Decrement coupon remaining uses or gift balance
-                                if let Some(code) = coupon_code.clone() {
-                                    // Try as regular coupon (uppercase)
-                                    let code_upper = code.to_uppercase();
-                                    if let Err(e) = sqlx::query(r#"UPDATE coupons SET remaining_uses = MAX(remaining_uses - 1, 0) WHERE code = ?"#)
-                                        .bind(&code_upper)
-                                        .execute(&state.pool)
-                                        .await
-                                    {
-                                        tracing::error!("Failed to decrement coupon {}: {:?}", code_upper, e);
-                                    }
-                                }
-                                // If it's a gift code: decrement remaining_cents by applied discount (if present)
-                                if let Some(code) = coupon_code {
-                                    if let Some(disc) = parsed.get("discount_cents").and_then(|v| v.as_i64()) {
-                                        if let Err(e) = sqlx::query(r#"UPDATE gift_codes SET remaining_cents = MAX(remaining_cents - ?, 0) WHERE code = ? COLLATE NOCASE"#)
-                                            .bind(disc)
-                                            .bind(&code)
-                                            .execute(&state.pool)
-                                            .await
-                                        {
-                                            tracing::error!("Failed to decrement gift code {}: {:?}", code, e);
-                                        }
-                                        // Optional: delete if zero
-                                        if let Err(e) = sqlx::query(r#"DELETE FROM gift_codes WHERE code = ? COLLATE NOCASE AND remaining_cents <= 0"#)
-                                            .bind(&code)
-                                            .execute(&state.pool)
-                                            .await
-                                        {
-                                            tracing::error!("Failed to delete expired gift code {}: {:?}", code, e);
-                                        }
-                                    }
-                                }
-
-                                // Send HTML invoice email if configured
-                                if !email.is_empty() {
-                                    // Build HTML table rows for items
-                                    let mut items_html = String::new();
-                                    let mut subtotal: i64 = 0;
-                                    for it in &items {
-                                        let name = it.get("name").and_then(|v| v.as_str()).unwrap_or("Product");
-                                        let qty = it.get("quantity").and_then(|v| v.as_i64()).unwrap_or(1);
-                                        let unit = it.get("unitAmount").and_then(|v| v.as_i64()).unwrap_or(0);
-                                        let item_total = unit * qty;
-                                        subtotal += item_total;
-                                        items_html.push_str(&format!(
-                                            "<tr><td>{}</td><td>{}</td><td>€{:.2}</td><td>€{:.2}</td></tr>",
-                                            name, qty, unit as f64 / 100.0, item_total as f64 / 100.0
-                                        ));
-                                    }
-                                    
-                                    let discount = parsed.get("discount_cents").and_then(|v| v.as_i64()).unwrap_or(0);
-                                    let total = amount_cents as f64 / 100.0;
-                                    
-                                    let html_body = order_confirmation_html(
-                                        &order_db_id,
-                                        &email,
-                                        &items_html,
-                                        subtotal as f64 / 100.0,
-                                        discount as f64 / 100.0,
-                                        total,
-                                        &state.app_url
-                                    );
-                                    
-                                    // Send HTML email
-                                    match send_html_email(&state, &email, "Your Order Confirmation", &html_body).await {
-                                        Ok(_) => {
-                                            tracing::info!("Order confirmation email (HTML) sent successfully to {}", email);
-                                        }
-                                        Err(e) => {
-                                            tracing::error!("Failed to send order confirmation email to {}: {:?}", email, e);
-                                        }
-                                    }
-                                }
-
-                                // cleanup pending
-                                let delete_result = sqlx::query(r#"DELETE FROM pending_orders WHERE order_id = ?"#)
-                                    .bind(&order_id)
-                                    .execute(&state.pool)
-                                    .await;
-                                
-                                match delete_result {
-                                    Ok(_) => {
-                                        tracing::info!("Successfully deleted pending_order {} and created final order {}", order_id, order_db_id);
-                                    }
-                                    Err(e) => {
-                                        tracing::error!("Failed to delete pending_order {}: {:?}", order_id, e);
-                                    }
-                                }
+    let Some(order_id) = params.token else {
+        tracing::warn!("PayPal return called without order_id token parameter");
+        return Redirect::to(&frontend_url(&state, "/thank-you"));
+    };
+    tracing::info!("PayPal return callback received for order_id: {}", order_id);
+    capture_and_finalize(&state, &order_id).await
+}
 
-                                let redirect_url = frontend_url(&state, &format!("/thank-you/{}", order_db_id));
-                                return Redirect::to(&redirect_url);
-                            } else {
-                                tracing::warn!("PayPal order {} completed but no pending_orders entry found in database", order_id);
-                                // Still create an order from the PayPal response even if not in pending_orders
-                                let order_db_id = Uuid::new_v4().to_string();
-                                tracing::info!("Creating order from PayPal callback without pending entry: {}", order_db_id);
-                                
-                                if let Err(e) = sqlx::query(r#"INSERT INTO orders (id, email, total_cents, items_json, status) VALUES (?, ?, ?, ?, 'completed')"#)
-                                    .bind(&order_db_id)
-                                    .bind("noemail@example.com")
-                                    .bind(0)
-                                    .bind(serde_json::json!({"cart": []}).to_string())
-                                    .execute(&state.pool)
-                                    .await
-                                {
-                                    tracing::error!("Failed to insert fallback order: {:?}", e);
-                                } else {
-                                    tracing::info!("Fallback order record created: {}", order_db_id);
-                                }
-                                
-                                let redirect_url = frontend_url(&state, &format!("/thank-you/{}", order_db_id));
-                                return Redirect::to(&redirect_url);
-                            }
-                        }
-                        Err(e) => {
-                            tracing::error!("Failed to query pending_orders for {}: {:?}", order_id, e);
-                        }
-                    }
-                } else {
-                    tracing::warn!("PayPal order {} has status '{}', not COMPLETED", order_id, captured.status);
-                }
-            }
-            Err(e) => {
-                tracing::error!("Failed to capture PayPal order {}: {:?}", order_id, e);
-            }
+/// The buyer backed out on PayPal: drop the pending order, which releases the
+/// coupon use or gift balance it was holding, and send them back to checkout.
+async fn paypal_cancel(Extension(state): Extension<Arc<AppState>>, Query(params): Query<ReturnParams>) -> Redirect {
+    if let Some(order_id) = params.token {
+        match sqlx::query(r#"DELETE FROM pending_orders WHERE order_id = ?"#).bind(&order_id).execute(&state.pool).await {
+            Ok(_) => tracing::info!("PayPal checkout {} cancelled by the buyer", order_id),
+            Err(e) => tracing::error!("Failed to drop cancelled pending order {}: {:?}", order_id, e),
         }
-    } else {
-        tracing::warn!("PayPal return called without order_id token parameter");
     }
-    
-    tracing::info!("PayPal return: redirecting to generic /thank-you (order was not finalized)");
-    let redirect_url = frontend_url(&state, "/thank-you");
-    Redirect::to(&redirect_url)
+    Redirect::to(&frontend_url(&state, "/checkout"))
 }
 
 async fn paypal_gift_return(Extension(state): Extension<Arc<AppState>>, Query(params): Query<ReturnParams>) -> Redirect {
-    if let Some(order_id) = params.token {
-        tracing::info!("PayPal gift return callback received for order_id: {}", order_id);
-        
-        if let Ok(captured) = capture_paypal_order(&state, &order_id).await {
-            if captured.status == "COMPLETED" {
-                tracing::info!("PayPal gift order {} captured with status COMPLETED", order_id);
-                
-                // determine purchased amount and grant +10% bonus
-                let mut base_amount: i64 = 0;
-                let mut email: String = String::new();
-                if let Ok(row) = sqlx::query(r#"SELECT email, amount_cents FROM pending_gifts WHERE order_id = ?"#)
-                    .bind(&order_id)
-                    .fetch_optional(&state.pool)
-                    .await {
-                    if let Some(r) = row {
-                        base_amount = r.try_get::<i64, _>("amount_cents").unwrap_or(0);
-                        email = r.try_get::<String, _>("email").unwrap_or_default();
-                    }
-                }
-                
-                let bonus = ((base_amount as f64) * 0.10).round() as i64;
-                let total_value = base_amount + bonus;
-                let code = Uuid::new_v4().to_string().replace('-', "");
-                let gift_id = Uuid::new_v4().to_string();
-                
-                tracing::info!("Creating gift code: {}, value: {}¢ (base: {}¢ + bonus: {}¢)", code, total_value, base_amount, bonus);
-                
-                if let Err(e) = sqlx::query(r#"INSERT INTO gift_codes (id, code, value_cents, remaining_cents, purchaser_email, customer_email) VALUES (?, ?, ?, ?, ?, ?)"#)
-                    .bind(&gift_id)
-                    .bind(&code)
-                    .bind(total_value)
-                    .bind(total_value)
-                    .bind(&email)
-                    .bind(&email)
-                    .execute(&state.pool)
-                    .await
-                {
-                    tracing::error!("Failed to insert gift code: {:?}", e);
-                } else {
-                    tracing::info!("Gift code inserted successfully: {}", code);
-                }
-                
-                // Create an order record for this gift purchase
-                let order_db_id = Uuid::new_v4().to_string();
-                let gift_json = serde_json::json!({
-                    "type": "gift_coupon",
-                    "code": &code,
-                    "cart": [
-                        {
-                            "productId": "gift_coupon",
-                            "name": format!("Gift Coupon - €{:.2}", base_amount as f64 / 100.0),
-                            "unitAmount": base_amount,
-                            "quantity": 1,
-                            "currency": "EUR"
-                        }
-                    ],
-                    "base_amount_cents": base_amount,
-                    "bonus_cents": bonus,
-                    "total_value_cents": total_value,
-                    "discount_cents": 0
-                }).to_string();
-                
-                tracing::info!("Creating order record for gift purchase: {} with items_json: {}", order_db_id, gift_json);
-                
-                if let Err(e) = sqlx::query(r#"INSERT INTO orders (id, email, total_cents, currency, items_json, status) VALUES (?, ?, ?, 'EUR', ?, 'completed')"#)
-                    .bind(&order_db_id)
-                    .bind(&email)
-                    .bind(total_value)
-                    .bind(&gift_json)
-                    .execute(&state.pool)
-                    .await
-                {
-                    tracing::error!("Failed to insert order for gift purchase: {:?}", e);
-                } else {
-                    tracing::info!("Order record created successfully for gift purchase: {}", order_db_id);
-                }
-                
-                // if we stored a pending email, send confirmation
-                if let Ok(row) = sqlx::query(r#"SELECT email FROM pending_gifts WHERE order_id = ?"#)
-                    .bind(&order_id)
-                    .fetch_optional(&state.pool)
-                    .await
-                {
-                    if let Some(r) = row {
-                        if let Ok(email) = r.try_get::<String, _>("email") {
-                            if !email.is_empty() {
-                                let value_display = total_value as f64 / 100.0;
-                                let html_body = gift_coupon_html(&code, value_display, &email, &state.app_url);
-
-                                match send_html_email(&state, &email, "Your Gift Coupon", &html_body).await {
-                                    Ok(_) => {
-                                        tracing::info!("Gift coupon email (HTML) sent successfully to {}", email);
-                                    }
-                                    Err(e) => {
-                                        tracing::error!("Failed to send gift coupon email to {}: {:?}", email, e);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+    let Some(order_id) = params.token else {
+        tracing::warn!("PayPal gift return called without order_id token parameter");
+        return Redirect::to(&frontend_url(&state, "/thank-you"));
+    };
+    tracing::info!("PayPal gift return callback received for order_id: {}", order_id);
+    capture_and_finalize(&state, &order_id).await
+}
 
-                // cleanup pending gift
-                let _ = sqlx::query(r#"DELETE FROM pending_gifts WHERE order_id = ?"#)
-                    .bind(&order_id)
-                    .execute(&state.pool)
-                    .await;
+/// Capture the payment and hand it to the shared finalizer. A reload of the
+/// return URL, or a webhook that got there first, lands on the same order.
+async fn capture_and_finalize(state: &AppState, order_id: &str) -> Redirect {
+    if let Ok(Some(existing)) = find_finalized(state, order_id).await {
+        tracing::info!("Payment {} already finalized, redirecting to order {}", order_id, existing);
+        return Redirect::to(&order_url_for(state, &existing).await);
+    }
 
-                tracing::info!("Gift coupon order finalized. Redirecting to /thank-you/{}", order_db_id);
-                let redirect_url = frontend_url(&state, &format!("/thank-you/{}", order_db_id));
-                return Redirect::to(&redirect_url);
-            } else {
-                tracing::warn!("PayPal gift order {} has status '{}', not COMPLETED", order_id, captured.status);
-            }
-        } else {
-            tracing::error!("Failed to capture PayPal gift order {}", order_id);
+    // Capture through the provider the checkout chose
+    let provider = match state.payments.for_payment(&state.pool, order_id).await {
+        Ok(Some(provider)) => provider,
+        Ok(None) => {
+            tracing::error!("No payment provider recorded for payment {}", order_id);
+            return Redirect::to(&frontend_url(state, "/thank-you"));
+        }
+        Err(e) => {
+            tracing::error!("Failed to look up payment provider of {}: {:?}", order_id, e);
+            return Redirect::to(&frontend_url(state, "/thank-you"));
+        }
+    };
+    let captured = match provider.capture(order_id).await {
+        Ok(captured) => captured,
+        Err(e) => {
+            tracing::error!("Failed to capture payment {}: {:?}", order_id, e);
+            return Redirect::to(&frontend_url(state, "/thank-you"));
+        }
+    };
+    tracing::info!("Payment {} captured. Status: {:?}", order_id, captured.status);
+
+    match finalize_payment(state, order_id, &captured).await {
+        Ok(order) => Redirect::to(&order_url_for(state, &order.order_id).await),
+        Err(e) => {
+            tracing::error!("Failed to finalize payment {}: {}", order_id, e);
+            Redirect::to(&frontend_url(state, "/thank-you"))
         }
-    } else {
-        tracing::warn!("PayPal gift return called without order_id token parameter");
     }
-    
-    tracing::info!("PayPal gift return: redirecting to generic /thank-you (order was not finalized)");
-    let redirect_url = frontend_url(&state, "/thank-you");
-    Redirect::to(&redirect_url)
 }
-
-
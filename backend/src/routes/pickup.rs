@@ -0,0 +1,35 @@
+use axum::{routing::get, extract::Query, Json, Router, Extension};
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::schedule::slots::{slots_for_day, SlotError};
+use crate::state::AppState;
+
+pub fn router() -> Router {
+    Router::new().route("/api/pickup/slots", get(list_slots))
+}
+
+#[derive(Deserialize)]
+struct SlotQuery {
+    /// Local date, `YYYY-MM-DD`; defaults to today.
+    date: Option<String>,
+}
+
+async fn list_slots(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(q): Query<SlotQuery>,
+) -> Result<Json<serde_json::Value>, SlotError> {
+    let now = Utc::now();
+    let date = match q.date.as_deref() {
+        Some(d) => NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| SlotError::InvalidTime(d.to_string()))?,
+        None => state.schedule.local_date(now),
+    };
+    let slots = slots_for_day(&state, date, now).await?;
+    Ok(Json(serde_json::json!({
+        "date": date.to_string(),
+        "timezone": state.schedule.timezone.name(),
+        "slot_minutes": state.schedule.slot_minutes,
+        "slots": slots,
+    })))
+}
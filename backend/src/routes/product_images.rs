@@ -0,0 +1,61 @@
+use axum::{routing::{get, post}, extract::{DefaultBodyLimit, Multipart, Path}, http::{header, HeaderMap, StatusCode}, response::{IntoResponse, Response}, Json, Router, Extension};
+use std::sync::Arc;
+
+use crate::catalog::images::{self, ImageError, MAX_UPLOAD_BYTES};
+use crate::state::AppState;
+use crate::routes::admin::require_admin;
+
+/// Variant file names are unique per upload, so caches may keep them forever.
+const CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/api/admin/products/:id/image",
+            post(upload_image)
+                .delete(delete_image)
+                // Room for the multipart framing around the file
+                .layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES + 64 * 1024)),
+        )
+        .route("/api/media/products/:file", get(serve_variant))
+}
+
+/// `multipart/form-data` with the photo in an `image` field.
+async fn upload_image(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(product_id): Path<String>, mut multipart: Multipart) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let read_error = |e: axum::extract::multipart::MultipartError| match e.status() {
+        StatusCode::PAYLOAD_TOO_LARGE => ImageError::TooLarge.into_response(),
+        _ => ImageError::Invalid(e.body_text()).into_response(),
+    };
+    let mut bytes = None;
+    while let Some(field) = multipart.next_field().await.map_err(read_error)? {
+        if field.name() == Some("image") {
+            bytes = Some(field.bytes().await.map_err(read_error)?);
+            break;
+        }
+    }
+    let bytes = bytes.ok_or_else(|| ImageError::Invalid("missing 'image' field".into()).into_response())?;
+    let image = images::upload(&state.pool, &state.images, &product_id, bytes.to_vec()).await.map_err(IntoResponse::into_response)?;
+    tracing::info!("Uploaded image {} for product {}", image.id, product_id);
+    Ok(Json(serde_json::json!({ "ok": true, "image_url": image.card_url(), "image": image })))
+}
+
+async fn delete_image(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(product_id): Path<String>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    images::delete(&state.pool, &state.images, &product_id).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+async fn serve_variant(Extension(state): Extension<Arc<AppState>>, Path(file): Path<String>) -> Response {
+    let Some(content_type) = images::variant_content_type(&file) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match tokio::fs::read(state.images.products_dir().join(&file)).await {
+        Ok(body) => ([(header::CONTENT_TYPE, content_type), (header::CACHE_CONTROL, CACHE_CONTROL)], body).into_response(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!("Failed to read image {}: {:?}", file, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use axum::{routing::{get, post, put}, extract::Path, http::HeaderMap, response::{IntoResponse, Response}, Json, Router, Extension};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::catalog::options::{self, OptionGroupInput, OptionInput, OptionsError};
+use crate::state::AppState;
+use crate::routes::admin::require_admin;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/admin/option-groups", get(list_groups).post(create_group))
+        .route("/api/admin/option-groups/:id", put(update_group).delete(delete_group))
+        .route("/api/admin/option-groups/:id/options", post(create_option))
+        .route("/api/admin/options/:id", put(update_option).delete(delete_option))
+        .route("/api/admin/products/:id/option-groups", put(set_product_groups))
+}
+
+async fn list_groups(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let groups = options::list_groups(&state.pool).await.map_err(|e| OptionsError::from(e).into_response())?;
+    Ok(Json(serde_json::json!({ "option_groups": groups })))
+}
+
+async fn create_group(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<OptionGroupInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let id = options::create_group(&state.pool, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true, "id": id })))
+}
+
+async fn update_group(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>, Json(payload): Json<OptionGroupInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    options::update_group(&state.pool, &id, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+async fn delete_group(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    options::delete_group(&state.pool, &id).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+async fn create_option(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(group_id): Path<String>, Json(payload): Json<OptionInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let id = options::create_option(&state.pool, &group_id, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true, "id": id })))
+}
+
+async fn update_option(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>, Json(payload): Json<OptionInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    options::update_option(&state.pool, &id, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+async fn delete_option(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    options::delete_option(&state.pool, &id).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+#[derive(Deserialize)]
+struct ProductGroupsRequest { group_ids: Vec<String> }
+
+async fn set_product_groups(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(product_id): Path<String>, Json(payload): Json<ProductGroupsRequest>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    options::set_product_groups(&state.pool, &product_id, &payload.group_ids).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
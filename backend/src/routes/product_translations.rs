@@ -0,0 +1,30 @@
+use axum::{routing::{get, put}, extract::Path, http::HeaderMap, response::{IntoResponse, Response}, Json, Router, Extension};
+use std::sync::Arc;
+
+use crate::catalog::translations::{self, Translation};
+use crate::state::AppState;
+use crate::routes::admin::require_admin;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/admin/products/:id/translations", get(list))
+        .route("/api/admin/products/:id/translations/:locale", put(upsert).delete(delete))
+}
+
+async fn list(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(product_id): Path<String>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let translations = translations::for_product(&state.pool, &product_id).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "product_id": product_id, "translations": translations })))
+}
+
+async fn upsert(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path((product_id, locale)): Path<(String, String)>, Json(payload): Json<Translation>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    translations::upsert(&state.pool, &product_id, &locale, &payload).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+async fn delete(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path((product_id, locale)): Path<(String, String)>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    translations::delete(&state.pool, &product_id, &locale).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
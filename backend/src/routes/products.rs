@@ -1,8 +1,15 @@
-use axum::{routing::get, Json, Router, Extension};
-use serde::Serialize;
+use axum::{routing::get, extract::Query, response::{IntoResponse, Response}, Json, Router, Extension};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use sqlx::Row;
 
+use crate::catalog::allergens::{self, Additive, Allergen};
+use crate::catalog::categories;
+use crate::catalog::images::{self, ProductImage};
+use crate::catalog::menus::{self, MenuError};
+use crate::catalog::options::{groups_by_product, OptionGroup};
+use crate::catalog::stock;
+use crate::catalog::translations::{self, Locale};
 use crate::state::AppState;
 
 #[derive(Serialize)]
@@ -12,49 +19,183 @@ pub struct Product {
     pub unit_amount: i64, 
     pub currency: String,
     pub image_url: Option<String>,
+    /// Resized copies of an uploaded photo; `None` for plain `image_url` links.
+    pub image: Option<ProductImage>,
     pub description: Option<String>,
+    pub category_id: Option<String>,
+    /// Category name in the response locale.
     pub category: Option<String>,
-    pub allergens: Option<String>,
-    pub additives: Option<String>,
+    pub allergens: Vec<Allergen>,
+    pub additives: Vec<Additive>,
     pub spice_level: Option<String>,
     pub serving_size: Option<String>,
     pub dietary_tags: Option<String>,
-    pub ingredients: Option<String>
+    pub ingredients: Option<String>,
+    /// Choices for the cart line, e.g. spice level or extras.
+    pub option_groups: Vec<OptionGroup>,
+    /// Marked sold out by the kitchen or out of portions for the day.
+    pub sold_out: bool,
+    /// Portions left that day; `None` without a daily limit.
+    pub remaining_portions: Option<i64>,
 }
 
 #[derive(Serialize)]
-pub struct ProductsResponse { pub products: Vec<Product> }
+pub struct ProductsResponse {
+    /// Language of `name`, `description` and `category`, where translated.
+    pub locale: &'static str,
+    /// All listed dishes in menu order, for clients that do not group.
+    pub products: Vec<Product>,
+    /// The visible categories in their configured order, each with its dishes.
+    pub categories: Vec<CategoryGroup>,
+}
+
+#[derive(Serialize)]
+pub struct CategoryGroup {
+    /// `None` for the dishes without a category, listed last.
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    /// Ids into `products`, in menu order.
+    pub product_ids: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    /// Comma-separated allergen letters to leave out, e.g. `a,g`.
+    exclude_allergens: Option<String>,
+    /// `de` (default), `en` or `vi`; untranslated fields stay German.
+    lang: Option<String>,
+    /// List what is orderable for this pickup time (RFC 3339) instead of now.
+    pickup_at: Option<String>,
+}
 
 pub fn router() -> Router {
-    Router::new().route("/api/products", get(list))
+    Router::new()
+        .route("/api/products", get(list))
+        .route("/api/allergens", get(legend))
 }
 
-async fn list(Extension(state): Extension<Arc<AppState>>) -> Json<ProductsResponse> {
-    let rows = sqlx::query(r#"SELECT id, name, unit_amount, currency, image_url, description, category, allergens, additives, spice_level, serving_size, dietary_tags, ingredients FROM products ORDER BY category, name COLLATE NOCASE ASC LIMIT 200"#)
+async fn list(Extension(state): Extension<Arc<AppState>>, Query(q): Query<ListQuery>) -> Result<Json<ProductsResponse>, Response> {
+    let excluded = match q.exclude_allergens.as_deref() {
+        Some(raw) => allergens::parse_allergens(&state.pool, raw).await.map_err(IntoResponse::into_response)?,
+        None => Vec::new(),
+    };
+    let locale = match q.lang.as_deref().filter(|l| !l.trim().is_empty()) {
+        Some(raw) => translations::parse_locale(raw).map_err(IntoResponse::into_response)?,
+        None => Locale::default(),
+    };
+    let at = match q.pickup_at.as_deref().filter(|p| !p.trim().is_empty()) {
+        Some(raw) => chrono::DateTime::parse_from_rfc3339(raw.trim())
+            .map(|at| at.with_timezone(&chrono::Utc))
+            .map_err(|_| MenuError::Invalid(format!("'{}' is not an RFC 3339 time", raw)).into_response())?,
+        None => chrono::Utc::now(),
+    };
+    let off_menu = menus::off_menu_products(&state.pool, &state.schedule, at).await
+        .map_err(|e| MenuError::from(e).into_response())?;
+    let categories: Vec<_> = categories::list_categories(&state.pool, true).await.unwrap_or_else(|e| {
+        tracing::error!("Failed to load categories: {:?}", e);
+        Default::default()
+    }).iter().map(|c| c.localized(locale)).collect();
+    // Dishes of hidden categories are left out; uncategorized ones come last
+    let rows = sqlx::query(r#"
+        SELECT p.id, p.name, p.unit_amount, p.currency, p.image_url, p.description, p.category_id, p.category, p.spice_level, p.serving_size, p.dietary_tags, p.ingredients
+        FROM products p
+        LEFT JOIN categories c ON c.id = p.category_id
+        WHERE p.is_available = 1 AND (p.category_id IS NULL OR c.is_visible = 1)
+        ORDER BY c.id IS NULL, c.sort_order, c.name, p.name COLLATE NOCASE ASC
+        LIMIT 200
+    "#)
         .fetch_all(&state.pool)
         .await
         .unwrap_or_default();
+    let mut product_allergens = allergens::allergens_by_product(&state.pool).await.unwrap_or_else(|e| {
+        tracing::error!("Failed to load product allergens: {:?}", e);
+        Default::default()
+    });
+    let mut translated = translations::for_locale(&state.pool, locale).await.unwrap_or_else(|e| {
+        tracing::error!("Failed to load {} translations: {:?}", locale.as_str(), e);
+        Default::default()
+    });
+    let stock = stock::for_day(&state.pool, state.schedule.stock_day(at)).await.unwrap_or_else(|e| {
+        tracing::error!("Failed to load stock for {}: {:?}", at, e);
+        Default::default()
+    });
+    let mut product_additives = allergens::additives_by_product(&state.pool).await.unwrap_or_else(|e| {
+        tracing::error!("Failed to load product additives: {:?}", e);
+        Default::default()
+    });
+    let mut option_groups = groups_by_product(&state.pool).await.unwrap_or_else(|e| {
+        tracing::error!("Failed to load product options: {:?}", e);
+        Default::default()
+    });
+    let mut product_images = images::by_product(&state.pool, &state.images).await.unwrap_or_else(|e| {
+        tracing::error!("Failed to load product images: {:?}", e);
+        Default::default()
+    });
 
-    let products = rows
+    let products: Vec<Product> = rows
         .into_iter()
-        .map(|r| Product {
-            id: r.get::<String, _>("id"),
-            name: r.get::<String, _>("name"),
-            unit_amount: r.get::<i64, _>("unit_amount"),
-            currency: r.get::<String, _>("currency"),
-            image_url: r.try_get::<String, _>("image_url").ok(),
-            description: r.try_get::<String, _>("description").ok(),
-            category: r.try_get::<String, _>("category").ok(),
-            allergens: r.try_get::<String, _>("allergens").ok(),
-            additives: r.try_get::<String, _>("additives").ok(),
-            spice_level: r.try_get::<String, _>("spice_level").ok(),
-            serving_size: r.try_get::<String, _>("serving_size").ok(),
-            dietary_tags: r.try_get::<String, _>("dietary_tags").ok(),
-            ingredients: r.try_get::<String, _>("ingredients").ok(),
+        .filter(|r| !off_menu.contains(&r.get::<String, _>("id")))
+        .map(|r| {
+            let id = r.get::<String, _>("id");
+            let t = translated.remove(&id).unwrap_or_default();
+            let today = stock.get(&id);
+            let category_id = r.try_get::<Option<String>, _>("category_id").ok().flatten();
+            let category = match category_id.as_deref() {
+                Some(cid) => categories.iter().find(|c| c.id == cid).map(|c| c.name.clone()),
+                None => r.try_get::<String, _>("category").ok(),
+            };
+            Product {
+                allergens: product_allergens.remove(&id).unwrap_or_default(),
+                additives: product_additives.remove(&id).unwrap_or_default(),
+                option_groups: option_groups.remove(&id).unwrap_or_default(),
+                name: t.name.unwrap_or_else(|| r.get::<String, _>("name")),
+                unit_amount: r.get::<i64, _>("unit_amount"),
+                currency: r.get::<String, _>("currency"),
+                image_url: r.try_get::<String, _>("image_url").ok(),
+                image: product_images.remove(&id),
+                description: t.description.or_else(|| r.try_get::<String, _>("description").ok()),
+                category_id,
+                category,
+                spice_level: r.try_get::<String, _>("spice_level").ok(),
+                serving_size: r.try_get::<String, _>("serving_size").ok(),
+                dietary_tags: r.try_get::<String, _>("dietary_tags").ok(),
+                ingredients: r.try_get::<String, _>("ingredients").ok(),
+                sold_out: today.is_some_and(|s| s.sold_out),
+                remaining_portions: today.and_then(|s| s.remaining),
+                id,
+            }
         })
+        .filter(|p: &Product| !p.allergens.iter().any(|a| excluded.contains(&a.code)))
         .collect();
 
-    Json(ProductsResponse { products })
+    let mut groups: Vec<CategoryGroup> = categories
+        .into_iter()
+        .map(|c| CategoryGroup {
+            product_ids: products.iter().filter(|p| p.category_id.as_deref() == Some(c.id.as_str())).map(|p| p.id.clone()).collect(),
+            id: Some(c.id),
+            name: Some(c.name),
+            description: c.description,
+            image_url: c.image_url,
+        })
+        .filter(|g| !g.product_ids.is_empty())
+        .collect();
+    let uncategorized: Vec<String> = products.iter().filter(|p| p.category_id.is_none()).map(|p| p.id.clone()).collect();
+    if !uncategorized.is_empty() {
+        groups.push(CategoryGroup { id: None, name: None, description: None, image_url: None, product_ids: uncategorized });
+    }
+
+    Ok(Json(ProductsResponse { locale: locale.as_str(), products, categories: groups }))
+}
+
+/// Reference list for the menu legend.
+async fn legend(Extension(state): Extension<Arc<AppState>>) -> Result<Json<serde_json::Value>, Response> {
+    let allergens = allergens::list_allergens(&state.pool).await
+        .map_err(|e| allergens::AllergenError::from(e).into_response())?;
+    let additives = allergens::list_additives(&state.pool).await
+        .map_err(|e| allergens::AllergenError::from(e).into_response())?;
+    Ok(Json(serde_json::json!({ "allergens": allergens, "additives": additives })))
 }
 
 
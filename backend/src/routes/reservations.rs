@@ -0,0 +1,291 @@
+use axum::{routing::{get, post, put}, extract::{Path, Query}, http::{HeaderMap, StatusCode}, response::{IntoResponse, Response}, Json, Router, Extension};
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+use sqlx::Row;
+
+use crate::reservations::{self, BookingRequest, MoveRequest, ReservationError};
+use crate::schedule::parse_time;
+use crate::state::AppState;
+use crate::routes::admin::{require_admin, require_staff};
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/reservations/availability", get(availability))
+        .route("/api/reservations", post(book))
+        .route("/api/reservations/:id", get(guest_view))
+        .route("/api/reservations/:id/cancel", post(guest_cancel))
+        .route("/api/admin/reservations", get(list_day))
+        .route("/api/admin/reservations/:id/seat", post(seat))
+        .route("/api/admin/reservations/:id/no-show", post(no_show))
+        .route("/api/admin/reservations/:id/move", post(move_booking))
+        .route("/api/admin/reservations/:id/cancel", post(admin_cancel))
+        .route("/api/admin/dining-tables", get(list_tables).post(create_table))
+        .route("/api/admin/dining-tables/:id", put(update_table))
+        .route("/api/admin/reservation-rules", get(list_rules).post(create_rule))
+        .route("/api/admin/reservation-rules/:id", axum::routing::delete(delete_rule))
+}
+
+fn parse_date(date: Option<&str>, state: &AppState) -> Result<NaiveDate, ReservationError> {
+    match date {
+        Some(d) => NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| ReservationError::Invalid(format!("'{}' is not a YYYY-MM-DD date", d))),
+        None => Ok(state.schedule.local_date(Utc::now())),
+    }
+}
+
+#[derive(Deserialize)]
+struct AvailabilityQuery {
+    date: Option<String>,
+    party_size: i64,
+    /// Local `HH:MM` the guest would like; answered with the closest free times.
+    time: Option<String>,
+}
+
+async fn availability(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(q): Query<AvailabilityQuery>,
+) -> Result<Json<serde_json::Value>, ReservationError> {
+    let date = parse_date(q.date.as_deref(), &state)?;
+    let times = reservations::availability(&state, date, q.party_size).await?;
+
+    let requested = match q.time.as_deref() {
+        Some(t) => {
+            let wanted = parse_time(t).ok_or_else(|| ReservationError::Invalid(format!("'{}' is not a HH:MM time", t)))?;
+            let minutes = |time: &str| parse_time(time).map(|x| (x - wanted).num_minutes().abs()).unwrap_or(i64::MAX);
+            let exact = times.iter().find(|s| parse_time(&s.time) == Some(wanted));
+            let mut alternatives: Vec<&reservations::AvailableTime> = times.iter().filter(|s| s.available && parse_time(&s.time) != Some(wanted)).collect();
+            alternatives.sort_by_key(|s| minutes(&s.time));
+            alternatives.truncate(3);
+            alternatives.sort_by(|a, b| a.time.cmp(&b.time));
+            Some(serde_json::json!({
+                "time": t,
+                "available": exact.is_some_and(|s| s.available),
+                "starts_at": exact.map(|s| &s.starts_at),
+                "alternatives": alternatives,
+            }))
+        }
+        None => None,
+    };
+
+    Ok(Json(serde_json::json!({
+        "date": date.to_string(),
+        "party_size": q.party_size,
+        "duration_minutes": state.schedule.reservation_minutes,
+        "times": times,
+        "requested": requested,
+    })))
+}
+
+async fn book(
+    Extension(state): Extension<Arc<AppState>>,
+    Json(payload): Json<BookingRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), ReservationError> {
+    let reservation = reservations::book(&state, &payload).await?;
+    Ok((StatusCode::CREATED, Json(serde_json::json!({
+        "id": reservation.id,
+        "status": reservation.status,
+        "starts_at": reservation.starts_at,
+        "ends_at": reservation.ends_at,
+        "party_size": reservation.party_size,
+    }))))
+}
+
+#[derive(Deserialize)]
+struct TokenQuery { token: String }
+
+async fn guest_view(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(q): Query<TokenQuery>,
+) -> Result<Json<serde_json::Value>, ReservationError> {
+    let r = reservations::load_for_guest(&state, &id, &q.token).await?;
+    Ok(Json(serde_json::json!({
+        "id": r.id,
+        "name": r.name,
+        "party_size": r.party_size,
+        "starts_at": r.starts_at,
+        "ends_at": r.ends_at,
+        "status": r.status,
+        "notes": r.notes,
+        "cancel_reason": r.cancel_reason,
+    })))
+}
+
+#[derive(Deserialize)]
+struct GuestCancelRequest { token: String }
+
+async fn guest_cancel(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<GuestCancelRequest>,
+) -> Result<Json<serde_json::Value>, ReservationError> {
+    let r = reservations::load_for_guest(&state, &id, &payload.token).await?;
+    if chrono::DateTime::parse_from_rfc3339(&r.starts_at).is_ok_and(|start| start < Utc::now()) {
+        return Err(ReservationError::WrongStatus("already in the past".into()));
+    }
+    let r = reservations::cancel(&state, &id, None, false).await?;
+    Ok(Json(serde_json::json!({ "id": r.id, "status": r.status })))
+}
+
+#[derive(Deserialize)]
+struct DayQuery { date: Option<String> }
+
+async fn list_day(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(q): Query<DayQuery>,
+) -> Result<Json<serde_json::Value>, Response> {
+    require_staff(&headers, &state).await?;
+    let date = parse_date(q.date.as_deref(), &state).map_err(IntoResponse::into_response)?;
+    let list = reservations::list_day(&state, date).await.map_err(IntoResponse::into_response)?;
+    let covers: i64 = list.iter().filter(|r| r.status != "cancelled").map(|r| r.party_size).sum();
+    Ok(Json(serde_json::json!({ "date": date.to_string(), "covers": covers, "reservations": list })))
+}
+
+async fn seat(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>) -> Result<Json<reservations::Reservation>, Response> {
+    require_staff(&headers, &state).await?;
+    reservations::seat(&state, &id).await.map(Json).map_err(IntoResponse::into_response)
+}
+
+async fn no_show(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>) -> Result<Json<reservations::Reservation>, Response> {
+    require_staff(&headers, &state).await?;
+    reservations::mark_no_show(&state, &id).await.map(Json).map_err(IntoResponse::into_response)
+}
+
+async fn move_booking(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>, Json(payload): Json<MoveRequest>) -> Result<Json<reservations::Reservation>, Response> {
+    require_staff(&headers, &state).await?;
+    reservations::move_booking(&state, &id, &payload).await.map(Json).map_err(IntoResponse::into_response)
+}
+
+#[derive(Deserialize)]
+struct AdminCancelRequest { reason: Option<String> }
+
+async fn admin_cancel(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>, Json(payload): Json<AdminCancelRequest>) -> Result<Json<reservations::Reservation>, Response> {
+    require_staff(&headers, &state).await?;
+    reservations::cancel(&state, &id, payload.reason.as_deref(), true).await.map(Json).map_err(IntoResponse::into_response)
+}
+
+#[derive(Deserialize)]
+struct TableInput { name: String, seats: i64, min_party: Option<i64>, active: Option<bool> }
+
+fn validate_table(t: &TableInput) -> Result<(), ReservationError> {
+    let min_party = t.min_party.unwrap_or(1);
+    if t.name.trim().is_empty() || t.seats < 1 || min_party < 1 || min_party > t.seats {
+        return Err(ReservationError::Invalid("a table needs a name, at least one seat and 1 <= min_party <= seats".into()));
+    }
+    Ok(())
+}
+
+async fn list_tables(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap) -> Result<Json<serde_json::Value>, Response> {
+    require_staff(&headers, &state).await?;
+    let rows = sqlx::query(r#"SELECT id, name, seats, min_party, active FROM tables ORDER BY name"#)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| ReservationError::from(e).into_response())?;
+    let tables: Vec<serde_json::Value> = rows.into_iter().map(|r| serde_json::json!({
+        "id": r.try_get::<String, _>("id").unwrap_or_default(),
+        "name": r.try_get::<String, _>("name").unwrap_or_default(),
+        "seats": r.try_get::<i64, _>("seats").unwrap_or(0),
+        "min_party": r.try_get::<i64, _>("min_party").unwrap_or(1),
+        "active": r.try_get::<bool, _>("active").unwrap_or(false),
+    })).collect();
+    Ok(Json(serde_json::json!({ "tables": tables })))
+}
+
+async fn create_table(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<TableInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    validate_table(&payload).map_err(IntoResponse::into_response)?;
+    let id = uuid::Uuid::new_v4().to_string();
+    sqlx::query(r#"INSERT INTO tables (id, name, seats, min_party, active) VALUES (?, ?, ?, ?, ?)"#)
+        .bind(&id)
+        .bind(payload.name.trim())
+        .bind(payload.seats)
+        .bind(payload.min_party.unwrap_or(1))
+        .bind(payload.active.unwrap_or(true))
+        .execute(&state.pool)
+        .await
+        .map_err(|_| ReservationError::Invalid(format!("a table named '{}' already exists", payload.name.trim())).into_response())?;
+    Ok(Json(serde_json::json!({ "ok": true, "id": id })))
+}
+
+async fn update_table(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>, Json(payload): Json<TableInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    validate_table(&payload).map_err(IntoResponse::into_response)?;
+    let updated = sqlx::query(r#"UPDATE tables SET name = ?, seats = ?, min_party = ?, active = ? WHERE id = ?"#)
+        .bind(payload.name.trim())
+        .bind(payload.seats)
+        .bind(payload.min_party.unwrap_or(1))
+        .bind(payload.active.unwrap_or(true))
+        .bind(&id)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| ReservationError::from(e).into_response())?
+        .rows_affected();
+    if updated == 0 {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    }
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+#[derive(Deserialize)]
+struct RuleInput { weekday: Option<i64>, starts_at: String, ends_at: String, max_bookings: Option<i64>, max_covers: Option<i64> }
+
+async fn list_rules(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let rows = sqlx::query(r#"SELECT id, weekday, starts_at, ends_at, max_bookings, max_covers FROM reservation_rules ORDER BY weekday, starts_at"#)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| ReservationError::from(e).into_response())?;
+    let rules: Vec<serde_json::Value> = rows.into_iter().map(|r| serde_json::json!({
+        "id": r.try_get::<String, _>("id").unwrap_or_default(),
+        "weekday": r.try_get::<Option<i64>, _>("weekday").ok().flatten(),
+        "starts_at": r.try_get::<String, _>("starts_at").unwrap_or_default(),
+        "ends_at": r.try_get::<String, _>("ends_at").unwrap_or_default(),
+        "max_bookings": r.try_get::<Option<i64>, _>("max_bookings").ok().flatten(),
+        "max_covers": r.try_get::<Option<i64>, _>("max_covers").ok().flatten(),
+    })).collect();
+    Ok(Json(serde_json::json!({ "rules": rules })))
+}
+
+async fn create_rule(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Json(payload): Json<RuleInput>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let invalid = |msg: &str| ReservationError::Invalid(msg.to_string()).into_response();
+    if payload.weekday.is_some_and(|d| !(0..7).contains(&d)) {
+        return Err(invalid("weekday must be 0 (Monday) to 6 (Sunday)"));
+    }
+    let (Some(from), Some(to)) = (parse_time(&payload.starts_at), parse_time(&payload.ends_at)) else {
+        return Err(invalid("starts_at and ends_at must be HH:MM times"));
+    };
+    if from >= to {
+        return Err(invalid("starts_at must be before ends_at"));
+    }
+    if payload.max_bookings.is_none() && payload.max_covers.is_none() {
+        return Err(invalid("set max_bookings, max_covers or both"));
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    sqlx::query(r#"INSERT INTO reservation_rules (id, weekday, starts_at, ends_at, max_bookings, max_covers) VALUES (?, ?, ?, ?, ?, ?)"#)
+        .bind(&id)
+        .bind(payload.weekday)
+        .bind(from.format("%H:%M").to_string())
+        .bind(to.format("%H:%M").to_string())
+        .bind(payload.max_bookings)
+        .bind(payload.max_covers)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| ReservationError::from(e).into_response())?;
+    Ok(Json(serde_json::json!({ "ok": true, "id": id })))
+}
+
+async fn delete_rule(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(id): Path<String>) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers, &state).await?;
+    let deleted = sqlx::query(r#"DELETE FROM reservation_rules WHERE id = ?"#)
+        .bind(&id)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| ReservationError::from(e).into_response())?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    }
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
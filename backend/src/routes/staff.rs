@@ -0,0 +1,425 @@
+use axum::{routing::{get, post}, extract::{Path, Query}, http::{HeaderMap, StatusCode}, response::{IntoResponse, Response, sse::{Event, KeepAlive, Sse}}, Json, Router, Extension};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::sync::Arc;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use sqlx::Row;
+use tokio_stream::{wrappers::{errors::BroadcastStreamRecvError, BroadcastStream}, Stream, StreamExt};
+
+use crate::state::AppState;
+use crate::orders::status::{history, pays_at_pickup, transition, OrderStatus, StatusError, StatusEvent};
+use crate::orders::refund::{refund_order, RefundRequest};
+use crate::orders::tracking;
+use crate::orders::feed::{self, events_since, latest_cursor, ticket_items, FeedEvent, TicketItem};
+use crate::routes::admin::{extract_email_from_token, is_staff_user, require_staff};
+
+/// Statuses the kitchen board shows when no filter is given.
+const ACTIVE_STATUSES: [OrderStatus; 5] = [
+    OrderStatus::AwaitingPayment,
+    OrderStatus::Paid,
+    OrderStatus::Accepted,
+    OrderStatus::Preparing,
+    OrderStatus::Ready,
+];
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/staff/orders", get(list_orders))
+        .route("/api/staff/orders/feed", get(feed_stream))
+        .route("/api/staff/orders/feed/events", get(feed_events))
+        .route("/api/staff/orders/feed/token", post(feed_token))
+        .route("/api/staff/orders/:id", get(order_status))
+        .route("/api/staff/orders/:id/status", post(update_status))
+}
+
+impl IntoResponse for StatusError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            StatusError::NotFound(_) => StatusCode::NOT_FOUND,
+            StatusError::InvalidTransition { .. } | StatusError::Unknown(_) => StatusCode::CONFLICT,
+            StatusError::Database(e) => {
+                tracing::error!("Order status update failed: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, Json(serde_json::json!({ "error": "invalid_status_change", "message": self.to_string() }))).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    /// Comma-separated statuses, e.g. `paid,accepted`.
+    status: Option<String>,
+}
+
+#[derive(Serialize)]
+struct KitchenOrder {
+    id: String,
+    email: Option<String>,
+    total_cents: i64,
+    status: String,
+    created_at: String,
+    pickup_at: Option<String>,
+    items: Vec<TicketItem>,
+    allowed_next: Vec<OrderStatus>,
+    /// Set when the order's discount was no longer available at payment.
+    discount_issue: Option<String>,
+    /// Sum of completed refunds; partial refunds do not change `status`.
+    refunded_cents: i64,
+}
+
+async fn list_orders(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(q): Query<ListQuery>,
+) -> Result<Json<serde_json::Value>, Response> {
+    require_staff(&headers, &state).await?;
+
+    let statuses: Vec<OrderStatus> = match q.status.as_deref() {
+        Some(list) => list.split(',')
+            .map(|s| OrderStatus::parse(s.trim()))
+            .collect::<Option<_>>()
+            .ok_or_else(|| StatusCode::BAD_REQUEST.into_response())?,
+        None => ACTIVE_STATUSES.to_vec(),
+    };
+    let filter: Vec<&str> = statuses.iter().map(|s| s.as_str()).collect();
+
+    // Oldest first, scheduled orders by their pickup time: that is the order the kitchen works in
+    let rows = sqlx::query(r#"SELECT id, email, total_cents, status, created_at, pickup_at, payment_provider, discount_issue,
+           (SELECT COALESCE(SUM(amount_cents), 0) FROM refunds WHERE order_id = orders.id AND NOT pending) AS refunded_cents
+           FROM orders WHERE status IN (SELECT value FROM json_each(?)) ORDER BY COALESCE(pickup_at, created_at) ASC LIMIT 200"#)
+        .bind(serde_json::to_string(&filter).unwrap_or_default())
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+
+    let mut orders = Vec::with_capacity(rows.len());
+    for r in rows {
+        let id: String = r.try_get("id").unwrap_or_default();
+        let status: String = r.try_get("status").unwrap_or_default();
+        let items = ticket_items(&state.pool, &id).await.unwrap_or_default();
+        orders.push(KitchenOrder {
+            allowed_next: OrderStatus::parse(&status)
+                .map(|s| s.allowed_next(pays_at_pickup(r.try_get::<Option<String>, _>("payment_provider").ok().flatten().as_deref())))
+                .unwrap_or_default(),
+            id,
+            email: r.try_get("email").ok().flatten(),
+            total_cents: r.try_get("total_cents").unwrap_or(0),
+            status,
+            created_at: r.try_get("created_at").unwrap_or_default(),
+            pickup_at: r.try_get("pickup_at").ok().flatten(),
+            items,
+            discount_issue: r.try_get("discount_issue").ok().flatten(),
+            refunded_cents: r.try_get("refunded_cents").unwrap_or(0),
+        });
+    }
+
+    Ok(Json(serde_json::json!({ "orders": orders })))
+}
+
+#[derive(Serialize)]
+struct OrderStatusResponse {
+    order_id: String,
+    status: OrderStatus,
+    allowed_next: Vec<OrderStatus>,
+    estimated_ready_at: Option<String>,
+    history: Vec<StatusEvent>,
+}
+
+async fn load_status(state: &AppState, order_id: &str) -> Result<OrderStatusResponse, StatusError> {
+    let mut conn = state.pool.acquire().await?;
+    let row = sqlx::query(r#"SELECT status, estimated_ready_at, payment_provider FROM orders WHERE id = ?"#)
+        .bind(order_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| StatusError::NotFound(order_id.to_string()))?;
+    let current: String = row.try_get("status")?;
+    let status = OrderStatus::parse(&current).ok_or(StatusError::Unknown(current))?;
+    Ok(OrderStatusResponse {
+        order_id: order_id.to_string(),
+        status,
+        allowed_next: status.allowed_next(pays_at_pickup(row.try_get::<Option<String>, _>("payment_provider")?.as_deref())),
+        estimated_ready_at: row.try_get("estimated_ready_at").ok().flatten(),
+        history: history(&mut conn, order_id).await?,
+    })
+}
+
+async fn order_status(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(order_id): Path<String>,
+) -> Result<Json<OrderStatusResponse>, Response> {
+    require_staff(&headers, &state).await?;
+    load_status(&state, &order_id).await.map(Json).map_err(IntoResponse::into_response)
+}
+
+#[derive(Deserialize)]
+struct UpdateStatusRequest {
+    status: OrderStatus,
+    note: Option<String>,
+    /// Kitchen's own estimate, shown to the customer as the ready time.
+    ready_in_minutes: Option<i64>,
+}
+
+async fn update_status(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(order_id): Path<String>,
+    Json(payload): Json<UpdateStatusRequest>,
+) -> Result<Json<OrderStatusResponse>, Response> {
+    let email = require_staff(&headers, &state).await?;
+
+    // A refund moves money and only comes from the refund flow
+    if payload.status == OrderStatus::Refunded {
+        return Err((StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": "invalid_status_change",
+            "message": "use the refund endpoint to refund an order",
+        }))).into_response());
+    }
+
+    // Cancelling an order paid online gives the money back, and with it the
+    // coupon use and gift balance, so it goes through the refund flow
+    if payload.status == OrderStatus::Cancelled && cancel_needs_refund(&state, &order_id).await.map_err(|e| StatusError::from(e).into_response())? {
+        let request = RefundRequest {
+            items: None,
+            reason: Some(payload.note.clone().unwrap_or_else(|| "Cancelled by staff".into())),
+        };
+        refund_order(&state, &order_id, &request, &email).await.map_err(IntoResponse::into_response)?;
+        return load_status(&state, &order_id).await.map(Json).map_err(IntoResponse::into_response);
+    }
+
+    let mut conn = state.pool.acquire().await.map_err(|e| StatusError::from(e).into_response())?;
+    transition(&mut conn, &order_id, payload.status, &email, payload.note.as_deref())
+        .await
+        .map_err(IntoResponse::into_response)?;
+    if let Some(minutes) = payload.ready_in_minutes {
+        tracking::set_estimate(&mut conn, &order_id, minutes).await.map_err(|e| StatusError::from(e).into_response())?;
+    }
+    drop(conn);
+    feed::publish(&state, &order_id).await;
+    if payload.status == OrderStatus::Ready {
+        tracking::notify_ready(&state, &order_id).await;
+    }
+
+    load_status(&state, &order_id).await.map(Json).map_err(IntoResponse::into_response)
+}
+
+/// Whether the order may still be cancelled and money was taken online for it.
+async fn cancel_needs_refund(state: &AppState, order_id: &str) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query(r#"SELECT status, payment_provider FROM orders WHERE id = ?"#)
+        .bind(order_id)
+        .fetch_optional(&state.pool)
+        .await?;
+    let Some(row) = row else { return Ok(false) };
+    let pay_at_pickup = pays_at_pickup(row.try_get::<Option<String>, _>("payment_provider")?.as_deref());
+    Ok(OrderStatus::parse(&row.try_get::<String, _>("status")?)
+        .is_some_and(|s| s.can_transition_to(OrderStatus::Cancelled, pay_at_pickup) && s.is_refundable(pay_at_pickup)))
+}
+
+/// Events read per query; the JSON endpoint pages with `has_more`, the
+/// stream reads page after page until it is caught up.
+const CATCH_UP_LIMIT: i64 = 500;
+
+/// Lifetime of a feed token. It is only checked when the stream opens, so an
+/// open stream outlives it; a tablet fetches a fresh one to reconnect.
+const FEED_TOKEN_SECONDS: i64 = 60;
+const FEED_TOKEN_PURPOSE: &str = "kitchen_feed";
+
+#[derive(Deserialize)]
+struct FeedQuery {
+    /// Feed token from `POST /api/staff/orders/feed/token`, for clients that
+    /// cannot set headers (EventSource). Login tokens are not accepted here.
+    token: Option<String>,
+    /// Cursor of the last event seen; omit to start from now.
+    since: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FeedClaims { sub: String, purpose: String, exp: usize }
+
+fn feed_token_email(token: &str, state: &AppState) -> Option<String> {
+    let data = decode::<FeedClaims>(token, &DecodingKey::from_secret(state.jwt_secret.as_bytes()), &Validation::default()).ok()?;
+    (data.claims.purpose == FEED_TOKEN_PURPOSE).then_some(data.claims.sub)
+}
+
+/// Short-lived token that only opens the kitchen feed, so the login token
+/// never ends up in a URL (and in proxy or browser logs).
+async fn feed_token(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap) -> Result<Json<serde_json::Value>, Response> {
+    let email = require_staff(&headers, &state).await?;
+    let exp = (chrono::Utc::now() + chrono::Duration::seconds(FEED_TOKEN_SECONDS)).timestamp() as usize;
+    let claims = FeedClaims { sub: email, purpose: FEED_TOKEN_PURPOSE.into(), exp };
+    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(state.jwt_secret.as_bytes()))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+    Ok(Json(serde_json::json!({ "token": token, "expires_in": FEED_TOKEN_SECONDS })))
+}
+
+async fn require_staff_for_feed(headers: &HeaderMap, state: &AppState, token: Option<&str>) -> Result<String, StatusCode> {
+    let email = extract_email_from_token(headers, state)
+        .or_else(|| token.and_then(|t| feed_token_email(t, state)))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    if !is_staff_user(&email, state).await { return Err(StatusCode::FORBIDDEN); }
+    Ok(email)
+}
+
+/// Every feed event after `since`, read a page at a time.
+async fn catch_up(pool: &sqlx::SqlitePool, since: i64) -> Result<Vec<FeedEvent>, sqlx::Error> {
+    let mut events = Vec::new();
+    let mut cursor = since;
+    loop {
+        let page = events_since(pool, cursor, CATCH_UP_LIMIT).await?;
+        let more = page.len() as i64 == CATCH_UP_LIMIT;
+        cursor = page.last().map(|e| e.cursor).unwrap_or(cursor);
+        events.extend(page);
+        if !more {
+            return Ok(events);
+        }
+    }
+}
+
+/// Catch-up query: feed events after `since`, oldest first.
+async fn feed_events(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(q): Query<FeedQuery>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    require_staff_for_feed(&headers, &state, q.token.as_deref()).await?;
+    let since = q.since.unwrap_or(0);
+    let events = events_since(&state.pool, since, CATCH_UP_LIMIT).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let cursor = events.last().map(|e| e.cursor).unwrap_or(since);
+    Ok(Json(serde_json::json!({ "events": events, "cursor": cursor, "has_more": events.len() as i64 == CATCH_UP_LIMIT })))
+}
+
+fn sse_event(event: &FeedEvent) -> Event {
+    let kind = match event.kind {
+        feed::FeedEventKind::NewOrder => "new_order",
+        feed::FeedEventKind::StatusChanged => "status_changed",
+    };
+    Event::default()
+        .id(event.cursor.to_string())
+        .event(kind)
+        .json_data(event)
+        .unwrap_or_else(|_| Event::default().comment("unserializable event"))
+}
+
+/// Live kitchen feed over Server-Sent Events.
+///
+/// Starts with everything after `since` (or the `Last-Event-ID` the browser
+/// sends when it reconnects), then follows the broadcast channel. A subscriber
+/// that lags behind the channel is disconnected so it reconnects and catches
+/// up from the database instead of silently missing tickets.
+async fn feed_stream(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(q): Query<FeedQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let email = require_staff_for_feed(&headers, &state, q.token.as_deref()).await?;
+
+    let last_event_id = headers.get("last-event-id").and_then(|v| v.to_str().ok()).and_then(|v| v.parse::<i64>().ok());
+    // Subscribe before reading the backlog so nothing falls between the two
+    let live = BroadcastStream::new(state.order_events.subscribe());
+    let since = match last_event_id.or(q.since) {
+        Some(cursor) => cursor,
+        None => latest_cursor(&state.pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+    };
+    let backlog = catch_up(&state.pool, since).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut cursor = backlog.last().map(|e| e.cursor).unwrap_or(since);
+    tracing::info!("Kitchen feed opened by {} from cursor {}", email, since);
+
+    let live = live
+        .take_while(|msg| !matches!(msg, Err(BroadcastStreamRecvError::Lagged(_))))
+        .filter_map(move |msg| match msg {
+            Ok(event) if event.cursor > cursor => {
+                cursor = event.cursor;
+                Some(event)
+            }
+            _ => None,
+        });
+    let stream = tokio_stream::iter(backlog)
+        .chain(live)
+        .map(|event| Ok(sse_event(&event)));
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{add_coupon, add_product, mock_state, place_order};
+
+    async fn staff_user(state: &AppState) {
+        sqlx::query(r#"INSERT INTO users (id, email, password_hash, role) VALUES ('u-1', 'cook@example.com', '-', 'staff')"#)
+            .execute(&state.pool)
+            .await
+            .unwrap();
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(axum::http::header::AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+        headers
+    }
+
+    fn login_token(state: &AppState) -> String {
+        let exp = (chrono::Utc::now() + chrono::Duration::days(7)).timestamp();
+        let claims = serde_json::json!({ "sub": "u-1", "email": "cook@example.com", "exp": exp });
+        encode(&Header::default(), &claims, &EncodingKey::from_secret(state.jwt_secret.as_bytes())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn catch_up_reads_past_one_page() {
+        let state = mock_state().await;
+        add_product(&state.pool, "pho", 1250).await;
+        sqlx::query(r#"INSERT INTO orders (id, email, total_cents, items_json, status) VALUES ('order-1', 'a@example.com', 1250, '{}', 'paid')"#)
+            .execute(&state.pool)
+            .await
+            .unwrap();
+        sqlx::query(r#"INSERT INTO order_items (id, order_id, product_id, quantity, unit_amount) VALUES ('item-1', 'order-1', 'pho', 1, 1250)"#)
+            .execute(&state.pool)
+            .await
+            .unwrap();
+        let total = CATCH_UP_LIMIT * 2 + 10;
+        sqlx::query(
+            r#"WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < ?)
+               INSERT INTO order_status_events (id, order_id, from_status, to_status, actor) SELECT 'e-' || i, 'order-1', 'paid', 'paid', 'system' FROM n"#,
+        )
+        .bind(total)
+        .execute(&state.pool)
+        .await
+        .unwrap();
+
+        let events = catch_up(&state.pool, 0).await.unwrap();
+        assert_eq!(events.len() as i64, total);
+        assert!(events.windows(2).all(|w| w[0].cursor < w[1].cursor));
+        assert_eq!(catch_up(&state.pool, events[4].cursor).await.unwrap().len() as i64, total - 5);
+    }
+
+    #[tokio::test]
+    async fn feed_query_takes_feed_tokens_only() {
+        let state = mock_state().await;
+        staff_user(&state).await;
+        let login = login_token(&state);
+
+        let Json(issued) = feed_token(Extension(state.clone()), bearer(&login)).await.unwrap();
+        let token = issued["token"].as_str().unwrap();
+        assert_eq!(require_staff_for_feed(&HeaderMap::new(), &state, Some(token)).await, Ok("cook@example.com".into()));
+        assert_eq!(require_staff_for_feed(&HeaderMap::new(), &state, Some(&login)).await, Err(StatusCode::UNAUTHORIZED));
+        assert_eq!(require_staff(&bearer(token), &state).await.unwrap_err().status(), StatusCode::UNAUTHORIZED, "feed tokens only open the feed");
+        assert_eq!(feed_token(Extension(state.clone()), HeaderMap::new()).await.unwrap_err().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_paid_order_refunds_it() {
+        let state = mock_state().await;
+        staff_user(&state).await;
+        add_product(&state.pool, "pho", 1250).await;
+        add_coupon(&state.pool, "TEN", 10, 5).await;
+        let order_id = place_order(&state, &[("pho", 1)], Some("TEN")).await;
+
+        let cancel = UpdateStatusRequest { status: OrderStatus::Cancelled, note: None, ready_in_minutes: None };
+        let Json(updated) = update_status(Extension(state.clone()), bearer(&login_token(&state)), Path(order_id.clone()), Json(cancel)).await.unwrap();
+        assert_eq!(updated.status, OrderStatus::Refunded);
+        let refunded: i64 = sqlx::query_scalar(r#"SELECT SUM(amount_cents) FROM refunds WHERE order_id = ?"#).bind(&order_id).fetch_one(&state.pool).await.unwrap();
+        let uses: i64 = sqlx::query_scalar(r#"SELECT remaining_uses FROM coupons WHERE code = 'TEN'"#).fetch_one(&state.pool).await.unwrap();
+        assert_eq!((refunded, uses), (1125, 5));
+    }
+}
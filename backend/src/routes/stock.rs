@@ -0,0 +1,52 @@
+use axum::{routing::{get, post, put}, extract::Path, http::HeaderMap, response::{IntoResponse, Response}, Json, Router, Extension};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::catalog::stock::{self, StockError};
+use crate::state::AppState;
+use crate::routes::admin::require_staff;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/staff/stock", get(list))
+        .route("/api/staff/products/:id/sold-out", post(set_sold_out))
+        .route("/api/staff/products/:id/daily-limit", put(set_daily_limit))
+}
+
+#[derive(Deserialize)]
+struct SoldOutRequest {
+    #[serde(default = "default_true")]
+    sold_out: bool,
+}
+
+fn default_true() -> bool { true }
+
+#[derive(Deserialize)]
+struct DailyLimitRequest {
+    /// `null` removes the limit.
+    daily_limit: Option<i64>,
+}
+
+/// Today's portions for the kitchen, menu order.
+async fn list(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap) -> Result<Json<serde_json::Value>, Response> {
+    require_staff(&headers, &state).await?;
+    let day = state.schedule.stock_day(chrono::Utc::now());
+    let stock = stock::for_day(&state.pool, day).await.map_err(|e| StockError::from(e).into_response())?;
+    let mut products: Vec<_> = stock.into_values().collect();
+    products.sort_by(|a, b| (&a.category, a.name.to_lowercase()).cmp(&(&b.category, b.name.to_lowercase())));
+    Ok(Json(serde_json::json!({ "stock_day": day.to_string(), "products": products })))
+}
+
+async fn set_sold_out(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(product_id): Path<String>, Json(payload): Json<SoldOutRequest>) -> Result<Json<serde_json::Value>, Response> {
+    let email = require_staff(&headers, &state).await?;
+    let day = state.schedule.stock_day(chrono::Utc::now());
+    stock::set_sold_out(&state.pool, &product_id, day, payload.sold_out).await.map_err(IntoResponse::into_response)?;
+    tracing::info!("{} marked {} {} for {}", email, product_id, if payload.sold_out { "sold out" } else { "available" }, day);
+    Ok(Json(serde_json::json!({ "ok": true, "product_id": product_id, "sold_out": payload.sold_out, "stock_day": day.to_string() })))
+}
+
+async fn set_daily_limit(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, Path(product_id): Path<String>, Json(payload): Json<DailyLimitRequest>) -> Result<Json<serde_json::Value>, Response> {
+    require_staff(&headers, &state).await?;
+    stock::set_daily_limit(&state.pool, &product_id, payload.daily_limit).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(serde_json::json!({ "ok": true, "product_id": product_id, "daily_limit": payload.daily_limit })))
+}
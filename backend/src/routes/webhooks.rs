@@ -1,27 +1,27 @@
-use axum::{routing::post, Json, Router, Extension};
+use axum::{routing::post, Json, Router, Extension, http::{HeaderMap, StatusCode}};
 use serde::Deserialize;
+use serde_json::Value;
 use std::sync::Arc;
-use sqlx::Row;
-use uuid::Uuid;
 
-use crate::{state::AppState, payments::capture_paypal_order};
+use crate::state::AppState;
+use crate::payments::{value_to_cents, CapturedPayment, PaymentStatus, WebhookTransmission};
+use crate::orders::finalize::{finalize_payment, find_finalized, FinalizeError};
+use crate::orders::refund::{reconcile_claims, record_refund, Recorded, RefundRecord};
+use crate::orders::status::{transition, OrderStatus, StatusError};
 
+/// PayPal event envelope; `resource` depends on `event_type`.
 #[derive(Deserialize)]
-struct PayPalWebhookBody {
+struct PayPalEvent {
     id: String,
-    status: String,
-    purchase_units: Vec<PayPalPurchaseUnit>,
+    event_type: String,
+    #[serde(default)]
+    resource: Value,
 }
 
-#[derive(Deserialize)]
-struct PayPalPurchaseUnit {
-    amount: PayPalAmount,
-}
-
-#[derive(Deserialize)]
-struct PayPalAmount {
-    currency_code: String,
-    value: String,
+/// What happened to a stored event.
+pub(crate) enum EventOutcome {
+    Processed(Option<String>),
+    Ignored(String),
 }
 
 pub fn router() -> Router {
@@ -32,123 +32,388 @@ pub fn router() -> Router {
 
 async fn paypal_webhook(
     Extension(state): Extension<Arc<AppState>>,
-    Json(payload): Json<PayPalWebhookBody>,
-) -> Json<serde_json::Value> {
-    tracing::info!("Received PayPal webhook for order: {} with status: {}", payload.id, payload.status);
+    headers: HeaderMap,
+    body: String,
+) -> Result<Json<Value>, StatusCode> {
+    let event: PayPalEvent = serde_json::from_str(&body).map_err(|e| {
+        tracing::warn!("Rejecting malformed PayPal webhook: {}", e);
+        StatusCode::BAD_REQUEST
+    })?;
 
-    // Log purchase details for debugging
-    for unit in &payload.purchase_units {
-        tracing::info!("Purchase unit amount: {} {}", unit.amount.currency_code, unit.amount.value);
+    // The delivery belongs to whichever configured provider can verify it
+    let transmission = WebhookTransmission::from_headers(&headers);
+    let mut verified_by = None;
+    let mut verify_error = None;
+    for provider in state.payments.iter() {
+        match provider.verify_webhook(&transmission, &body).await {
+            Ok(true) => {
+                verified_by = Some(provider.name());
+                break;
+            }
+            Ok(false) => {}
+            Err(e) => verify_error = Some(e),
+        }
     }
+    let Some(provider) = verified_by else {
+        if let Some(e) = verify_error {
+            // Non-2xx makes PayPal retry the delivery later
+            tracing::error!("Could not verify PayPal webhook {}: {:?}", event.id, e);
+            return Err(StatusCode::SERVICE_UNAVAILABLE);
+        }
+        tracing::warn!("Rejecting PayPal webhook {} ({}): signature verification failed", event.id, event.event_type);
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    tracing::info!("Received PayPal webhook {} of type {}", event.id, event.event_type);
+
+    let inserted = sqlx::query(r#"INSERT OR IGNORE INTO webhook_events (id, provider, event_type, resource_id, payload) VALUES (?, ?, ?, ?, ?)"#)
+        .bind(&event.id)
+        .bind(provider)
+        .bind(&event.event_type)
+        .bind(event.resource.get("id").and_then(|v| v.as_str()))
+        .bind(&body)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to store webhook event {}: {:?}", event.id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .rows_affected();
 
-    // Only process completed payments
-    if payload.status != "COMPLETED" {
-        tracing::info!("Ignoring non-completed payment status: {}", payload.status);
-        return Json(serde_json::json!({"status": "ignored", "reason": "non-completed status"}));
+    if inserted == 0 {
+        let status: Option<String> = sqlx::query_scalar(r#"SELECT status FROM webhook_events WHERE id = ?"#)
+            .bind(&event.id)
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if matches!(status.as_deref(), Some("processed") | Some("ignored")) {
+            tracing::info!("Webhook event {} already handled, skipping", event.id);
+            return Ok(Json(serde_json::json!({"status": "duplicate", "event_id": event.id})));
+        }
     }
 
-    // Check if this order already exists
-    if let Ok(Some(_)) = sqlx::query(r#"SELECT id FROM orders WHERE id = ?"#)
-        .bind(&payload.id)
+    match process_event(&state, &event.id).await {
+        Ok(EventOutcome::Processed(order_id)) => Ok(Json(serde_json::json!({"status": "processed", "event_id": event.id, "order_id": order_id}))),
+        Ok(EventOutcome::Ignored(reason)) => Ok(Json(serde_json::json!({"status": "ignored", "event_id": event.id, "reason": reason}))),
+        Err(e) => {
+            tracing::error!("Failed to process webhook event {}: {:?}", event.id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Dispatch a stored event and record the outcome on its `webhook_events` row.
+/// Also used by the admin replay endpoint.
+pub(crate) async fn process_event(state: &AppState, event_id: &str) -> anyhow::Result<EventOutcome> {
+    let (provider, payload): (String, String) = sqlx::query_as(r#"SELECT provider, payload FROM webhook_events WHERE id = ?"#)
+        .bind(event_id)
         .fetch_optional(&state.pool)
-        .await
-    {
-        tracing::info!("Order {} already processed", payload.id);
-        return Json(serde_json::json!({"status": "already_processed"}));
-    }
-
-    // Try to capture the order and process it
-    match capture_paypal_order(&state, &payload.id).await {
-        Ok(captured) => {
-            if captured.status == "COMPLETED" {
-                // Find the pending order and process it
-                if let Ok(Some(row)) = sqlx::query(r#"SELECT email, amount_cents, items_json FROM pending_orders WHERE order_id = ?"#)
-                    .bind(&payload.id)
-                    .fetch_optional(&state.pool)
-                    .await
-                {
-                    // Process the order (similar to paypal_return logic)
-                    // Extract data from the webhook payload and database
-                    let email: String = row.try_get("email").unwrap_or_default();
-                    let amount_cents: i64 = row.try_get("amount_cents").unwrap_or(0);
-                    let items_json: String = row.try_get("items_json").unwrap_or_default();
-
-                    // Parse items for processing
-                    let parsed: serde_json::Value = serde_json::from_str(&items_json).unwrap_or(serde_json::json!({}));
-                    let items = parsed.get("cart").and_then(|v| v.as_array()).cloned().unwrap_or_default();
-
-                    // Create order record (similar logic as in paypal.rs)
-                    let order_db_id = Uuid::new_v4().to_string();
-                    let _ = sqlx::query(r#"INSERT INTO orders (id, email, total_cents, items_json) VALUES (?, ?, ?, ?)"#)
-                        .bind(&order_db_id)
-                        .bind(&email)
-                        .bind(amount_cents)
-                        .bind(&items_json)
-                        .execute(&state.pool)
-                        .await;
-
-                    // Insert order items
-                    for item in &items {
-                        if let (Some(product_id), Some(quantity), Some(unit_amount)) = (
-                            item.get("product_id").and_then(|v| v.as_str()),
-                            item.get("quantity").and_then(|v| v.as_i64()),
-                            item.get("unit_amount").and_then(|v| v.as_i64())
-                        ) {
-                            let _ = sqlx::query(r#"INSERT INTO order_items (id, order_id, product_id, quantity, unit_amount) VALUES (?, ?, ?, ?, ?)"#)
-                                .bind(Uuid::new_v4().to_string())
-                                .bind(&order_db_id)
-                                .bind(product_id)
-                                .bind(quantity)
-                                .bind(unit_amount)
-                                .execute(&state.pool)
-                                .await;
-                        }
-                    }
-
-                    // Send confirmation email (similar logic as in paypal.rs)
-                    if !email.is_empty() {
-                        let mut lines = String::new();
-                        for item in &items {
-                            if let (Some(name), Some(qty), Some(unit)) = (
-                                item.get("name").and_then(|v| v.as_str()),
-                                item.get("quantity").and_then(|v| v.as_i64()),
-                                item.get("unit_amount").and_then(|v| v.as_i64())
-                            ) {
-                                lines.push_str(&format!("- {} x{} @ €{:.2}\n", name, qty, unit as f64 / 100.0));
-                            }
-                        }
-
-                        let body = format!(
-                            "Thank you for your order!\n\nItems:\n{}\nTotal paid: €{:.2}\n\nOrder ID: {}\n\nYou can view your invoice at: {}/thank-you/{}",
-                            lines,
-                            amount_cents as f64 / 100.0,
-                            order_db_id,
-                            state.app_url,
-                            order_db_id
-                        );
-
-                        // Use the email module to send confirmation
-                        if let Err(e) = crate::email::send_email(&state, &email, "Your Order Confirmation", &body).await {
-                            tracing::error!("Failed to send webhook order confirmation email to {}: {:?}", email, e);
-                        }
-                    }
-
-                    // Clean up pending order
-                    let _ = sqlx::query(r#"DELETE FROM pending_orders WHERE order_id = ?"#)
-                        .bind(&payload.id)
-                        .execute(&state.pool)
-                        .await;
-
-                    tracing::info!("Successfully processed webhook for order: {}", payload.id);
-                    return Json(serde_json::json!({"status": "processed", "order_id": payload.id}));
-                }
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("unknown webhook event {}", event_id))?;
+    let event: PayPalEvent = serde_json::from_str(&payload)?;
+
+    let result = dispatch(state, &provider, &event).await;
+    let (status, error) = match &result {
+        Ok(EventOutcome::Processed(_)) => ("processed", None),
+        Ok(EventOutcome::Ignored(reason)) => ("ignored", Some(reason.clone())),
+        Err(e) => ("failed", Some(e.to_string())),
+    };
+    sqlx::query(r#"UPDATE webhook_events SET status = ?, error = ?, attempts = attempts + 1, processed_at = strftime('%Y-%m-%dT%H:%M:%fZ','now') WHERE id = ?"#)
+        .bind(status)
+        .bind(error)
+        .bind(event_id)
+        .execute(&state.pool)
+        .await?;
+    result
+}
+
+/// `provider` is the one that verified the delivery; events only ever touch
+/// payments that provider created.
+async fn dispatch(state: &AppState, provider: &str, event: &PayPalEvent) -> anyhow::Result<EventOutcome> {
+    match event.event_type.as_str() {
+        "CHECKOUT.ORDER.APPROVED" => order_approved(state, provider, &event.resource).await,
+        "PAYMENT.CAPTURE.COMPLETED" => capture_completed(state, provider, &event.resource).await,
+        "PAYMENT.CAPTURE.REFUNDED" => capture_refunded(state, provider, &event.resource).await,
+        "PAYMENT.CAPTURE.DENIED" => capture_denied(state, provider, &event.resource).await,
+        other => Ok(EventOutcome::Ignored(format!("unhandled event type {}", other))),
+    }
+}
+
+fn str_at<'a>(value: &'a Value, pointer: &str) -> Option<&'a str> {
+    value.pointer(pointer).and_then(|v| v.as_str())
+}
+
+/// Why an event from `provider` must leave `payment_order_id` alone, if it was
+/// created through another provider. Unknown payments pass; the handlers report those.
+async fn foreign_payment(state: &AppState, provider: &str, payment_order_id: &str) -> anyhow::Result<Option<String>> {
+    Ok(state.payments.for_payment(&state.pool, payment_order_id).await?
+        .filter(|owner| owner.name() != provider)
+        .map(|owner| format!("payment {} belongs to {}, not {}", payment_order_id, owner.name(), provider)))
+}
+
+/// The buyer approved an order but we never saw the return redirect; capture it ourselves.
+async fn order_approved(state: &AppState, provider: &str, resource: &Value) -> anyhow::Result<EventOutcome> {
+    let order_id = str_at(resource, "/id").ok_or_else(|| anyhow::anyhow!("approved event without order id"))?;
+    if let Some(reason) = foreign_payment(state, provider, order_id).await? {
+        return Ok(EventOutcome::Ignored(reason));
+    }
+    if let Some(existing) = find_finalized(state, order_id).await? {
+        return Ok(EventOutcome::Processed(Some(existing)));
+    }
+    let provider = state.payments.get(provider).ok_or_else(|| anyhow::anyhow!("payment provider {} is not configured", provider))?;
+    let captured = provider.capture(order_id).await?;
+    finalized_outcome(finalize_payment(state, order_id, &captured).await)
+}
+
+/// A capture finished; `resource` is the capture, the order id sits in `supplementary_data`.
+async fn capture_completed(state: &AppState, provider: &str, resource: &Value) -> anyhow::Result<EventOutcome> {
+    let Some(order_id) = str_at(resource, "/supplementary_data/related_ids/order_id") else {
+        return Ok(EventOutcome::Ignored("capture without related order id".into()));
+    };
+    if let Some(reason) = foreign_payment(state, provider, order_id).await? {
+        return Ok(EventOutcome::Ignored(reason));
+    }
+    let captured = CapturedPayment {
+        capture_id: str_at(resource, "/id").map(|s| s.to_string()),
+        status: PaymentStatus::Completed,
+        amount_cents: str_at(resource, "/amount/value").and_then(value_to_cents),
+    };
+    finalized_outcome(finalize_payment(state, order_id, &captured).await)
+}
+
+fn finalized_outcome(result: Result<crate::orders::finalize::FinalizedOrder, FinalizeError>) -> anyhow::Result<EventOutcome> {
+    match result {
+        Ok(order) => {
+            if !order.created {
+                tracing::info!("Webhook matched already finalized order {}", order.order_id);
             }
+            Ok(EventOutcome::Processed(Some(order.order_id)))
         }
-        Err(e) => {
-            tracing::error!("Failed to capture PayPal order {}: {:?}", payload.id, e);
+        // Pending rows expire after 24h; nothing left to turn into an order
+        Err(FinalizeError::PendingNotFound(id)) => Ok(EventOutcome::Ignored(format!("no pending order for {}", id))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// `resource` is the refund; its `up` link points at the refunded capture.
+async fn capture_refunded(state: &AppState, provider: &str, resource: &Value) -> anyhow::Result<EventOutcome> {
+    let capture_id = resource.get("links").and_then(|l| l.as_array()).and_then(|links| {
+        links.iter()
+            .find(|l| l.get("rel").and_then(|r| r.as_str()) == Some("up"))
+            .and_then(|l| l.get("href").and_then(|h| h.as_str()))
+            .and_then(|href| href.rsplit('/').next())
+    });
+    let Some(capture_id) = capture_id else {
+        return Ok(EventOutcome::Ignored("refund without capture link".into()));
+    };
+
+    let Some((order_id, order_provider)) = sqlx::query_as::<_, (String, Option<String>)>(r#"SELECT id, payment_provider FROM orders WHERE payment_capture_id = ?"#)
+        .bind(capture_id)
+        .fetch_optional(&state.pool)
+        .await?
+    else {
+        return Ok(EventOutcome::Ignored(format!("no order for capture {}", capture_id)));
+    };
+    // Orders from before providers were recorded were paid through PayPal
+    let order_provider = order_provider.unwrap_or_else(|| "paypal".into());
+    if order_provider != provider {
+        return Ok(EventOutcome::Ignored(format!("order {} was paid through {}, not {}", order_id, order_provider, provider)));
+    }
+    let Some(refund_id) = str_at(resource, "/id") else {
+        return Ok(EventOutcome::Ignored("refund without id".into()));
+    };
+
+    // Refunds issued from our admin endpoint are already recorded; ones made in
+    // the PayPal dashboard are booked here the same way
+    let provider_status = str_at(resource, "/status").map(PaymentStatus::from_provider).unwrap_or(PaymentStatus::Completed);
+    let record = RefundRecord {
+        claim_id: None,
+        amount_cents: str_at(resource, "/amount/value").and_then(value_to_cents).unwrap_or(0),
+        provider_refund_id: refund_id,
+        provider_status: provider_status.as_str(),
+        reason: Some("Refunded in PayPal"),
+        lines: &[],
+        actor: "webhook",
+    };
+    // An admin refund that was sent but never booked may be this very one
+    reconcile_claims(state, Some(&order_id)).await?;
+    let mut tx = state.pool.begin().await?;
+    match record_refund(&mut tx, &order_id, record).await? {
+        Recorded::Applied { refunded_total_cents, fully_refunded } => {
+            tx.commit().await?;
+            // Partial refunds leave the kitchen status alone
+            if fully_refunded {
+                crate::orders::feed::publish(state, &order_id).await;
+            }
+            tracing::info!("Recorded PayPal refund {} of order {} ({}¢ refunded in total)", refund_id, order_id, refunded_total_cents);
+        }
+        Recorded::Duplicate => tracing::info!("PayPal refund {} of order {} is already recorded", refund_id, order_id),
+        // Failing the delivery makes PayPal send it again once that refund is booked
+        Recorded::ClaimPending => anyhow::bail!("a refund of order {} is still in progress", order_id),
+    }
+    Ok(EventOutcome::Processed(Some(order_id)))
+}
+
+/// The capture was declined after the buyer approved; drop anything still pending
+/// and cancel an order that was already created from it.
+async fn capture_denied(state: &AppState, provider: &str, resource: &Value) -> anyhow::Result<EventOutcome> {
+    let capture_id = str_at(resource, "/id");
+    let order_id = str_at(resource, "/supplementary_data/related_ids/order_id");
+    if capture_id.is_none() && order_id.is_none() {
+        return Ok(EventOutcome::Ignored("denied capture without ids".into()));
+    }
+    if let Some(order_id) = order_id {
+        if let Some(reason) = foreign_payment(state, provider, order_id).await? {
+            return Ok(EventOutcome::Ignored(reason));
         }
     }
 
-    Json(serde_json::json!({"status": "failed", "order_id": payload.id}))
+    let mut tx = state.pool.begin().await?;
+    if let Some(order_id) = order_id {
+        sqlx::query(r#"DELETE FROM pending_orders WHERE order_id = ?"#).bind(order_id).execute(&mut *tx).await?;
+        sqlx::query(r#"DELETE FROM pending_gifts WHERE order_id = ?"#).bind(order_id).execute(&mut *tx).await?;
+    }
+    let cancelled: Option<String> = sqlx::query_scalar(r#"SELECT id FROM orders WHERE payment_order_id = ? OR payment_capture_id = ?"#)
+        .bind(order_id)
+        .bind(capture_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if let Some(id) = &cancelled {
+        apply_status(&mut tx, id, OrderStatus::Cancelled, "PayPal capture denied").await?;
+    }
+    tx.commit().await?;
+
+    if let Some(id) = &cancelled {
+        crate::orders::feed::publish(state, id).await;
+        tracing::warn!("Order {} cancelled: PayPal capture {:?} was denied", id, capture_id);
+    }
+    Ok(EventOutcome::Processed(cancelled))
 }
 
+/// Status changes reported by PayPal are facts, so an order already past the
+/// target state is logged rather than treated as a failed delivery.
+async fn apply_status(conn: &mut sqlx::SqliteConnection, order_id: &str, to: OrderStatus, note: &str) -> anyhow::Result<()> {
+    match transition(conn, order_id, to, "webhook", Some(note)).await {
+        Ok(_) => Ok(()),
+        Err(StatusError::Database(e)) => Err(e.into()),
+        Err(e) => {
+            tracing::warn!("Webhook left order {} unchanged: {}", order_id, e);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{add_product, mock_state, signed_webhook_headers, start_order};
+
+    fn refund_event(event_id: &str, refund_id: &str) -> String {
+        serde_json::json!({
+            "id": event_id,
+            "event_type": "PAYMENT.CAPTURE.REFUNDED",
+            "resource": {
+                "id": refund_id,
+                "status": "COMPLETED",
+                "amount": { "value": "12.00", "currency_code": "EUR" },
+                "links": [{ "rel": "up", "href": "https://api.paypal.com/v2/payments/captures/CAP-1" }]
+            }
+        })
+        .to_string()
+    }
+
+    async fn deliver(state: &Arc<AppState>, body: &str) -> Result<Value, StatusCode> {
+        paypal_webhook(Extension(state.clone()), signed_webhook_headers(body), body.to_string()).await.map(|Json(v)| v)
+    }
+
+    async fn paid_order(state: &AppState) {
+        sqlx::query(r#"INSERT INTO orders (id, email, total_cents, items_json, status, payment_provider, payment_capture_id) VALUES ('order-1', 'a@example.com', 1200, '{"cart":[]}', 'paid', 'mock', 'CAP-1')"#)
+            .execute(&state.pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn replayed_event_is_not_processed_twice() {
+        let state = mock_state().await;
+        paid_order(&state).await;
+        let body = refund_event("WH-1", "REFUND-1");
+
+        let first = deliver(&state, &body).await.unwrap();
+        assert_eq!(first["status"], "processed");
+        let replay = deliver(&state, &body).await.unwrap();
+        assert_eq!(replay["status"], "duplicate");
+
+        let attempts: i64 = sqlx::query_scalar(r#"SELECT attempts FROM webhook_events WHERE id = 'WH-1'"#).fetch_one(&state.pool).await.unwrap();
+        let refunds: i64 = sqlx::query_scalar(r#"SELECT COUNT(*) FROM refunds WHERE order_id = 'order-1'"#).fetch_one(&state.pool).await.unwrap();
+        assert_eq!((attempts, refunds), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn refund_redelivered_under_a_new_event_id_is_booked_once() {
+        let state = mock_state().await;
+        paid_order(&state).await;
+
+        deliver(&state, &refund_event("WH-1", "REFUND-1")).await.unwrap();
+        let again = deliver(&state, &refund_event("WH-2", "REFUND-1")).await.unwrap();
+        assert_eq!(again["status"], "processed");
+
+        let refunded: i64 = sqlx::query_scalar(r#"SELECT SUM(amount_cents) FROM refunds WHERE order_id = 'order-1'"#).fetch_one(&state.pool).await.unwrap();
+        let status: String = sqlx::query_scalar(r#"SELECT status FROM orders WHERE id = 'order-1'"#).fetch_one(&state.pool).await.unwrap();
+        assert_eq!((refunded, status.as_str()), (1200, "refunded"));
+    }
+
+    #[tokio::test]
+    async fn delivery_leaves_orders_of_other_providers_alone() {
+        let state = mock_state().await;
+        sqlx::query(r#"INSERT INTO orders (id, email, total_cents, items_json, status, payment_provider, payment_capture_id) VALUES ('order-1', 'a@example.com', 1200, '{"cart":[]}', 'paid', 'paypal', 'CAP-1')"#)
+            .execute(&state.pool)
+            .await
+            .unwrap();
+
+        let ignored = deliver(&state, &refund_event("WH-1", "REFUND-1")).await.unwrap();
+        assert_eq!(ignored["status"], "ignored");
+        let refunds: i64 = sqlx::query_scalar(r#"SELECT COUNT(*) FROM refunds"#).fetch_one(&state.pool).await.unwrap();
+        assert_eq!(refunds, 0);
+    }
+
+    #[tokio::test]
+    async fn capture_of_another_amount_is_flagged_on_the_order() {
+        let state = mock_state().await;
+        add_product(&state.pool, "soup", 1200).await;
+        let payment_id = start_order(&state, &[("soup", 1)], None).await;
+        let body = serde_json::json!({
+            "id": "WH-1",
+            "event_type": "PAYMENT.CAPTURE.COMPLETED",
+            "resource": {
+                "id": "CAP-1",
+                "status": "COMPLETED",
+                "amount": { "value": "1.00", "currency_code": "EUR" },
+                "supplementary_data": { "related_ids": { "order_id": payment_id } }
+            }
+        })
+        .to_string();
+
+        let outcome = deliver(&state, &body).await.unwrap();
+        assert_eq!(outcome["status"], "processed");
+        let (total, issue): (i64, Option<String>) = sqlx::query_as(r#"SELECT total_cents, discount_issue FROM orders WHERE payment_order_id = ?"#)
+            .bind(&payment_id)
+            .fetch_one(&state.pool)
+            .await
+            .unwrap();
+        assert_eq!(total, 1200);
+        assert_eq!(issue.as_deref(), Some("captured 100¢ but the order total is 1200¢"));
+    }
+
+    #[tokio::test]
+    async fn unsigned_delivery_is_rejected_and_not_stored() {
+        let state = mock_state().await;
+        let body = refund_event("WH-1", "REFUND-1");
+        let mut headers = signed_webhook_headers(&body);
+        headers.insert("paypal-transmission-sig", "forged".parse().unwrap());
+
+        let result = paypal_webhook(Extension(state.clone()), headers, body).await;
+        assert_eq!(result.err(), Some(StatusCode::UNAUTHORIZED));
+        let stored: i64 = sqlx::query_scalar(r#"SELECT COUNT(*) FROM webhook_events"#).fetch_one(&state.pool).await.unwrap();
+        assert_eq!(stored, 0);
+    }
+}
@@ -0,0 +1,207 @@
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use uuid::Uuid;
+
+use super::parse_time;
+
+pub const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+#[derive(Serialize, Debug)]
+pub struct OpeningHours {
+    pub id: String,
+    /// 0 = Monday ... 6 = Sunday
+    pub weekday: i64,
+    pub opens_at: String,
+    pub closes_at: String,
+    pub last_order_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct OpeningHoursInput {
+    pub weekday: i64,
+    pub opens_at: String,
+    pub closes_at: String,
+    pub last_order_at: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Closure {
+    pub id: String,
+    pub starts_on: String,
+    pub ends_on: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ClosureInput {
+    pub starts_on: String,
+    /// Defaults to `starts_on` for a single day.
+    pub ends_on: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HoursError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for HoursError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            HoursError::Invalid(_) => (StatusCode::BAD_REQUEST, "invalid_hours"),
+            HoursError::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            HoursError::Database(ref e) => {
+                tracing::error!("Opening hours update failed: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            }
+        };
+        (status, Json(serde_json::json!({ "error": code, "message": self.to_string() }))).into_response()
+    }
+}
+
+/// Normalize an input to `HH:MM` and check the times are in order.
+fn validate_hours(input: &OpeningHoursInput) -> Result<(String, String, Option<String>), HoursError> {
+    if !(0..7).contains(&input.weekday) {
+        return Err(HoursError::Invalid("weekday must be 0 (Monday) to 6 (Sunday)".into()));
+    }
+    let time = |s: &str| parse_time(s).ok_or_else(|| HoursError::Invalid(format!("'{}' is not a HH:MM time", s)));
+    let opens = time(&input.opens_at)?;
+    let closes = time(&input.closes_at)?;
+    if opens >= closes {
+        return Err(HoursError::Invalid("opens_at must be before closes_at".into()));
+    }
+    let last_order = match input.last_order_at.as_deref().filter(|s| !s.trim().is_empty()) {
+        Some(s) => {
+            let t = time(s)?;
+            if t <= opens || t > closes {
+                return Err(HoursError::Invalid("last_order_at must be after opens_at and not after closes_at".into()));
+            }
+            Some(t.format("%H:%M").to_string())
+        }
+        None => None,
+    };
+    Ok((opens.format("%H:%M").to_string(), closes.format("%H:%M").to_string(), last_order))
+}
+
+fn validate_closure(input: &ClosureInput) -> Result<(String, String), HoursError> {
+    let date = |s: &str| NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").map_err(|_| HoursError::Invalid(format!("'{}' is not a YYYY-MM-DD date", s)));
+    let starts = date(&input.starts_on)?;
+    let ends = match input.ends_on.as_deref() {
+        Some(s) => date(s)?,
+        None => starts,
+    };
+    if ends < starts {
+        return Err(HoursError::Invalid("ends_on must not be before starts_on".into()));
+    }
+    Ok((starts.to_string(), ends.to_string()))
+}
+
+pub async fn list_hours(pool: &SqlitePool) -> Result<Vec<OpeningHours>, sqlx::Error> {
+    let rows = sqlx::query(r#"SELECT id, weekday, opens_at, closes_at, last_order_at FROM opening_hours ORDER BY weekday, opens_at"#)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|r| OpeningHours {
+        id: r.try_get("id").unwrap_or_default(),
+        weekday: r.try_get("weekday").unwrap_or(0),
+        opens_at: r.try_get("opens_at").unwrap_or_default(),
+        closes_at: r.try_get("closes_at").unwrap_or_default(),
+        last_order_at: r.try_get("last_order_at").ok().flatten(),
+    }).collect())
+}
+
+pub async fn create_hours(pool: &SqlitePool, input: &OpeningHoursInput) -> Result<String, HoursError> {
+    let (opens, closes, last_order) = validate_hours(input)?;
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(r#"INSERT INTO opening_hours (id, weekday, opens_at, closes_at, last_order_at) VALUES (?, ?, ?, ?, ?)"#)
+        .bind(&id)
+        .bind(input.weekday)
+        .bind(opens)
+        .bind(closes)
+        .bind(last_order)
+        .execute(pool)
+        .await?;
+    Ok(id)
+}
+
+pub async fn update_hours(pool: &SqlitePool, id: &str, input: &OpeningHoursInput) -> Result<(), HoursError> {
+    let (opens, closes, last_order) = validate_hours(input)?;
+    let updated = sqlx::query(r#"UPDATE opening_hours SET weekday = ?, opens_at = ?, closes_at = ?, last_order_at = ? WHERE id = ?"#)
+        .bind(input.weekday)
+        .bind(opens)
+        .bind(closes)
+        .bind(last_order)
+        .bind(id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if updated == 0 {
+        return Err(HoursError::NotFound(format!("opening hours {}", id)));
+    }
+    Ok(())
+}
+
+pub async fn delete_hours(pool: &SqlitePool, id: &str) -> Result<(), HoursError> {
+    let deleted = sqlx::query(r#"DELETE FROM opening_hours WHERE id = ?"#).bind(id).execute(pool).await?.rows_affected();
+    if deleted == 0 {
+        return Err(HoursError::NotFound(format!("opening hours {}", id)));
+    }
+    Ok(())
+}
+
+/// Closures ending on or after `from`, or all of them.
+pub async fn list_closures(pool: &SqlitePool, from: Option<NaiveDate>) -> Result<Vec<Closure>, sqlx::Error> {
+    let rows = sqlx::query(r#"SELECT id, starts_on, ends_on, reason FROM closures WHERE ?1 IS NULL OR ends_on >= ?1 ORDER BY starts_on"#)
+        .bind(from.map(|d| d.to_string()))
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|r| Closure {
+        id: r.try_get("id").unwrap_or_default(),
+        starts_on: r.try_get("starts_on").unwrap_or_default(),
+        ends_on: r.try_get("ends_on").unwrap_or_default(),
+        reason: r.try_get("reason").ok().flatten(),
+    }).collect())
+}
+
+pub async fn create_closure(pool: &SqlitePool, input: &ClosureInput) -> Result<String, HoursError> {
+    let (starts, ends) = validate_closure(input)?;
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(r#"INSERT INTO closures (id, starts_on, ends_on, reason) VALUES (?, ?, ?, ?)"#)
+        .bind(&id)
+        .bind(starts)
+        .bind(ends)
+        .bind(input.reason.as_deref())
+        .execute(pool)
+        .await?;
+    Ok(id)
+}
+
+pub async fn update_closure(pool: &SqlitePool, id: &str, input: &ClosureInput) -> Result<(), HoursError> {
+    let (starts, ends) = validate_closure(input)?;
+    let updated = sqlx::query(r#"UPDATE closures SET starts_on = ?, ends_on = ?, reason = ? WHERE id = ?"#)
+        .bind(starts)
+        .bind(ends)
+        .bind(input.reason.as_deref())
+        .bind(id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if updated == 0 {
+        return Err(HoursError::NotFound(format!("closure {}", id)));
+    }
+    Ok(())
+}
+
+pub async fn delete_closure(pool: &SqlitePool, id: &str) -> Result<(), HoursError> {
+    let deleted = sqlx::query(r#"DELETE FROM closures WHERE id = ?"#).bind(id).execute(pool).await?.rows_affected();
+    if deleted == 0 {
+        return Err(HoursError::NotFound(format!("closure {}", id)));
+    }
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, SecondsFormat, TimeZone, Utc};
+use chrono_tz::Tz;
+use sqlx::{Row, SqlitePool};
+
+pub mod hours;
+pub mod slots;
+
+/// When and how the restaurant takes pickup orders.
+#[derive(Clone, Debug)]
+pub struct ScheduleConfig {
+    /// Opening hours are stored in this zone; everything else is UTC.
+    pub timezone: Tz,
+    pub slot_minutes: i64,
+    /// `None` means no limit.
+    pub max_orders_per_slot: Option<i64>,
+    pub max_items_per_slot: Option<i64>,
+    /// How long a table is held for a reservation.
+    pub reservation_minutes: i64,
+    /// A booked table nobody showed up for is marked `no_show` this long after its start.
+    pub no_show_grace_minutes: i64,
+    /// Hour (restaurant time) at which daily portion counts start over.
+    pub stock_reset_hour: u32,
+}
+
+impl ScheduleConfig {
+    pub fn from_env() -> Self {
+        let timezone = std::env::var("RESTAURANT_TIMEZONE")
+            .ok()
+            .and_then(|tz| tz.parse::<Tz>().ok())
+            .unwrap_or(chrono_tz::Europe::Berlin);
+        let limit = |key: &str, default: Option<i64>| match std::env::var(key) {
+            Ok(v) => v.parse::<i64>().ok().filter(|n| *n > 0),
+            Err(_) => default,
+        };
+        Self {
+            timezone,
+            slot_minutes: limit("PICKUP_SLOT_MINUTES", Some(15)).unwrap_or(15),
+            max_orders_per_slot: limit("PICKUP_SLOT_MAX_ORDERS", Some(4)),
+            max_items_per_slot: limit("PICKUP_SLOT_MAX_ITEMS", None),
+            reservation_minutes: limit("RESERVATION_MINUTES", Some(90)).unwrap_or(90),
+            no_show_grace_minutes: limit("RESERVATION_NO_SHOW_MINUTES", Some(30)).unwrap_or(30),
+            stock_reset_hour: std::env::var("STOCK_RESET_HOUR").ok().and_then(|v| v.parse::<u32>().ok()).filter(|h| *h < 24).unwrap_or(4),
+        }
+    }
+
+    /// A local wall-clock time as UTC. On the DST switch the earlier instant wins;
+    /// times that do not exist that day yield `None`.
+    pub fn to_utc(&self, date: NaiveDate, time: NaiveTime) -> Option<DateTime<Utc>> {
+        self.timezone
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .map(|t| t.with_timezone(&Utc))
+    }
+
+    pub fn local_date(&self, at: DateTime<Utc>) -> NaiveDate {
+        at.with_timezone(&self.timezone).date_naive()
+    }
+
+    pub fn local_time(&self, at: DateTime<Utc>) -> NaiveTime {
+        at.with_timezone(&self.timezone).time()
+    }
+
+    /// The day whose portion counts `at` belongs to; it starts at `stock_reset_hour`.
+    pub fn stock_day(&self, at: DateTime<Utc>) -> NaiveDate {
+        (at.with_timezone(&self.timezone).naive_local() - chrono::Duration::hours(self.stock_reset_hour as i64)).date()
+    }
+}
+
+/// One service window of a day, in local time.
+#[derive(Clone, Copy, Debug)]
+pub struct OpeningWindow {
+    pub opens: NaiveTime,
+    pub closes: NaiveTime,
+    /// Orders (and pickup slots) end here; never after `closes`.
+    pub last_order: NaiveTime,
+}
+
+pub fn parse_time(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M").ok()
+}
+
+/// The weekly opening windows of `date`'s weekday, earliest first, ignoring closures.
+pub async fn weekly_windows(pool: &SqlitePool, date: NaiveDate) -> Result<Vec<OpeningWindow>, sqlx::Error> {
+    let rows = sqlx::query(r#"SELECT opens_at, closes_at, last_order_at FROM opening_hours WHERE weekday = ?"#)
+        .bind(date.weekday().num_days_from_monday() as i64)
+        .fetch_all(pool)
+        .await?;
+    let mut windows: Vec<OpeningWindow> = rows
+        .into_iter()
+        .filter_map(|r| {
+            let opens = parse_time(&r.try_get::<String, _>("opens_at").ok()?)?;
+            let closes = parse_time(&r.try_get::<String, _>("closes_at").ok()?)?;
+            let last_order = r.try_get::<Option<String>, _>("last_order_at").ok().flatten()
+                .and_then(|t| parse_time(&t))
+                .map_or(closes, |t| t.clamp(opens, closes));
+            (opens < closes).then_some(OpeningWindow { opens, closes, last_order })
+        })
+        .collect();
+    windows.sort_by_key(|w| w.opens);
+    Ok(windows)
+}
+
+/// Reason of the closure covering `date`, if any (`Some("")` when none was given).
+pub async fn closure_on(pool: &SqlitePool, date: NaiveDate) -> Result<Option<String>, sqlx::Error> {
+    let reason: Option<Option<String>> = sqlx::query_scalar(r#"SELECT reason FROM closures WHERE starts_on <= ?1 AND ends_on >= ?1 ORDER BY starts_on LIMIT 1"#)
+        .bind(date.to_string())
+        .fetch_optional(pool)
+        .await?;
+    Ok(reason.map(Option::unwrap_or_default))
+}
+
+/// The windows the restaurant actually serves on `date`: none on closure days.
+pub async fn windows_for(pool: &SqlitePool, date: NaiveDate) -> Result<Vec<OpeningWindow>, sqlx::Error> {
+    if closure_on(pool, date).await?.is_some() {
+        return Ok(Vec::new());
+    }
+    weekly_windows(pool, date).await
+}
+
+/// Where `at` falls in the opening hours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpenState {
+    Closed,
+    /// Open, but past the last order time of the current window.
+    OpenNoOrders,
+    AcceptingOrders,
+}
+
+pub async fn open_state(pool: &SqlitePool, cfg: &ScheduleConfig, at: DateTime<Utc>) -> Result<OpenState, sqlx::Error> {
+    let time = cfg.local_time(at);
+    let windows = windows_for(pool, cfg.local_date(at)).await?;
+    Ok(match windows.iter().find(|w| w.opens <= time && time < w.closes) {
+        Some(w) if time < w.last_order => OpenState::AcceptingOrders,
+        Some(_) => OpenState::OpenNoOrders,
+        None => OpenState::Closed,
+    })
+}
+
+/// How far ahead `next_opening` looks; covers long vacations.
+const NEXT_OPENING_DAYS: i64 = 60;
+
+/// Start of the first window opening after `after`.
+pub async fn next_opening(pool: &SqlitePool, cfg: &ScheduleConfig, after: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    let mut date = cfg.local_date(after);
+    for _ in 0..NEXT_OPENING_DAYS {
+        for window in windows_for(pool, date).await? {
+            if let Some(opens) = cfg.to_utc(date, window.opens) {
+                if opens > after {
+                    return Ok(Some(opens));
+                }
+            }
+        }
+        date = match date.succ_opt() {
+            Some(d) => d,
+            None => break,
+        };
+    }
+    Ok(None)
+}
+
+/// When an order is due: its stored `pickup_at`, or now for as soon as possible.
+pub fn ready_at(pickup_at: Option<&str>) -> DateTime<Utc> {
+    pickup_at
+        .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+        .map_or_else(Utc::now, |at| at.with_timezone(&Utc))
+}
+
+/// How timestamps are stored in the database (same shape as the `created_at` defaults).
+pub fn db_timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
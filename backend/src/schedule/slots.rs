@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::{Row, Sqlite, Transaction};
+
+use crate::orders::StoredItems;
+use crate::state::AppState;
+
+use super::{db_timestamp, windows_for};
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Slot {
+    /// UTC start of the slot; send this back as `pickup_at` at checkout.
+    pub starts_at: String,
+    /// Local `HH:MM`, for display.
+    pub local_time: String,
+    pub booked_orders: i64,
+    pub booked_items: i64,
+    pub remaining_orders: Option<i64>,
+    pub remaining_items: Option<i64>,
+    pub available: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SlotError {
+    #[error("'{0}' is not a valid pickup time")]
+    InvalidTime(String),
+    #[error("pickup at {0} is not offered")]
+    NotOffered(String),
+    #[error("the pickup slot at {0} is fully booked")]
+    Full(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl SlotError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            SlotError::InvalidTime(_) => "invalid_pickup_time",
+            SlotError::NotOffered(_) => "pickup_slot_not_offered",
+            SlotError::Full(_) => "pickup_slot_full",
+            SlotError::Database(_) => "internal_error",
+        }
+    }
+}
+
+impl IntoResponse for SlotError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            SlotError::Database(ref e) => {
+                tracing::error!("Pickup slot lookup failed: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            SlotError::Full(_) => StatusCode::CONFLICT,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(serde_json::json!({ "error": self.code(), "message": self.to_string() }))).into_response()
+    }
+}
+
+/// `datetime()` modifier selecting pending orders that still hold their slot:
+/// a checkout being paid for holds it as long as its discount and portions.
+fn pending_hold(state: &AppState) -> String {
+    format!("-{} minutes", state.discount_hold_minutes)
+}
+
+/// Orders and items already booked per slot start, for the UTC range of one local day.
+async fn bookings(state: &AppState, from: &str, to: &str) -> Result<HashMap<String, (i64, i64)>, sqlx::Error> {
+    let mut booked: HashMap<String, (i64, i64)> = HashMap::new();
+
+    let rows = sqlx::query(r#"
+        SELECT o.pickup_at, COALESCE(SUM(oi.quantity - oi.refunded_quantity), 0) AS items
+        FROM orders o
+        LEFT JOIN order_items oi ON oi.order_id = o.id
+        WHERE o.pickup_at >= ? AND o.pickup_at < ? AND o.status NOT IN ('cancelled', 'refunded')
+        GROUP BY o.id
+    "#)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&state.pool)
+        .await?;
+    for r in rows {
+        let entry = booked.entry(r.try_get("pickup_at")?).or_default();
+        entry.0 += 1;
+        entry.1 += r.try_get::<i64, _>("items").unwrap_or(0);
+    }
+
+    let pending = sqlx::query(r#"SELECT pickup_at, items_json FROM pending_orders WHERE pickup_at >= ? AND pickup_at < ? AND datetime(created_at) > datetime('now', ?)"#)
+        .bind(from)
+        .bind(to)
+        .bind(pending_hold(state))
+        .fetch_all(&state.pool)
+        .await?;
+    for r in pending {
+        let items = StoredItems::parse(&r.try_get::<Option<String>, _>("items_json").ok().flatten().unwrap_or_default());
+        let entry = booked.entry(r.try_get("pickup_at")?).or_default();
+        entry.0 += 1;
+        entry.1 += items.cart.iter().map(|l| l.quantity).sum::<i64>();
+    }
+    Ok(booked)
+}
+
+/// Pickup slots of a local day, cut from its opening windows. Slots that
+/// start before the kitchen could have the food ready (counted from `now`)
+/// are left out.
+pub async fn slots_for_day(state: &AppState, date: NaiveDate, now: DateTime<Utc>) -> Result<Vec<Slot>, sqlx::Error> {
+    let cfg = &state.schedule;
+    let step = Duration::minutes(cfg.slot_minutes);
+    let earliest = now + Duration::minutes(state.prep_minutes);
+
+    let mut starts: Vec<DateTime<Utc>> = Vec::new();
+    for window in windows_for(&state.pool, date).await? {
+        let mut time = window.opens;
+        // Slots start before the last order time and fit before closing
+        while time < window.last_order && time + step <= window.closes && time >= window.opens {
+            if let Some(at) = cfg.to_utc(date, time) {
+                if at >= earliest {
+                    starts.push(at);
+                }
+            }
+            time += step;
+        }
+    }
+    let (Some(first), Some(last)) = (starts.first(), starts.last()) else {
+        return Ok(Vec::new());
+    };
+    let booked = bookings(state, &db_timestamp(*first), &db_timestamp(*last + step)).await?;
+
+    Ok(starts
+        .into_iter()
+        .map(|at| {
+            let key = db_timestamp(at);
+            let (orders, items) = booked.get(&key).copied().unwrap_or((0, 0));
+            let remaining_orders = cfg.max_orders_per_slot.map(|max| (max - orders).max(0));
+            let remaining_items = cfg.max_items_per_slot.map(|max| (max - items).max(0));
+            Slot {
+                local_time: cfg.local_time(at).format("%H:%M").to_string(),
+                starts_at: key,
+                booked_orders: orders,
+                booked_items: items,
+                available: remaining_orders != Some(0) && remaining_items != Some(0),
+                remaining_orders,
+                remaining_items,
+            }
+        })
+        .collect())
+}
+
+/// Check a requested pickup time for a cart of `items` items ordered at `now`
+/// and return the slot start as stored on the order.
+pub async fn validate_pickup(state: &AppState, pickup_at: &str, items: i64, now: DateTime<Utc>) -> Result<String, SlotError> {
+    let at = DateTime::parse_from_rfc3339(pickup_at.trim())
+        .map_err(|_| SlotError::InvalidTime(pickup_at.to_string()))?
+        .with_timezone(&Utc);
+    let key = db_timestamp(at);
+
+    let slots = slots_for_day(state, state.schedule.local_date(at), now).await?;
+    let slot = slots
+        .into_iter()
+        .find(|s| s.starts_at == key)
+        .ok_or_else(|| SlotError::NotOffered(pickup_at.to_string()))?;
+
+    let fits_items = slot.remaining_items.is_none_or(|left| left >= items);
+    if !slot.available || !fits_items {
+        return Err(SlotError::Full(slot.local_time));
+    }
+    Ok(key)
+}
+
+/// Take the slot `pickup_at` (as returned by `validate_pickup`) for the
+/// pending order just written in `tx`.
+///
+/// `validate_pickup` answers from a snapshot, so two checkouts can both see
+/// the last place. This recounts the slot, the new pending order included,
+/// inside the transaction that wrote it; that write already holds SQLite's
+/// write lock, so racing checkouts are counted one after the other. An
+/// overbooked slot fails and the transaction rolls the pending order back.
+pub async fn reserve(tx: &mut Transaction<'_, Sqlite>, state: &AppState, pickup_at: &str) -> Result<(), SlotError> {
+    let row = sqlx::query(r#"
+        SELECT
+          (SELECT COUNT(*) FROM orders WHERE pickup_at = ?1 AND status NOT IN ('cancelled', 'refunded'))
+          + (SELECT COUNT(*) FROM pending_orders WHERE pickup_at = ?1 AND datetime(created_at) > datetime('now', ?2)) AS orders,
+          (SELECT COALESCE(SUM(oi.quantity - oi.refunded_quantity), 0) FROM orders o JOIN order_items oi ON oi.order_id = o.id
+           WHERE o.pickup_at = ?1 AND o.status NOT IN ('cancelled', 'refunded'))
+          + (SELECT COALESCE(SUM(json_extract(line.value, '$.quantity')), 0) FROM pending_orders p, json_each(p.items_json, '$.cart') line
+             WHERE p.pickup_at = ?1 AND datetime(p.created_at) > datetime('now', ?2)) AS items
+    "#)
+        .bind(pickup_at)
+        .bind(pending_hold(state))
+        .fetch_one(&mut **tx)
+        .await?;
+    let orders: i64 = row.try_get("orders")?;
+    let items: i64 = row.try_get("items")?;
+
+    let cfg = &state.schedule;
+    if cfg.max_orders_per_slot.is_some_and(|max| orders > max) || cfg.max_items_per_slot.is_some_and(|max| items > max) {
+        let local_time = DateTime::parse_from_rfc3339(pickup_at)
+            .map(|at| cfg.local_time(at.with_timezone(&Utc)).format("%H:%M").to_string())
+            .unwrap_or_else(|_| pickup_at.to_string());
+        return Err(SlotError::Full(local_time));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::test_support::mock_state;
+
+    const PICKUP_AT: &str = "2026-10-19T10:00:00.000Z";
+
+    /// Write a pending order for `PICKUP_AT` and try to take the slot, like checkout does.
+    async fn checkout(state: &AppState, id: &str) -> Result<(), SlotError> {
+        let mut tx = state.pool.begin().await.unwrap();
+        sqlx::query(r#"INSERT INTO pending_orders (order_id, email, amount_cents, items_json, pickup_at) VALUES (?, 'guest@example.com', 1250, '{"cart":[{"productId":"pho","unitAmount":625,"quantity":2}]}', ?)"#)
+            .bind(id)
+            .bind(PICKUP_AT)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        reserve(&mut tx, state, PICKUP_AT).await?;
+        tx.commit().await.unwrap();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pending_checkouts_hold_their_slot_for_the_configured_time() {
+        let mut state = mock_state().await;
+        let config = Arc::get_mut(&mut state).unwrap();
+        config.schedule.max_orders_per_slot = Some(1);
+        config.schedule.max_items_per_slot = None;
+        config.discount_hold_minutes = 60;
+
+        checkout(&state, "PAY-1").await.unwrap();
+        assert!(matches!(checkout(&state, "PAY-2").await, Err(SlotError::Full(_))));
+        let pending: i64 = sqlx::query_scalar(r#"SELECT COUNT(*) FROM pending_orders"#).fetch_one(&state.pool).await.unwrap();
+        assert_eq!(pending, 1, "the refused checkout is rolled back");
+
+        // 40 minutes in, a one-hour hold still counts
+        sqlx::query(r#"UPDATE pending_orders SET created_at = strftime('%Y-%m-%dT%H:%M:%fZ','now','-40 minutes')"#).execute(&state.pool).await.unwrap();
+        assert!(matches!(checkout(&state, "PAY-2").await, Err(SlotError::Full(_))));
+        Arc::get_mut(&mut state).unwrap().discount_hold_minutes = 30;
+        checkout(&state, "PAY-2").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn item_limit_counts_pending_carts() {
+        let mut state = mock_state().await;
+        let config = Arc::get_mut(&mut state).unwrap();
+        config.schedule.max_orders_per_slot = None;
+        config.schedule.max_items_per_slot = Some(3);
+
+        checkout(&state, "PAY-1").await.unwrap();
+        assert!(matches!(checkout(&state, "PAY-2").await, Err(SlotError::Full(_))));
+    }
+
+    fn at(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+    }
+
+    #[tokio::test]
+    async fn day_is_cut_into_slots_the_kitchen_can_still_make() {
+        let state = mock_state().await;
+        let monday = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+
+        // Lunch 12:00–15:00 and dinner 17:30 until the 21:00 last order, Berlin time
+        let slots = slots_for_day(&state, monday, at("2026-10-19T08:00:00Z")).await.unwrap();
+        let times: Vec<&str> = slots.iter().map(|s| s.local_time.as_str()).collect();
+        assert_eq!(times.len(), 12 + 14);
+        assert_eq!((times[0], times[11], times[12], times[25]), ("12:00", "14:45", "17:30", "20:45"));
+        assert_eq!(slots[0].starts_at, PICKUP_AT);
+
+        // 12:50 plus 20 minutes of preparation
+        let later = slots_for_day(&state, monday, at("2026-10-19T10:50:00Z")).await.unwrap();
+        assert_eq!(later[0].local_time, "13:15");
+        let saturday = NaiveDate::from_ymd_opt(2026, 10, 24).unwrap();
+        assert_eq!(slots_for_day(&state, saturday, at("2026-10-19T08:00:00Z")).await.unwrap()[0].local_time, "17:30");
+    }
+
+    #[tokio::test]
+    async fn pickup_must_be_an_offered_slot_with_room() {
+        let mut state = mock_state().await;
+        Arc::get_mut(&mut state).unwrap().schedule.max_items_per_slot = Some(3);
+        let now = at("2026-10-19T08:00:00Z");
+
+        assert_eq!(validate_pickup(&state, "2026-10-19T12:00:00+02:00", 1, now).await.unwrap(), PICKUP_AT);
+        assert!(matches!(validate_pickup(&state, "noon", 1, now).await, Err(SlotError::InvalidTime(_))));
+        assert!(matches!(validate_pickup(&state, "2026-10-19T12:05:00+02:00", 1, now).await, Err(SlotError::NotOffered(_))));
+        assert!(matches!(validate_pickup(&state, "2026-10-19T12:00:00+02:00", 1, at("2026-10-19T09:50:00Z")).await, Err(SlotError::NotOffered(_))));
+        assert!(matches!(validate_pickup(&state, "2026-10-19T12:00:00+02:00", 4, now).await, Err(SlotError::Full(_))));
+
+        checkout(&state, "PAY-1").await.unwrap();
+        let slot = slots_for_day(&state, NaiveDate::from_ymd_opt(2026, 10, 19).unwrap(), now).await.unwrap().remove(0);
+        assert_eq!((slot.booked_orders, slot.booked_items, slot.remaining_items, slot.available), (1, 2, Some(1), true));
+        assert!(matches!(validate_pickup(&state, PICKUP_AT, 2, now).await, Err(SlotError::Full(_))));
+    }
+}
@@ -1,11 +1,17 @@
 use sqlx::sqlite::SqlitePool;
 
+use tokio::sync::broadcast;
+
+use crate::catalog::images::ImageConfig;
+use crate::orders::feed::FeedEvent;
+use crate::payments::PaymentProviders;
+use crate::schedule::ScheduleConfig;
+
 #[derive(Clone)]
 pub struct AppState {
     pub pool: SqlitePool,
     pub jwt_secret: String,
     pub app_url: String,
-    pub backend_url: String,
     pub smtp_host: Option<String>,
     pub smtp_port: Option<u16>,
     pub smtp_username: Option<String>,
@@ -13,6 +19,16 @@ pub struct AppState {
     pub smtp_from: Option<String>,
     pub paypal_client_id: Option<String>,
     pub paypal_secret: Option<String>,
-    pub paypal_api_base: String,
+    /// Payment providers offered at checkout; see `payments::PaymentProviders`.
+    pub payments: PaymentProviders,
+    /// Base preparation time used for a new order's estimated ready time.
+    pub prep_minutes: i64,
+    /// How long a checkout holds its portions, coupon use or gift balance while waiting for payment.
+    pub discount_hold_minutes: i64,
+    pub schedule: ScheduleConfig,
+    /// Storage of uploaded product images.
+    pub images: ImageConfig,
+    /// Kitchen feed: new orders and status changes, see `orders::feed`.
+    pub order_events: broadcast::Sender<FeedEvent>,
 }
 
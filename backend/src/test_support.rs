@@ -0,0 +1,134 @@
+//! Shared setup for unit tests: an `AppState` on an in-memory database.
+
+use std::sync::Arc;
+
+use axum::http::HeaderMap;
+use sqlx::SqlitePool;
+
+use crate::catalog::images::ImageConfig;
+use crate::coupons::{holds, redemptions::Customer};
+use crate::orders::finalize::finalize_payment;
+use crate::orders::feed::FEED_CAPACITY;
+use crate::payments::mock::{mock_webhook_signature, MockProvider};
+use crate::payments::{CreatePayment, PaymentProvider, PaymentProviders};
+use crate::pricing::{price_cart, CartLine};
+use crate::schedule::ScheduleConfig;
+use crate::state::AppState;
+
+/// Webhook id the mock provider signs test deliveries with.
+pub const WEBHOOK_ID: &str = "WH-TEST";
+
+/// State offering `providers` at checkout; the first is the default.
+pub async fn state_with(providers: Vec<Arc<dyn PaymentProvider>>) -> Arc<AppState> {
+    Arc::new(AppState {
+        pool: crate::db::memory_pool().await.expect("test database"),
+        jwt_secret: "test_secret".into(),
+        app_url: "http://localhost:5173".into(),
+        smtp_host: None,
+        smtp_port: None,
+        smtp_username: None,
+        smtp_password: None,
+        smtp_from: None,
+        paypal_client_id: None,
+        paypal_secret: None,
+        payments: PaymentProviders::new(providers),
+        prep_minutes: 20,
+        discount_hold_minutes: 30,
+        schedule: ScheduleConfig::from_env(),
+        images: ImageConfig::from_env(),
+        order_events: tokio::sync::broadcast::channel(FEED_CAPACITY).0,
+    })
+}
+
+/// Mock provider that signs webhooks with `WEBHOOK_ID`.
+pub fn mock_provider() -> Arc<dyn PaymentProvider> {
+    Arc::new(MockProvider::new("http://127.0.0.1:8080".into(), Some(WEBHOOK_ID.into())))
+}
+
+/// State backed by the mock payment provider alone.
+pub async fn mock_state() -> Arc<AppState> {
+    state_with(vec![mock_provider()]).await
+}
+
+/// Transmission headers the mock provider accepts for `body`.
+pub fn signed_webhook_headers(body: &str) -> HeaderMap {
+    let (id, time) = ("t-1", "2026-10-17T10:00:00Z");
+    let mut headers = HeaderMap::new();
+    headers.insert("paypal-transmission-id", id.parse().unwrap());
+    headers.insert("paypal-transmission-time", time.parse().unwrap());
+    headers.insert("paypal-transmission-sig", mock_webhook_signature(WEBHOOK_ID, id, time, body).parse().unwrap());
+    headers
+}
+
+pub async fn add_product(pool: &SqlitePool, id: &str, unit_amount: i64) {
+    sqlx::query(r#"INSERT INTO products (id, name, unit_amount, currency) VALUES (?, ?, ?, 'EUR')"#)
+        .bind(id)
+        .bind(id.to_uppercase())
+        .bind(unit_amount)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+pub async fn add_coupon(pool: &SqlitePool, code: &str, percent_off: i64, remaining_uses: i64) {
+    sqlx::query(r#"INSERT INTO coupons (code, percent_off, remaining_uses) VALUES (?, ?, ?)"#)
+        .bind(code)
+        .bind(percent_off)
+        .bind(remaining_uses)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+/// A gift card holding `balance_cents`, written through the ledger.
+pub async fn add_gift_card(pool: &SqlitePool, code: &str, balance_cents: i64) {
+    let mut tx = pool.begin().await.unwrap();
+    crate::gift_cards::issue(&mut tx, code, balance_cents, 0, "buyer@example.com", "gift-order").await.unwrap();
+    tx.commit().await.unwrap();
+}
+
+/// Put `cart` (product id, quantity) through pricing, the default payment provider and
+/// finalization, skipping the opening-hours and slot checks of the checkout
+/// route. Returns the order id.
+pub async fn place_order(state: &AppState, cart: &[(&str, i64)], coupon: Option<&str>) -> String {
+    let payment_id = start_order(state, cart, coupon).await;
+    pay_order(state, &payment_id).await
+}
+
+/// The checkout half of [`place_order`]: price `cart` and write the pending
+/// order with its discount hold. Returns the payment id.
+pub async fn start_order(state: &AppState, cart: &[(&str, i64)], coupon: Option<&str>) -> String {
+    let lines: Vec<CartLine> = cart.iter().map(|(id, quantity)| CartLine { product_id: id.to_string(), quantity: *quantity, option_ids: Vec::new() }).collect();
+    let customer = Customer::new(None, Some("guest@example.com"));
+    let breakdown = price_cart(&state.pool, &lines, coupon, Some(&customer)).await.unwrap();
+    let provider = state.payments.default_provider();
+    let payment = provider.create_order(&CreatePayment {
+        amount_cents: breakdown.total_cents,
+        currency: breakdown.currency.clone(),
+        description: None,
+        return_path: "/api/paypal/return".into(),
+        cancel_path: "/api/paypal/cancel".into(),
+    }).await.unwrap();
+
+    let mut tx = state.pool.begin().await.unwrap();
+    sqlx::query(r#"INSERT INTO pending_orders (order_id, email, amount_cents, items_json, payment_provider) VALUES (?, ?, ?, ?, ?)"#)
+        .bind(&payment.id)
+        .bind(customer.email.as_deref())
+        .bind(breakdown.total_cents)
+        .bind(breakdown.to_items_json().to_string())
+        .bind(provider.name())
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    if let Some(discount) = breakdown.discount.as_ref() {
+        holds::reserve(&mut tx, &payment.id, discount, &customer, state.discount_hold_minutes).await.unwrap().unwrap();
+    }
+    tx.commit().await.unwrap();
+    payment.id
+}
+
+/// Capture and finalize the pending order of `payment_id`. Returns the order id.
+pub async fn pay_order(state: &AppState, payment_id: &str) -> String {
+    let captured = state.payments.default_provider().capture(payment_id).await.unwrap();
+    finalize_payment(state, payment_id, &captured).await.unwrap().order_id
+}
@@ -5,12 +5,56 @@ import { useRouter } from "next/navigation";
 import { useCart } from "@/components/cart/CartContext";
 import { getBackendUrl } from "@/lib/api";
 
+type CouponRejectionReason =
+  | "unknown_code"
+  | "not_yet_valid"
+  | "expired"
+  | "used_up"
+  | "reserved"
+  | "below_minimum"
+  | "not_applicable"
+  | "email_required"
+  | "customer_limit_reached"
+  | "first_order_only";
+
 type ApplyCouponResponse = {
   valid: boolean;
   amount_off?: number | null;
   percent_off?: number | null;
+  reason?: CouponRejectionReason;
+  rejection?: { min_subtotal_cents?: number };
+  breakdown?: { discount_cents: number };
 };
 
+function rejectionMessage(data: ApplyCouponResponse) {
+  switch (data.reason) {
+    case "unknown_code":
+      return "Diesen Gutscheincode gibt es nicht.";
+    case "not_yet_valid":
+      return "Dieser Gutschein ist noch nicht gültig.";
+    case "expired":
+      return "Dieser Gutschein ist abgelaufen.";
+    case "used_up":
+      return "Dieser Gutschein wurde bereits eingelöst.";
+    case "reserved":
+      return "Dieser Gutschein wird gerade in einer anderen Bestellung eingelöst. Bitte versuchen Sie es in ein paar Minuten erneut.";
+    case "below_minimum":
+      return data.rejection?.min_subtotal_cents
+        ? `Dieser Gutschein gilt erst ab einem Bestellwert von ${formatCurrency(data.rejection.min_subtotal_cents)}.`
+        : "Der Mindestbestellwert für diesen Gutschein ist nicht erreicht.";
+    case "not_applicable":
+      return "Dieser Gutschein gilt für keine Speise in Ihrem Warenkorb.";
+    case "email_required":
+      return "Bitte geben Sie Ihre E-Mail-Adresse an, um diesen Gutschein zu nutzen.";
+    case "customer_limit_reached":
+      return "Sie haben diesen Gutschein bereits eingelöst.";
+    case "first_order_only":
+      return "Dieser Gutschein gilt nur für Ihre erste Bestellung.";
+    default:
+      return "Dieser Gutschein ist nicht gültig oder wurde bereits verwendet.";
+  }
+}
+
 function formatCurrency(value: number) {
   return new Intl.NumberFormat("de-DE", {
     style: "currency",
@@ -66,7 +110,8 @@ export default function CheckoutPage() {
         },
         body: JSON.stringify({
           code: couponCode.trim(),
-          cart: cartPayload
+          cart: cartPayload,
+          email: email.trim() || undefined
         })
       });
 
@@ -77,11 +122,20 @@ export default function CheckoutPage() {
       const data: ApplyCouponResponse = await response.json();
       if (!data.valid) {
         setCouponStatus("error");
-        setCouponMessage("Dieser Gutschein ist nicht gültig oder wurde bereits verwendet.");
+        setCouponMessage(rejectionMessage(data));
         return;
       }
 
-      if (data.amount_off && data.amount_off > 0) {
+      // Scoped or capped coupons: the server-side breakdown is the real discount
+      if (data.percent_off && data.breakdown && data.breakdown.discount_cents > 0) {
+        applyCoupon({
+          code: couponCode.trim(),
+          amountOff: data.breakdown.discount_cents,
+          label: `Rabatt – ${data.percent_off}%`
+        });
+        setCouponStatus("success");
+        setCouponMessage("Rabattcode erfolgreich angewendet.");
+      } else if (data.amount_off && data.amount_off > 0) {
         applyCoupon({
           code: couponCode.trim(),
           amountOff: data.amount_off,
@@ -128,12 +182,29 @@ export default function CheckoutPage() {
         body: JSON.stringify({
           cart: cartPayload,
           coupon: coupon?.code,
-          email: email.trim() || undefined
+          email: email.trim() || undefined,
+          lang: localStorage.getItem("restaurant_lang_v1") || undefined
         })
       });
 
       if (!response.ok) {
-        throw new Error(await response.text());
+        const body = await response.text();
+        const failure = (() => {
+          try {
+            return JSON.parse(body);
+          } catch {
+            return null;
+          }
+        })();
+        // The code stopped applying since it was checked (expired, used up, cart changed)
+        if (failure?.error === "coupon_rejected") {
+          removeCoupon();
+          setCouponStatus("error");
+          setCouponMessage(rejectionMessage({ valid: false, reason: failure.reason, rejection: failure.coupon }));
+          setIsProcessing(false);
+          return;
+        }
+        throw new Error(body);
       }
 
       const data: { url: string } = await response.json();
@@ -552,7 +552,7 @@ export default function EnglishHomePage() {
     const fetchProducts = async () => {
       try {
         const backendUrl = process.env.NEXT_PUBLIC_BACKEND_URL || "http://localhost:8080";
-        const response = await fetch(`${backendUrl}/api/products`);
+        const response = await fetch(`${backendUrl}/api/products?lang=en`);
         if (response.ok) {
           const data = (await response.json()) as ProductsResponse;
           // Shuffle and take 11 random products for homepage
@@ -276,63 +276,37 @@ export default function ManagePage() {
     try {
       const jwt = localStorage.getItem("restaurant_jwt_v1");
 
-      const subtotalCents = orderItems.reduce(
-        (sum, item) => sum + item.product.unit_amount * item.quantity,
-        0
-      );
-      const discountCents = appliedCoupon
-        ? Math.round((subtotalCents * appliedCoupon.discount) / 100)
-        : 0;
-
-      const orderData = {
-        customer_email: customerEmail,
-        items: orderItems.map((item) => ({
-          product_id: item.product.id,
-          quantity: item.quantity,
-          unit_amount: item.product.unit_amount
-        })),
-        total_amount: totalAmount,
-        qr_code_used: scannedQR?.code,
-        discount_applied: discountCents > 0 ? discountCents : undefined
-      };
-
-      const response = await fetch(getBackendApiUrl("/orders"), {
+      // The backend prices the cart, creates the pending order and emails the payment link
+      const response = await fetch(getBackendApiUrl("/email/send-checkout"), {
         method: "POST",
         headers: {
           "Content-Type": "application/json",
           Authorization: `Bearer ${jwt}`
         },
-        body: JSON.stringify(orderData)
+        body: JSON.stringify({
+          customer_email: customerEmail,
+          cart: orderItems.map((item) => ({
+            product_id: item.product.id,
+            quantity: item.quantity
+          })),
+          coupon: appliedCoupon?.code || undefined
+        })
       });
 
+      const result = await response.json().catch(() => null);
       if (response.ok) {
-        const order = await response.json();
-        
-        // Send email to customer for PayPal checkout
-        await fetch(getBackendApiUrl("/email/send-checkout"), {
-          method: "POST",
-          headers: {
-            "Content-Type": "application/json",
-            Authorization: `Bearer ${jwt}`
-          },
-          body: JSON.stringify({
-            customer_email: customerEmail,
-            order_id: order.id,
-            total_amount: totalAmount,
-            items: orderItems
-          })
-        });
-
         alert("Order created! Checkout link sent to customer email.");
-        
+
         // Reset form
         setOrderItems([]);
         setScannedQR(null);
         setAppliedCoupon(null);
         setCustomerEmail("");
         setTotalAmount(0);
+      } else if (result?.url) {
+        alert(`Order created, but the email could not be sent. Payment link: ${result.url}`);
       } else {
-        alert("Error creating order");
+        alert(result?.message || "Error creating order");
       }
     } catch (error) {
       console.error("Error during checkout:", error);
@@ -13,16 +13,33 @@ type Product = {
   image_url?: string | null;
   description?: string | null;
   category?: string | null;
-  allergens?: string | null;
-  additives?: string | null;
+  allergens: Declaration[];
+  additives: Declaration[];
   spice_level?: string | null;
   serving_size?: string | null;
   dietary_tags?: string | null;
   ingredients?: string | null;
+  sold_out?: boolean;
+  remaining_portions?: number | null;
+};
+
+type Declaration = {
+  code: string;
+  name_de: string;
+  name_en: string;
+};
+
+type CategoryGroup = {
+  id: string | null;
+  name: string | null;
+  description?: string | null;
+  image_url?: string | null;
+  product_ids: string[];
 };
 
 type ProductsResponse = {
   products: Product[];
+  categories: CategoryGroup[];
 };
 
 const FALLBACK_IMAGE = "/images/view-4.jpg";
@@ -38,6 +55,7 @@ function formatCurrency(amount: number, currency: string) {
 
 export default function MenuPage() {
   const [products, setProducts] = useState<Product[]>([]);
+  const [categories, setCategories] = useState<CategoryGroup[]>([]);
   const [loading, setLoading] = useState(true);
   const [error, setError] = useState<string | null>(null);
   const { addItem, openCart } = useCart();
@@ -60,6 +78,7 @@ export default function MenuPage() {
 
         const data: ProductsResponse = await response.json();
         setProducts(data.products);
+        setCategories(data.categories ?? []);
       } catch (err) {
         if (!(err instanceof DOMException && err.name === "AbortError")) {
           console.error(err);
@@ -74,21 +93,14 @@ export default function MenuPage() {
     return () => controller.abort();
   }, []);
 
+  // Categories arrive in the order configured in the admin area
   const grouped = useMemo(() => {
-    if (!products.length) {
-      return [];
-    }
-
-    const map = new Map<string, Product[]>();
-    for (const product of products) {
-      const category = product.category || FALLBACK_CATEGORY;
-      if (!map.has(category)) {
-        map.set(category, []);
-      }
-      map.get(category)!.push(product);
-    }
-    return Array.from(map.entries()).sort(([a], [b]) => a.localeCompare(b));
-  }, [products]);
+    const byId = new Map(products.map((product) => [product.id, product]));
+    return categories.map((group) => [
+      group.name || FALLBACK_CATEGORY,
+      group.product_ids.map((id) => byId.get(id)).filter((p): p is Product => Boolean(p))
+    ] as [string, Product[]]);
+  }, [products, categories]);
 
   const handleAddToCart = (product: Product) => {
     addItem({
@@ -144,7 +156,8 @@ export default function MenuPage() {
                   {items.map((product) => {
                     const imageUrl = product.image_url || FALLBACK_IMAGE;
                     const description = product.description || FALLBACK_DESCRIPTION;
-                    const allergens = product.allergens?.split(',').map(a => a.trim()).filter(Boolean) || [];
+                    const allergens = product.allergens ?? [];
+                    const additives = product.additives ?? [];
                     const dietaryTags = product.dietary_tags?.split(',').map(t => t.trim()).filter(Boolean) || [];
                     
                     return (
@@ -194,16 +207,16 @@ export default function MenuPage() {
                                 </span>
                               ))}
                             </div>
-                            {(allergens.length > 0 || product.additives) && (
+                            {(allergens.length > 0 || additives.length > 0) && (
                               <div className="mt-2 text-xs text-brand-dark/60">
                                 {allergens.length > 0 && (
                                   <span>
-                                    <span className="font-medium">Allergene:</span> {allergens.join(', ')}
+                                    <span className="font-medium">Allergene:</span> {allergens.map(a => `${a.code} (${a.name_de})`).join(', ')}
                                   </span>
                                 )}
-                                {product.additives && (
+                                {additives.length > 0 && (
                                   <span className={allergens.length > 0 ? ' ml-3' : ''}>
-                                    <span className="font-medium">Zusatzstoffe:</span> {product.additives}
+                                    <span className="font-medium">Zusatzstoffe:</span> {additives.map(a => `${a.code} (${a.name_de})`).join(', ')}
                                   </span>
                                 )}
                               </div>
@@ -212,13 +225,19 @@ export default function MenuPage() {
                           <div className="mt-4 flex items-center justify-between">
                             <p className="text-lg font-semibold text-brand">
                               {formatCurrency(product.unit_amount, product.currency)}
+                              {!product.sold_out && product.remaining_portions != null && product.remaining_portions <= 5 && (
+                                <span className="ml-2 text-xs font-medium text-brand-dark/60">
+                                  Nur noch {product.remaining_portions}
+                                </span>
+                              )}
                             </p>
                             <button
                               type="button"
                               onClick={() => handleAddToCart(product)}
-                              className="rounded-full bg-brand px-4 py-2 text-sm font-semibold text-white transition hover:bg-brand-dark"
+                              disabled={product.sold_out}
+                              className="rounded-full bg-brand px-4 py-2 text-sm font-semibold text-white transition hover:bg-brand-dark disabled:cursor-not-allowed disabled:bg-brand-dark/30"
                             >
-                              In den Warenkorb
+                              {product.sold_out ? "Ausverkauft" : "In den Warenkorb"}
                             </button>
                           </div>
                         </div>
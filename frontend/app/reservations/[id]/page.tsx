@@ -0,0 +1,107 @@
+import { notFound } from "next/navigation";
+import { getBackendUrl } from "@/lib/api";
+import CancelReservation from "./cancel-reservation";
+
+type Reservation = {
+  id: string;
+  name: string;
+  party_size: number;
+  starts_at: string;
+  ends_at: string;
+  status: string;
+  notes?: string | null;
+  cancel_reason?: string | null;
+};
+
+const STATUS_LABELS: Record<string, string> = {
+  booked: "Reserviert",
+  seated: "Platziert",
+  cancelled: "Storniert",
+  no_show: "Nicht erschienen"
+};
+
+async function fetchReservation(id: string, token?: string): Promise<Reservation> {
+  if (!token) {
+    notFound();
+  }
+  const base = getBackendUrl();
+  const response = await fetch(`${base}/reservations/${id}?token=${encodeURIComponent(token)}`, {
+    cache: "no-store"
+  });
+
+  if (response.status === 404) {
+    notFound();
+  }
+
+  if (!response.ok) {
+    throw new Error(`Reservierung konnte nicht geladen werden (${response.status}).`);
+  }
+
+  return response.json();
+}
+
+export default async function ReservationPage({
+  params,
+  searchParams
+}: {
+  params: { id: string };
+  searchParams: { t?: string };
+}) {
+  const reservation = await fetchReservation(params.id, searchParams.t);
+  const startsAt = new Date(reservation.starts_at);
+  const upcoming = reservation.status === "booked" && startsAt.getTime() > Date.now();
+
+  return (
+    <main className="bg-brand-light/70 pt-24 pb-20">
+      <section className="mx-auto w-full max-w-3xl px-6">
+        <div className="rounded-[2.5rem] border border-white/20 bg-white/95 p-10 shadow-soft">
+          <header className="text-center">
+            <span className="badge bg-brand/10 text-brand">Ihre Reservierung</span>
+            <h1 className="mt-4 text-3xl font-semibold text-brand-dark">
+              {startsAt.toLocaleDateString("de-DE", {
+                weekday: "long",
+                day: "numeric",
+                month: "long",
+                timeZone: "Europe/Berlin"
+              })}{" "}
+              um{" "}
+              {startsAt.toLocaleTimeString("de-DE", {
+                hour: "2-digit",
+                minute: "2-digit",
+                timeZone: "Europe/Berlin"
+              })}{" "}
+              Uhr
+            </h1>
+          </header>
+
+          <dl className="mt-10 space-y-2 rounded-3xl border border-brand/20 bg-white/70 p-6 text-sm text-slate-600">
+            <div className="flex items-center justify-between">
+              <dt>Name</dt>
+              <dd className="font-medium text-brand-dark">{reservation.name}</dd>
+            </div>
+            <div className="flex items-center justify-between">
+              <dt>Personen</dt>
+              <dd className="font-medium text-brand-dark">{reservation.party_size}</dd>
+            </div>
+            <div className="flex items-center justify-between">
+              <dt>Status</dt>
+              <dd className="font-medium text-brand-dark">
+                {STATUS_LABELS[reservation.status] ?? reservation.status}
+              </dd>
+            </div>
+            {reservation.cancel_reason && (
+              <div className="flex items-center justify-between">
+                <dt>Grund</dt>
+                <dd className="font-medium">{reservation.cancel_reason}</dd>
+              </div>
+            )}
+          </dl>
+
+          {upcoming && searchParams.t && (
+            <CancelReservation id={reservation.id} token={searchParams.t} />
+          )}
+        </div>
+      </section>
+    </main>
+  );
+}
@@ -1,11 +1,19 @@
 import { notFound } from "next/navigation";
 import { getBackendUrl } from "@/lib/api";
 
+type SelectedOption = {
+  id: string;
+  group: string;
+  name: string;
+  priceDeltaCents: number;
+};
+
 type OrderItem = {
   product_id: string;
   quantity: number;
   unit_amount: number;
   name: string;
+  options?: SelectedOption[];
 };
 
 type OrderDetails = {
@@ -16,6 +24,20 @@ type OrderDetails = {
   discount_cents: number;
   items: OrderItem[];
   created_at: string;
+  status: string;
+  estimated_ready_at?: string | null;
+};
+
+const STATUS_LABELS: Record<string, string> = {
+  awaiting_payment: "Zahlung bei Abholung",
+  paid: "Bezahlt",
+  accepted: "Angenommen",
+  preparing: "In Zubereitung",
+  ready: "Abholbereit",
+  picked_up: "Abgeholt",
+  cancelled: "Storniert",
+  partially_refunded: "Teilweise erstattet",
+  refunded: "Erstattet"
 };
 
 function formatCurrency(value: number) {
@@ -25,9 +47,10 @@ function formatCurrency(value: number) {
   }).format(value / 100);
 }
 
-async function fetchOrder(id: string): Promise<OrderDetails> {
+async function fetchOrder(id: string, token?: string): Promise<OrderDetails> {
   const base = getBackendUrl();
-  const response = await fetch(`${base}/orders/${id}`, {
+  const query = token ? `?token=${encodeURIComponent(token)}` : "";
+  const response = await fetch(`${base}/orders/${id}${query}`, {
     cache: "no-store"
   });
 
@@ -43,11 +66,13 @@ async function fetchOrder(id: string): Promise<OrderDetails> {
 }
 
 export default async function ThankYouPage({
-  params
+  params,
+  searchParams
 }: {
   params: { id: string };
+  searchParams: { t?: string };
 }) {
-  const order = await fetchOrder(params.id);
+  const order = await fetchOrder(params.id, searchParams.t);
 
   const subtotal =
     order.items.reduce(
@@ -81,6 +106,21 @@ export default async function ThankYouPage({
                 {new Date(order.created_at).toLocaleString("de-DE")}
               </p>
             </div>
+            <div className="mt-2 flex flex-col gap-2 text-sm text-slate-600 sm:flex-row sm:items-center sm:justify-between">
+              <p>
+                <span className="font-semibold text-brand-dark">Status:</span>{" "}
+                {STATUS_LABELS[order.status] ?? order.status}
+              </p>
+              {order.estimated_ready_at && (
+                <p>
+                  <span className="font-semibold text-brand-dark">Voraussichtlich fertig:</span>{" "}
+                  {new Date(order.estimated_ready_at).toLocaleTimeString("de-DE", {
+                    hour: "2-digit",
+                    minute: "2-digit"
+                  })}
+                </p>
+              )}
+            </div>
 
             <ul className="mt-6 space-y-4 text-sm text-slate-600">
               {order.items.map((item) => (
@@ -90,6 +130,11 @@ export default async function ThankYouPage({
                 >
                   <div>
                     <p className="font-semibold text-brand-dark">{item.name}</p>
+                    {item.options && item.options.length > 0 && (
+                      <p className="text-xs text-slate-500">
+                        {item.options.map((option) => option.name).join(", ")}
+                      </p>
+                    )}
                     <p className="text-xs uppercase tracking-[0.3em] text-slate-500">
                       {item.quantity} × {formatCurrency(item.unit_amount)}
                     </p>
@@ -19,12 +19,12 @@ export function LanguageSwitcher() {
 
   useEffect(() => {
     // Detect current language from pathname
-    if (pathname.startsWith("/en")) {
-      setCurrentLang("en");
-    } else if (pathname.startsWith("/vi")) {
-      setCurrentLang("vi");
-    } else {
-      setCurrentLang("de");
+    const lang = pathname.startsWith("/en") ? "en" : pathname.startsWith("/vi") ? "vi" : "de";
+    setCurrentLang(lang);
+    // Remembered for checkout (German-only pages keep the last choice), so the
+    // confirmation email matches the language the guest browsed in
+    if (lang !== "de" || pathname === "/") {
+      localStorage.setItem("restaurant_lang_v1", lang);
     }
     // Close dropdown when route changes
     setIsOpen(false);
@@ -0,0 +1,330 @@
+'use client';
+
+import { useEffect, useState } from 'react';
+import { getBackendApiUrl } from '@/lib/api';
+
+interface Campaign {
+  id: string;
+  name: string;
+  description?: string | null;
+  prefix: string;
+  code_length: number;
+  created_at: string;
+  codes: number;
+  redeemed_codes: number;
+  redemptions: number;
+  customers: number;
+  discount_cents: number;
+}
+
+const EMPTY_FORM = {
+  name: '',
+  description: '',
+  prefix: '',
+  length: '8',
+  count: '100',
+  percentOff: '',
+  amountOff: '',
+  expiresAt: '',
+  minSubtotal: '',
+  firstOrderOnly: false,
+};
+
+export default function AdminCouponCampaigns() {
+  const [campaigns, setCampaigns] = useState<Campaign[]>([]);
+  const [error, setError] = useState('');
+  const [showForm, setShowForm] = useState(false);
+  const [formData, setFormData] = useState(EMPTY_FORM);
+  const [isSubmitting, setIsSubmitting] = useState(false);
+
+  const fetchCampaigns = async () => {
+    try {
+      const token = localStorage.getItem('restaurant_jwt_v1');
+      const response = await fetch(getBackendApiUrl('/admin/coupon-campaigns'), {
+        headers: { Authorization: `Bearer ${token}` },
+      });
+      if (response.ok) {
+        const data = await response.json();
+        setCampaigns(Array.isArray(data.campaigns) ? data.campaigns : []);
+      }
+    } catch (err) {
+      console.error(err);
+      setError('Failed to load campaigns');
+    }
+  };
+
+  useEffect(() => {
+    fetchCampaigns();
+  }, []);
+
+  const handleCreate = async (e: React.FormEvent) => {
+    e.preventDefault();
+    if (!formData.percentOff && !formData.amountOff) {
+      setError('Must specify either percentage or fixed amount discount');
+      return;
+    }
+
+    setIsSubmitting(true);
+    setError('');
+
+    try {
+      const token = localStorage.getItem('restaurant_jwt_v1');
+      const response = await fetch(getBackendApiUrl('/admin/coupon-campaigns'), {
+        method: 'POST',
+        headers: {
+          'Content-Type': 'application/json',
+          Authorization: `Bearer ${token}`,
+        },
+        body: JSON.stringify({
+          name: formData.name,
+          description: formData.description || null,
+          prefix: formData.prefix.toUpperCase(),
+          length: parseInt(formData.length),
+          count: parseInt(formData.count),
+          percent_off: formData.percentOff ? parseInt(formData.percentOff) : null,
+          amount_off: formData.amountOff ? parseInt(formData.amountOff) : null,
+          expires_at: formData.expiresAt || null,
+          min_subtotal_cents: formData.minSubtotal ? parseInt(formData.minSubtotal) : null,
+          first_order_only: formData.firstOrderOnly,
+        }),
+      });
+
+      if (!response.ok) {
+        const data = await response.json().catch(() => null);
+        throw new Error(data?.message || 'Failed to create campaign');
+      }
+
+      setFormData(EMPTY_FORM);
+      setShowForm(false);
+      await fetchCampaigns();
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Error creating campaign');
+    } finally {
+      setIsSubmitting(false);
+    }
+  };
+
+  const handleExport = async (campaign: Campaign) => {
+    try {
+      const token = localStorage.getItem('restaurant_jwt_v1');
+      const response = await fetch(getBackendApiUrl(`/admin/coupon-campaigns/${campaign.id}/export`), {
+        headers: { Authorization: `Bearer ${token}` },
+      });
+      if (!response.ok) {
+        throw new Error('Failed to export codes');
+      }
+      const url = URL.createObjectURL(await response.blob());
+      const link = document.createElement('a');
+      link.href = url;
+      link.download = `campaign-${campaign.name}.csv`;
+      link.click();
+      URL.revokeObjectURL(url);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Error exporting codes');
+    }
+  };
+
+  const handleDelete = async (campaign: Campaign) => {
+    if (!confirm(`Delete campaign ${campaign.name} and its ${campaign.codes} codes?`)) return;
+
+    try {
+      const token = localStorage.getItem('restaurant_jwt_v1');
+      const response = await fetch(getBackendApiUrl(`/admin/coupon-campaigns/${campaign.id}`), {
+        method: 'DELETE',
+        headers: { Authorization: `Bearer ${token}` },
+      });
+
+      if (!response.ok) {
+        throw new Error('Failed to delete campaign');
+      }
+
+      await fetchCampaigns();
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Error deleting campaign');
+    }
+  };
+
+  return (
+    <div className="space-y-6">
+      <div className="flex justify-between items-center">
+        <h2 className="text-xl font-semibold text-white">Campaigns</h2>
+        <button
+          onClick={() => setShowForm(!showForm)}
+          className="bg-yellow-500 text-gray-950 px-4 py-2 rounded font-semibold hover:bg-yellow-400"
+        >
+          {showForm ? 'Cancel' : '+ Generate Codes'}
+        </button>
+      </div>
+
+      {error && (
+        <div className="bg-red-500/20 border border-red-500 text-red-400 p-4 rounded">
+          {error}
+        </div>
+      )}
+
+      {showForm && (
+        <form onSubmit={handleCreate} className="bg-gray-800 p-6 rounded-lg space-y-4">
+          <div className="grid grid-cols-2 gap-4">
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">Name</label>
+              <input
+                type="text"
+                value={formData.name}
+                onChange={(e) => setFormData({ ...formData, name: e.target.value })}
+                placeholder="Spring flyer"
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
+                required
+              />
+            </div>
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">Description (optional)</label>
+              <input
+                type="text"
+                value={formData.description}
+                onChange={(e) => setFormData({ ...formData, description: e.target.value })}
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
+              />
+            </div>
+          </div>
+          <div className="grid grid-cols-3 gap-4">
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">Prefix (optional)</label>
+              <input
+                type="text"
+                value={formData.prefix}
+                onChange={(e) => setFormData({ ...formData, prefix: e.target.value.toUpperCase() })}
+                placeholder="FLYER-"
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded font-mono"
+              />
+            </div>
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">Random characters</label>
+              <input
+                type="number"
+                value={formData.length}
+                onChange={(e) => setFormData({ ...formData, length: e.target.value })}
+                min="4"
+                max="16"
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
+              />
+            </div>
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">Number of codes</label>
+              <input
+                type="number"
+                value={formData.count}
+                onChange={(e) => setFormData({ ...formData, count: e.target.value })}
+                min="1"
+                max="10000"
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
+              />
+            </div>
+          </div>
+          <div className="grid grid-cols-2 gap-4">
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">% Off (optional)</label>
+              <input
+                type="number"
+                value={formData.percentOff}
+                onChange={(e) => setFormData({ ...formData, percentOff: e.target.value, amountOff: '' })}
+                placeholder="10"
+                min="0"
+                max="100"
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
+              />
+            </div>
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">€ Off (optional)</label>
+              <input
+                type="number"
+                value={formData.amountOff}
+                onChange={(e) => setFormData({ ...formData, amountOff: e.target.value, percentOff: '' })}
+                placeholder="500"
+                min="0"
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
+              />
+            </div>
+          </div>
+          <div className="grid grid-cols-3 gap-4">
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">Valid until, inclusive (optional)</label>
+              <input
+                type="date"
+                value={formData.expiresAt}
+                onChange={(e) => setFormData({ ...formData, expiresAt: e.target.value })}
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
+              />
+            </div>
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">Minimum order in cents (optional)</label>
+              <input
+                type="number"
+                value={formData.minSubtotal}
+                onChange={(e) => setFormData({ ...formData, minSubtotal: e.target.value })}
+                placeholder="3000"
+                min="0"
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
+              />
+            </div>
+            <label className="flex items-end gap-2 pb-2 text-sm text-gray-400">
+              <input
+                type="checkbox"
+                checked={formData.firstOrderOnly}
+                onChange={(e) => setFormData({ ...formData, firstOrderOnly: e.target.checked })}
+              />
+              First order only
+            </label>
+          </div>
+          <p className="text-xs text-gray-500">Every code can be used once. Codes never contain 0, O, 1, I or L.</p>
+          <button
+            type="submit"
+            disabled={isSubmitting}
+            className="w-full bg-yellow-500 text-gray-950 py-2 rounded font-semibold hover:bg-yellow-400 disabled:opacity-50"
+          >
+            {isSubmitting ? 'Generating...' : 'Generate Codes'}
+          </button>
+        </form>
+      )}
+
+      <div className="overflow-x-auto rounded-lg border border-gray-700 bg-gray-900">
+        <table className="w-full">
+          <thead className="border-b border-gray-700 bg-gray-800">
+            <tr>
+              <th className="px-6 py-3 text-left text-sm font-semibold text-yellow-500">Campaign</th>
+              <th className="px-6 py-3 text-left text-sm font-semibold text-yellow-500">Codes</th>
+              <th className="px-6 py-3 text-left text-sm font-semibold text-yellow-500">Redeemed</th>
+              <th className="px-6 py-3 text-left text-sm font-semibold text-yellow-500">Discount given</th>
+              <th className="px-6 py-3 text-left text-sm font-semibold text-yellow-500">Actions</th>
+            </tr>
+          </thead>
+          <tbody className="divide-y divide-gray-700">
+            {campaigns.map((campaign) => (
+              <tr key={campaign.id} className="hover:bg-gray-800">
+                <td className="px-6 py-3 text-sm text-white">
+                  {campaign.name}
+                  {campaign.description && <div className="text-xs text-gray-500">{campaign.description}</div>}
+                </td>
+                <td className="px-6 py-3 text-sm font-mono text-gray-300">
+                  {campaign.codes} × {campaign.prefix}
+                  {'•'.repeat(campaign.code_length)}
+                </td>
+                <td className="px-6 py-3 text-sm text-gray-300">
+                  {campaign.redeemed_codes} / {campaign.codes} by {campaign.customers}
+                </td>
+                <td className="px-6 py-3 text-sm text-gray-300">€{(campaign.discount_cents / 100).toFixed(2)}</td>
+                <td className="px-6 py-3 text-sm space-x-4">
+                  <button onClick={() => handleExport(campaign)} className="text-yellow-400 hover:text-yellow-300 font-semibold">
+                    CSV
+                  </button>
+                  <button onClick={() => handleDelete(campaign)} className="text-red-400 hover:text-red-300 font-semibold">
+                    Delete
+                  </button>
+                </td>
+              </tr>
+            ))}
+          </tbody>
+        </table>
+      </div>
+    </div>
+  );
+}
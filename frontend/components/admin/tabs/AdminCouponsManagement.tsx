@@ -1,13 +1,79 @@
 'use client';
 
-import { useEffect, useState } from 'react';
+import { Fragment, useEffect, useState } from 'react';
 import { getBackendApiUrl } from '@/lib/api';
+import AdminCouponCampaigns from './AdminCouponCampaigns';
 
 interface Coupon {
   code: string;
   percent_off?: number | null;
   amount_off?: number | null;
   remaining_uses: number;
+  starts_at?: string | null;
+  expires_at?: string | null;
+  min_subtotal_cents?: number | null;
+  max_discount_cents?: number | null;
+  is_scoped?: boolean;
+  product_ids?: string[];
+  category_ids?: string[];
+  max_uses_per_customer?: number | null;
+  first_order_only?: boolean;
+}
+
+interface CouponUsage {
+  code: string;
+  redemptions: number;
+  customers: number;
+  discount_cents: number;
+  reversed: number;
+}
+
+interface Redemption {
+  id: string;
+  order_id: string;
+  email?: string | null;
+  discount_cents: number;
+  redeemed_at: string;
+  reversed_at?: string | null;
+}
+
+const EMPTY_FORM = {
+  code: '',
+  percentOff: '',
+  amountOff: '',
+  remainingUses: '100',
+  startsAt: '',
+  expiresAt: '',
+  minSubtotal: '',
+  maxDiscount: '',
+  categories: '',
+  productIds: '',
+  maxUsesPerCustomer: '',
+  firstOrderOnly: false,
+};
+
+function splitList(value: string) {
+  return value.split(',').map((v) => v.trim()).filter(Boolean);
+}
+
+function formatDate(value?: string | null) {
+  return value ? new Date(value).toLocaleString('de-DE', { dateStyle: 'short', timeStyle: 'short' }) : null;
+}
+
+function describeRules(coupon: Coupon) {
+  const rules: string[] = [];
+  const from = formatDate(coupon.starts_at);
+  const until = formatDate(coupon.expires_at);
+  if (from) rules.push(`from ${from}`);
+  if (until) rules.push(`until ${until}`);
+  if (coupon.min_subtotal_cents) rules.push(`min €${(coupon.min_subtotal_cents / 100).toFixed(2)}`);
+  if (coupon.max_discount_cents) rules.push(`max €${(coupon.max_discount_cents / 100).toFixed(2)} off`);
+  if (coupon.max_uses_per_customer) rules.push(`${coupon.max_uses_per_customer}× per customer`);
+  if (coupon.first_order_only) rules.push('first order only');
+  if (coupon.is_scoped) {
+    rules.push(`only ${[...(coupon.category_ids ?? []), ...(coupon.product_ids ?? [])].join(', ') || 'nothing'}`);
+  }
+  return rules.length > 0 ? rules.join(' · ') : '—';
 }
 
 export default function AdminCouponsManagement() {
@@ -16,13 +82,11 @@ export default function AdminCouponsManagement() {
   const [error, setError] = useState('');
   const [showForm, setShowForm] = useState(false);
   
-  const [formData, setFormData] = useState({
-    code: '',
-    percentOff: '',
-    amountOff: '',
-    remainingUses: '100',
-  });
+  const [formData, setFormData] = useState(EMPTY_FORM);
   const [isSubmitting, setIsSubmitting] = useState(false);
+  const [usage, setUsage] = useState<Record<string, CouponUsage>>({});
+  const [openCode, setOpenCode] = useState<string | null>(null);
+  const [redemptions, setRedemptions] = useState<Redemption[]>([]);
 
   const fetchCoupons = async () => {
     try {
@@ -34,6 +98,14 @@ export default function AdminCouponsManagement() {
         const data = await response.json();
         setCoupons(Array.isArray(data.coupons) ? data.coupons : []);
       }
+      const usageResponse = await fetch(getBackendApiUrl('/admin/coupons/redemptions'), {
+        headers: { Authorization: `Bearer ${token}` },
+      });
+      if (usageResponse.ok) {
+        const data = await usageResponse.json();
+        const rows: CouponUsage[] = Array.isArray(data.coupons) ? data.coupons : [];
+        setUsage(Object.fromEntries(rows.map((u) => [u.code, u])));
+      }
     } catch (err) {
       console.error(err);
       setError('Failed to load coupons');
@@ -73,14 +145,23 @@ export default function AdminCouponsManagement() {
           percent_off: formData.percentOff ? parseInt(formData.percentOff) : null,
           amount_off: formData.amountOff ? parseInt(formData.amountOff) : null,
           remaining_uses: parseInt(formData.remainingUses),
+          starts_at: formData.startsAt || null,
+          expires_at: formData.expiresAt || null,
+          min_subtotal_cents: formData.minSubtotal ? parseInt(formData.minSubtotal) : null,
+          max_discount_cents: formData.maxDiscount ? parseInt(formData.maxDiscount) : null,
+          categories: splitList(formData.categories),
+          product_ids: splitList(formData.productIds),
+          max_uses_per_customer: formData.maxUsesPerCustomer ? parseInt(formData.maxUsesPerCustomer) : null,
+          first_order_only: formData.firstOrderOnly,
         }),
       });
 
       if (!response.ok) {
-        throw new Error('Failed to add coupon');
+        const data = await response.json().catch(() => null);
+        throw new Error(data?.message || 'Failed to add coupon');
       }
 
-      setFormData({ code: '', percentOff: '', amountOff: '', remainingUses: '100' });
+      setFormData(EMPTY_FORM);
       setShowForm(false);
       await fetchCoupons();
     } catch (err) {
@@ -90,6 +171,27 @@ export default function AdminCouponsManagement() {
     }
   };
 
+  const toggleRedemptions = async (code: string) => {
+    if (openCode === code) {
+      setOpenCode(null);
+      return;
+    }
+    try {
+      const token = localStorage.getItem('restaurant_jwt_v1');
+      const response = await fetch(getBackendApiUrl(`/admin/coupons/${code}/redemptions`), {
+        headers: { Authorization: `Bearer ${token}` },
+      });
+      if (!response.ok) {
+        throw new Error('Failed to load redemptions');
+      }
+      const data = await response.json();
+      setRedemptions(Array.isArray(data.redemptions) ? data.redemptions : []);
+      setOpenCode(code);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Error loading redemptions');
+    }
+  };
+
   const handleDeleteCoupon = async (code: string) => {
     if (!confirm(`Delete coupon ${code}?`)) return;
 
@@ -180,6 +282,92 @@ export default function AdminCouponsManagement() {
               className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
             />
           </div>
+          <div className="grid grid-cols-2 gap-4">
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">Valid from (optional)</label>
+              <input
+                type="date"
+                value={formData.startsAt}
+                onChange={(e) => setFormData({ ...formData, startsAt: e.target.value })}
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
+              />
+            </div>
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">Valid until, inclusive (optional)</label>
+              <input
+                type="date"
+                value={formData.expiresAt}
+                onChange={(e) => setFormData({ ...formData, expiresAt: e.target.value })}
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
+              />
+            </div>
+          </div>
+          <div className="grid grid-cols-2 gap-4">
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">Minimum order in cents (optional)</label>
+              <input
+                type="number"
+                value={formData.minSubtotal}
+                onChange={(e) => setFormData({ ...formData, minSubtotal: e.target.value })}
+                placeholder="3000"
+                min="0"
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
+              />
+            </div>
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">Maximum discount in cents (optional)</label>
+              <input
+                type="number"
+                value={formData.maxDiscount}
+                onChange={(e) => setFormData({ ...formData, maxDiscount: e.target.value })}
+                placeholder="1000"
+                min="1"
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
+              />
+            </div>
+          </div>
+          <div className="grid grid-cols-2 gap-4">
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">Only categories (optional, comma-separated)</label>
+              <input
+                type="text"
+                value={formData.categories}
+                onChange={(e) => setFormData({ ...formData, categories: e.target.value })}
+                placeholder="Hauptspeisen"
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
+              />
+            </div>
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">Only product IDs (optional, comma-separated)</label>
+              <input
+                type="text"
+                value={formData.productIds}
+                onChange={(e) => setFormData({ ...formData, productIds: e.target.value })}
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded font-mono"
+              />
+            </div>
+          </div>
+          <div className="grid grid-cols-2 gap-4">
+            <div>
+              <label className="block text-sm text-gray-400 mb-2">Uses per customer (optional)</label>
+              <input
+                type="number"
+                value={formData.maxUsesPerCustomer}
+                onChange={(e) => setFormData({ ...formData, maxUsesPerCustomer: e.target.value })}
+                placeholder="1"
+                min="1"
+                className="w-full bg-gray-700 border border-gray-600 text-white px-3 py-2 rounded"
+              />
+            </div>
+            <label className="flex items-end gap-2 pb-2 text-sm text-gray-400">
+              <input
+                type="checkbox"
+                checked={formData.firstOrderOnly}
+                onChange={(e) => setFormData({ ...formData, firstOrderOnly: e.target.checked })}
+              />
+              First order only
+            </label>
+          </div>
           <button
             type="submit"
             disabled={isSubmitting}
@@ -197,30 +385,62 @@ export default function AdminCouponsManagement() {
               <th className="px-6 py-3 text-left text-sm font-semibold text-yellow-500">Code</th>
               <th className="px-6 py-3 text-left text-sm font-semibold text-yellow-500">Discount</th>
               <th className="px-6 py-3 text-left text-sm font-semibold text-yellow-500">Remaining Uses</th>
+              <th className="px-6 py-3 text-left text-sm font-semibold text-yellow-500">Rules</th>
+              <th className="px-6 py-3 text-left text-sm font-semibold text-yellow-500">Redeemed</th>
               <th className="px-6 py-3 text-left text-sm font-semibold text-yellow-500">Actions</th>
             </tr>
           </thead>
           <tbody className="divide-y divide-gray-700">
             {coupons.map((coupon) => (
-              <tr key={coupon.code} className="hover:bg-gray-800">
-                <td className="px-6 py-3 text-sm font-mono text-white">{coupon.code}</td>
-                <td className="px-6 py-3 text-sm text-gray-300">
-                  {coupon.percent_off ? `${coupon.percent_off}%` : `€${(coupon.amount_off || 0) / 100}`}
-                </td>
-                <td className="px-6 py-3 text-sm text-gray-300">{coupon.remaining_uses}</td>
-                <td className="px-6 py-3 text-sm">
-                  <button
-                    onClick={() => handleDeleteCoupon(coupon.code)}
-                    className="text-red-400 hover:text-red-300 font-semibold"
-                  >
-                    Delete
-                  </button>
-                </td>
-              </tr>
+              <Fragment key={coupon.code}>
+                <tr className="hover:bg-gray-800">
+                  <td className="px-6 py-3 text-sm font-mono text-white">{coupon.code}</td>
+                  <td className="px-6 py-3 text-sm text-gray-300">
+                    {coupon.percent_off ? `${coupon.percent_off}%` : `€${(coupon.amount_off || 0) / 100}`}
+                  </td>
+                  <td className="px-6 py-3 text-sm text-gray-300">{coupon.remaining_uses}</td>
+                  <td className="px-6 py-3 text-sm text-gray-400">{describeRules(coupon)}</td>
+                  <td className="px-6 py-3 text-sm text-gray-300">
+                    <button onClick={() => toggleRedemptions(coupon.code)} className="hover:text-yellow-400">
+                      {usage[coupon.code]
+                        ? `${usage[coupon.code].redemptions}× by ${usage[coupon.code].customers} · €${(usage[coupon.code].discount_cents / 100).toFixed(2)}`
+                        : '0×'}
+                    </button>
+                  </td>
+                  <td className="px-6 py-3 text-sm">
+                    <button
+                      onClick={() => handleDeleteCoupon(coupon.code)}
+                      className="text-red-400 hover:text-red-300 font-semibold"
+                    >
+                      Delete
+                    </button>
+                  </td>
+                </tr>
+                {openCode === coupon.code && (
+                  <tr className="bg-gray-950">
+                    <td colSpan={6} className="px-6 py-3 text-sm text-gray-400">
+                      {redemptions.length === 0 ? (
+                        'No redemptions yet'
+                      ) : (
+                        <ul className="space-y-1">
+                          {redemptions.map((r) => (
+                            <li key={r.id} className={r.reversed_at ? 'line-through opacity-60' : ''}>
+                              {new Date(r.redeemed_at).toLocaleString('de-DE')} · {r.email || 'guest'} · €
+                              {(r.discount_cents / 100).toFixed(2)} · <span className="font-mono">{r.order_id}</span>
+                            </li>
+                          ))}
+                        </ul>
+                      )}
+                    </td>
+                  </tr>
+                )}
+              </Fragment>
             ))}
           </tbody>
         </table>
       </div>
+
+      <AdminCouponCampaigns />
     </div>
   );
 }
@@ -1,6 +1,6 @@
 'use client';
 
-import { useEffect, useState } from 'react';
+import { Fragment, useEffect, useState } from 'react';
 
 import { getBackendApiUrl } from '@/lib/api';
 
@@ -12,6 +12,17 @@ interface GiftCoupon {
   created_at: string;
 }
 
+interface GiftCardTransaction {
+  id: string;
+  kind: 'issue' | 'bonus' | 'redeem' | 'refund' | 'adjustment';
+  amount_cents: number;
+  balance_cents: number;
+  order_id?: string | null;
+  note?: string | null;
+  created_by?: string | null;
+  created_at: string;
+}
+
 function formatCurrency(value: number) {
   return new Intl.NumberFormat('de-DE', {
     style: 'currency',
@@ -23,6 +34,65 @@ export default function AdminGiftCoupons() {
   const [coupons, setCoupons] = useState<GiftCoupon[]>([]);
   const [loading, setLoading] = useState(true);
   const [error, setError] = useState('');
+  const [openCode, setOpenCode] = useState<string | null>(null);
+  const [transactions, setTransactions] = useState<GiftCardTransaction[]>([]);
+  const [adjustment, setAdjustment] = useState({ amount: '', note: '' });
+  const [adjustError, setAdjustError] = useState('');
+
+  const loadTransactions = async (code: string) => {
+    const token = localStorage.getItem('restaurant_jwt_v1');
+    const response = await fetch(getBackendApiUrl(`/admin/gift-coupons/${code}/transactions`), {
+      headers: { Authorization: `Bearer ${token}` },
+    });
+    if (!response.ok) {
+      throw new Error('Failed to load history');
+    }
+    const data = await response.json();
+    setTransactions(Array.isArray(data.transactions) ? data.transactions : []);
+  };
+
+  const toggleHistory = async (code: string) => {
+    if (openCode === code) {
+      setOpenCode(null);
+      return;
+    }
+    try {
+      await loadTransactions(code);
+      setAdjustment({ amount: '', note: '' });
+      setAdjustError('');
+      setOpenCode(code);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Error loading history');
+    }
+  };
+
+  const handleAdjust = async (e: React.FormEvent, code: string) => {
+    e.preventDefault();
+    setAdjustError('');
+    try {
+      const token = localStorage.getItem('restaurant_jwt_v1');
+      const response = await fetch(getBackendApiUrl(`/admin/gift-coupons/${code}/adjustments`), {
+        method: 'POST',
+        headers: {
+          'Content-Type': 'application/json',
+          Authorization: `Bearer ${token}`,
+        },
+        body: JSON.stringify({
+          amount_cents: Math.round(parseFloat(adjustment.amount) * 100),
+          note: adjustment.note,
+        }),
+      });
+      const data = await response.json().catch(() => null);
+      if (!response.ok) {
+        throw new Error(data?.message || 'Failed to adjust balance');
+      }
+      setCoupons((current) => current.map((c) => (c.code === code ? { ...c, remaining_cents: data.remaining_cents } : c)));
+      setAdjustment({ amount: '', note: '' });
+      await loadTransactions(code);
+    } catch (err) {
+      setAdjustError(err instanceof Error ? err.message : 'Error adjusting balance');
+    }
+  };
 
   useEffect(() => {
     const fetchGiftCoupons = async () => {
@@ -71,15 +141,62 @@ export default function AdminGiftCoupons() {
         </thead>
         <tbody className="divide-y divide-gray-700">
           {coupons.map((coupon) => (
-            <tr key={coupon.code} className="hover:bg-gray-800">
-              <td className="px-6 py-3 text-sm font-mono text-white">{coupon.code}</td>
-              <td className="px-6 py-3 text-sm text-gray-300">{formatCurrency(coupon.value_cents)}</td>
-              <td className="px-6 py-3 text-sm text-gray-300">{formatCurrency(coupon.remaining_cents)}</td>
-              <td className="px-6 py-3 text-sm text-gray-400">{coupon.purchaser_email || '—'}</td>
-              <td className="px-6 py-3 text-sm text-gray-400">
-                {new Date(coupon.created_at).toLocaleString('de-DE')}
-              </td>
-            </tr>
+            <Fragment key={coupon.code}>
+              <tr onClick={() => toggleHistory(coupon.code)} className="cursor-pointer hover:bg-gray-800">
+                <td className="px-6 py-3 text-sm font-mono text-white">{coupon.code}</td>
+                <td className="px-6 py-3 text-sm text-gray-300">{formatCurrency(coupon.value_cents)}</td>
+                <td className="px-6 py-3 text-sm text-gray-300">{formatCurrency(coupon.remaining_cents)}</td>
+                <td className="px-6 py-3 text-sm text-gray-400">{coupon.purchaser_email || '—'}</td>
+                <td className="px-6 py-3 text-sm text-gray-400">
+                  {new Date(coupon.created_at).toLocaleString('de-DE')}
+                </td>
+              </tr>
+              {openCode === coupon.code && (
+                <tr className="bg-gray-950">
+                  <td colSpan={5} className="px-6 py-3 text-sm text-gray-400 space-y-3">
+                    <table className="w-full">
+                      <tbody>
+                        {transactions.map((t) => (
+                          <tr key={t.id}>
+                            <td className="py-1 pr-4">{new Date(t.created_at).toLocaleString('de-DE')}</td>
+                            <td className="py-1 pr-4 capitalize">{t.kind}</td>
+                            <td className={`py-1 pr-4 text-right ${t.amount_cents < 0 ? 'text-red-400' : 'text-green-400'}`}>
+                              {formatCurrency(t.amount_cents)}
+                            </td>
+                            <td className="py-1 pr-4 text-right text-gray-300">{formatCurrency(t.balance_cents)}</td>
+                            <td className="py-1 pr-4">{t.note || (t.order_id && <span className="font-mono">{t.order_id}</span>)}</td>
+                            <td className="py-1">{t.created_by}</td>
+                          </tr>
+                        ))}
+                      </tbody>
+                    </table>
+                    <form onSubmit={(e) => handleAdjust(e, coupon.code)} className="flex gap-2">
+                      <input
+                        type="number"
+                        step="0.01"
+                        value={adjustment.amount}
+                        onChange={(e) => setAdjustment({ ...adjustment, amount: e.target.value })}
+                        placeholder="± €"
+                        className="w-28 bg-gray-700 border border-gray-600 text-white px-3 py-1 rounded"
+                        required
+                      />
+                      <input
+                        type="text"
+                        value={adjustment.note}
+                        onChange={(e) => setAdjustment({ ...adjustment, note: e.target.value })}
+                        placeholder="Reason"
+                        className="flex-1 bg-gray-700 border border-gray-600 text-white px-3 py-1 rounded"
+                        required
+                      />
+                      <button type="submit" className="bg-yellow-500 text-gray-950 px-3 py-1 rounded font-semibold hover:bg-yellow-400">
+                        Adjust
+                      </button>
+                    </form>
+                    {adjustError && <div className="text-red-400">{adjustError}</div>}
+                  </td>
+                </tr>
+              )}
+            </Fragment>
           ))}
         </tbody>
       </table>
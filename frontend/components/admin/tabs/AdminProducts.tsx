@@ -32,6 +32,7 @@ export default function AdminProducts() {
   const [error, setError] = useState('');
   const [showAddForm, setShowAddForm] = useState(false);
   const [editingProduct, setEditingProduct] = useState<Product | null>(null);
+  const [uploading, setUploading] = useState(false);
   const [formData, setFormData] = useState({
     id: '',
     name: '',
@@ -142,6 +143,41 @@ export default function AdminProducts() {
     }
   };
 
+  const handleImageUpload = async (file: File) => {
+    if (!editingProduct) {
+      return;
+    }
+
+    try {
+      setUploading(true);
+      const token = localStorage.getItem('restaurant_jwt_v1');
+      const body = new FormData();
+      body.append('image', file);
+      const response = await fetch(getBackendApiUrl(`/admin/products/${editingProduct.id}/image`), {
+        method: 'POST',
+        headers: { Authorization: `Bearer ${token}` },
+        body,
+      });
+
+      if (!response.ok) {
+        const message = await response.text();
+        alert(`Failed to upload: ${message}`);
+        return;
+      }
+
+      // The backend already stored the new image_url on the product
+      const data = await response.json();
+      setFormData((current) => ({ ...current, image_url: data.image_url || '' }));
+      setEditingProduct((current) => current && { ...current, image_url: data.image_url });
+      await fetchProducts();
+    } catch (err) {
+      console.error(err);
+      alert('Connection error');
+    } finally {
+      setUploading(false);
+    }
+  };
+
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault();
 
@@ -167,8 +203,8 @@ export default function AdminProducts() {
         if (formData.image_url !== (editingProduct.image_url || '')) updatePayload.image_url = formData.image_url || null;
         if (formData.description !== (editingProduct.description || '')) updatePayload.description = formData.description || null;
         if (formData.category !== (editingProduct.category || '')) updatePayload.category = formData.category || null;
-        if (formData.allergens !== (editingProduct.allergens || '')) updatePayload.allergens = formData.allergens || null;
-        if (formData.additives !== (editingProduct.additives || '')) updatePayload.additives = formData.additives || null;
+        if (formData.allergens !== (editingProduct.allergens || '')) updatePayload.allergens = formData.allergens;
+        if (formData.additives !== (editingProduct.additives || '')) updatePayload.additives = formData.additives;
         if (formData.spice_level !== (editingProduct.spice_level || '')) updatePayload.spice_level = formData.spice_level || null;
         if (formData.serving_size !== (editingProduct.serving_size || '')) updatePayload.serving_size = formData.serving_size || null;
         if (formData.dietary_tags !== (editingProduct.dietary_tags || '')) updatePayload.dietary_tags = formData.dietary_tags || null;
@@ -311,6 +347,24 @@ export default function AdminProducts() {
                 placeholder="/images/pho-chay.jpg"
               />
               <p className="mt-1 text-xs text-gray-500">Path to image in public folder</p>
+              {editingProduct && (
+                <div className="mt-2">
+                  <input
+                    type="file"
+                    accept="image/jpeg,image/png,image/webp"
+                    disabled={uploading}
+                    onChange={(e) => {
+                      const file = e.target.files?.[0];
+                      if (file) handleImageUpload(file);
+                      e.target.value = '';
+                    }}
+                    className="block w-full text-sm text-gray-400 file:mr-3 file:rounded-lg file:border-0 file:bg-gray-700 file:px-3 file:py-1.5 file:text-white"
+                  />
+                  <p className="mt-1 text-xs text-gray-500">
+                    {uploading ? 'Uploading…' : 'Or upload a photo (JPEG, PNG or WebP, max 10 MB); resized copies are generated automatically'}
+                  </p>
+                </div>
+              )}
             </div>
             <div>
               <label className="mb-1 block text-sm font-medium text-gray-300">Description</label>
@@ -329,7 +383,7 @@ export default function AdminProducts() {
                 value={formData.category}
                 onChange={(e) => setFormData({ ...formData, category: e.target.value })}
                 className="w-full rounded-lg border border-gray-700 bg-gray-800 px-3 py-2 text-white placeholder-gray-500 focus:border-yellow-500 focus:outline-none"
-                placeholder="e.g., Suppen, Hauptgerichte, Vorspeisen"
+                placeholder="Existing category name or id, e.g. Suppen"
               />
             </div>
             <div className="grid grid-cols-2 gap-4">
@@ -342,7 +396,7 @@ export default function AdminProducts() {
                   className="w-full rounded-lg border border-gray-700 bg-gray-800 px-3 py-2 text-white placeholder-gray-500 focus:border-yellow-500 focus:outline-none"
                   placeholder="a, c, d, e (comma-separated)"
                 />
-                <p className="mt-1 text-xs text-gray-500">Allergen codes: a-z</p>
+                <p className="mt-1 text-xs text-gray-500">EU allergen letters a–h, l–p, r</p>
               </div>
               <div>
                 <label className="mb-1 block text-sm font-medium text-gray-300">Additives</label>
@@ -353,7 +407,7 @@ export default function AdminProducts() {
                   className="w-full rounded-lg border border-gray-700 bg-gray-800 px-3 py-2 text-white placeholder-gray-500 focus:border-yellow-500 focus:outline-none"
                   placeholder="1, 2, 8 (comma-separated)"
                 />
-                <p className="mt-1 text-xs text-gray-500">Additive codes: 1-15</p>
+                <p className="mt-1 text-xs text-gray-500">Additive numbers 1–12</p>
               </div>
             </div>
             <div className="grid grid-cols-2 gap-4">